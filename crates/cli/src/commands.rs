@@ -0,0 +1,410 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::signal::unix::{SignalKind, signal};
+use v2ray_rs_core::backend::backend_name;
+use v2ray_rs_core::config::{
+    ConfigError, ConfigWriter, LISTEN_ADDRESS, active_nodes, proxies_nothing, unrouted_rules,
+};
+use v2ray_rs_core::models::{AppSettings, ProxyNode, Subscription, SubscriptionSource};
+use v2ray_rs_core::persistence::{self, AppPaths, PersistenceError};
+use v2ray_rs_process::{
+    ControlRequest, ControlResponse, ControlServer, PidFile, ProcessError, ProcessManager,
+    ProcessState, Status, StatusFile, next_log_line, send_request,
+};
+use v2ray_rs_subscription::manager::SubscriptionService;
+use v2ray_rs_subscription::parser::ParseOptions;
+
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("invalid usage")]
+    Usage,
+    #[error("{0}")]
+    Args(#[from] pico_args::Error),
+    #[error("unexpected argument: {0}")]
+    UnexpectedArgument(String),
+    #[error("no backend binary configured — set one in Preferences")]
+    NoBackend,
+    #[error("no enabled proxy nodes — add a subscription first")]
+    NoNodes,
+    #[error("no enabled node named {0:?}")]
+    NodeNotFound(String),
+    #[error("none of the enabled nodes work with {0}")]
+    Unsupported(&'static str),
+    #[error("already connected (pid {0}); run `v2ray-rs-cli disconnect` first")]
+    AlreadyConnected(u32),
+    #[error("another v2ray-rs instance is running; disconnect it first")]
+    InstanceRunning,
+    #[error("not running")]
+    NotRunning,
+    #[error("{0}")]
+    Instance(String),
+    #[error("backend stopped: {0}")]
+    Backend(String),
+    #[error("subscription proxy unusable: {0}")]
+    FetchProxy(String),
+    #[error("{failed} of {total} subscriptions failed to update")]
+    UpdateFailed { failed: usize, total: usize },
+    #[error("config generation failed: {0}")]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Persistence(#[from] PersistenceError),
+    #[error(transparent)]
+    Process(#[from] ProcessError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Shared with the GUI, so either side can tell the other's backend is up.
+fn pid_path(paths: &AppPaths) -> PathBuf {
+    paths.data_dir().join("backend.pid")
+}
+
+fn backend_binary(settings: &AppSettings) -> Result<PathBuf, CliError> {
+    settings
+        .backend
+        .binary_path
+        .clone()
+        .ok_or(CliError::NoBackend)
+}
+
+fn node_label(node: &ProxyNode) -> String {
+    match node.remark() {
+        Some(remark) => remark.to_owned(),
+        None => format!("{}:{}", node.address(), node.port()),
+    }
+}
+
+/// Disables every node except those whose remark is `remark`.
+fn only_node(subscriptions: &mut [Subscription], remark: &str) -> Result<(), CliError> {
+    let mut found = false;
+    for sub in subscriptions.iter_mut().filter(|s| s.enabled) {
+        for node in &mut sub.nodes {
+            node.enabled &= node.node.remark() == Some(remark);
+            found |= node.enabled;
+        }
+    }
+    if found {
+        Ok(())
+    } else {
+        Err(CliError::NodeNotFound(remark.to_owned()))
+    }
+}
+
+/// A config written for the current nodes, rules and settings.
+struct Prepared {
+    settings: AppSettings,
+    binary: PathBuf,
+    config: PathBuf,
+    status_file: StatusFile,
+}
+
+fn prepare(paths: &AppPaths, node: Option<&str>) -> Result<Prepared, CliError> {
+    let settings = persistence::load_settings(paths)?;
+    let binary = backend_binary(&settings)?;
+
+    let mut subscriptions = persistence::load_subscriptions(paths)?;
+    if let Some(remark) = node {
+        only_node(&mut subscriptions, remark)?;
+    }
+    let backend = settings.backend.backend_type;
+    let (support, groups, targets) = active_nodes(backend, &subscriptions);
+    if support.kept.is_empty() && support.skipped.is_empty() {
+        return Err(CliError::NoNodes);
+    }
+    if support.kept.is_empty() {
+        return Err(CliError::Unsupported(backend_name(backend)));
+    }
+    if !support.skipped.is_empty() {
+        eprintln!(
+            "{} nodes skipped: not supported by {}",
+            support.skipped.len(),
+            backend_name(backend)
+        );
+    }
+
+    let rules = persistence::load_routing_rules(paths)?;
+    let rules: Vec<_> = rules.enabled_rules().cloned().collect();
+    if proxies_nothing(&rules, settings.default_action) {
+        eprintln!(
+            "no rule routes traffic to a proxy and the default action is not proxy; all traffic goes around the nodes"
+        );
+    }
+    let offered = if settings.serves_clash_api() {
+        groups.as_slice()
+    } else {
+        &[]
+    };
+    let unrouted = unrouted_rules(&rules, &targets, offered).len();
+    if unrouted > 0 {
+        eprintln!("{unrouted} rules route to a node that is not enabled; using the primary node");
+    }
+    let config = ConfigWriter::new(&settings, paths)
+        .with_groups(groups)
+        .with_targets(targets)
+        .write_config(&support.kept, &rules, &settings)?;
+
+    let mut status_file = StatusFile::new(paths.status_path());
+    status_file.set_active_node(Some(node_label(&support.kept[0])));
+    status_file.set_ports(
+        settings.enable_socks.then_some(settings.socks_port),
+        settings.enable_http.then_some(settings.http_port),
+    );
+    Ok(Prepared {
+        settings,
+        binary,
+        config,
+        status_file,
+    })
+}
+
+/// Whether a failed request means nobody is listening on the socket.
+fn no_instance(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// Forwards `request` to the running instance; `None` when there is none.
+async fn forward(socket: &Path, request: ControlRequest) -> Result<Option<()>, CliError> {
+    match send_request(socket, request).await {
+        Ok(ControlResponse::Ok { message }) => println!("{message}"),
+        Ok(ControlResponse::Status(status)) => print_status(&status),
+        Ok(ControlResponse::Error { message }) => return Err(CliError::Instance(message)),
+        Err(e) if no_instance(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some(()))
+}
+
+fn read_status(paths: &AppPaths) -> io::Result<Status> {
+    match StatusFile::new(paths.status_path()).read() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Status::stopped()),
+        result => result,
+    }
+}
+
+/// Starts the backend and supervises it until interrupted or until it is
+/// stopped from elsewhere. While it runs, this process is the instance other
+/// invocations and the GUI forward to; if one is already running, the
+/// connect is forwarded to it instead.
+pub async fn connect(paths: &AppPaths, socket: &Path, node: Option<&str>) -> Result<(), CliError> {
+    if node.is_none() && forward(socket, ControlRequest::Connect).await?.is_some() {
+        return Ok(());
+    }
+    let server = match ControlServer::bind(socket.to_path_buf()) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => return Err(CliError::InstanceRunning),
+        server => server?,
+    };
+
+    let prepared = prepare(paths, node)?;
+    if let Some(pid) = PidFile::new(pid_path(paths)).running_instance(&prepared.binary)? {
+        return Err(CliError::AlreadyConnected(pid));
+    }
+    let mut mgr = ProcessManager::new(prepared.binary, prepared.config, pid_path(paths));
+    mgr.set_ports(prepared.settings.inbound_ports());
+    mgr.set_status_file(prepared.status_file);
+
+    let mut log_rx = mgr.subscribe();
+    tokio::spawn(async move {
+        while let Some(line) = next_log_line(&mut log_rx).await {
+            eprintln!("{}", line.content);
+        }
+    });
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    match mgr.start().await {
+        Ok(()) => {}
+        Err(ProcessError::AlreadyRunning(pid) | ProcessError::PortHeldByPrevious { pid, .. }) => {
+            return Err(CliError::AlreadyConnected(pid));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    println!("Connected via {}", connected_node(paths));
+
+    let mut requester = None;
+    // Every way out goes through the shutdown below, so no exit here can
+    // leave the backend running.
+    let mut outcome = Ok(());
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = sigterm.recv() => break,
+            status = mgr.wait_exit() => {
+                // Outside the select, so a request cannot cancel a restart.
+                if let Some(status) = status {
+                    mgr.handle_exit(status).await;
+                }
+                match mgr.state() {
+                    ProcessState::Running => continue,
+                    ProcessState::Error(failure) => {
+                        outcome = Err(CliError::Backend(failure.summary));
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+            accepted = server.accept() => {
+                let (request, responder) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        outcome = Err(e.into());
+                        break;
+                    }
+                };
+                let response = match request {
+                    ControlRequest::Disconnect => {
+                        requester = Some(responder);
+                        break;
+                    }
+                    ControlRequest::Connect => {
+                        ControlResponse::ok(format!("Already connected via {}", connected_node(paths)))
+                    }
+                    ControlRequest::Status => match read_status(paths) {
+                        Ok(status) => ControlResponse::Status(status),
+                        Err(e) => ControlResponse::error(e.to_string()),
+                    },
+                    ControlRequest::Reload => match reload(&mut mgr, paths, node).await {
+                        Ok(()) => ControlResponse::ok(format!("Reconnected via {}", connected_node(paths))),
+                        Err(e) => ControlResponse::error(e.to_string()),
+                    },
+                    ControlRequest::Show => {
+                        ControlResponse::error("running headless; there is no window to show")
+                    }
+                };
+                responder.send(&response).await.ok();
+            }
+        }
+    }
+
+    mgr.shutdown().await;
+    outcome?;
+    println!("Disconnected");
+    if let Some(responder) = requester {
+        responder
+            .send(&ControlResponse::ok("Disconnected"))
+            .await
+            .ok();
+    }
+    Ok(())
+}
+
+fn connected_node(paths: &AppPaths) -> String {
+    read_status(paths)
+        .ok()
+        .and_then(|s| s.active_node)
+        .unwrap_or_default()
+}
+
+/// Rewrites the config from what is stored now and restarts the backend on it.
+async fn reload(
+    mgr: &mut ProcessManager,
+    paths: &AppPaths,
+    node: Option<&str>,
+) -> Result<(), CliError> {
+    let prepared = prepare(paths, node)?;
+    mgr.set_status_file(prepared.status_file);
+    mgr.restart().await?;
+    Ok(())
+}
+
+/// Asks the running instance to stop its backend. Without one, stops the
+/// backend named in the shared PID file, e.g. one left behind by a crash.
+pub async fn disconnect(paths: &AppPaths, socket: &Path) -> Result<(), CliError> {
+    if forward(socket, ControlRequest::Disconnect).await?.is_some() {
+        return Ok(());
+    }
+    let settings = persistence::load_settings(paths)?;
+    let binary = backend_binary(&settings)?;
+    if PidFile::new(pid_path(paths)).terminate_instance(&binary, STOP_TIMEOUT)? {
+        StatusFile::new(paths.status_path()).write(&ProcessState::Stopped, None)?;
+        println!("Disconnected");
+    } else {
+        println!("Not connected");
+    }
+    Ok(())
+}
+
+pub async fn reload_instance(socket: &Path) -> Result<(), CliError> {
+    match forward(socket, ControlRequest::Reload).await? {
+        Some(()) => Ok(()),
+        None => Err(CliError::NotRunning),
+    }
+}
+
+pub async fn status(paths: &AppPaths, socket: &Path) -> Result<(), CliError> {
+    if forward(socket, ControlRequest::Status).await?.is_some() {
+        return Ok(());
+    }
+    let mut status = read_status(paths)?;
+
+    // A status file left behind by an owner that died without cleaning up.
+    let settings = persistence::load_settings(paths)?;
+    if status.pid.is_some()
+        && let Some(binary) = &settings.backend.binary_path
+        && PidFile::new(pid_path(paths))
+            .running_instance(binary)?
+            .is_none()
+    {
+        status = Status::stopped();
+    }
+    print_status(&status);
+    Ok(())
+}
+
+fn print_status(status: &Status) {
+    println!("state: {}", status.state);
+    if let Some(node) = &status.active_node {
+        println!("node: {node}");
+    }
+    if let Some(port) = status.socks_port {
+        println!("socks: {LISTEN_ADDRESS}:{port}");
+    }
+    if let Some(port) = status.http_port {
+        println!("http: {LISTEN_ADDRESS}:{port}");
+    }
+    if let Some(pid) = status.pid {
+        println!("pid: {pid}");
+    }
+}
+
+pub async fn update_subscriptions(paths: &AppPaths) -> Result<(), CliError> {
+    let settings = persistence::load_settings(paths)?;
+    let mut service = SubscriptionService::new(paths.clone()).with_parse_options(ParseOptions {
+        strict_uuid: settings.strict_uuid,
+    });
+    service
+        .set_fetch_proxy(&settings.subscription_proxy)
+        .map_err(|e| CliError::FetchProxy(e.to_string()))?;
+    let subscriptions = persistence::load_subscriptions(paths)?;
+    let targets: Vec<_> = subscriptions
+        .iter()
+        .filter(|s| s.enabled && s.source != SubscriptionSource::Manual)
+        .collect();
+
+    let mut failed = 0;
+    for sub in &targets {
+        match service.refresh(sub.id).await {
+            Ok((_, result)) => println!(
+                "{}: {} added, {} removed, {} unchanged",
+                sub.name, result.added, result.removed, result.unchanged
+            ),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {e}", sub.name);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(CliError::UpdateFailed {
+            failed,
+            total: targets.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+mod commands;
+
+use std::process::ExitCode;
+
+use pico_args::Arguments;
+use v2ray_rs_core::persistence::{self, AppPaths};
+
+use commands::CliError;
+
+const USAGE: &str = "\
+Usage: v2ray-rs-cli <command>
+
+Commands:
+  connect [--node <remark>]  Start the backend and stay in the foreground
+  disconnect                 Stop the backend, whoever started it
+  status                     Print the connection state
+  reload                     Regenerate the config and restart the backend
+  subscriptions update       Refresh every enabled subscription
+
+Uses the same data as the GUI (or $V2RAY_RS_HOME) and its current profile.
+While a GUI or `connect` is running, commands are forwarded to it.
+";
+
+fn main() -> ExitCode {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("failed to install rustls crypto provider");
+
+    match run(Arguments::from_env()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage) => {
+            eprint!("{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("v2ray-rs-cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut args: Arguments) -> Result<(), CliError> {
+    if args.contains(["-h", "--help"]) {
+        print!("{USAGE}");
+        return Ok(());
+    }
+    let command = args.subcommand()?;
+    let base = AppPaths::from_env()?;
+    let paths = base.for_profile(&persistence::load_current_profile(&base));
+    let socket = base.control_socket_path();
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    match command.as_deref() {
+        Some("connect") => {
+            let node: Option<String> = args.opt_value_from_str("--node")?;
+            finish(args)?;
+            runtime.block_on(commands::connect(&paths, &socket, node.as_deref()))
+        }
+        Some("disconnect") => {
+            finish(args)?;
+            runtime.block_on(commands::disconnect(&paths, &socket))
+        }
+        Some("status") => {
+            finish(args)?;
+            runtime.block_on(commands::status(&paths, &socket))
+        }
+        Some("reload") => {
+            finish(args)?;
+            runtime.block_on(commands::reload_instance(&socket))
+        }
+        Some("subscriptions") => match args.subcommand()?.as_deref() {
+            Some("update") => {
+                finish(args)?;
+                runtime.block_on(commands::update_subscriptions(&paths))
+            }
+            _ => Err(CliError::Usage),
+        },
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn finish(args: Arguments) -> Result<(), CliError> {
+    match args.finish().first() {
+        Some(extra) => Err(CliError::UnexpectedArgument(
+            extra.to_string_lossy().into_owned(),
+        )),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,163 @@
+use std::fs;
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+use v2ray_rs_core::models::{
+    AppSettings, ProxyNode, ShadowsocksConfig, Subscription, SubscriptionNode,
+};
+use v2ray_rs_core::persistence::{self, AppPaths, HOME_ENV_VAR};
+use v2ray_rs_process::StatusFile;
+
+fn cli(home: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_v2ray-rs-cli"));
+    cmd.env(HOME_ENV_VAR, home);
+    cmd
+}
+
+fn run(home: &Path, args: &[&str]) -> Output {
+    cli(home).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn ss_node(remark: &str) -> SubscriptionNode {
+    SubscriptionNode::new(ProxyNode::Shadowsocks(ShadowsocksConfig {
+        address: "ss.example.com".into(),
+        port: 8388,
+        method: "aes-256-gcm".into(),
+        password: "secret".into(),
+        remark: Some(remark.into()),
+    }))
+}
+
+/// A home with a mock backend and one subscription of two nodes.
+fn setup() -> (TempDir, AppPaths, u16) {
+    let home = TempDir::new().unwrap();
+    let paths = AppPaths::from_home(home.path());
+    paths.ensure_dirs().unwrap();
+
+    let backend: PathBuf = home.path().join("mock-backend");
+    fs::write(&backend, "#!/bin/sh\nwhile true; do sleep 1; done\n").unwrap();
+    fs::set_permissions(&backend, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let socks_port = free_port();
+    let mut settings = AppSettings::default();
+    settings.backend.binary_path = Some(backend);
+    settings.socks_port = socks_port;
+    settings.enable_http = false;
+    persistence::save_settings(&paths, &settings).unwrap();
+
+    let mut sub = Subscription::new_manual("Mine");
+    sub.nodes = vec![ss_node("Frankfurt"), ss_node("Tokyo")];
+    persistence::add_subscription(&paths, sub).unwrap();
+
+    (home, paths, socks_port)
+}
+
+fn wait_for_state(paths: &AppPaths, state: &str) {
+    let file = StatusFile::new(paths.status_path());
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while file.read().map(|s| s.state).ok().as_deref() != Some(state) {
+        assert!(Instant::now() < deadline, "status never became {state}");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn wait_exit(child: &mut Child) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status.success();
+        }
+        assert!(Instant::now() < deadline, "connect did not exit");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn connect_status_disconnect() {
+    let (home, paths, socks_port) = setup();
+
+    let mut connect = cli(home.path())
+        .args(["connect", "--node", "Tokyo"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_state(&paths, "running");
+
+    let status = stdout(&run(home.path(), &["status"]));
+    assert!(status.contains("state: running"), "{status}");
+    assert!(status.contains("node: Tokyo"), "{status}");
+    assert!(
+        status.contains(&format!("socks: 127.0.0.1:{socks_port}")),
+        "{status}"
+    );
+    assert!(!status.contains("http:"), "{status}");
+
+    let second = run(home.path(), &["connect"]);
+    assert!(second.status.success());
+    assert_eq!(stdout(&second), "Already connected via Tokyo\n");
+    let picky = run(home.path(), &["connect", "--node", "Frankfurt"]);
+    assert!(!picky.status.success());
+    assert!(String::from_utf8_lossy(&picky.stderr).contains("instance is running"));
+
+    let reload = run(home.path(), &["reload"]);
+    assert_eq!(stdout(&reload), "Reconnected via Tokyo\n");
+    wait_for_state(&paths, "running");
+
+    let disconnect = run(home.path(), &["disconnect"]);
+    assert!(disconnect.status.success());
+    assert_eq!(stdout(&disconnect), "Disconnected\n");
+    assert!(wait_exit(&mut connect));
+
+    let status = stdout(&run(home.path(), &["status"]));
+    assert_eq!(status, "state: stopped\n");
+    assert!(!paths.data_dir().join("backend.pid").exists());
+    assert!(!paths.control_socket_path().exists());
+    let reload = run(home.path(), &["reload"]);
+    assert!(String::from_utf8_lossy(&reload.stderr).contains("not running"));
+    assert_eq!(
+        stdout(&run(home.path(), &["disconnect"])),
+        "Not connected\n"
+    );
+}
+
+#[test]
+fn connect_to_unknown_node_fails() {
+    let (home, paths, _) = setup();
+    let output = run(home.path(), &["connect", "--node", "Paris"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no enabled node named \"Paris\""));
+    assert!(!paths.status_path().exists());
+}
+
+#[test]
+fn status_without_data_is_stopped() {
+    let home = TempDir::new().unwrap();
+    let output = run(home.path(), &["status"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "state: stopped\n");
+}
+
+#[test]
+fn unknown_command_prints_usage() {
+    let home = TempDir::new().unwrap();
+    let output = run(home.path(), &["frobnicate"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage:"));
+}
@@ -5,7 +5,7 @@ use std::process::Command;
 
 use thiserror::Error;
 
-use crate::models::BackendType;
+use crate::models::{BackendConfig, BackendType};
 
 #[derive(Error, Debug)]
 pub enum BackendError {
@@ -180,6 +180,41 @@ pub fn validate_custom_path(path: &Path, bt: BackendType) -> Result<DetectedBack
     })
 }
 
+/// A stored backend path checked again, e.g. at startup.
+#[derive(Debug)]
+pub enum Revalidation {
+    Valid,
+    /// The stored binary is gone, but the same backend was found elsewhere.
+    Replaced(DetectedBackend),
+    /// Neither the stored path nor detection turned up the backend.
+    Missing(BackendError),
+}
+
+/// Checks that `config` still points at a runnable binary, falling back to
+/// detecting the same backend type when it does not.
+pub fn revalidate_backend(config: &BackendConfig) -> Revalidation {
+    revalidate_with(config, detect_single)
+}
+
+fn revalidate_with(
+    config: &BackendConfig,
+    detect: impl FnOnce(BackendType) -> Result<DetectedBackend, BackendError>,
+) -> Revalidation {
+    let stored = match &config.binary_path {
+        Some(path) => match validate_custom_path(path, config.backend_type) {
+            Ok(_) => return Revalidation::Valid,
+            Err(e) => e,
+        },
+        None => BackendError::NotFound {
+            path: PathBuf::from(binary_name(config.backend_type)),
+        },
+    };
+    match detect(config.backend_type) {
+        Ok(found) => Revalidation::Replaced(found),
+        Err(_) => Revalidation::Missing(stored),
+    }
+}
+
 pub fn install_guidance(bt: BackendType) -> &'static str {
     match bt {
         BackendType::V2ray => {
@@ -244,6 +279,60 @@ mod tests {
         assert!(is_executable(path));
     }
 
+    fn config(binary_path: Option<PathBuf>) -> BackendConfig {
+        BackendConfig {
+            backend_type: BackendType::Xray,
+            binary_path,
+            config_output_dir: None,
+        }
+    }
+
+    #[test]
+    fn test_revalidate_keeps_existing_binary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("xray");
+        fs::write(&path, "#!/bin/sh\necho 'Xray 1.8.0'\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let result = revalidate_with(&config(Some(path)), |_| panic!("detection not needed"));
+        assert!(matches!(result, Revalidation::Valid));
+    }
+
+    #[test]
+    fn test_revalidate_missing_path_falls_back_to_detection() {
+        let moved = PathBuf::from("/opt/xray/bin/xray");
+        let stored = config(Some(PathBuf::from("/nonexistent/xray")));
+        let result = revalidate_with(&stored, |bt| {
+            assert_eq!(bt, BackendType::Xray);
+            Ok(DetectedBackend {
+                backend_type: bt,
+                binary_path: moved.clone(),
+                version: None,
+            })
+        });
+        assert!(matches!(result, Revalidation::Replaced(found) if found.binary_path == moved));
+    }
+
+    #[test]
+    fn test_revalidate_missing_everywhere_needs_reconfiguring() {
+        let stored = config(Some(PathBuf::from("/nonexistent/xray")));
+        let result = revalidate_with(&stored, |bt| {
+            Err(BackendError::NotFound {
+                path: PathBuf::from(binary_name(bt)),
+            })
+        });
+        assert!(matches!(
+            result,
+            Revalidation::Missing(BackendError::NotFound { path }) if path == Path::new("/nonexistent/xray")
+        ));
+
+        let result = revalidate_with(&config(None), |_| Err(BackendError::NoneFound));
+        assert!(matches!(
+            result,
+            Revalidation::Missing(BackendError::NotFound { .. })
+        ));
+    }
+
     #[test]
     fn test_validate_custom_path_not_found() {
         let result = validate_custom_path(Path::new("/nonexistent/binary"), BackendType::Xray);
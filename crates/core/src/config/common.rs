@@ -1,8 +1,249 @@
-use crate::models::ProxyNode;
+use std::collections::BTreeMap;
 
+use uuid::Uuid;
+
+use crate::config::{ConfigError, RouteTarget};
+use crate::models::{
+    AppSettings, ProxyNode, RoutingRule, UpstreamProxy, VlessConfig, WsSettings, validate_inbounds,
+    validate_upstream_proxy,
+};
+
+/// Address every generated inbound listens on.
+pub const LISTEN_ADDRESS: &str = "127.0.0.1";
+
+/// Longest remark slug kept in a tag; the index already makes tags unique.
+const MAX_TAG_SLUG_CHARS: usize = 32;
+
+/// `proxy-<index>` plus a slug of the remark. The remark is reduced to
+/// letters, digits, `_` and `.` joined by single dashes, so spaces, emoji and
+/// punctuation never reach tag references.
 pub(crate) fn outbound_tag(node: &ProxyNode, index: usize) -> String {
-    match node.remark() {
-        Some(name) if !name.is_empty() => format!("proxy-{index}-{name}"),
-        _ => format!("proxy-{index}"),
+    let slug = node.remark().map(tag_slug).unwrap_or_default();
+    if slug.is_empty() {
+        format!("proxy-{index}")
+    } else {
+        format!("proxy-{index}-{slug}")
+    }
+}
+
+/// The VLESS user's `encryption`, `none` when the link left it out or empty.
+/// Suites carry their keys in the string itself, so it is passed on whole.
+pub(crate) fn vless_encryption(c: &VlessConfig) -> &str {
+    c.encryption
+        .as_deref()
+        .filter(|e| !e.is_empty())
+        .unwrap_or("none")
+}
+
+/// Headers sent on the WebSocket upgrade: the link's own headers plus `Host`
+/// from `host`, unless they already set one (in any case).
+pub(crate) fn ws_headers(ws: &WsSettings) -> BTreeMap<String, String> {
+    let mut headers: BTreeMap<String, String> = ws
+        .headers
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if let Some(host) = &ws.host
+        && !headers.keys().any(|name| name.eq_ignore_ascii_case("host"))
+    {
+        headers.insert("Host".to_owned(), host.clone());
+    }
+    headers
+}
+
+/// Tag of a routing target's outbound. It depends only on the target's id,
+/// so it stays the same however the nodes are ordered.
+pub(crate) fn target_tag(id: Uuid) -> String {
+    format!("target-{}", id.simple())
+}
+
+/// The outbound tag of each node: `target_tag` for routing targets,
+/// `outbound_tag` for the rest. A target id shared by several nodes tags
+/// only the first, as backends refuse duplicate tags.
+pub(crate) fn node_tags(nodes: &[ProxyNode], targets: &[RouteTarget]) -> Vec<String> {
+    let mut used = Vec::new();
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| match targets.iter().find(|t| t.member == i) {
+            Some(target) if !used.contains(&target.id) => {
+                used.push(target.id);
+                target_tag(target.id)
+            }
+            _ => outbound_tag(node, i),
+        })
+        .collect()
+}
+
+/// Tag of the outbound proxied traffic goes to: the first node's.
+pub(crate) fn primary_tag(tags: &[String]) -> String {
+    tags.first()
+        .cloned()
+        .unwrap_or_else(|| "proxy-0".to_owned())
+}
+
+/// Where `rule` sends proxied matches: its target's outbound when that node
+/// is being generated, otherwise `proxy`, the primary one.
+pub(crate) fn rule_proxy(rule: &RoutingRule, targets: &[RouteTarget], proxy: &str) -> String {
+    match rule.target {
+        Some(id) if targets.iter().any(|t| t.id == id) => target_tag(id),
+        _ => proxy.to_owned(),
+    }
+}
+
+fn tag_slug(remark: &str) -> String {
+    let mut slug = String::new();
+    let mut pending_dash = false;
+    for c in remark.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            if pending_dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_dash = false;
+            slug.push(c);
+        } else {
+            pending_dash = true;
+        }
+    }
+    slug.chars()
+        .take(MAX_TAG_SLUG_CHARS)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_owned()
+}
+
+/// The configured upstream proxy, rejected early when its address is bad.
+pub(crate) fn upstream_proxy(
+    settings: &AppSettings,
+) -> Result<Option<&UpstreamProxy>, ConfigError> {
+    if let Some(proxy) = &settings.upstream_proxy {
+        validate_upstream_proxy(proxy).map_err(ConfigError::Upstream)?;
+    }
+    Ok(settings.upstream_proxy.as_ref())
+}
+
+pub(crate) fn check_inbounds(settings: &AppSettings) -> Result<(), ConfigError> {
+    validate_inbounds(settings).map_err(ConfigError::Inbounds)
+}
+
+/// Shell `export` line pointing terminal tools at the local inbounds.
+pub fn proxy_env_exports(settings: &AppSettings) -> String {
+    let mut vars = Vec::new();
+    if settings.enable_http {
+        let http = format!("http://{LISTEN_ADDRESS}:{}", settings.http_port);
+        vars.push(format!("http_proxy={http} https_proxy={http}"));
+    }
+    if settings.enable_socks {
+        let socks = format!("socks5://{LISTEN_ADDRESS}:{}", settings.socks_port);
+        vars.push(format!("all_proxy={socks}"));
+    }
+    format!("export {}", vars.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BackendType, ShadowsocksConfig};
+
+    #[test]
+    fn test_ws_headers_keep_custom_headers_and_host() {
+        let mut ws = WsSettings {
+            path: "/ws".into(),
+            host: Some("cdn.example.com".into()),
+            headers: [("X-Token".to_owned(), "abc".to_owned())].into(),
+        };
+        let headers = ws_headers(&ws);
+        assert_eq!(headers["X-Token"], "abc");
+        assert_eq!(headers["Host"], "cdn.example.com");
+
+        ws.headers.insert("host".into(), "other.example.com".into());
+        let headers = ws_headers(&ws);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["host"], "other.example.com");
+    }
+
+    fn node_with_remark(remark: Option<&str>) -> ProxyNode {
+        ProxyNode::Shadowsocks(ShadowsocksConfig {
+            address: "ss.example.com".into(),
+            port: 8388,
+            method: "aes-256-gcm".into(),
+            password: "secret".into(),
+            remark: remark.map(Into::into),
+        })
+    }
+
+    #[test]
+    fn outbound_tag_sanitizes_remark() {
+        let tag = |remark, index| outbound_tag(&node_with_remark(remark), index);
+        assert_eq!(tag(Some("Tokyo"), 0), "proxy-0-Tokyo");
+        assert_eq!(tag(Some("🇯🇵 Tokyo  #2 (fast)"), 3), "proxy-3-Tokyo-2-fast");
+        assert_eq!(tag(Some("Москва v2.1"), 1), "proxy-1-Москва-v2.1");
+        assert_eq!(tag(Some("🔥🔥"), 2), "proxy-2");
+        assert_eq!(tag(Some(""), 4), "proxy-4");
+        assert_eq!(tag(None, 5), "proxy-5");
+        assert_eq!(
+            tag(Some("🇯🇵 Tokyo  #2 (fast)"), 3),
+            tag(Some("🇯🇵 Tokyo  #2 (fast)"), 3)
+        );
+    }
+
+    #[test]
+    fn outbound_tag_slug_is_bounded_and_unique_per_index() {
+        let long = "a b ".repeat(40);
+        let first = outbound_tag(&node_with_remark(Some(&long)), 0);
+        let second = outbound_tag(&node_with_remark(Some(&long)), 1);
+        assert_ne!(first, second);
+        assert!(first.len() <= "proxy-0-".len() + MAX_TAG_SLUG_CHARS);
+        assert!(!first.ends_with('-'));
+    }
+
+    #[test]
+    fn node_tags_give_a_shared_target_to_the_first_node_only() {
+        let id = Uuid::new_v4();
+        let nodes = vec![node_with_remark(Some("A")), node_with_remark(Some("A"))];
+        let targets = [RouteTarget { id, member: 0 }, RouteTarget { id, member: 1 }];
+        assert_eq!(
+            node_tags(&nodes, &targets),
+            [target_tag(id), "proxy-1-A".to_owned()]
+        );
+    }
+
+    #[test]
+    fn proxy_env_uses_configured_ports() {
+        let settings = AppSettings {
+            socks_port: 2080,
+            http_port: 2081,
+            ..AppSettings::default()
+        };
+        assert_eq!(
+            proxy_env_exports(&settings),
+            "export http_proxy=http://127.0.0.1:2081 https_proxy=http://127.0.0.1:2081 \
+             all_proxy=socks5://127.0.0.1:2080"
+        );
+    }
+
+    #[test]
+    fn proxy_env_skips_disabled_inbounds() {
+        let settings = AppSettings {
+            enable_http: false,
+            ..AppSettings::default()
+        };
+        assert_eq!(
+            proxy_env_exports(&settings),
+            "export all_proxy=socks5://127.0.0.1:1080"
+        );
+    }
+
+    #[test]
+    fn proxy_env_singbox_uses_http_port() {
+        let mut settings = AppSettings {
+            socks_port: 2080,
+            http_port: 2081,
+            ..AppSettings::default()
+        };
+        settings.backend.backend_type = BackendType::SingBox;
+        assert!(
+            proxy_env_exports(&settings).starts_with("export http_proxy=http://127.0.0.1:2081 ")
+        );
     }
 }
@@ -0,0 +1,138 @@
+use std::fmt;
+
+/// Beyond this many cells the LCS table is skipped and the changed block is
+/// reported as a full replacement.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Added(String),
+    Removed(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub lines: Vec<DiffLine>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn added(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|l| matches!(l, DiffLine::Added(_)))
+            .count()
+    }
+
+    pub fn removed(&self) -> usize {
+        self.lines.len() - self.added()
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                DiffLine::Added(text) => writeln!(f, "+ {text}")?,
+                DiffLine::Removed(text) => writeln!(f, "- {text}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Line-based diff of `old` against `new`, listing only the changed lines.
+pub fn diff_lines(old: &str, new: &str) -> ConfigDiff {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let mut lines = Vec::new();
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        lines.extend(old.iter().map(|l| DiffLine::Removed((*l).to_owned())));
+        lines.extend(new.iter().map(|l| DiffLine::Added((*l).to_owned())));
+        return ConfigDiff { lines };
+    }
+
+    // lcs[i][j] = length of the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            lines.push(DiffLine::Removed(old[i].to_owned()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_owned()));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| DiffLine::Removed((*l).to_owned())));
+    lines.extend(new[j..].iter().map(|l| DiffLine::Added((*l).to_owned())));
+
+    ConfigDiff { lines }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_input_has_no_changes() {
+        let text = "{\n  \"a\": 1\n}";
+        assert!(diff_lines(text, text).is_empty());
+    }
+
+    #[test]
+    fn changed_line_is_removed_and_added() {
+        let diff = diff_lines("a\nb\nc", "a\nx\nc");
+        assert_eq!(
+            diff.lines,
+            vec![DiffLine::Removed("b".into()), DiffLine::Added("x".into())]
+        );
+        assert_eq!(diff.to_string(), "- b\n+ x\n");
+    }
+
+    #[test]
+    fn insertions_and_deletions() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nc\nd\ne");
+        assert_eq!(
+            diff.lines,
+            vec![DiffLine::Removed("b".into()), DiffLine::Added("e".into())]
+        );
+        assert_eq!(diff.added(), 1);
+        assert_eq!(diff.removed(), 1);
+    }
+
+    #[test]
+    fn empty_old_is_all_added() {
+        let diff = diff_lines("", "a\nb");
+        assert_eq!(diff.added(), 2);
+        assert_eq!(diff.removed(), 0);
+    }
+}
@@ -0,0 +1,310 @@
+use uuid::Uuid;
+
+use crate::config::support::{NodeSupport, unsupported_feature};
+use crate::models::{BackendType, ProxyNode, RoutingRule, RuleAction, Subscription};
+
+/// One subscription's nodes, offered by sing-box as a single selector outbound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeGroup {
+    /// The subscription's id, which routing rules target the group by.
+    pub id: Uuid,
+    pub name: String,
+    /// Positions of the members in the node list handed to the generator.
+    pub members: Vec<usize>,
+}
+
+/// A node routing rules can send traffic to by `id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteTarget {
+    pub id: Uuid,
+    /// Position of the node in the node list handed to the generator.
+    pub member: usize,
+}
+
+/// Enabled nodes of enabled subscriptions split by whether `backend` can run
+/// them, with the group each subscription's kept nodes form and the kept
+/// routing targets. The primary node, when one is set and kept, comes first
+/// and its group leads with it, so proxied traffic goes to it however the
+/// nodes are ordered.
+pub fn active_nodes(
+    backend: BackendType,
+    subscriptions: &[Subscription],
+) -> (NodeSupport, Vec<NodeGroup>, Vec<RouteTarget>) {
+    let mut support = NodeSupport::default();
+    let mut groups = Vec::new();
+    let mut targets = Vec::new();
+    let mut primary = None;
+    for sub in subscriptions.iter().filter(|s| s.enabled) {
+        let mut members = Vec::new();
+        for entry in sub.nodes.iter().filter(|n| n.enabled) {
+            let node = &entry.node;
+            if unsupported_feature(backend, node).is_some() {
+                support.skipped.push(node.clone());
+            } else {
+                if entry.primary && primary.is_none() {
+                    primary = Some(support.kept.len());
+                }
+                if let Some(id) = entry.route_target {
+                    targets.push(RouteTarget {
+                        id,
+                        member: support.kept.len(),
+                    });
+                }
+                members.push(support.kept.len());
+                support.kept.push(node.clone());
+            }
+        }
+        if !members.is_empty() {
+            groups.push(NodeGroup {
+                id: sub.id,
+                name: sub.name.clone(),
+                members,
+            });
+        }
+    }
+    if let Some(position) = primary {
+        promote(&mut support.kept, &mut groups, &mut targets, position);
+    }
+    (support, groups, targets)
+}
+
+/// Enabled proxy rules whose target is neither among `targets` nor one of
+/// `groups`, typically because its node was disabled. The generated config
+/// sends them to the primary node. Pass no groups for a config without
+/// selectors.
+pub fn unrouted_rules<'a>(
+    rules: &'a [RoutingRule],
+    targets: &[RouteTarget],
+    groups: &[NodeGroup],
+) -> Vec<&'a RoutingRule> {
+    rules
+        .iter()
+        .filter(|r| r.enabled && r.action == RuleAction::Proxy)
+        .filter(|r| {
+            r.target.is_some_and(|id| {
+                !targets.iter().any(|t| t.id == id) && !groups.iter().any(|g| g.id == id)
+            })
+        })
+        .collect()
+}
+
+/// Whether no traffic would reach a proxy node: the default action is not
+/// Proxy and no enabled rule proxies anything, so enabled nodes go unused.
+pub fn proxies_nothing(rules: &[RoutingRule], default_action: RuleAction) -> bool {
+    default_action != RuleAction::Proxy
+        && !rules
+            .iter()
+            .any(|r| r.enabled && r.action == RuleAction::Proxy)
+}
+
+/// Moves the node at `position` to the front, renumbering group members and
+/// targets and putting its group, led by it, first.
+fn promote(
+    nodes: &mut Vec<ProxyNode>,
+    groups: &mut Vec<NodeGroup>,
+    targets: &mut [RouteTarget],
+    position: usize,
+) {
+    let node = nodes.remove(position);
+    nodes.insert(0, node);
+    let members = groups.iter_mut().flat_map(|g| g.members.iter_mut());
+    for member in members.chain(targets.iter_mut().map(|t| &mut t.member)) {
+        *member = match *member {
+            m if m == position => 0,
+            m if m < position => m + 1,
+            m => m,
+        };
+    }
+    if let Some(index) = groups.iter().position(|g| g.members.contains(&0)) {
+        let mut group = groups.remove(index);
+        group.members.retain(|&m| m != 0);
+        group.members.insert(0, 0);
+        groups.insert(0, group);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_fixtures::fixtures::*;
+    use crate::models::*;
+
+    fn rule(target: Option<uuid::Uuid>, action: RuleAction) -> RoutingRule {
+        RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.com".into(),
+            },
+            action,
+            enabled: true,
+            target,
+        }
+    }
+
+    fn subscription(name: &str, nodes: Vec<(ProxyNode, bool)>) -> Subscription {
+        let mut sub = Subscription::new_manual(name);
+        sub.nodes = nodes
+            .into_iter()
+            .map(|(node, enabled)| SubscriptionNode {
+                enabled,
+                ..SubscriptionNode::new(node)
+            })
+            .collect();
+        sub
+    }
+
+    #[test]
+    fn test_groups_index_kept_nodes() {
+        let ProxyNode::Vless(mut vision) = vless_node() else {
+            unreachable!()
+        };
+        vision.flow = Some("xtls-rprx-vision".into());
+        let mut disabled = subscription("Off", vec![(ss_node(), true)]);
+        disabled.enabled = false;
+        let subs = [
+            subscription(
+                "A",
+                vec![
+                    (ProxyNode::Vless(vision.clone()), true),
+                    (vmess_node(), true),
+                    (ss_node(), false),
+                ],
+            ),
+            disabled,
+            subscription("B", vec![(trojan_node(), true), (ss_node(), true)]),
+            subscription("Empty", vec![(vless_node(), false)]),
+        ];
+
+        let (support, groups, _) = active_nodes(BackendType::V2ray, &subs);
+        assert_eq!(support.kept, [vmess_node(), trojan_node(), ss_node()]);
+        assert_eq!(support.skipped, [ProxyNode::Vless(vision)]);
+        assert_eq!(
+            groups,
+            [
+                NodeGroup {
+                    id: subs[0].id,
+                    name: "A".into(),
+                    members: vec![0],
+                },
+                NodeGroup {
+                    id: subs[2].id,
+                    name: "B".into(),
+                    members: vec![1, 2],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_primary_outbound_ignores_node_order() {
+        let mut a = subscription("A", vec![(vless_node(), true), (vmess_node(), true)]);
+        let mut b = subscription("B", vec![(ss_node(), true), (trojan_node(), true)]);
+        b.nodes[1].primary = true;
+        let rules = [RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.com".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let primary_outbound = |subs: &[Subscription]| {
+            let (support, groups, _) = active_nodes(BackendType::V2ray, subs);
+            assert_eq!(support.kept[0], trojan_node());
+            assert_eq!(groups[0].name, "B");
+            assert_eq!(groups[0].members[0], 0);
+            let config = crate::config::generator_for(BackendType::V2ray)
+                .generate(&support.kept, &rules, &AppSettings::default(), None)
+                .unwrap();
+            let target = config["routing"]["rules"][0]["outboundTag"].clone();
+            config["outbounds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|o| o["tag"] == target)
+                .unwrap()["settings"]
+                .clone()
+        };
+
+        let before = primary_outbound(&[a.clone(), b.clone()]);
+        a.nodes.reverse();
+        b.nodes.reverse();
+        let after = primary_outbound(&[b, a]);
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_targets_follow_promoted_nodes() {
+        let (first, second) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let mut a = subscription("A", vec![(vless_node(), true), (vmess_node(), true)]);
+        let mut b = subscription("B", vec![(ss_node(), true), (trojan_node(), false)]);
+        a.nodes[1].route_target = Some(first);
+        b.nodes[0].route_target = Some(second);
+        b.nodes[0].primary = true;
+        b.nodes[1].route_target = Some(uuid::Uuid::new_v4());
+
+        let (support, _, targets) = active_nodes(BackendType::V2ray, &[a, b]);
+        assert_eq!(support.kept, [ss_node(), vless_node(), vmess_node()]);
+        assert_eq!(
+            targets,
+            [
+                RouteTarget {
+                    id: first,
+                    member: 2
+                },
+                RouteTarget {
+                    id: second,
+                    member: 0
+                },
+            ]
+        );
+
+        let gone = uuid::Uuid::new_v4();
+        let rules = [
+            rule(Some(first), RuleAction::Proxy),
+            rule(Some(gone), RuleAction::Proxy),
+            rule(Some(gone), RuleAction::Direct),
+            rule(None, RuleAction::Proxy),
+        ];
+        let unrouted = unrouted_rules(&rules, &targets, &[]);
+        assert_eq!(unrouted, [&rules[1]]);
+    }
+
+    #[test]
+    fn test_rules_targeting_a_group_are_routed_when_groups_are() {
+        let subs = [subscription("A", vec![(vless_node(), true)])];
+        let (_, groups, targets) = active_nodes(BackendType::SingBox, &subs);
+        let rules = [rule(Some(subs[0].id), RuleAction::Proxy)];
+
+        assert!(unrouted_rules(&rules, &targets, &groups).is_empty());
+        assert_eq!(unrouted_rules(&rules, &targets, &[]), [&rules[0]]);
+    }
+
+    #[test]
+    fn test_proxies_nothing_without_proxy_rule_or_default() {
+        let direct = [
+            rule(None, RuleAction::Direct),
+            rule(None, RuleAction::Block),
+        ];
+        assert!(proxies_nothing(&direct, RuleAction::Direct));
+        assert!(proxies_nothing(&[], RuleAction::Block));
+        assert!(!proxies_nothing(&direct, RuleAction::Proxy));
+
+        let mut with_proxy = direct.to_vec();
+        with_proxy.push(rule(None, RuleAction::Proxy));
+        assert!(!proxies_nothing(&with_proxy, RuleAction::Direct));
+        with_proxy[2].enabled = false;
+        assert!(proxies_nothing(&with_proxy, RuleAction::Direct));
+    }
+
+    #[test]
+    fn test_without_primary_first_enabled_node_leads() {
+        let subs = [subscription(
+            "A",
+            vec![(vmess_node(), true), (ss_node(), true)],
+        )];
+        let (support, _, _) = active_nodes(BackendType::V2ray, &subs);
+        assert_eq!(support.kept, [vmess_node(), ss_node()]);
+    }
+}
@@ -1,24 +1,40 @@
 mod common;
+mod diff;
+mod groups;
 mod singbox;
+mod support;
 #[cfg(test)]
 mod test_fixtures;
 pub(crate) mod v2ray;
 mod writer;
 mod xray;
 
+pub use common::{LISTEN_ADDRESS, proxy_env_exports};
+pub use diff::{ConfigDiff, DiffLine, diff_lines};
+pub use groups::{NodeGroup, RouteTarget, active_nodes, proxies_nothing, unrouted_rules};
 pub use singbox::SingboxGenerator;
+pub use support::{NodeSupport, partition_supported, unsupported_feature};
 pub use v2ray::V2rayGenerator;
-pub use writer::ConfigWriter;
+pub use writer::{ConfigWriter, check_output_dir};
 pub use xray::XrayGenerator;
 
 use std::path::Path;
 
-use crate::models::{AppSettings, BackendType, ProxyNode, RoutingRule};
+use crate::models::{AppSettings, BackendType, ProxyNode, RoutingRule, ValidationError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("no enabled proxy nodes")]
     NoNodes,
+    #[error("upstream proxy: {0}")]
+    Upstream(ValidationError),
+    #[error("inbounds: {0}")]
+    Inbounds(ValidationError),
+    #[error("{backend} does not support {feature}")]
+    Unsupported {
+        backend: &'static str,
+        feature: &'static str,
+    },
     #[error("serialize config: {0}")]
     Serialize(#[from] serde_json::Error),
     #[error("write config: {0}")]
@@ -32,6 +48,21 @@ pub trait ConfigGenerator {
         rules: &[RoutingRule],
         settings: &AppSettings,
         geodata_dir: Option<&Path>,
+    ) -> Result<serde_json::Value, ConfigError> {
+        self.generate_grouped(nodes, &[], &[], rules, settings, geodata_dir)
+    }
+
+    /// Like `generate`, also offering each group as one outbound on backends
+    /// that can switch between members at runtime, and giving each of
+    /// `targets` an outbound rules can send traffic to.
+    fn generate_grouped(
+        &self,
+        nodes: &[ProxyNode],
+        groups: &[NodeGroup],
+        targets: &[RouteTarget],
+        rules: &[RoutingRule],
+        settings: &AppSettings,
+        geodata_dir: Option<&Path>,
     ) -> Result<serde_json::Value, ConfigError>;
 }
 
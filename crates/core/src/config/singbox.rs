@@ -2,11 +2,13 @@ use std::collections::BTreeSet;
 use std::path::Path;
 
 use serde_json::{Value, json};
+use uuid::Uuid;
 
-use crate::config::{ConfigError, ConfigGenerator};
+use crate::config::{ConfigError, ConfigGenerator, LISTEN_ADDRESS, NodeGroup, RouteTarget};
 use crate::models::{
     AppSettings, GrpcSettings, H2Settings, ProxyNode, RoutingRule, RuleAction, RuleMatch,
-    ShadowsocksConfig, TransportSettings, TrojanConfig, VlessConfig, VmessConfig, WsSettings,
+    ShadowsocksConfig, TransportSettings, TrojanConfig, UpstreamProtocol, UpstreamProxy,
+    VlessConfig, VmessConfig, WsSettings, bare_host, split_geosite_negation,
 };
 
 const GEOIP_RULESET_URL: &str = "https://raw.githubusercontent.com/SagerNet/sing-geoip/rule-set";
@@ -16,9 +18,11 @@ const GEOSITE_RULESET_URL: &str =
 pub struct SingboxGenerator;
 
 impl ConfigGenerator for SingboxGenerator {
-    fn generate(
+    fn generate_grouped(
         &self,
         nodes: &[ProxyNode],
+        groups: &[NodeGroup],
+        targets: &[RouteTarget],
         rules: &[RoutingRule],
         settings: &AppSettings,
         geodata_dir: Option<&Path>,
@@ -26,57 +30,209 @@ impl ConfigGenerator for SingboxGenerator {
         if nodes.is_empty() {
             return Err(ConfigError::NoNodes);
         }
-        Ok(assemble(nodes, rules, settings, geodata_dir))
+        super::common::check_inbounds(settings)?;
+        if nodes
+            .iter()
+            .any(|n| matches!(n.transport(), Some(TransportSettings::Mkcp(_))))
+        {
+            return Err(ConfigError::Unsupported {
+                backend: "sing-box",
+                feature: "mKCP",
+            });
+        }
+        let upstream = super::common::upstream_proxy(settings)?;
+        let groups = if settings.subscription_groups {
+            groups
+        } else {
+            &[]
+        };
+        Ok(assemble(
+            nodes,
+            groups,
+            targets,
+            rules,
+            settings,
+            upstream,
+            geodata_dir,
+        ))
     }
 }
 
 fn assemble(
     nodes: &[ProxyNode],
+    groups: &[NodeGroup],
+    targets: &[RouteTarget],
     rules: &[RoutingRule],
     settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
     geodata_dir: Option<&Path>,
 ) -> Value {
+    let tags = super::common::node_tags(nodes, targets);
     let inbounds = build_inbounds(settings);
-    let outbounds = build_outbounds(nodes);
-    let route = build_route(rules, geodata_dir);
+    let selectors = build_selectors(&tags, groups);
+    // Proxied traffic goes through the first group so switching it takes effect.
+    let proxy = selectors
+        .first()
+        .and_then(|(_, s)| s["tag"].as_str())
+        .map(str::to_owned)
+        .unwrap_or_else(|| super::common::primary_tag(&tags));
+    let mut outbounds: Vec<Value> = selectors.iter().map(|(_, s)| s.clone()).collect();
+    outbounds.extend(build_outbounds(nodes, &tags, settings, upstream));
+    let mut route = build_route(rules, targets, &selectors, geodata_dir, &proxy);
+    if !settings.socks_udp
+        && let Some(tag) = socks_inbound_tag(settings)
+        && let Some(route_rules) = route["rules"].as_array_mut()
+    {
+        route_rules.insert(0, block_socks_udp_rule(tag));
+    }
+    // Without `final` sing-box falls back to the first outbound, a proxy.
+    if settings.default_action != RuleAction::Proxy {
+        route["final"] = json!(action_outbound(settings.default_action, &proxy));
+    }
 
-    json!({
+    let mut config = json!({
         "log": { "level": "warn" },
         "inbounds": inbounds,
         "outbounds": outbounds,
         "route": route,
-    })
+    });
+    if !selectors.is_empty() {
+        config["experimental"] = json!({
+            "clash_api": {
+                "external_controller": format!("{LISTEN_ADDRESS}:{}", settings.clash_api_port),
+            },
+        });
+    }
+    config
 }
 
+/// A selector per non-empty group with the group's id, tagged `group-<name>`,
+/// numbered when that is taken, and defaulting to its first member.
+fn build_selectors(node_tags: &[String], groups: &[NodeGroup]) -> Vec<(Uuid, Value)> {
+    let names: Vec<String> = groups.iter().map(|g| format!("group-{}", g.name)).collect();
+    let mut tags: Vec<String> = Vec::new();
+    let mut selectors = Vec::new();
+    for group in groups {
+        let members: Vec<String> = group
+            .members
+            .iter()
+            .filter_map(|&i| node_tags.get(i).cloned())
+            .collect();
+        let Some(default) = members.first().cloned() else {
+            continue;
+        };
+        let name = format!("group-{}", group.name);
+        let mut tag = name.clone();
+        // A numbered tag must not be another group's own name either.
+        for n in 2.. {
+            if !tags.contains(&tag) && (tag == name || !names.contains(&tag)) {
+                break;
+            }
+            tag = format!("{name}-{n}");
+        }
+        tags.push(tag.clone());
+        selectors.push((
+            group.id,
+            json!({
+                "type": "selector",
+                "tag": tag,
+                "outbounds": members,
+                "default": default,
+            }),
+        ));
+    }
+    selectors
+}
+
+/// With both enabled, the mixed inbound serves SOCKS and HTTP on the SOCKS
+/// port; a separate HTTP inbound keeps the configured HTTP port working when
+/// it differs.
 fn build_inbounds(settings: &AppSettings) -> Value {
-    json!([{
-        "type": "mixed",
-        "tag": "mixed-in",
-        "listen": "127.0.0.1",
-        "listen_port": settings.socks_port,
-    }])
+    let inbound = |kind: &str, port: u16| {
+        json!({
+            "type": kind,
+            "tag": format!("{kind}-in"),
+            "listen": LISTEN_ADDRESS,
+            "listen_port": port,
+        })
+    };
+    let inbounds = match (settings.enable_socks, settings.enable_http) {
+        (true, true) if settings.http_port != settings.socks_port => vec![
+            inbound("mixed", settings.socks_port),
+            inbound("http", settings.http_port),
+        ],
+        (true, true) => vec![inbound("mixed", settings.socks_port)],
+        (true, false) => vec![inbound("socks", settings.socks_port)],
+        (false, _) => vec![inbound("http", settings.http_port)],
+    };
+    Value::Array(inbounds)
+}
+
+fn socks_inbound_tag(settings: &AppSettings) -> Option<&'static str> {
+    match (settings.enable_socks, settings.enable_http) {
+        (true, true) => Some("mixed-in"),
+        (true, false) => Some("socks-in"),
+        (false, _) => None,
+    }
 }
 
-fn build_outbounds(nodes: &[ProxyNode]) -> Value {
+/// sing-box inbounds have no UDP switch, so SOCKS UDP is refused by routing it to block.
+fn block_socks_udp_rule(inbound: &str) -> Value {
+    json!({
+        "inbound": [inbound],
+        "network": "udp",
+        "outbound": "block",
+    })
+}
+
+fn build_outbounds(
+    nodes: &[ProxyNode],
+    tags: &[String],
+    settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
+) -> Vec<Value> {
     let mut outbounds: Vec<Value> = nodes
         .iter()
-        .enumerate()
-        .map(|(i, node)| {
-            let tag = super::common::outbound_tag(node, i);
-            build_outbound(node, &tag)
-        })
+        .zip(tags)
+        .map(|(node, tag)| build_outbound(node, tag))
         .collect();
 
-    outbounds.push(json!({
-        "type": "direct",
-        "tag": "direct",
-    }));
+    outbounds.push(match upstream {
+        Some(proxy) => upstream_outbound(proxy),
+        None => {
+            let mut direct = json!({
+                "type": "direct",
+                "tag": "direct",
+            });
+            if !settings.ipv6 {
+                direct["domain_strategy"] = json!("ipv4_only");
+            }
+            direct
+        }
+    });
     outbounds.push(json!({
         "type": "block",
         "tag": "block",
     }));
 
-    Value::Array(outbounds)
+    outbounds
+}
+
+/// Takes the `direct` tag so rules and rule-set downloads that go direct
+/// dial through the upstream proxy instead.
+fn upstream_outbound(proxy: &UpstreamProxy) -> Value {
+    let mut out = match proxy.protocol {
+        UpstreamProtocol::Socks => json!({ "type": "socks", "version": "5" }),
+        UpstreamProtocol::Http => json!({ "type": "http" }),
+    };
+    out["tag"] = json!("direct");
+    out["server"] = json!(bare_host(&proxy.host));
+    out["server_port"] = json!(proxy.port);
+    if let Some(user) = &proxy.username {
+        out["username"] = json!(user);
+        out["password"] = json!(proxy.password.as_deref().unwrap_or(""));
+    }
+    out
 }
 
 fn build_outbound(node: &ProxyNode, tag: &str) -> Value {
@@ -92,7 +248,7 @@ fn build_vless(c: &VlessConfig, tag: &str) -> Value {
     let mut out = json!({
         "type": "vless",
         "tag": tag,
-        "server": c.address,
+        "server": bare_host(&c.address),
         "server_port": c.port,
         "uuid": c.uuid,
     });
@@ -110,7 +266,7 @@ fn build_vmess(c: &VmessConfig, tag: &str) -> Value {
     let mut out = json!({
         "type": "vmess",
         "tag": tag,
-        "server": c.address,
+        "server": bare_host(&c.address),
         "server_port": c.port,
         "uuid": c.uuid,
         "alter_id": c.alter_id,
@@ -126,7 +282,7 @@ fn build_ss(c: &ShadowsocksConfig, tag: &str) -> Value {
     json!({
         "type": "shadowsocks",
         "tag": tag,
-        "server": c.address,
+        "server": bare_host(&c.address),
         "server_port": c.port,
         "method": c.method,
         "password": c.password,
@@ -137,7 +293,7 @@ fn build_trojan(c: &TrojanConfig, tag: &str) -> Value {
     let mut out = json!({
         "type": "trojan",
         "tag": tag,
-        "server": c.address,
+        "server": bare_host(&c.address),
         "server_port": c.port,
         "password": c.password,
     });
@@ -159,6 +315,8 @@ fn apply_transport(out: &mut Value, transport: &TransportSettings) {
         TransportSettings::H2(h2) => {
             out["transport"] = build_h2_transport(h2);
         }
+        // Rejected in `generate_grouped`.
+        TransportSettings::Mkcp(_) => {}
     }
 }
 
@@ -167,15 +325,15 @@ fn build_ws_transport(ws: &WsSettings) -> Value {
         "type": "ws",
         "path": ws.path,
     });
-    if let Some(host) = &ws.host {
-        transport["headers"] = json!({ "Host": host });
-    }
-    if !ws.headers.is_empty() {
-        transport["headers"] = json!(ws.headers);
+    let headers = super::common::ws_headers(ws);
+    if !headers.is_empty() {
+        transport["headers"] = json!(headers);
     }
     transport
 }
 
+/// sing-box always uses gun mode and has no `authority` override, so both
+/// are dropped here.
 fn build_grpc_transport(grpc: &GrpcSettings) -> Value {
     json!({
         "type": "grpc",
@@ -207,11 +365,28 @@ fn apply_tls(out: &mut Value, tls: Option<&crate::models::TlsSettings>) {
     if !tls_cfg.verify {
         tls_obj["insecure"] = json!(true);
     }
+    if let Some(reality) = &tls_cfg.reality {
+        tls_obj["reality"] = json!({
+            "enabled": true,
+            "public_key": reality.public_key,
+            "short_id": reality.short_id.as_deref().unwrap_or(""),
+        });
+        tls_obj["utls"] = json!({
+            "enabled": true,
+            "fingerprint": tls_cfg.fingerprint.as_deref().unwrap_or("chrome"),
+        });
+    }
 
     out["tls"] = tls_obj;
 }
 
-fn build_route(rules: &[RoutingRule], _geodata_dir: Option<&Path>) -> Value {
+fn build_route(
+    rules: &[RoutingRule],
+    targets: &[RouteTarget],
+    selectors: &[(Uuid, Value)],
+    _geodata_dir: Option<&Path>,
+    proxy: &str,
+) -> Value {
     let enabled: Vec<&RoutingRule> = rules.iter().filter(|r| r.enabled).collect();
 
     if enabled.is_empty() {
@@ -227,7 +402,8 @@ fn build_route(rules: &[RoutingRule], _geodata_dir: Option<&Path>) -> Value {
                 geoip_tags.insert(country_code.to_lowercase());
             }
             RuleMatch::GeoSite { category } => {
-                geosite_tags.insert(category.to_lowercase());
+                let (_, base) = split_geosite_negation(category);
+                geosite_tags.insert(base.to_lowercase());
             }
             _ => {}
         }
@@ -254,7 +430,10 @@ fn build_route(rules: &[RoutingRule], _geodata_dir: Option<&Path>) -> Value {
         }));
     }
 
-    let route_rules: Vec<Value> = enabled.iter().map(|r| build_route_rule(r)).collect();
+    let route_rules: Vec<Value> = enabled
+        .iter()
+        .map(|r| build_route_rule(r, &rule_proxy(r, targets, selectors, proxy)))
+        .collect();
 
     if rule_sets.is_empty() {
         json!({ "rules": route_rules })
@@ -266,22 +445,50 @@ fn build_route(rules: &[RoutingRule], _geodata_dir: Option<&Path>) -> Value {
     }
 }
 
-fn build_route_rule(rule: &RoutingRule) -> Value {
-    let outbound = match rule.action {
-        RuleAction::Proxy => "proxy-0",
+fn action_outbound(action: RuleAction, proxy: &str) -> &str {
+    match action {
+        RuleAction::Proxy => proxy,
         RuleAction::Direct => "direct",
         RuleAction::Block => "block",
-    };
+    }
+}
+
+/// Where `rule` sends proxied matches: the selector of the group it targets,
+/// or else as for other backends.
+fn rule_proxy(
+    rule: &RoutingRule,
+    targets: &[RouteTarget],
+    selectors: &[(Uuid, Value)],
+    proxy: &str,
+) -> String {
+    let group = rule
+        .target
+        .and_then(|id| selectors.iter().find(|(group, _)| *group == id));
+    match group.and_then(|(_, s)| s["tag"].as_str()) {
+        Some(tag) => tag.to_owned(),
+        None => super::common::rule_proxy(rule, targets, proxy),
+    }
+}
+
+fn build_route_rule(rule: &RoutingRule, proxy: &str) -> Value {
+    let outbound = action_outbound(rule.action, proxy);
 
     match &rule.match_condition {
         RuleMatch::GeoIp { country_code } => json!({
             "rule_set": [format!("geoip-{}", country_code.to_lowercase())],
             "outbound": outbound,
         }),
-        RuleMatch::GeoSite { category } => json!({
-            "rule_set": [format!("geosite-{}", category.to_lowercase())],
-            "outbound": outbound,
-        }),
+        RuleMatch::GeoSite { category } => {
+            let (negated, base) = split_geosite_negation(category);
+            let mut out = json!({
+                "rule_set": [format!("geosite-{}", base.to_lowercase())],
+                "outbound": outbound,
+            });
+            if negated {
+                out["invert"] = json!(true);
+            }
+            out
+        }
         RuleMatch::Domain { pattern } => json!({
             "domain_suffix": [pattern],
             "outbound": outbound,
@@ -309,6 +516,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_singbox_rejects_mkcp() {
+        let result = SingboxGenerator.generate(
+            &[ss_node(), mkcp_vmess_node()],
+            &[],
+            &default_settings(),
+            None,
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Unsupported {
+                feature: "mKCP",
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_singbox_basic_structure() {
         let generator = SingboxGenerator;
@@ -330,9 +554,119 @@ mod tests {
             .unwrap();
 
         let inbounds = config["inbounds"].as_array().unwrap();
-        assert_eq!(inbounds.len(), 1);
+        assert_eq!(inbounds.len(), 2);
         assert_eq!(inbounds[0]["type"], "mixed");
         assert_eq!(inbounds[0]["listen_port"], 1080);
+        assert_eq!(inbounds[1]["type"], "http");
+        assert_eq!(inbounds[1]["listen"], LISTEN_ADDRESS);
+        assert_eq!(inbounds[1]["listen_port"], 1081);
+    }
+
+    #[test]
+    fn test_singbox_socks_udp_toggle() {
+        let mut settings = default_settings();
+        let config = SingboxGenerator
+            .generate(&[ss_node()], &[], &settings, None)
+            .unwrap();
+        assert!(config["route"]["rules"].as_array().unwrap().is_empty());
+
+        settings.socks_udp = false;
+        let rule = RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::GeoIp {
+                country_code: "RU".into(),
+            },
+            action: RuleAction::Direct,
+            enabled: true,
+            target: None,
+        };
+        let config = SingboxGenerator
+            .generate(&[ss_node()], &[rule], &settings, None)
+            .unwrap();
+        let rules = config["route"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["inbound"][0], "mixed-in");
+        assert_eq!(rules[0]["network"], "udp");
+        assert_eq!(rules[0]["outbound"], "block");
+    }
+
+    #[test]
+    fn test_singbox_inbound_types_follow_enabled_inbounds() {
+        let inbounds = |enable_socks: bool, enable_http: bool| {
+            let settings = AppSettings {
+                enable_socks,
+                enable_http,
+                ..default_settings()
+            };
+            let config = SingboxGenerator
+                .generate(&[ss_node()], &[], &settings, None)
+                .unwrap();
+            config["inbounds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|i| (i["type"].clone(), i["listen_port"].clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            inbounds(true, true),
+            [(json!("mixed"), json!(1080)), (json!("http"), json!(1081))]
+        );
+        assert_eq!(inbounds(true, false), [(json!("socks"), json!(1080))]);
+        assert_eq!(inbounds(false, true), [(json!("http"), json!(1081))]);
+    }
+
+    #[test]
+    fn test_singbox_socks_udp_block_targets_socks_inbound() {
+        let settings = AppSettings {
+            enable_http: false,
+            socks_udp: false,
+            ..default_settings()
+        };
+        let config = SingboxGenerator
+            .generate(&[ss_node()], &[], &settings, None)
+            .unwrap();
+        assert_eq!(config["route"]["rules"][0]["inbound"][0], "socks-in");
+
+        let settings = AppSettings {
+            enable_socks: false,
+            enable_http: true,
+            ..settings
+        };
+        let config = SingboxGenerator
+            .generate(&[ss_node()], &[], &settings, None)
+            .unwrap();
+        assert!(config["route"]["rules"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_singbox_rejects_no_inbounds() {
+        let settings = AppSettings {
+            enable_socks: false,
+            enable_http: false,
+            ..default_settings()
+        };
+        assert!(matches!(
+            SingboxGenerator.generate(&[ss_node()], &[], &settings, None),
+            Err(ConfigError::Inbounds(ValidationError::NoInbounds))
+        ));
+    }
+
+    #[test]
+    fn test_singbox_no_http_inbound_when_ports_equal() {
+        let settings = AppSettings {
+            socks_port: 2080,
+            http_port: 2080,
+            ..AppSettings::default()
+        };
+        let config = SingboxGenerator
+            .generate(&[ss_node()], &[], &settings, None)
+            .unwrap();
+
+        let inbounds = config["inbounds"].as_array().unwrap();
+        assert_eq!(inbounds.len(), 1);
+        assert_eq!(inbounds[0]["type"], "mixed");
+        assert_eq!(inbounds[0]["listen_port"], 2080);
     }
 
     #[test]
@@ -348,6 +682,25 @@ mod tests {
         assert_eq!(out["method"], "aes-256-gcm");
     }
 
+    #[test]
+    fn test_singbox_ipv6_server_is_bare() {
+        let ProxyNode::Shadowsocks(mut ss) = ss_node() else {
+            unreachable!()
+        };
+        ss.address = "[2001:db8::1]".into();
+
+        let config = SingboxGenerator
+            .generate(
+                &[ProxyNode::Shadowsocks(ss)],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(config["outbounds"][0]["server"], "2001:db8::1");
+        assert_eq!(config["outbounds"][0]["server_port"], 8388);
+    }
+
     #[test]
     fn test_singbox_vless_with_ws_tls() {
         let generator = SingboxGenerator;
@@ -376,6 +729,47 @@ mod tests {
         assert_eq!(out["tls"]["enabled"], true);
     }
 
+    #[test]
+    fn test_singbox_unverified_tls_is_insecure() {
+        let config = SingboxGenerator
+            .generate(
+                &[insecure_trojan_node(), trojan_node()],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(config["outbounds"][0]["tls"]["insecure"], true);
+        assert!(config["outbounds"][1]["tls"].get("insecure").is_none());
+    }
+
+    #[test]
+    fn test_singbox_trojan_over_ws_and_grpc() {
+        let config = SingboxGenerator
+            .generate(
+                &[trojan_ws_node(), trojan_grpc_node()],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+
+        let ws = &config["outbounds"][0];
+        assert_eq!(ws["type"], "trojan");
+        assert_eq!(ws["transport"]["type"], "ws");
+        assert_eq!(ws["transport"]["path"], "/trojan");
+        assert_eq!(ws["transport"]["headers"]["Host"], "cdn.example.com");
+        assert_eq!(ws["transport"]["headers"]["X-Token"], "abc");
+        assert_eq!(ws["tls"]["enabled"], true);
+        assert_eq!(ws["tls"]["server_name"], "trojan.example.com");
+
+        let grpc = &config["outbounds"][1];
+        assert_eq!(grpc["transport"]["type"], "grpc");
+        assert_eq!(grpc["transport"]["service_name"], "trojan-grpc");
+        assert_eq!(grpc["tls"]["enabled"], true);
+    }
+
     #[test]
     fn test_singbox_direct_block_outbounds() {
         let generator = SingboxGenerator;
@@ -402,6 +796,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -427,6 +822,29 @@ mod tests {
         assert_eq!(rule_sets[0]["download_detour"], "direct");
     }
 
+    #[test]
+    fn test_singbox_negated_geosite_route() {
+        let rules = vec![RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::GeoSite {
+                category: "!cn".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let config = SingboxGenerator
+            .generate(&[vless_node()], &rules, &default_settings(), None)
+            .unwrap();
+
+        let route_rules = config["route"]["rules"].as_array().unwrap();
+        assert_eq!(route_rules[0]["rule_set"][0], "geosite-cn");
+        assert_eq!(route_rules[0]["invert"], true);
+        let rule_sets = config["route"]["rule_set"].as_array().unwrap();
+        assert_eq!(rule_sets.len(), 1);
+        assert_eq!(rule_sets[0]["tag"], "geosite-cn");
+    }
+
     #[test]
     fn test_singbox_geosite_route() {
         let generator = SingboxGenerator;
@@ -437,6 +855,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -471,6 +890,7 @@ mod tests {
                 },
                 action: RuleAction::Direct,
                 enabled: false,
+                target: None,
             },
             RoutingRule {
                 id: uuid::Uuid::new_v4(),
@@ -479,6 +899,7 @@ mod tests {
                 },
                 action: RuleAction::Proxy,
                 enabled: true,
+                target: None,
             },
         ];
 
@@ -501,6 +922,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -509,4 +931,224 @@ mod tests {
         let json_str = serde_json::to_string_pretty(&config).unwrap();
         let _: Value = serde_json::from_str(&json_str).unwrap();
     }
+
+    #[test]
+    fn test_singbox_upstream_proxy_replaces_direct() {
+        let settings = AppSettings {
+            upstream_proxy: Some(UpstreamProxy {
+                protocol: UpstreamProtocol::Socks,
+                host: "10.0.0.1".into(),
+                port: 1080,
+                username: Some("alice".into()),
+                password: Some("secret".into()),
+            }),
+            ..default_settings()
+        };
+        let config = SingboxGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+
+        let outbounds = config["outbounds"].as_array().unwrap();
+        let direct: Vec<&Value> = outbounds.iter().filter(|o| o["tag"] == "direct").collect();
+        assert_eq!(direct.len(), 1);
+        assert_eq!(direct[0]["type"], "socks");
+        assert_eq!(direct[0]["version"], "5");
+        assert_eq!(direct[0]["server"], "10.0.0.1");
+        assert_eq!(direct[0]["server_port"], 1080);
+        assert_eq!(direct[0]["username"], "alice");
+        assert_eq!(direct[0]["password"], "secret");
+        assert!(!outbounds.iter().any(|o| o["type"] == "direct"));
+    }
+
+    #[test]
+    fn test_singbox_rejects_invalid_upstream_proxy() {
+        let settings = AppSettings {
+            upstream_proxy: Some(UpstreamProxy {
+                protocol: UpstreamProtocol::Http,
+                host: "".into(),
+                port: 3128,
+                username: None,
+                password: None,
+            }),
+            ..default_settings()
+        };
+        let result = SingboxGenerator.generate(&[vless_node()], &[], &settings, None);
+        assert!(matches!(result, Err(ConfigError::Upstream(_))));
+    }
+
+    #[test]
+    fn test_singbox_direct_ipv4_only_when_ipv6_disabled() {
+        let direct_strategy = |ipv6: bool| {
+            let settings = AppSettings {
+                ipv6,
+                ..default_settings()
+            };
+            let config = SingboxGenerator
+                .generate(&[vless_node()], &[], &settings, None)
+                .unwrap();
+            config["outbounds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|o| o["tag"] == "direct")
+                .unwrap()
+                .get("domain_strategy")
+                .cloned()
+        };
+        assert_eq!(direct_strategy(true), None);
+        assert_eq!(direct_strategy(false), Some(json!("ipv4_only")));
+    }
+
+    #[test]
+    fn test_singbox_route_final_follows_default_action() {
+        let route_final = |default_action: RuleAction| {
+            let settings = AppSettings {
+                default_action,
+                ..default_settings()
+            };
+            let config = SingboxGenerator
+                .generate(&[vless_node()], &[], &settings, None)
+                .unwrap();
+            config["route"].get("final").cloned()
+        };
+        assert_eq!(route_final(RuleAction::Proxy), None);
+        assert_eq!(route_final(RuleAction::Direct), Some(json!("direct")));
+        assert_eq!(route_final(RuleAction::Block), Some(json!("block")));
+    }
+
+    #[test]
+    fn test_singbox_subscription_group_selectors() {
+        let nodes = [vless_node(), ss_node(), trojan_node()];
+        let groups = [
+            NodeGroup {
+                id: uuid::Uuid::new_v4(),
+                name: "Work".into(),
+                members: vec![0, 2],
+            },
+            NodeGroup {
+                id: uuid::Uuid::new_v4(),
+                name: "Home".into(),
+                members: vec![1],
+            },
+        ];
+        let rules = [RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.org".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let mut settings = AppSettings {
+            subscription_groups: true,
+            ..default_settings()
+        };
+
+        let config = SingboxGenerator
+            .generate_grouped(&nodes, &groups, &[], &rules, &settings, None)
+            .unwrap();
+        let outbounds = config["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds[0]["type"], "selector");
+        assert_eq!(outbounds[0]["tag"], "group-Work");
+        assert_eq!(
+            outbounds[0]["outbounds"],
+            json!(["proxy-0-Test-VLESS", "proxy-2-Test-Trojan"])
+        );
+        assert_eq!(outbounds[0]["default"], "proxy-0-Test-VLESS");
+        assert_eq!(outbounds[1]["tag"], "group-Home");
+        assert_eq!(outbounds[1]["outbounds"], json!(["proxy-1-Test-SS"]));
+        assert_eq!(config["route"]["rules"][0]["outbound"], "group-Work");
+        assert_eq!(
+            config["experimental"]["clash_api"]["external_controller"],
+            "127.0.0.1:9090"
+        );
+
+        settings.subscription_groups = false;
+        let config = SingboxGenerator
+            .generate_grouped(&nodes, &groups, &[], &rules, &settings, None)
+            .unwrap();
+        assert_eq!(config["outbounds"][0]["type"], "vless");
+        assert!(config.get("experimental").is_none());
+    }
+
+    #[test]
+    fn test_singbox_rules_target_groups() {
+        let nodes = [vless_node(), ss_node()];
+        let home = uuid::Uuid::new_v4();
+        let groups = [
+            NodeGroup {
+                id: uuid::Uuid::new_v4(),
+                name: "Work".into(),
+                members: vec![0],
+            },
+            NodeGroup {
+                id: home,
+                name: "Home".into(),
+                members: vec![1],
+            },
+        ];
+        let rule = |target| RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.org".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target,
+        };
+        let rules = [
+            rule(Some(home)),
+            rule(None),
+            rule(Some(uuid::Uuid::new_v4())),
+        ];
+        let settings = AppSettings {
+            subscription_groups: true,
+            ..default_settings()
+        };
+
+        let config = SingboxGenerator
+            .generate_grouped(&nodes, &groups, &[], &rules, &settings, None)
+            .unwrap();
+        let route_rules = &config["route"]["rules"];
+        assert_eq!(route_rules[0]["outbound"], "group-Home");
+        assert_eq!(route_rules[1]["outbound"], "group-Work");
+        assert_eq!(route_rules[2]["outbound"], "group-Work");
+    }
+
+    #[test]
+    fn test_singbox_group_tags_stay_unique() {
+        let group = |name: &str, member| NodeGroup {
+            id: uuid::Uuid::new_v4(),
+            name: name.into(),
+            members: vec![member],
+        };
+        let tags = ["proxy-0".to_owned(), "proxy-1".into(), "proxy-2".into()];
+        let groups = [group("A", 0), group("A", 1), group("A-2", 2)];
+
+        let selectors = build_selectors(&tags, &groups);
+        let names: Vec<&str> = selectors
+            .iter()
+            .map(|(_, s)| s["tag"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["group-A", "group-A-3", "group-A-2"]);
+        assert_eq!(selectors[1].0, groups[1].id);
+    }
+
+    #[test]
+    fn test_singbox_clash_api_port_must_differ_from_inbounds() {
+        let mut settings = AppSettings {
+            subscription_groups: true,
+            clash_api_port: 1080,
+            ..default_settings()
+        };
+        settings.backend.backend_type = BackendType::SingBox;
+        let result = SingboxGenerator.generate(&[vless_node()], &[], &settings, None);
+        assert!(matches!(
+            result,
+            Err(ConfigError::Inbounds(ValidationError::ClashApiPortInUse(
+                1080
+            )))
+        ));
+    }
 }
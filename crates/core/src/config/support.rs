@@ -0,0 +1,171 @@
+use crate::models::{
+    BackendType, ProxyNode, TransportSettings, is_ss2022_method, is_vless_encrypted,
+};
+
+/// The only XTLS flow sing-box implements; the rest are Xray-only.
+const SINGBOX_FLOW: &str = "xtls-rprx-vision";
+
+/// The feature of `node` that `backend` cannot express, or `None` when the
+/// generated outbound would work.
+pub fn unsupported_feature(backend: BackendType, node: &ProxyNode) -> Option<&'static str> {
+    match backend {
+        BackendType::Xray => None,
+        BackendType::V2ray => match node {
+            ProxyNode::Vless(c) if c.flow.as_deref().is_some_and(|f| !f.is_empty()) => {
+                Some("XTLS flow")
+            }
+            ProxyNode::Vless(c) if is_vless_encrypted(c.encryption.as_deref()) => {
+                Some("VLESS encryption")
+            }
+            ProxyNode::Shadowsocks(c) if is_ss2022_method(&c.method) => Some("Shadowsocks 2022"),
+            _ if node.tls().is_some_and(|t| t.reality.is_some()) => Some("REALITY"),
+            _ => None,
+        },
+        BackendType::SingBox => match node {
+            ProxyNode::Vless(c)
+                if c.flow
+                    .as_deref()
+                    .is_some_and(|f| !f.is_empty() && f != SINGBOX_FLOW) =>
+            {
+                Some("XTLS flow")
+            }
+            ProxyNode::Vless(c) if is_vless_encrypted(c.encryption.as_deref()) => {
+                Some("VLESS encryption")
+            }
+            _ if matches!(node.transport(), Some(TransportSettings::Mkcp(_))) => Some("mKCP"),
+            _ => None,
+        },
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NodeSupport {
+    pub kept: Vec<ProxyNode>,
+    pub skipped: Vec<ProxyNode>,
+}
+
+/// Splits `nodes` into those `backend` can run and those it would break on.
+pub fn partition_supported(backend: BackendType, nodes: Vec<ProxyNode>) -> NodeSupport {
+    let (kept, skipped) = nodes
+        .into_iter()
+        .partition(|node| unsupported_feature(backend, node).is_none());
+    NodeSupport { kept, skipped }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_fixtures::fixtures::*;
+    use crate::models::*;
+
+    fn vision_node() -> ProxyNode {
+        let ProxyNode::Vless(mut c) = vless_node() else {
+            unreachable!()
+        };
+        c.flow = Some("xtls-rprx-vision".into());
+        ProxyNode::Vless(c)
+    }
+
+    fn reality_node() -> ProxyNode {
+        let mut node = trojan_node();
+        node.tls_mut().unwrap().reality = Some(RealitySettings {
+            public_key: "key".into(),
+            short_id: None,
+            spider_x: None,
+        });
+        node
+    }
+
+    fn ss2022_node() -> ProxyNode {
+        let ProxyNode::Shadowsocks(mut c) = ss_node() else {
+            unreachable!()
+        };
+        c.method = "2022-blake3-aes-128-gcm".into();
+        ProxyNode::Shadowsocks(c)
+    }
+
+    fn encrypted_vless_node() -> ProxyNode {
+        let ProxyNode::Vless(mut c) = vless_node() else {
+            unreachable!()
+        };
+        c.encryption = Some("mlkem768x25519plus.native.0rtt.KEY".into());
+        ProxyNode::Vless(c)
+    }
+
+    fn all_nodes() -> Vec<ProxyNode> {
+        vec![
+            vless_node(),
+            vmess_node(),
+            ss_node(),
+            trojan_node(),
+            vision_node(),
+            reality_node(),
+            ss2022_node(),
+            encrypted_vless_node(),
+        ]
+    }
+
+    #[test]
+    fn test_xray_runs_every_node() {
+        let support = partition_supported(BackendType::Xray, all_nodes());
+        assert_eq!(support.kept, all_nodes());
+        assert!(support.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_v2ray_skips_xray_only_features() {
+        let support = partition_supported(BackendType::V2ray, all_nodes());
+        assert_eq!(
+            support.kept,
+            [vless_node(), vmess_node(), ss_node(), trojan_node()]
+        );
+        assert_eq!(
+            support.skipped,
+            [
+                vision_node(),
+                reality_node(),
+                ss2022_node(),
+                encrypted_vless_node()
+            ]
+        );
+        assert_eq!(
+            unsupported_feature(BackendType::V2ray, &reality_node()),
+            Some("REALITY")
+        );
+    }
+
+    #[test]
+    fn test_singbox_skips_unknown_flows_and_vless_encryption() {
+        let ProxyNode::Vless(mut c) = vless_node() else {
+            unreachable!()
+        };
+        c.flow = Some("xtls-rprx-direct".into());
+        let legacy_flow = ProxyNode::Vless(c);
+
+        let mut nodes = all_nodes();
+        nodes.push(legacy_flow.clone());
+        let support = partition_supported(BackendType::SingBox, nodes);
+        assert_eq!(support.kept, all_nodes()[..7]);
+        assert_eq!(support.skipped, [encrypted_vless_node(), legacy_flow]);
+        assert_eq!(
+            unsupported_feature(BackendType::SingBox, &encrypted_vless_node()),
+            Some("VLESS encryption")
+        );
+    }
+
+    #[test]
+    fn test_only_singbox_skips_mkcp() {
+        assert_eq!(
+            unsupported_feature(BackendType::SingBox, &mkcp_vmess_node()),
+            Some("mKCP")
+        );
+        assert_eq!(
+            unsupported_feature(BackendType::V2ray, &mkcp_vmess_node()),
+            None
+        );
+        assert_eq!(
+            unsupported_feature(BackendType::Xray, &mkcp_vmess_node()),
+            None
+        );
+    }
+}
@@ -23,6 +23,7 @@ pub(crate) mod fixtures {
                 alpn: vec!["h2".into()],
                 verify: true,
                 fingerprint: None,
+                reality: None,
             }),
             remark: Some("Test VLESS".into()),
         })
@@ -62,8 +63,54 @@ pub(crate) mod fixtures {
                 alpn: vec![],
                 verify: true,
                 fingerprint: None,
+                reality: None,
             }),
             remark: Some("Test Trojan".into()),
         })
     }
+
+    pub fn insecure_trojan_node() -> ProxyNode {
+        let ProxyNode::Trojan(mut c) = trojan_node() else {
+            unreachable!()
+        };
+        if let Some(tls) = c.tls.as_mut() {
+            tls.verify = false;
+        }
+        ProxyNode::Trojan(c)
+    }
+
+    pub fn trojan_ws_node() -> ProxyNode {
+        let ProxyNode::Trojan(mut c) = trojan_node() else {
+            unreachable!()
+        };
+        c.transport = TransportSettings::Ws(WsSettings {
+            path: "/trojan".into(),
+            host: Some("cdn.example.com".into()),
+            headers: [("X-Token".to_owned(), "abc".to_owned())].into(),
+        });
+        ProxyNode::Trojan(c)
+    }
+
+    pub fn trojan_grpc_node() -> ProxyNode {
+        let ProxyNode::Trojan(mut c) = trojan_node() else {
+            unreachable!()
+        };
+        c.transport = TransportSettings::Grpc(GrpcSettings {
+            service_name: "trojan-grpc".into(),
+            multi_mode: false,
+            authority: None,
+        });
+        ProxyNode::Trojan(c)
+    }
+
+    pub fn mkcp_vmess_node() -> ProxyNode {
+        let ProxyNode::Vmess(mut c) = vmess_node() else {
+            unreachable!()
+        };
+        c.transport = TransportSettings::Mkcp(MkcpSettings {
+            header_type: Some("wechat-video".into()),
+            seed: Some("s3cret".into()),
+        });
+        ProxyNode::Vmess(c)
+    }
 }
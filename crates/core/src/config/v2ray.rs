@@ -2,18 +2,21 @@ use std::path::Path;
 
 use serde_json::{Value, json};
 
-use crate::config::{ConfigError, ConfigGenerator};
+use crate::config::{ConfigError, ConfigGenerator, LISTEN_ADDRESS, NodeGroup, RouteTarget};
 use crate::models::{
-    AppSettings, GrpcSettings, H2Settings, ProxyNode, RoutingRule, RuleAction, RuleMatch,
-    ShadowsocksConfig, TransportSettings, TrojanConfig, VlessConfig, VmessConfig, WsSettings,
+    AppSettings, GrpcSettings, H2Settings, MkcpSettings, ProxyNode, RoutingRule, RuleAction,
+    RuleMatch, ShadowsocksConfig, TlsSettings, TransportSettings, TrojanConfig, UpstreamProtocol,
+    UpstreamProxy, VlessConfig, VmessConfig, WsSettings, bare_host, split_geosite_negation,
 };
 
 pub struct V2rayGenerator;
 
 impl ConfigGenerator for V2rayGenerator {
-    fn generate(
+    fn generate_grouped(
         &self,
         nodes: &[ProxyNode],
+        _groups: &[NodeGroup],
+        targets: &[RouteTarget],
         rules: &[RoutingRule],
         settings: &AppSettings,
         _geodata_dir: Option<&Path>,
@@ -21,14 +24,24 @@ impl ConfigGenerator for V2rayGenerator {
         if nodes.is_empty() {
             return Err(ConfigError::NoNodes);
         }
-        Ok(assemble(nodes, rules, settings))
+        super::common::check_inbounds(settings)?;
+        let upstream = super::common::upstream_proxy(settings)?;
+        Ok(assemble(nodes, targets, rules, settings, upstream))
     }
 }
 
-fn assemble(nodes: &[ProxyNode], rules: &[RoutingRule], settings: &AppSettings) -> Value {
+fn assemble(
+    nodes: &[ProxyNode],
+    targets: &[RouteTarget],
+    rules: &[RoutingRule],
+    settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
+) -> Value {
+    let tags = super::common::node_tags(nodes, targets);
     let inbounds = build_inbounds(settings);
-    let outbounds = build_outbounds(nodes);
-    let routing = build_routing(rules);
+    let outbounds = build_outbounds(nodes, &tags, settings, upstream);
+    let proxy = super::common::primary_tag(&tags);
+    let routing = build_routing(rules, targets, settings.default_action, &proxy);
 
     json!({
         "log": { "loglevel": "warning" },
@@ -38,49 +51,92 @@ fn assemble(nodes: &[ProxyNode], rules: &[RoutingRule], settings: &AppSettings)
     })
 }
 
-fn build_inbounds(settings: &AppSettings) -> Value {
-    json!([
-        {
+pub(crate) fn build_inbounds(settings: &AppSettings) -> Value {
+    let mut inbounds = Vec::new();
+    if settings.enable_socks {
+        inbounds.push(json!({
             "tag": "socks-in",
             "protocol": "socks",
-            "listen": "127.0.0.1",
+            "listen": LISTEN_ADDRESS,
             "port": settings.socks_port,
-            "settings": { "udp": true },
-        },
-        {
+            "settings": { "udp": settings.socks_udp },
+        }));
+    }
+    if settings.enable_http {
+        inbounds.push(json!({
             "tag": "http-in",
             "protocol": "http",
-            "listen": "127.0.0.1",
+            "listen": LISTEN_ADDRESS,
             "port": settings.http_port,
-        },
-    ])
+        }));
+    }
+    Value::Array(inbounds)
 }
 
-fn build_outbounds(nodes: &[ProxyNode]) -> Value {
+fn build_outbounds(
+    nodes: &[ProxyNode],
+    tags: &[String],
+    settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
+) -> Value {
     let mut outbounds: Vec<Value> = nodes
         .iter()
-        .enumerate()
-        .map(|(i, node)| {
-            let tag = super::common::outbound_tag(node, i);
-            build_outbound(node, &tag)
-        })
+        .zip(tags)
+        .map(|(node, tag)| build_outbound(node, tag))
         .collect();
 
-    outbounds.push(json!({
-        "tag": "direct",
-        "protocol": "freedom",
-        "settings": {},
-    }));
-    outbounds.push(json!({
-        "tag": "block",
-        "protocol": "blackhole",
-        "settings": {},
-    }));
-
+    outbounds.extend(builtin_outbounds(settings, upstream));
     Value::Array(outbounds)
 }
 
-fn build_outbound(node: &ProxyNode, tag: &str) -> Value {
+/// `direct` and `block`; with an upstream proxy, `direct` dials through it.
+pub(crate) fn builtin_outbounds(
+    settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
+) -> [Value; 2] {
+    let direct = match upstream {
+        Some(proxy) => upstream_outbound(proxy),
+        None => json!({
+            "tag": "direct",
+            "protocol": "freedom",
+            "settings": {
+                "domainStrategy": if settings.ipv6 { "AsIs" } else { "UseIPv4" },
+            },
+        }),
+    };
+    [
+        direct,
+        json!({
+            "tag": "block",
+            "protocol": "blackhole",
+            "settings": {},
+        }),
+    ]
+}
+
+fn upstream_outbound(proxy: &UpstreamProxy) -> Value {
+    let protocol = match proxy.protocol {
+        UpstreamProtocol::Socks => "socks",
+        UpstreamProtocol::Http => "http",
+    };
+    let mut server = json!({
+        "address": bare_host(&proxy.host),
+        "port": proxy.port,
+    });
+    if let Some(user) = &proxy.username {
+        server["users"] = json!([{
+            "user": user,
+            "pass": proxy.password.as_deref().unwrap_or(""),
+        }]);
+    }
+    json!({
+        "tag": "direct",
+        "protocol": protocol,
+        "settings": { "servers": [server] },
+    })
+}
+
+pub(crate) fn build_outbound(node: &ProxyNode, tag: &str) -> Value {
     match node {
         ProxyNode::Vless(c) => build_vless_outbound(c, tag),
         ProxyNode::Vmess(c) => build_vmess_outbound(c, tag),
@@ -92,7 +148,7 @@ fn build_outbound(node: &ProxyNode, tag: &str) -> Value {
 fn build_vless_outbound(c: &VlessConfig, tag: &str) -> Value {
     let mut user = json!({
         "id": c.uuid,
-        "encryption": c.encryption.as_deref().unwrap_or("none"),
+        "encryption": super::common::vless_encryption(c),
     });
     if let Some(flow) = &c.flow {
         user["flow"] = json!(flow);
@@ -103,7 +159,7 @@ fn build_vless_outbound(c: &VlessConfig, tag: &str) -> Value {
         "protocol": "vless",
         "settings": {
             "vnext": [{
-                "address": c.address,
+                "address": bare_host(&c.address),
                 "port": c.port,
                 "users": [user],
             }],
@@ -120,7 +176,7 @@ fn build_vmess_outbound(c: &VmessConfig, tag: &str) -> Value {
         "protocol": "vmess",
         "settings": {
             "vnext": [{
-                "address": c.address,
+                "address": bare_host(&c.address),
                 "port": c.port,
                 "users": [{
                     "id": c.uuid,
@@ -141,7 +197,7 @@ fn build_ss_outbound(c: &ShadowsocksConfig, tag: &str) -> Value {
         "protocol": "shadowsocks",
         "settings": {
             "servers": [{
-                "address": c.address,
+                "address": bare_host(&c.address),
                 "port": c.port,
                 "method": c.method,
                 "password": c.password,
@@ -156,7 +212,7 @@ fn build_trojan_outbound(c: &TrojanConfig, tag: &str) -> Value {
         "protocol": "trojan",
         "settings": {
             "servers": [{
-                "address": c.address,
+                "address": bare_host(&c.address),
                 "port": c.port,
                 "password": c.password,
             }],
@@ -170,8 +226,18 @@ fn build_trojan_outbound(c: &TrojanConfig, tag: &str) -> Value {
 fn apply_stream_settings(
     outbound: &mut Value,
     transport: &TransportSettings,
-    tls: Option<&crate::models::TlsSettings>,
+    tls: Option<&TlsSettings>,
 ) {
+    let mut stream = transport_stream(transport);
+    if let Some(tls_cfg) = tls {
+        stream["security"] = json!("tls");
+        stream["tlsSettings"] = tls_settings(tls_cfg);
+    }
+    outbound["streamSettings"] = stream;
+}
+
+/// `streamSettings` carrying only the transport, without any security layer.
+pub(crate) fn transport_stream(transport: &TransportSettings) -> Value {
     let mut stream = json!({});
 
     match transport {
@@ -190,42 +256,57 @@ fn apply_stream_settings(
             stream["network"] = json!("h2");
             stream["httpSettings"] = build_h2_settings(h2);
         }
-    }
-
-    if let Some(tls_cfg) = tls {
-        stream["security"] = json!("tls");
-        let mut tls_obj = json!({});
-        if let Some(sni) = &tls_cfg.server_name {
-            tls_obj["serverName"] = json!(sni);
-        }
-        if !tls_cfg.alpn.is_empty() {
-            tls_obj["alpn"] = json!(tls_cfg.alpn);
+        TransportSettings::Mkcp(kcp) => {
+            stream["network"] = json!("kcp");
+            stream["kcpSettings"] = build_kcp_settings(kcp);
         }
-        tls_obj["allowInsecure"] = json!(!tls_cfg.verify);
-        if let Some(fp) = &tls_cfg.fingerprint {
-            tls_obj["fingerprint"] = json!(fp);
-        }
-        stream["tlsSettings"] = tls_obj;
     }
+    stream
+}
 
-    outbound["streamSettings"] = stream;
+pub(crate) fn tls_settings(tls_cfg: &TlsSettings) -> Value {
+    let mut tls_obj = json!({});
+    if let Some(sni) = &tls_cfg.server_name {
+        tls_obj["serverName"] = json!(sni);
+    }
+    if !tls_cfg.alpn.is_empty() {
+        tls_obj["alpn"] = json!(tls_cfg.alpn);
+    }
+    tls_obj["allowInsecure"] = json!(!tls_cfg.verify);
+    if let Some(fp) = &tls_cfg.fingerprint {
+        tls_obj["fingerprint"] = json!(fp);
+    }
+    tls_obj
 }
 
 fn build_ws_settings(ws: &WsSettings) -> Value {
     let mut settings = json!({ "path": ws.path });
-    if !ws.headers.is_empty() {
-        settings["headers"] = json!(ws.headers);
-    } else if let Some(host) = &ws.host {
-        settings["headers"] = json!({ "Host": host });
+    let headers = super::common::ws_headers(ws);
+    if !headers.is_empty() {
+        settings["headers"] = json!(headers);
     }
     settings
 }
 
 fn build_grpc_settings(grpc: &GrpcSettings) -> Value {
-    json!({
+    let mut settings = json!({
         "serviceName": grpc.service_name,
         "multiMode": grpc.multi_mode,
-    })
+    });
+    if let Some(authority) = &grpc.authority {
+        settings["authority"] = json!(authority);
+    }
+    settings
+}
+
+fn build_kcp_settings(kcp: &MkcpSettings) -> Value {
+    let mut settings = json!({
+        "header": { "type": kcp.header_type.as_deref().unwrap_or("none") },
+    });
+    if let Some(seed) = &kcp.seed {
+        settings["seed"] = json!(seed);
+    }
+    settings
 }
 
 fn build_h2_settings(h2: &H2Settings) -> Value {
@@ -235,17 +316,26 @@ fn build_h2_settings(h2: &H2Settings) -> Value {
     })
 }
 
-fn build_routing(rules: &[RoutingRule]) -> Value {
+fn build_routing(
+    rules: &[RoutingRule],
+    targets: &[RouteTarget],
+    default_action: RuleAction,
+    proxy: &str,
+) -> Value {
     let enabled: Vec<&RoutingRule> = rules.iter().filter(|r| r.enabled).collect();
 
     if enabled.is_empty() {
         return json!({
             "domainStrategy": "AsIs",
-            "rules": [],
+            "rules": catch_all_rule(default_action, proxy).into_iter().collect::<Vec<_>>(),
         });
     }
 
-    let routing_rules: Vec<Value> = enabled.iter().map(|r| build_routing_rule(r)).collect();
+    let mut routing_rules: Vec<Value> = enabled
+        .iter()
+        .map(|r| build_routing_rule(r, &super::common::rule_proxy(r, targets, proxy)))
+        .collect();
+    routing_rules.extend(catch_all_rule(default_action, proxy));
 
     json!({
         "domainStrategy": "IPIfNonMatch",
@@ -253,12 +343,30 @@ fn build_routing(rules: &[RoutingRule]) -> Value {
     })
 }
 
-fn build_routing_rule(rule: &RoutingRule) -> Value {
-    let outbound_tag = match rule.action {
-        RuleAction::Proxy => first_proxy_tag(),
+/// Unmatched traffic already falls through to the first outbound, which is a
+/// proxy, so only the direct and block defaults need a rule of their own.
+pub(crate) fn catch_all_rule(action: RuleAction, proxy: &str) -> Option<Value> {
+    (action != RuleAction::Proxy).then(|| {
+        json!({
+            "type": "field",
+            "network": "tcp,udp",
+            "outboundTag": action_tag(action, proxy),
+        })
+    })
+}
+
+/// `proxy` is the tag of the outbound proxied traffic goes to, exactly as
+/// emitted.
+fn action_tag(action: RuleAction, proxy: &str) -> String {
+    match action {
+        RuleAction::Proxy => proxy.to_string(),
         RuleAction::Direct => "direct".to_string(),
         RuleAction::Block => "block".to_string(),
-    };
+    }
+}
+
+pub(crate) fn build_routing_rule(rule: &RoutingRule, proxy: &str) -> Value {
+    let outbound_tag = action_tag(rule.action, proxy);
 
     match &rule.match_condition {
         RuleMatch::GeoIp { country_code } => json!({
@@ -268,7 +376,7 @@ fn build_routing_rule(rule: &RoutingRule) -> Value {
         }),
         RuleMatch::GeoSite { category } => json!({
             "type": "field",
-            "domain": [format!("geosite:{}", category.to_lowercase())],
+            "domain": [geosite_domain(category)],
             "outboundTag": outbound_tag,
         }),
         RuleMatch::Domain { pattern } => json!({
@@ -284,8 +392,18 @@ fn build_routing_rule(rule: &RoutingRule) -> Value {
     }
 }
 
-fn first_proxy_tag() -> String {
-    "proxy-0".to_string()
+/// v2ray/xray routing cannot invert a matcher, so a negated `!cn` (or
+/// `!geolocation-cn`) is rendered through the geosite list that already holds
+/// everything outside that region; validation allows no other negation.
+fn geosite_domain(category: &str) -> String {
+    let category = category.to_lowercase();
+    match split_geosite_negation(&category) {
+        (true, base) => format!(
+            "geosite:geolocation-!{}",
+            base.trim_start_matches("geolocation-")
+        ),
+        (false, name) => format!("geosite:{name}"),
+    }
 }
 
 #[cfg(test)]
@@ -293,6 +411,7 @@ mod tests {
     use super::*;
     use crate::config::test_fixtures::fixtures::*;
     use crate::models::*;
+    use uuid::Uuid;
 
     #[test]
     fn test_generate_returns_error_on_empty_nodes() {
@@ -329,6 +448,51 @@ mod tests {
         assert_eq!(inbounds[1]["protocol"], "http");
     }
 
+    #[test]
+    fn test_socks_udp_toggle() {
+        let mut settings = default_settings();
+        let config = V2rayGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+        assert_eq!(config["inbounds"][0]["settings"]["udp"], true);
+
+        settings.socks_udp = false;
+        let config = V2rayGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+        assert_eq!(config["inbounds"][0]["settings"]["udp"], false);
+    }
+
+    #[test]
+    fn test_ipv6_addresses_are_emitted_bare() {
+        let ProxyNode::Vless(mut vless) = vless_node() else {
+            unreachable!()
+        };
+        vless.address = "[2001:db8::1]".into();
+        let ProxyNode::Shadowsocks(mut ss) = ss_node() else {
+            unreachable!()
+        };
+        ss.address = "2001:db8::2".into();
+
+        let config = V2rayGenerator
+            .generate(
+                &[ProxyNode::Vless(vless), ProxyNode::Shadowsocks(ss)],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+        let outbounds = config["outbounds"].as_array().unwrap();
+        assert_eq!(
+            outbounds[0]["settings"]["vnext"][0]["address"],
+            "2001:db8::1"
+        );
+        assert_eq!(
+            outbounds[1]["settings"]["servers"][0]["address"],
+            "2001:db8::2"
+        );
+    }
+
     #[test]
     fn test_vless_outbound() {
         let generator = V2rayGenerator;
@@ -389,6 +553,47 @@ mod tests {
         assert_eq!(proxy["streamSettings"]["security"], "tls");
     }
 
+    #[test]
+    fn test_unverified_tls_allows_insecure() {
+        let config = V2rayGenerator
+            .generate(
+                &[insecure_trojan_node(), trojan_node()],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+
+        let tls = |i: usize| &config["outbounds"][i]["streamSettings"]["tlsSettings"];
+        assert_eq!(tls(0)["allowInsecure"], true);
+        assert_eq!(tls(1)["allowInsecure"], false);
+    }
+
+    #[test]
+    fn test_trojan_over_ws_and_grpc() {
+        let config = V2rayGenerator
+            .generate(
+                &[trojan_ws_node(), trojan_grpc_node()],
+                &[],
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+
+        let ws = &config["outbounds"][0]["streamSettings"];
+        assert_eq!(ws["network"], "ws");
+        assert_eq!(ws["wsSettings"]["path"], "/trojan");
+        assert_eq!(ws["wsSettings"]["headers"]["Host"], "cdn.example.com");
+        assert_eq!(ws["wsSettings"]["headers"]["X-Token"], "abc");
+        assert_eq!(ws["security"], "tls");
+        assert_eq!(ws["tlsSettings"]["serverName"], "trojan.example.com");
+
+        let grpc = &config["outbounds"][1]["streamSettings"];
+        assert_eq!(grpc["network"], "grpc");
+        assert_eq!(grpc["grpcSettings"]["serviceName"], "trojan-grpc");
+        assert_eq!(grpc["security"], "tls");
+    }
+
     #[test]
     fn test_direct_and_block_outbounds_present() {
         let generator = V2rayGenerator;
@@ -428,6 +633,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -440,6 +646,31 @@ mod tests {
         assert_eq!(routing_rules[0]["outboundTag"], "direct");
     }
 
+    #[test]
+    fn test_negated_geosite_routing_rule() {
+        let rules = vec![RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::GeoSite {
+                category: "!cn".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let config = V2rayGenerator
+            .generate(&[vless_node()], &rules, &default_settings(), None)
+            .unwrap();
+
+        let routing_rules = config["routing"]["rules"].as_array().unwrap();
+        assert_eq!(routing_rules[0]["domain"][0], "geosite:geolocation-!cn");
+    }
+
+    #[test]
+    fn test_negated_geolocation_category_uses_the_complement_list() {
+        assert_eq!(geosite_domain("!geolocation-cn"), "geosite:geolocation-!cn");
+        assert_eq!(geosite_domain("geolocation-!cn"), "geosite:geolocation-!cn");
+    }
+
     #[test]
     fn test_geosite_routing_rule() {
         let generator = V2rayGenerator;
@@ -450,6 +681,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -470,6 +702,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -480,6 +713,32 @@ mod tests {
         assert_eq!(routing_rules[0]["domain"][0], "*.google.com");
     }
 
+    #[test]
+    fn test_proxy_rule_targets_emitted_primary_tag() {
+        let mut node = vless_node();
+        if let ProxyNode::Vless(c) = &mut node {
+            c.remark = Some("🇯🇵 Tokyo #1".into());
+        }
+        let rules = vec![RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.com".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let config = V2rayGenerator
+            .generate(&[node, ss_node()], &rules, &default_settings(), None)
+            .unwrap();
+
+        assert_eq!(config["outbounds"][0]["tag"], "proxy-0-Tokyo-1");
+        assert_eq!(
+            config["routing"]["rules"][0]["outboundTag"],
+            "proxy-0-Tokyo-1"
+        );
+    }
+
     #[test]
     fn test_ip_cidr_routing_rule() {
         let generator = V2rayGenerator;
@@ -490,6 +749,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         }];
 
         let config = generator
@@ -512,6 +772,7 @@ mod tests {
                 },
                 action: RuleAction::Direct,
                 enabled: false,
+                target: None,
             },
             RoutingRule {
                 id: uuid::Uuid::new_v4(),
@@ -520,6 +781,7 @@ mod tests {
                 },
                 action: RuleAction::Proxy,
                 enabled: true,
+                target: None,
             },
         ];
 
@@ -543,6 +805,7 @@ mod tests {
             transport: TransportSettings::Grpc(GrpcSettings {
                 service_name: "mygrpc".into(),
                 multi_mode: true,
+                authority: Some("cdn.example.com".into()),
             }),
             tls: None,
             remark: None,
@@ -557,6 +820,91 @@ mod tests {
         assert_eq!(stream["network"], "grpc");
         assert_eq!(stream["grpcSettings"]["serviceName"], "mygrpc");
         assert_eq!(stream["grpcSettings"]["multiMode"], true);
+        assert_eq!(stream["grpcSettings"]["authority"], "cdn.example.com");
+    }
+
+    #[test]
+    fn test_rules_route_to_their_targets() {
+        let (to_vmess, to_trojan, gone) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let targets = [
+            RouteTarget {
+                id: to_vmess,
+                member: 1,
+            },
+            RouteTarget {
+                id: to_trojan,
+                member: 2,
+            },
+        ];
+        let rule = |pattern: &str, target| RoutingRule {
+            id: Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: pattern.into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target,
+        };
+        let rules = [
+            rule("a.com", Some(to_vmess)),
+            rule("b.com", Some(to_trojan)),
+            rule("c.com", Some(gone)),
+            rule("d.com", None),
+        ];
+
+        let config = V2rayGenerator
+            .generate_grouped(
+                &[vless_node(), vmess_node(), trojan_node()],
+                &[],
+                &targets,
+                &rules,
+                &default_settings(),
+                None,
+            )
+            .unwrap();
+
+        let outbounds = config["outbounds"].as_array().unwrap();
+        let vmess_tag = format!("target-{}", to_vmess.simple());
+        let trojan_tag = format!("target-{}", to_trojan.simple());
+        assert_eq!(outbounds[1]["tag"], vmess_tag.as_str());
+        assert_eq!(outbounds[1]["protocol"], "vmess");
+        assert_eq!(outbounds[2]["tag"], trojan_tag.as_str());
+        assert_eq!(outbounds[2]["protocol"], "trojan");
+
+        let primary = outbounds[0]["tag"].as_str().unwrap();
+        assert!(primary.starts_with("proxy-0"));
+        let routed: Vec<&str> = config["routing"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["outboundTag"].as_str().unwrap())
+            .collect();
+        assert_eq!(routed, [vmess_tag.as_str(), &trojan_tag, primary, primary]);
+    }
+
+    #[test]
+    fn test_mkcp_transport() {
+        let config = V2rayGenerator
+            .generate(&[mkcp_vmess_node()], &[], &default_settings(), None)
+            .unwrap();
+        let stream = &config["outbounds"][0]["streamSettings"];
+        assert_eq!(stream["network"], "kcp");
+        assert_eq!(stream["kcpSettings"]["header"]["type"], "wechat-video");
+        assert_eq!(stream["kcpSettings"]["seed"], "s3cret");
+
+        let ProxyNode::Vmess(mut c) = mkcp_vmess_node() else {
+            unreachable!()
+        };
+        c.transport = TransportSettings::Mkcp(MkcpSettings {
+            header_type: None,
+            seed: None,
+        });
+        let config = V2rayGenerator
+            .generate(&[ProxyNode::Vmess(c)], &[], &default_settings(), None)
+            .unwrap();
+        let kcp = &config["outbounds"][0]["streamSettings"]["kcpSettings"];
+        assert_eq!(kcp["header"]["type"], "none");
+        assert!(kcp.get("seed").is_none());
     }
 
     #[test]
@@ -597,6 +945,7 @@ mod tests {
                 },
                 action: RuleAction::Direct,
                 enabled: true,
+                target: None,
             },
             RoutingRule {
                 id: uuid::Uuid::new_v4(),
@@ -605,6 +954,7 @@ mod tests {
                 },
                 action: RuleAction::Proxy,
                 enabled: true,
+                target: None,
             },
         ];
 
@@ -614,4 +964,170 @@ mod tests {
         let json_str = serde_json::to_string_pretty(&config).unwrap();
         let _: Value = serde_json::from_str(&json_str).unwrap();
     }
+
+    #[test]
+    fn test_upstream_socks_proxy_replaces_freedom() {
+        let settings = AppSettings {
+            upstream_proxy: Some(UpstreamProxy {
+                protocol: UpstreamProtocol::Socks,
+                host: "proxy.corp.example".into(),
+                port: 1080,
+                username: None,
+                password: None,
+            }),
+            ..default_settings()
+        };
+        let config = V2rayGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+
+        let outbounds = config["outbounds"].as_array().unwrap();
+        let direct = outbounds.iter().find(|o| o["tag"] == "direct").unwrap();
+        assert_eq!(direct["protocol"], "socks");
+        let server = &direct["settings"]["servers"][0];
+        assert_eq!(server["address"], "proxy.corp.example");
+        assert_eq!(server["port"], 1080);
+        assert!(server.get("users").is_none());
+        assert!(!outbounds.iter().any(|o| o["protocol"] == "freedom"));
+    }
+
+    #[test]
+    fn test_upstream_http_proxy_with_credentials() {
+        let settings = AppSettings {
+            upstream_proxy: Some(UpstreamProxy {
+                protocol: UpstreamProtocol::Http,
+                host: "10.0.0.1".into(),
+                port: 3128,
+                username: Some("alice".into()),
+                password: Some("secret".into()),
+            }),
+            ..default_settings()
+        };
+        let config = crate::config::XrayGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+
+        let outbounds = config["outbounds"].as_array().unwrap();
+        let direct = outbounds.iter().find(|o| o["tag"] == "direct").unwrap();
+        assert_eq!(direct["protocol"], "http");
+        assert_eq!(
+            direct["settings"]["servers"][0]["users"][0],
+            json!({ "user": "alice", "pass": "secret" })
+        );
+    }
+
+    #[test]
+    fn test_direct_domain_strategy_follows_ipv6_setting() {
+        let direct_strategy = |ipv6: bool, generator: &dyn ConfigGenerator| {
+            let settings = AppSettings {
+                ipv6,
+                ..default_settings()
+            };
+            let config = generator
+                .generate(&[vless_node()], &[], &settings, None)
+                .unwrap();
+            config["outbounds"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|o| o["tag"] == "direct")
+                .unwrap()["settings"]["domainStrategy"]
+                .clone()
+        };
+        for generator in [
+            &V2rayGenerator as &dyn ConfigGenerator,
+            &crate::config::XrayGenerator,
+        ] {
+            assert_eq!(direct_strategy(true, generator), "AsIs");
+            assert_eq!(direct_strategy(false, generator), "UseIPv4");
+        }
+    }
+
+    #[test]
+    fn test_default_action_is_last_routing_rule() {
+        let rules = [RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition: RuleMatch::Domain {
+                pattern: "example.com".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let routing_rules = |default_action: RuleAction, generator: &dyn ConfigGenerator| {
+            let settings = AppSettings {
+                default_action,
+                ..default_settings()
+            };
+            let config = generator
+                .generate(&[vless_node()], &rules, &settings, None)
+                .unwrap();
+            config["routing"]["rules"].as_array().unwrap().clone()
+        };
+        for generator in [
+            &V2rayGenerator as &dyn ConfigGenerator,
+            &crate::config::XrayGenerator,
+        ] {
+            assert_eq!(routing_rules(RuleAction::Proxy, generator).len(), 1);
+            for (action, tag) in [(RuleAction::Direct, "direct"), (RuleAction::Block, "block")] {
+                let rules = routing_rules(action, generator);
+                assert_eq!(rules.len(), 2);
+                assert_eq!(rules[1]["network"], "tcp,udp");
+                assert_eq!(rules[1]["outboundTag"], tag);
+            }
+        }
+    }
+
+    #[test]
+    fn test_inbounds_follow_enabled_inbounds() {
+        let inbound_tags =
+            |enable_socks: bool, enable_http: bool, generator: &dyn ConfigGenerator| {
+                let settings = AppSettings {
+                    enable_socks,
+                    enable_http,
+                    ..default_settings()
+                };
+                let config = generator
+                    .generate(&[vless_node()], &[], &settings, None)
+                    .unwrap();
+                config["inbounds"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|i| i["tag"].as_str().unwrap().to_owned())
+                    .collect::<Vec<_>>()
+            };
+        for generator in [
+            &V2rayGenerator as &dyn ConfigGenerator,
+            &crate::config::XrayGenerator,
+        ] {
+            assert_eq!(inbound_tags(true, true, generator), ["socks-in", "http-in"]);
+            assert_eq!(inbound_tags(true, false, generator), ["socks-in"]);
+            assert_eq!(inbound_tags(false, true, generator), ["http-in"]);
+
+            let settings = AppSettings {
+                enable_socks: false,
+                enable_http: false,
+                ..default_settings()
+            };
+            assert!(matches!(
+                generator.generate(&[vless_node()], &[], &settings, None),
+                Err(ConfigError::Inbounds(ValidationError::NoInbounds))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_default_action_without_rules() {
+        let settings = AppSettings {
+            default_action: RuleAction::Direct,
+            ..default_settings()
+        };
+        let config = V2rayGenerator
+            .generate(&[vless_node()], &[], &settings, None)
+            .unwrap();
+        let routing_rules = config["routing"]["rules"].as_array().unwrap();
+        assert_eq!(routing_rules.len(), 1);
+        assert_eq!(routing_rules[0]["outboundTag"], "direct");
+    }
 }
@@ -1,13 +1,15 @@
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use crate::config::{ConfigError, generator_for};
+use crate::config::{ConfigDiff, ConfigError, NodeGroup, RouteTarget, diff_lines, generator_for};
 use crate::models::{AppSettings, BackendType, ProxyNode, RoutingRule};
 use crate::persistence::AppPaths;
 
 pub struct ConfigWriter {
     output_dir: PathBuf,
     geodata_dir: PathBuf,
+    groups: Vec<NodeGroup>,
+    targets: Vec<RouteTarget>,
 }
 
 impl ConfigWriter {
@@ -21,15 +23,29 @@ impl ConfigWriter {
         Self {
             output_dir,
             geodata_dir: paths.geodata_dir(),
+            groups: Vec::new(),
+            targets: Vec::new(),
         }
     }
 
+    pub fn with_groups(mut self, groups: Vec<NodeGroup>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn with_targets(mut self, targets: Vec<RouteTarget>) -> Self {
+        self.targets = targets;
+        self
+    }
+
     #[cfg(test)]
     pub fn with_dir(dir: PathBuf) -> Self {
         let geodata_dir = dir.join("geodata");
         Self {
             output_dir: dir,
             geodata_dir,
+            groups: Vec::new(),
+            targets: Vec::new(),
         }
     }
 
@@ -42,6 +58,41 @@ impl ConfigWriter {
         self.output_dir.join(filename)
     }
 
+    pub fn render_config(
+        &self,
+        nodes: &[ProxyNode],
+        rules: &[RoutingRule],
+        settings: &AppSettings,
+    ) -> Result<String, ConfigError> {
+        let generator = generator_for(settings.backend.backend_type);
+        let config = generator.generate_grouped(
+            nodes,
+            &self.groups,
+            &self.targets,
+            rules,
+            settings,
+            Some(&self.geodata_dir),
+        )?;
+        Ok(serde_json::to_string_pretty(&config)?)
+    }
+
+    /// Diff of the config that would be generated now against the one on disk.
+    pub fn diff_config(
+        &self,
+        nodes: &[ProxyNode],
+        rules: &[RoutingRule],
+        settings: &AppSettings,
+    ) -> Result<ConfigDiff, ConfigError> {
+        let json = self.render_config(nodes, rules, settings)?;
+        let written = match std::fs::read_to_string(self.output_path(settings.backend.backend_type))
+        {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(diff_lines(&written, &json))
+    }
+
     pub fn write_config(
         &self,
         nodes: &[ProxyNode],
@@ -49,9 +100,7 @@ impl ConfigWriter {
         settings: &AppSettings,
     ) -> Result<PathBuf, ConfigError> {
         let backend = settings.backend.backend_type;
-        let generator = generator_for(backend);
-        let config = generator.generate(nodes, rules, settings, Some(&self.geodata_dir))?;
-        let json = serde_json::to_string_pretty(&config)?;
+        let json = self.render_config(nodes, rules, settings)?;
 
         std::fs::create_dir_all(&self.output_dir)?;
         let path = self.output_path(backend);
@@ -61,6 +110,14 @@ impl ConfigWriter {
     }
 }
 
+/// Creates `dir` if needed and checks a file can be written in it, so a bad
+/// output directory is rejected when chosen rather than at connect time.
+pub fn check_output_dir(dir: &Path) -> Result<(), ConfigError> {
+    std::fs::create_dir_all(dir)?;
+    tempfile::NamedTempFile::new_in(dir)?;
+    Ok(())
+}
+
 fn atomic_write(path: &Path, data: &[u8]) -> Result<(), ConfigError> {
     let dir = path.parent().ok_or_else(|| {
         ConfigError::Io(std::io::Error::new(
@@ -98,6 +155,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         }]
     }
 
@@ -128,6 +186,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_config_does_not_write() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        let writer = ConfigWriter::with_dir(out.clone());
+        let settings = AppSettings::default();
+
+        let json = writer
+            .render_config(&sample_nodes(), &sample_rules(), &settings)
+            .unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(parsed["outbounds"].is_array());
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn test_render_config_no_nodes() {
+        let writer = ConfigWriter::with_dir(PathBuf::from("/tmp/test"));
+        let result = writer.render_config(&[], &[], &AppSettings::default());
+        assert!(matches!(result, Err(ConfigError::NoNodes)));
+    }
+
     #[test]
     fn test_write_config_creates_file() {
         let dir = tempfile::TempDir::new().unwrap();
@@ -198,6 +279,29 @@ mod tests {
         assert!(second_contents.contains("geoip"));
     }
 
+    #[test]
+    fn test_diff_config_against_written() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let writer = ConfigWriter::with_dir(dir.path().to_path_buf());
+        let settings = AppSettings::default();
+
+        let diff = writer.diff_config(&sample_nodes(), &[], &settings).unwrap();
+        assert_eq!(diff.removed(), 0);
+        assert!(diff.added() > 0);
+
+        writer
+            .write_config(&sample_nodes(), &[], &settings)
+            .unwrap();
+        let diff = writer.diff_config(&sample_nodes(), &[], &settings).unwrap();
+        assert!(diff.is_empty());
+
+        let diff = writer
+            .diff_config(&sample_nodes(), &sample_rules(), &settings)
+            .unwrap();
+        assert!(!diff.is_empty());
+        assert!(diff.to_string().contains("geoip:ru"));
+    }
+
     #[test]
     fn test_write_config_error_on_empty_nodes() {
         let dir = tempfile::TempDir::new().unwrap();
@@ -237,6 +341,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unwritable_output_dir_is_an_error() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let blocker = dir.path().join("not-a-dir");
+        std::fs::write(&blocker, "").unwrap();
+        let paths = AppPaths::from_paths(dir.path().join("config"), dir.path().join("data"));
+        let mut settings = AppSettings::default();
+        settings.backend.config_output_dir = Some(blocker.join("generated"));
+
+        let writer = ConfigWriter::new(&settings, &paths);
+        let result = writer.write_config(&sample_nodes(), &[], &settings);
+        assert!(matches!(result, Err(ConfigError::Io(_))));
+        assert!(check_output_dir(&blocker.join("generated")).is_err());
+        assert!(check_output_dir(&dir.path().join("fresh")).is_ok());
+        assert!(dir.path().join("fresh").is_dir());
+    }
+
     #[test]
     fn test_config_writer_new_uses_default_path() {
         let dir = tempfile::TempDir::new().unwrap();
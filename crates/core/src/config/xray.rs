@@ -1,63 +1,183 @@
 use std::path::Path;
 
-use serde_json::Value;
+use serde_json::{Value, json};
 
-use crate::config::v2ray::V2rayGenerator;
-use crate::config::{ConfigError, ConfigGenerator};
-use crate::models::{AppSettings, ProxyNode, RoutingRule, TransportSettings, VlessConfig};
+use crate::config::v2ray;
+use crate::config::{ConfigError, ConfigGenerator, NodeGroup, RouteTarget};
+use crate::models::{
+    AppSettings, ProxyNode, RealitySettings, RoutingRule, RuleAction, RuleMatch, TlsSettings,
+    TransportSettings, UpstreamProxy, VlessConfig, bare_host,
+};
+
+/// uTLS fingerprint REALITY falls back to when the link does not name one;
+/// Xray refuses REALITY without a fingerprint.
+const DEFAULT_REALITY_FINGERPRINT: &str = "chrome";
 
 pub struct XrayGenerator;
 
 impl ConfigGenerator for XrayGenerator {
-    fn generate(
+    fn generate_grouped(
         &self,
         nodes: &[ProxyNode],
+        _groups: &[NodeGroup],
+        targets: &[RouteTarget],
         rules: &[RoutingRule],
         settings: &AppSettings,
         _geodata_dir: Option<&Path>,
     ) -> Result<Value, ConfigError> {
-        let v2ray = V2rayGenerator;
-        let mut config = v2ray.generate(nodes, rules, settings, None)?;
+        if nodes.is_empty() {
+            return Err(ConfigError::NoNodes);
+        }
+        super::common::check_inbounds(settings)?;
+        let upstream = super::common::upstream_proxy(settings)?;
+        let tags = super::common::node_tags(nodes, targets);
 
-        patch_xray_outbounds(&mut config, nodes);
-        Ok(config)
+        Ok(json!({
+            "log": { "loglevel": "warning" },
+            "inbounds": v2ray::build_inbounds(settings),
+            "outbounds": build_outbounds(nodes, &tags, settings, upstream),
+            "routing": build_routing(
+                rules,
+                targets,
+                settings.default_action,
+                &super::common::primary_tag(&tags),
+            ),
+        }))
     }
 }
 
-fn patch_xray_outbounds(config: &mut Value, nodes: &[ProxyNode]) {
-    let Some(outbounds) = config["outbounds"].as_array_mut() else {
-        return;
-    };
+fn build_outbounds(
+    nodes: &[ProxyNode],
+    tags: &[String],
+    settings: &AppSettings,
+    upstream: Option<&UpstreamProxy>,
+) -> Value {
+    let mut outbounds: Vec<Value> = nodes
+        .iter()
+        .zip(tags)
+        .map(|(node, tag)| build_outbound(node, tag))
+        .collect();
 
-    for (i, node) in nodes.iter().enumerate() {
-        if let ProxyNode::Vless(c) = node
-            && let Some(outbound) = outbounds.get_mut(i)
-        {
-            apply_xray_vless_extensions(outbound, c);
-        }
-    }
+    outbounds.extend(v2ray::builtin_outbounds(settings, upstream));
+    Value::Array(outbounds)
 }
 
-fn apply_xray_vless_extensions(outbound: &mut Value, c: &VlessConfig) {
-    if let Some(ref flow) = c.flow
-        && is_xtls_flow(flow)
-    {
-        if let Some(users) = outbound["settings"]["vnext"][0]["users"].as_array_mut()
-            && let Some(user) = users.first_mut()
-        {
-            user["flow"] = serde_json::json!(flow);
+fn build_outbound(node: &ProxyNode, tag: &str) -> Value {
+    match node {
+        ProxyNode::Vless(c) => build_vless_outbound(c, tag),
+        ProxyNode::Vmess(c) => {
+            let mut outbound = v2ray::build_outbound(node, tag);
+            outbound["streamSettings"] = stream_settings(&c.transport, c.tls.as_ref());
+            outbound
         }
-
-        if matches!(c.transport, TransportSettings::Tcp) && c.tls.is_some() {
-            outbound["streamSettings"]["security"] = serde_json::json!("xtls");
+        ProxyNode::Trojan(c) => {
+            let mut outbound = v2ray::build_outbound(node, tag);
+            outbound["streamSettings"] = stream_settings(&c.transport, c.tls.as_ref());
+            outbound
         }
+        ProxyNode::Shadowsocks(_) => v2ray::build_outbound(node, tag),
     }
 }
 
+fn build_vless_outbound(c: &VlessConfig, tag: &str) -> Value {
+    let mut user = json!({
+        "id": c.uuid,
+        "encryption": super::common::vless_encryption(c),
+    });
+    if let Some(flow) = xtls_flow(c) {
+        user["flow"] = json!(flow);
+    }
+
+    json!({
+        "tag": tag,
+        "protocol": "vless",
+        "settings": {
+            "vnext": [{
+                "address": bare_host(&c.address),
+                "port": c.port,
+                "users": [user],
+            }],
+        },
+        "streamSettings": stream_settings(&c.transport, c.tls.as_ref()),
+    })
+}
+
+/// Xray only accepts XTLS flows (Vision) over raw TCP secured by TLS or REALITY;
+/// anywhere else the flow is dropped so the outbound still starts.
+fn xtls_flow(c: &VlessConfig) -> Option<&str> {
+    c.flow.as_deref().filter(|flow| {
+        is_xtls_flow(flow) && matches!(c.transport, TransportSettings::Tcp) && c.tls.is_some()
+    })
+}
+
 fn is_xtls_flow(flow: &str) -> bool {
     flow.starts_with("xtls-rprx-")
 }
 
+fn stream_settings(transport: &TransportSettings, tls: Option<&TlsSettings>) -> Value {
+    let mut stream = v2ray::transport_stream(transport);
+    let Some(tls_cfg) = tls else {
+        return stream;
+    };
+    match &tls_cfg.reality {
+        Some(reality) => {
+            stream["security"] = json!("reality");
+            stream["realitySettings"] = reality_settings(tls_cfg, reality);
+        }
+        None => {
+            stream["security"] = json!("tls");
+            stream["tlsSettings"] = v2ray::tls_settings(tls_cfg);
+        }
+    }
+    stream
+}
+
+fn reality_settings(tls_cfg: &TlsSettings, reality: &RealitySettings) -> Value {
+    let mut settings = json!({
+        "publicKey": reality.public_key,
+        "fingerprint": tls_cfg
+            .fingerprint
+            .as_deref()
+            .unwrap_or(DEFAULT_REALITY_FINGERPRINT),
+        "shortId": reality.short_id.as_deref().unwrap_or(""),
+    });
+    if let Some(sni) = &tls_cfg.server_name {
+        settings["serverName"] = json!(sni);
+    }
+    if let Some(spider_x) = &reality.spider_x {
+        settings["spiderX"] = json!(spider_x);
+    }
+    settings
+}
+
+/// Resolving domains to IPs is only worth it when some rule matches on IP;
+/// with domain-only rules `AsIs` avoids a DNS lookup per connection.
+fn build_routing(
+    rules: &[RoutingRule],
+    targets: &[RouteTarget],
+    default_action: RuleAction,
+    proxy: &str,
+) -> Value {
+    let enabled: Vec<&RoutingRule> = rules.iter().filter(|r| r.enabled).collect();
+    let matches_ip = enabled.iter().any(|r| {
+        matches!(
+            r.match_condition,
+            RuleMatch::GeoIp { .. } | RuleMatch::IpCidr { .. }
+        )
+    });
+
+    let mut routing_rules: Vec<Value> = enabled
+        .iter()
+        .map(|r| v2ray::build_routing_rule(r, &super::common::rule_proxy(r, targets, proxy)))
+        .collect();
+    routing_rules.extend(v2ray::catch_all_rule(default_action, proxy));
+
+    json!({
+        "domainStrategy": if matches_ip { "IPIfNonMatch" } else { "AsIs" },
+        "rules": routing_rules,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,6 +196,7 @@ mod tests {
                 alpn: vec![],
                 verify: true,
                 fingerprint: Some("chrome".into()),
+                reality: None,
             }),
             remark: Some("XTLS Node".into()),
         })
@@ -98,6 +219,7 @@ mod tests {
                 alpn: vec![],
                 verify: true,
                 fingerprint: None,
+                reality: None,
             }),
             remark: Some("Plain VLESS".into()),
         })
@@ -118,7 +240,7 @@ mod tests {
         let outbound = &config["outbounds"][0];
         let user = &outbound["settings"]["vnext"][0]["users"][0];
         assert_eq!(user["flow"], "xtls-rprx-vision");
-        assert_eq!(outbound["streamSettings"]["security"], "xtls");
+        assert_eq!(outbound["streamSettings"]["security"], "tls");
     }
 
     #[test]
@@ -155,11 +277,179 @@ mod tests {
         // 3 proxy + direct + block = 5
         assert_eq!(outbounds.len(), 5);
 
-        assert_eq!(outbounds[0]["streamSettings"]["security"], "xtls");
+        assert_eq!(
+            outbounds[0]["settings"]["vnext"][0]["users"][0]["flow"],
+            "xtls-rprx-vision"
+        );
+        assert_eq!(outbounds[0]["streamSettings"]["security"], "tls");
         assert_eq!(outbounds[1]["streamSettings"]["security"], "tls");
         assert_eq!(outbounds[2]["protocol"], "shadowsocks");
     }
 
+    fn reality_vision_node() -> ProxyNode {
+        ProxyNode::Vless(VlessConfig {
+            address: "reality.example.com".into(),
+            port: 443,
+            uuid: "b831381d-6324-4d53-ad4f-8cda48b30811".into(),
+            encryption: None,
+            flow: Some("xtls-rprx-vision".into()),
+            transport: TransportSettings::Tcp,
+            tls: Some(TlsSettings {
+                server_name: Some("www.microsoft.com".into()),
+                alpn: vec![],
+                verify: true,
+                fingerprint: None,
+                reality: Some(RealitySettings {
+                    public_key: "Z84J2IelR9ch3k8VtlVhhs5ycBUlXA7wHBWcBrjqnAw".into(),
+                    short_id: Some("6ba85179e30d4fc2".into()),
+                    spider_x: Some("/".into()),
+                }),
+            }),
+            remark: Some("Reality".into()),
+        })
+    }
+
+    #[test]
+    fn test_xray_reality_vision() {
+        let config = XrayGenerator
+            .generate(&[reality_vision_node()], &[], &AppSettings::default(), None)
+            .unwrap();
+
+        let outbound = &config["outbounds"][0];
+        let user = &outbound["settings"]["vnext"][0]["users"][0];
+        assert_eq!(user["flow"], "xtls-rprx-vision");
+        assert_eq!(user["encryption"], "none");
+
+        let stream = &outbound["streamSettings"];
+        assert_eq!(stream["network"], "tcp");
+        assert_eq!(stream["security"], "reality");
+        assert!(stream.get("tlsSettings").is_none());
+
+        let reality = &stream["realitySettings"];
+        assert_eq!(reality["serverName"], "www.microsoft.com");
+        assert_eq!(
+            reality["publicKey"],
+            "Z84J2IelR9ch3k8VtlVhhs5ycBUlXA7wHBWcBrjqnAw"
+        );
+        assert_eq!(reality["shortId"], "6ba85179e30d4fc2");
+        assert_eq!(reality["spiderX"], "/");
+        assert_eq!(reality["fingerprint"], DEFAULT_REALITY_FINGERPRINT);
+    }
+
+    #[test]
+    fn test_xray_vision_flow_dropped_off_tcp() {
+        let mut node = vless_without_xtls();
+        if let ProxyNode::Vless(c) = &mut node {
+            c.flow = Some("xtls-rprx-vision".into());
+        }
+        let config = XrayGenerator
+            .generate(&[node], &[], &AppSettings::default(), None)
+            .unwrap();
+
+        let user = &config["outbounds"][0]["settings"]["vnext"][0]["users"][0];
+        assert!(user.get("flow").is_none());
+    }
+
+    #[test]
+    fn test_xray_domain_strategy_follows_rules() {
+        let rule = |match_condition| RoutingRule {
+            id: uuid::Uuid::new_v4(),
+            match_condition,
+            action: RuleAction::Direct,
+            enabled: true,
+            target: None,
+        };
+        let domain_only = [rule(RuleMatch::GeoSite {
+            category: "cn".into(),
+        })];
+        let with_ip = [
+            rule(RuleMatch::GeoSite {
+                category: "cn".into(),
+            }),
+            rule(RuleMatch::GeoIp {
+                country_code: "CN".into(),
+            }),
+        ];
+
+        let nodes = [reality_vision_node()];
+        let settings = AppSettings::default();
+        let config = XrayGenerator
+            .generate(&nodes, &domain_only, &settings, None)
+            .unwrap();
+        assert_eq!(config["routing"]["domainStrategy"], "AsIs");
+        assert_eq!(config["routing"]["rules"].as_array().unwrap().len(), 1);
+
+        let config = XrayGenerator
+            .generate(&nodes, &with_ip, &settings, None)
+            .unwrap();
+        assert_eq!(config["routing"]["domainStrategy"], "IPIfNonMatch");
+    }
+
+    #[test]
+    fn test_xray_trojan_over_ws_and_grpc() {
+        use crate::config::test_fixtures::fixtures::{trojan_grpc_node, trojan_ws_node};
+
+        let config = XrayGenerator
+            .generate(
+                &[trojan_ws_node(), trojan_grpc_node()],
+                &[],
+                &AppSettings::default(),
+                None,
+            )
+            .unwrap();
+
+        let ws = &config["outbounds"][0];
+        assert_eq!(ws["protocol"], "trojan");
+        assert_eq!(ws["streamSettings"]["network"], "ws");
+        assert_eq!(ws["streamSettings"]["wsSettings"]["path"], "/trojan");
+        assert_eq!(ws["streamSettings"]["security"], "tls");
+        assert_eq!(
+            ws["streamSettings"]["tlsSettings"]["serverName"],
+            "trojan.example.com"
+        );
+
+        let grpc = &config["outbounds"][1]["streamSettings"];
+        assert_eq!(grpc["network"], "grpc");
+        assert_eq!(grpc["grpcSettings"]["serviceName"], "trojan-grpc");
+        assert_eq!(grpc["security"], "tls");
+    }
+
+    #[test]
+    fn test_xray_unverified_tls_allows_insecure() {
+        use crate::config::test_fixtures::fixtures::insecure_trojan_node;
+
+        let config = XrayGenerator
+            .generate(
+                &[insecure_trojan_node()],
+                &[],
+                &AppSettings::default(),
+                None,
+            )
+            .unwrap();
+        let stream = &config["outbounds"][0]["streamSettings"];
+        assert_eq!(stream["security"], "tls");
+        assert_eq!(stream["tlsSettings"]["allowInsecure"], true);
+    }
+
+    #[test]
+    fn test_xray_vless_encryption() {
+        let suite = "mlkem768x25519plus.native.0rtt.100-111-1111.ptjHQxBQxTJ9MWr2cd5qWIflBSACHOevTauCQwa_71U";
+        let ProxyNode::Vless(mut c) = vless_without_xtls() else {
+            unreachable!()
+        };
+        c.encryption = Some(suite.into());
+        let encrypted = ProxyNode::Vless(c.clone());
+        c.encryption = None;
+        let plain = ProxyNode::Vless(c);
+
+        let config = XrayGenerator
+            .generate(&[encrypted, plain], &[], &AppSettings::default(), None)
+            .unwrap();
+        let user = |i: usize| config["outbounds"][i]["settings"]["vnext"][0]["users"][0].clone();
+        assert_eq!(user(0)["encryption"], suite);
+        assert_eq!(user(1)["encryption"], "none");
+    }
+
     #[test]
     fn test_xray_error_on_empty_nodes() {
         let generator = XrayGenerator;
@@ -6,7 +6,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::models::BackendType;
+use crate::models::{BackendType, register_geosite_categories};
 use crate::persistence::AppPaths;
 
 const GEODATA_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
@@ -19,6 +19,8 @@ pub enum GeodataError {
     Io(#[from] std::io::Error),
     #[error("metadata: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("malformed geosite file: {0}")]
+    Malformed(&'static str),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +89,22 @@ impl GeodataManager {
         self.geoip_path(backend).exists() && self.geosite_path(backend).exists()
     }
 
+    /// Makes every category in the downloaded geosite file valid for routing rules.
+    /// sing-box uses remote rule sets instead of a local list, so there is nothing to read.
+    pub fn register_geosite_categories(&self, backend: BackendType) -> Result<usize, GeodataError> {
+        if backend == BackendType::SingBox {
+            return Ok(0);
+        }
+        let path = self.geosite_path(backend);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let categories = read_geosite_categories(&std::fs::read(path)?)?;
+        let count = categories.len();
+        register_geosite_categories(categories);
+        Ok(count)
+    }
+
     pub fn load_metadata(&self) -> Result<Option<GeodataMetadata>, GeodataError> {
         if !self.metadata_path.exists() {
             return Ok(None);
@@ -153,6 +171,77 @@ impl GeodataManager {
     }
 }
 
+/// Extracts the category names (`GeoSite.country_code`) from a v2ray `geosite.dat`,
+/// a protobuf `GeoSiteList` whose field 1 holds the repeated entries.
+pub fn read_geosite_categories(data: &[u8]) -> Result<Vec<String>, GeodataError> {
+    let mut categories = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (field, entry) = read_proto_field(data, &mut pos)?;
+        if field != 1 {
+            continue;
+        }
+        let Some(entry) = entry else { continue };
+        let mut entry_pos = 0;
+        while entry_pos < entry.len() {
+            if let (1, Some(code)) = read_proto_field(entry, &mut entry_pos)? {
+                let code = std::str::from_utf8(code)
+                    .map_err(|_| GeodataError::Malformed("category name is not UTF-8"))?;
+                categories.push(code.to_ascii_lowercase());
+                break;
+            }
+        }
+    }
+    Ok(categories)
+}
+
+/// Reads one field, returning its number and, for length-delimited fields, its payload.
+fn read_proto_field<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+) -> Result<(u64, Option<&'a [u8]>), GeodataError> {
+    let key = read_varint(data, pos)?;
+    let field = key >> 3;
+    match key & 0x7 {
+        0 => {
+            read_varint(data, pos)?;
+            Ok((field, None))
+        }
+        1 => skip_bytes(data, pos, 8).map(|_| (field, None)),
+        2 => {
+            let len = read_varint(data, pos)? as usize;
+            skip_bytes(data, pos, len).map(|bytes| (field, Some(bytes)))
+        }
+        5 => skip_bytes(data, pos, 4).map(|_| (field, None)),
+        _ => Err(GeodataError::Malformed("unsupported wire type")),
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, GeodataError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or(GeodataError::Malformed("truncated varint"))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(GeodataError::Malformed("varint too long"))
+}
+
+fn skip_bytes<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], GeodataError> {
+    let end = pos
+        .checked_add(len)
+        .filter(|&end| end <= data.len())
+        .ok_or(GeodataError::Malformed("truncated field"))?;
+    let bytes = &data[*pos..end];
+    *pos = end;
+    Ok(bytes)
+}
+
 #[cfg(feature = "geodata-fetch")]
 pub fn check_and_download(
     manager: &GeodataManager,
@@ -234,6 +323,52 @@ mod tests {
         (tmp, manager)
     }
 
+    fn length_delimited(field: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![(field << 3) | 2, payload.len() as u8];
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn geosite_entry(code: &str) -> Vec<u8> {
+        let domain = [length_delimited(2, b"example.com"), vec![0x08, 0x02]].concat();
+        let entry = [
+            length_delimited(1, code.as_bytes()),
+            length_delimited(2, &domain),
+        ]
+        .concat();
+        length_delimited(1, &entry)
+    }
+
+    #[test]
+    fn test_read_geosite_categories() {
+        let data = [geosite_entry("CATEGORY-FOO"), geosite_entry("BAR")].concat();
+        assert_eq!(
+            read_geosite_categories(&data).unwrap(),
+            vec!["category-foo", "bar"]
+        );
+
+        assert!(read_geosite_categories(&data[..data.len() - 3]).is_err());
+        assert!(read_geosite_categories(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_register_geosite_categories_from_file() {
+        let (_tmp, manager) = test_manager();
+        manager.ensure_dir().unwrap();
+        std::fs::write(
+            manager.geosite_path(BackendType::Xray),
+            geosite_entry("GEODATA-TEST-ONLY"),
+        )
+        .unwrap();
+
+        let count = manager
+            .register_geosite_categories(BackendType::Xray)
+            .unwrap();
+
+        assert_eq!(count, 1);
+        assert!(crate::models::validate_geosite_category("geodata-test-only").is_ok());
+    }
+
     #[test]
     fn test_metadata_save_load_roundtrip() {
         let (_tmp, manager) = test_manager();
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const CONNECTION_HISTORY_CAP: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionAction {
+    Connected,
+    Disconnected,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionEvent {
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_remark: Option<String>,
+    pub action: ConnectionAction,
+}
+
+impl ConnectionEvent {
+    pub fn now(action: ConnectionAction, node_remark: Option<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            node_remark,
+            action,
+        }
+    }
+}
+
+/// A connected period reconstructed from the event log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionSession {
+    pub started: DateTime<Utc>,
+    pub ended: Option<DateTime<Utc>>,
+    pub node_remark: Option<String>,
+    pub ended_with_error: bool,
+}
+
+impl ConnectionSession {
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        self.ended.map(|end| end - self.started)
+    }
+}
+
+/// Pairs each `Connected` event with the next `Disconnected`/`Error`, newest first.
+/// An error without a preceding connect (a failed start) becomes a zero-length session.
+pub fn connection_sessions(events: &[ConnectionEvent]) -> Vec<ConnectionSession> {
+    let mut sessions = Vec::new();
+    let mut open: Option<ConnectionSession> = None;
+
+    for event in events {
+        match event.action {
+            ConnectionAction::Connected => {
+                if let Some(session) = open.take() {
+                    sessions.push(session);
+                }
+                open = Some(ConnectionSession {
+                    started: event.timestamp,
+                    ended: None,
+                    node_remark: event.node_remark.clone(),
+                    ended_with_error: false,
+                });
+            }
+            ConnectionAction::Disconnected | ConnectionAction::Error => {
+                let error = event.action == ConnectionAction::Error;
+                match open.take() {
+                    Some(mut session) => {
+                        session.ended = Some(event.timestamp);
+                        session.ended_with_error = error;
+                        sessions.push(session);
+                    }
+                    None if error => sessions.push(ConnectionSession {
+                        started: event.timestamp,
+                        ended: Some(event.timestamp),
+                        node_remark: event.node_remark.clone(),
+                        ended_with_error: true,
+                    }),
+                    None => {}
+                }
+            }
+        }
+    }
+    sessions.extend(open);
+    sessions.reverse();
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(secs: i64, action: ConnectionAction) -> ConnectionEvent {
+        ConnectionEvent {
+            timestamp: Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap(),
+            node_remark: Some("Node".into()),
+            action,
+        }
+    }
+
+    #[test]
+    fn sessions_pair_connect_with_disconnect() {
+        let events = vec![
+            event(0, ConnectionAction::Connected),
+            event(60, ConnectionAction::Disconnected),
+            event(100, ConnectionAction::Connected),
+            event(130, ConnectionAction::Error),
+            event(200, ConnectionAction::Connected),
+        ];
+
+        let sessions = connection_sessions(&events);
+
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].ended, None);
+        assert_eq!(sessions[1].duration(), Some(chrono::Duration::seconds(30)));
+        assert!(sessions[1].ended_with_error);
+        assert_eq!(sessions[2].duration(), Some(chrono::Duration::seconds(60)));
+        assert!(!sessions[2].ended_with_error);
+    }
+
+    #[test]
+    fn failed_start_is_a_zero_length_session() {
+        let events = vec![
+            event(0, ConnectionAction::Error),
+            event(5, ConnectionAction::Disconnected),
+        ];
+
+        let sessions = connection_sessions(&events);
+
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].ended_with_error);
+        assert_eq!(sessions[0].duration(), Some(chrono::Duration::zero()));
+    }
+}
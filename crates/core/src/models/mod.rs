@@ -1,3 +1,4 @@
+mod history;
 mod presets;
 mod proxy;
 mod routing;
@@ -5,6 +6,7 @@ mod settings;
 mod subscription;
 mod validation;
 
+pub use history::*;
 pub use presets::*;
 pub use proxy::*;
 pub use routing::*;
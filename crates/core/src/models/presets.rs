@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{RoutingRule, RuleAction, RuleMatch};
+use super::{RoutingRule, RuleAction, RuleMatch, ValidationError, validate_rule_match};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Preset {
@@ -39,6 +39,20 @@ impl Preset {
                 match_condition: pr.match_condition.clone(),
                 action: pr.action,
                 enabled: true,
+                target: None,
+            })
+            .collect()
+    }
+
+    /// Rules that fail validation, by position in the preset.
+    pub fn invalid_rules(&self) -> Vec<(usize, ValidationError)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, pr)| {
+                validate_rule_match(&pr.match_condition)
+                    .err()
+                    .map(|e| (i, e))
             })
             .collect()
     }
@@ -90,6 +104,27 @@ pub fn builtin_presets() -> Vec<Preset> {
                 action: RuleAction::Block,
             }],
         },
+        Preset {
+            name: "Privacy Shield".into(),
+            description: "Block ads, trackers and Windows telemetry. Domain rules only cover \
+                          traffic that reaches the proxy, so point system DNS at it to avoid \
+                          DNS leaks"
+                .into(),
+            rules: vec![
+                PresetRule {
+                    match_condition: RuleMatch::GeoSite {
+                        category: "category-ads-all".into(),
+                    },
+                    action: RuleAction::Block,
+                },
+                PresetRule {
+                    match_condition: RuleMatch::GeoSite {
+                        category: "win-spy".into(),
+                    },
+                    action: RuleAction::Block,
+                },
+            ],
+        },
         Preset {
             name: "Popular AI".into(),
             description: "Route AI services through proxy".into(),
@@ -214,7 +249,7 @@ mod tests {
     #[test]
     fn test_builtin_presets_count() {
         let presets = builtin_presets();
-        assert_eq!(presets.len(), 6);
+        assert_eq!(presets.len(), 7);
     }
 
     #[test]
@@ -252,6 +287,65 @@ mod tests {
         assert_eq!(ids.len(), unique_ids.len());
     }
 
+    #[test]
+    fn test_invalid_rules_reports_positions() {
+        let rules = vec![
+            RoutingRule {
+                id: Uuid::new_v4(),
+                match_condition: RuleMatch::GeoIp {
+                    country_code: "US".into(),
+                },
+                action: RuleAction::Direct,
+                enabled: true,
+                target: None,
+            },
+            RoutingRule {
+                id: Uuid::new_v4(),
+                match_condition: RuleMatch::GeoIp {
+                    country_code: "XX".into(),
+                },
+                action: RuleAction::Direct,
+                enabled: true,
+                target: None,
+            },
+        ];
+        let preset = Preset::from_rules("Mixed", "", &rules);
+
+        let invalid = preset.invalid_rules();
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].0, 1);
+        assert_eq!(
+            invalid[0].1,
+            ValidationError::InvalidCountryCode("XX".into())
+        );
+    }
+
+    #[test]
+    fn test_privacy_shield_rules_validate() {
+        let preset = builtin_presets()
+            .into_iter()
+            .find(|p| p.name == "Privacy Shield")
+            .unwrap();
+        let rules = preset.rules();
+        assert_eq!(rules.len(), 2);
+        assert!(rules.iter().all(|r| r.action == RuleAction::Block));
+        for rule in &rules {
+            assert_eq!(validate_rule_match(&rule.match_condition), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_builtin_preset_rules_all_validate() {
+        for preset in builtin_presets() {
+            assert!(
+                preset.invalid_rules().is_empty(),
+                "preset {:?} has invalid rules: {:?}",
+                preset.name,
+                preset.invalid_rules()
+            );
+        }
+    }
+
     #[test]
     fn test_preset_rules_are_enabled() {
         let presets = builtin_presets();
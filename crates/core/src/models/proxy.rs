@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::{ValidationError, validate_tls_override};
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "protocol", rename_all = "lowercase")]
 pub enum ProxyNode {
@@ -19,6 +21,16 @@ impl ProxyNode {
         }
     }
 
+    /// Lowercase protocol name, as used in share links.
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            Self::Vless(_) => "vless",
+            Self::Vmess(_) => "vmess",
+            Self::Shadowsocks(_) => "shadowsocks",
+            Self::Trojan(_) => "trojan",
+        }
+    }
+
     pub fn address(&self) -> &str {
         match self {
             Self::Vless(c) => &c.address,
@@ -36,6 +48,88 @@ impl ProxyNode {
             Self::Trojan(c) => c.port,
         }
     }
+
+    pub fn tls(&self) -> Option<&TlsSettings> {
+        match self {
+            Self::Vless(c) => c.tls.as_ref(),
+            Self::Vmess(c) => c.tls.as_ref(),
+            Self::Shadowsocks(_) => None,
+            Self::Trojan(c) => c.tls.as_ref(),
+        }
+    }
+
+    pub fn transport(&self) -> Option<&TransportSettings> {
+        match self {
+            Self::Vless(c) => Some(&c.transport),
+            Self::Vmess(c) => Some(&c.transport),
+            Self::Shadowsocks(_) => None,
+            Self::Trojan(c) => Some(&c.transport),
+        }
+    }
+
+    pub fn tls_mut(&mut self) -> Option<&mut TlsSettings> {
+        match self {
+            Self::Vless(c) => c.tls.as_mut(),
+            Self::Vmess(c) => c.tls.as_mut(),
+            Self::Shadowsocks(_) => None,
+            Self::Trojan(c) => c.tls.as_mut(),
+        }
+    }
+
+    /// Identity used to find this node again after a refresh or reorder.
+    pub fn key(&self) -> NodeKey {
+        let credential = match self {
+            Self::Vless(c) => &c.uuid,
+            Self::Vmess(c) => &c.uuid,
+            Self::Shadowsocks(c) => &c.password,
+            Self::Trojan(c) => &c.password,
+        };
+        let path = match self.transport() {
+            Some(TransportSettings::Ws(ws)) => ws.path.clone(),
+            Some(TransportSettings::Grpc(grpc)) => grpc.service_name.clone(),
+            Some(TransportSettings::H2(h2)) => h2.path.clone(),
+            _ => String::new(),
+        };
+        NodeKey {
+            protocol: self.protocol(),
+            address: self.address().to_owned(),
+            port: self.port(),
+            credential: credential.clone(),
+            path,
+            sni: self.tls().and_then(|tls| tls.server_name.clone()),
+        }
+    }
+
+    /// Replaces the user-editable TLS fields; the node must already use TLS.
+    pub fn apply_tls_override(&mut self, tls: &TlsOverride) -> Result<(), ValidationError> {
+        validate_tls_override(tls)?;
+        let current = self.tls_mut().ok_or(ValidationError::NoTlsSettings)?;
+        current.server_name = Some(tls.server_name.trim().to_owned());
+        current.fingerprint = tls.fingerprint.clone();
+        current.alpn = tls.alpn.clone();
+        current.verify = tls.verify;
+        Ok(())
+    }
+}
+
+/// The server plus what tells apart users sharing it: credential, transport
+/// path and SNI. Remarks are left out, as providers rename freely.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeKey {
+    pub protocol: &'static str,
+    pub address: String,
+    pub port: u16,
+    pub credential: String,
+    pub path: String,
+    pub sni: Option<String>,
+}
+
+/// `host` without the brackets URLs put around IPv6 literals, the form
+/// backends expect in their `address` and `server` fields.
+pub fn bare_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -107,6 +201,7 @@ pub enum TransportSettings {
     Ws(WsSettings),
     Grpc(GrpcSettings),
     H2(H2Settings),
+    Mkcp(MkcpSettings),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -124,6 +219,10 @@ pub struct GrpcSettings {
     pub service_name: String,
     #[serde(default)]
     pub multi_mode: bool,
+    /// `:authority` sent instead of the server address, for CDNs that route
+    /// on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -134,6 +233,16 @@ pub struct H2Settings {
     pub path: String,
 }
 
+/// mKCP, a UDP transport only v2ray and Xray implement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MkcpSettings {
+    /// Packet header disguise (`none`, `srtp`, `wechat-video`, ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TlsSettings {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -144,6 +253,42 @@ pub struct TlsSettings {
     pub verify: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub fingerprint: Option<String>,
+    /// Set when the server uses Xray REALITY instead of a regular certificate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reality: Option<RealitySettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RealitySettings {
+    pub public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spider_x: Option<String>,
+}
+
+/// TLS fields a user changed by hand, kept so a subscription refresh can
+/// re-apply them to the provider's fresh copy of the node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsOverride {
+    pub server_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default)]
+    pub alpn: Vec<String>,
+    #[serde(default = "default_true")]
+    pub verify: bool,
+}
+
+impl TlsOverride {
+    pub fn from_settings(tls: &TlsSettings) -> Self {
+        Self {
+            server_name: tls.server_name.clone().unwrap_or_default(),
+            fingerprint: tls.fingerprint.clone(),
+            alpn: tls.alpn.clone(),
+            verify: tls.verify,
+        }
+    }
 }
 
 fn default_true() -> bool {
@@ -171,6 +316,7 @@ mod tests {
                 alpn: vec!["h2".into()],
                 verify: true,
                 fingerprint: None,
+                reality: None,
             }),
             remark: Some("Test VLESS".into()),
         })
@@ -210,11 +356,27 @@ mod tests {
                 alpn: vec![],
                 verify: true,
                 fingerprint: None,
+                reality: None,
             }),
             remark: Some("Test Trojan".into()),
         })
     }
 
+    #[test]
+    fn test_key_ignores_remark_but_not_credential() {
+        let node = sample_vless();
+        let mut renamed = node.clone();
+        let mut other_user = node.clone();
+        if let (ProxyNode::Vless(r), ProxyNode::Vless(u)) = (&mut renamed, &mut other_user) {
+            r.remark = Some("Renamed".into());
+            u.uuid = "123e4567-e89b-12d3-a456-426614174000".into();
+        }
+        assert_eq!(renamed.key(), node.key());
+        assert_ne!(other_user.key(), node.key());
+        assert_eq!(node.key().path, "/ws");
+        assert_eq!(node.key().sni.as_deref(), Some("example.com"));
+    }
+
     #[test]
     fn test_vless_serialization_roundtrip() {
         let node = sample_vless();
@@ -262,6 +424,50 @@ mod tests {
         assert!(json.contains(r#""protocol":"shadowsocks""#));
     }
 
+    #[test]
+    fn test_apply_tls_override() {
+        let mut node = sample_vless();
+        let tls = TlsOverride {
+            server_name: " cdn.example.net ".into(),
+            fingerprint: Some("firefox".into()),
+            alpn: vec!["http/1.1".into()],
+            verify: false,
+        };
+        node.apply_tls_override(&tls).unwrap();
+
+        let applied = node.tls().unwrap();
+        assert_eq!(applied.server_name.as_deref(), Some("cdn.example.net"));
+        assert_eq!(applied.fingerprint.as_deref(), Some("firefox"));
+        assert_eq!(applied.alpn, vec!["http/1.1".to_string()]);
+        assert!(!applied.verify);
+    }
+
+    #[test]
+    fn test_apply_tls_override_rejects_invalid() {
+        let mut node = sample_trojan();
+        let empty_sni = TlsOverride {
+            server_name: "  ".into(),
+            fingerprint: None,
+            alpn: vec![],
+            verify: true,
+        };
+        assert_eq!(
+            node.apply_tls_override(&empty_sni),
+            Err(ValidationError::EmptyServerName)
+        );
+        assert_eq!(node, sample_trojan());
+
+        let valid = TlsOverride {
+            server_name: "x.example.com".into(),
+            ..empty_sni
+        };
+        assert_eq!(
+            sample_ss().apply_tls_override(&valid),
+            Err(ValidationError::NoTlsSettings)
+        );
+        assert_eq!(sample_vmess().tls(), None);
+    }
+
     #[test]
     fn test_default_transport() {
         assert_eq!(TransportSettings::default(), TransportSettings::Tcp);
@@ -2,7 +2,7 @@ use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::validation::{ValidationError, validate_rule_match};
+use super::validation::{ValidationError, validate_ip_cidr, validate_rule_match};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RoutingRule {
@@ -10,6 +10,10 @@ pub struct RoutingRule {
     pub match_condition: RuleMatch,
     pub action: RuleAction,
     pub enabled: bool,
+    /// Routing target (see `SubscriptionNode::route_target`) proxied matches
+    /// go to instead of the primary node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -21,6 +25,18 @@ pub enum RuleMatch {
     IpCidr { cidr: IpNet },
 }
 
+impl RuleMatch {
+    /// Canonical form for storage: country codes are kept uppercase.
+    pub fn normalized(self) -> Self {
+        match self {
+            Self::GeoIp { country_code } => Self::GeoIp {
+                country_code: country_code.trim().to_ascii_uppercase(),
+            },
+            other => other,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RuleAction {
@@ -82,13 +98,15 @@ impl RoutingRuleSet {
         }
     }
 
-    pub fn add_validated(&mut self, rule: RoutingRule) -> Result<(), ValidationError> {
+    pub fn add_validated(&mut self, mut rule: RoutingRule) -> Result<(), ValidationError> {
+        rule.match_condition = rule.match_condition.normalized();
         validate_rule_match(&rule.match_condition)?;
         self.rules.push(rule);
         Ok(())
     }
 
-    pub fn add_at(&mut self, index: usize, rule: RoutingRule) -> Result<(), ValidationError> {
+    pub fn add_at(&mut self, index: usize, mut rule: RoutingRule) -> Result<(), ValidationError> {
+        rule.match_condition = rule.match_condition.normalized();
         validate_rule_match(&rule.match_condition)?;
         if index > self.rules.len() {
             return Err(ValidationError::IndexOutOfBounds(index));
@@ -105,6 +123,7 @@ impl RoutingRuleSet {
     ) -> Result<bool, ValidationError> {
         if let Some(rule) = self.rules.iter_mut().find(|r| r.id == *id) {
             if let Some(new_match) = match_condition {
+                let new_match = new_match.normalized();
                 validate_rule_match(&new_match)?;
                 rule.match_condition = new_match;
             }
@@ -124,7 +143,77 @@ impl Default for RoutingRuleSet {
     }
 }
 
+pub fn parse_rule_lines(
+    content: &str,
+    action: RuleAction,
+) -> (Vec<RoutingRule>, Vec<(String, ValidationError)>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+
+    for line in content.lines() {
+        let entry = line.split('#').next().unwrap_or("").trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        match parse_rule_entry(entry) {
+            Ok(match_condition) => rules.push(RoutingRule {
+                id: Uuid::new_v4(),
+                match_condition,
+                action,
+                enabled: true,
+                target: None,
+            }),
+            Err(e) => errors.push((entry.to_string(), e)),
+        }
+    }
+
+    (rules, errors)
+}
+
+/// Prefixes v2ray-style domain lists put on entries. `full:` has no exact
+/// counterpart here and is widened to the domain and its subdomains.
+const DOMAIN_LIST_PREFIXES: &[&str] = &["domain:", "full:"];
+
+/// Prefixes for matchers a rule cannot express.
+const UNSUPPORTED_LIST_PREFIXES: &[&str] = &["regexp:", "keyword:"];
+
+fn parse_rule_entry(entry: &str) -> Result<RuleMatch, ValidationError> {
+    if UNSUPPORTED_LIST_PREFIXES
+        .iter()
+        .any(|p| entry.starts_with(p))
+    {
+        return Err(ValidationError::InvalidDomainPattern(entry.to_string()));
+    }
+    let domain = DOMAIN_LIST_PREFIXES
+        .iter()
+        .find_map(|prefix| entry.strip_prefix(prefix));
+    let match_condition = if let Some(domain) = domain {
+        RuleMatch::Domain {
+            pattern: domain.trim().to_lowercase(),
+        }
+    } else if let Ok(addr) = entry.parse::<std::net::IpAddr>() {
+        RuleMatch::IpCidr {
+            cidr: IpNet::from(addr),
+        }
+    } else if entry.contains('/') || entry.contains(':') {
+        validate_ip_cidr(entry)?;
+        RuleMatch::IpCidr {
+            cidr: entry
+                .parse()
+                .map_err(|_| ValidationError::InvalidIpCidr(entry.to_string()))?,
+        }
+    } else {
+        RuleMatch::Domain {
+            pattern: entry.to_lowercase(),
+        }
+    };
+    validate_rule_match(&match_condition)?;
+    Ok(match_condition)
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -136,6 +225,7 @@ mod tests {
             },
             action,
             enabled: true,
+            target: None,
         }
     }
 
@@ -221,6 +311,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         };
         let json = serde_json::to_string(&rule).unwrap();
         let deserialized: RoutingRule = serde_json::from_str(&json).unwrap();
@@ -236,6 +327,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         };
         let json = serde_json::to_string(&rule).unwrap();
         let deserialized: RoutingRule = serde_json::from_str(&json).unwrap();
@@ -252,6 +344,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         };
 
         let result = set.add_validated(rule.clone());
@@ -270,6 +363,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         };
 
         let result = set.add_validated(rule);
@@ -277,6 +371,49 @@ mod tests {
         assert_eq!(set.rules().len(), 0);
     }
 
+    #[test]
+    fn test_country_code_any_case_stored_uppercase() {
+        let mut set = RoutingRuleSet::new();
+        for code in ["ru", "Ru", "RU"] {
+            set.add_validated(make_rule(code, RuleAction::Direct))
+                .unwrap();
+        }
+
+        assert_eq!(set.rules().len(), 3);
+        for rule in set.rules() {
+            assert_eq!(
+                rule.match_condition,
+                RuleMatch::GeoIp {
+                    country_code: "RU".into()
+                }
+            );
+        }
+
+        let id = set.rules()[0].id;
+        set.edit_rule(
+            &id,
+            Some(RuleMatch::GeoIp {
+                country_code: "cn".into(),
+            }),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            set.rules()[0].match_condition,
+            RuleMatch::GeoIp {
+                country_code: "CN".into()
+            }
+        );
+        assert!(
+            set.add_validated(make_rule("rus", RuleAction::Direct))
+                .is_err()
+        );
+        assert!(
+            set.add_validated(make_rule("zz", RuleAction::Direct))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_add_validated_invalid_domain() {
         let mut set = RoutingRuleSet::new();
@@ -287,6 +424,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         };
 
         let result = set.add_validated(rule);
@@ -309,6 +447,7 @@ mod tests {
             },
             action: RuleAction::Block,
             enabled: true,
+            target: None,
         };
 
         let result = set.add_at(1, r_middle.clone());
@@ -329,6 +468,7 @@ mod tests {
             },
             action: RuleAction::Proxy,
             enabled: true,
+            target: None,
         };
 
         let result = set.add_at(0, rule);
@@ -413,4 +553,94 @@ mod tests {
         let result = set.edit_rule(&id, Some(invalid_match), None);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_rule_lines_mixed() {
+        let content = "# migrated list\n\
+            example.com\n\
+            *.ads.example.net  # trailing comment\n\
+            \n\
+            10.0.0.0/8\n\
+            1.1.1.1\n\
+            2001:db8::/32\n\
+            not a domain\n\
+            300.1.1.0/24\n";
+
+        let (rules, errors) = parse_rule_lines(content, RuleAction::Block);
+
+        assert_eq!(rules.len(), 5);
+        assert!(
+            rules
+                .iter()
+                .all(|r| r.action == RuleAction::Block && r.enabled)
+        );
+        assert_eq!(
+            rules[0].match_condition,
+            RuleMatch::Domain {
+                pattern: "example.com".into()
+            }
+        );
+        assert_eq!(
+            rules[1].match_condition,
+            RuleMatch::Domain {
+                pattern: "*.ads.example.net".into()
+            }
+        );
+        assert_eq!(
+            rules[2].match_condition,
+            RuleMatch::IpCidr {
+                cidr: "10.0.0.0/8".parse().unwrap()
+            }
+        );
+        assert_eq!(
+            rules[3].match_condition,
+            RuleMatch::IpCidr {
+                cidr: "1.1.1.1/32".parse().unwrap()
+            }
+        );
+        assert_eq!(
+            rules[4].match_condition,
+            RuleMatch::IpCidr {
+                cidr: "2001:db8::/32".parse().unwrap()
+            }
+        );
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, "not a domain");
+        assert!(matches!(
+            errors[0].1,
+            ValidationError::InvalidDomainPattern(_)
+        ));
+        assert_eq!(errors[1].0, "300.1.1.0/24");
+        assert!(matches!(errors[1].1, ValidationError::InvalidIpCidr(_)));
+    }
+
+    #[test]
+    fn test_parse_rule_lines_domain_list_prefixes() {
+        let content = "domain:Example.com\nfull:www.example.org\nregexp:^ads\\.\nkeyword:ads\n";
+
+        let (rules, errors) = parse_rule_lines(content, RuleAction::Direct);
+
+        let patterns: Vec<_> = rules.iter().map(|r| &r.match_condition).collect();
+        assert_eq!(
+            patterns,
+            [
+                &RuleMatch::Domain {
+                    pattern: "example.com".into()
+                },
+                &RuleMatch::Domain {
+                    pattern: "www.example.org".into()
+                },
+            ]
+        );
+        let skipped: Vec<_> = errors.iter().map(|(line, _)| line.as_str()).collect();
+        assert_eq!(skipped, ["regexp:^ads\\.", "keyword:ads"]);
+    }
+
+    #[test]
+    fn test_parse_rule_lines_empty() {
+        let (rules, errors) = parse_rule_lines("# only comments\n\n", RuleAction::Proxy);
+        assert!(rules.is_empty());
+        assert!(errors.is_empty());
+    }
 }
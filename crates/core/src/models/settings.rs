@@ -2,6 +2,14 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use super::routing::RuleAction;
+use super::subscription::{NodeSort, SubscriptionSort};
+
+pub const DEFAULT_HEALTH_CHECK_URL: &str = "http://cp.cloudflare.com/generate_204";
+pub const DEFAULT_HEALTH_CHECK_STATUS: u16 = 204;
+pub const DEFAULT_PROCESS_EVENT_CAPACITY: usize = 1024;
+pub const DEFAULT_CLASH_API_PORT: u16 = 9090;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BackendType {
@@ -33,22 +41,99 @@ pub enum Language {
     #[default]
     English,
     Russian,
+    Chinese,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::Russian, Language::Chinese];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamProtocol {
+    Socks,
+    Http,
+}
+
+/// How subscription downloads reach the network, independent of the proxy
+/// this app runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum FetchProxy {
+    /// Connect directly, whatever the proxy environment variables say.
+    #[default]
+    Direct,
+    /// Follow `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY`.
+    System,
+    /// An `http://` or `https://` proxy URL.
+    Custom { url: String },
+}
+
+/// A proxy the backend's direct outbound dials through, e.g. a corporate one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamProxy {
+    pub protocol: UpstreamProtocol,
+    pub host: String,
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     pub version: u32,
     pub backend: BackendConfig,
     pub socks_port: u16,
     pub http_port: u16,
+    pub enable_socks: bool,
+    pub enable_http: bool,
+    pub socks_udp: bool,
+    /// Off makes direct connections resolve and dial IPv4 only.
+    pub ipv6: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_proxy: Option<UpstreamProxy>,
+    /// Where traffic that matches no routing rule goes.
+    pub default_action: RuleAction,
     pub auto_update_subscriptions: bool,
     pub subscription_update_interval_secs: u64,
+    pub subscription_proxy: FetchProxy,
+    /// sing-box only: offer each subscription as a selector outbound that
+    /// proxied traffic goes through, switchable over the Clash API.
+    pub subscription_groups: bool,
+    pub clash_api_port: u16,
+    /// Order kept for nodes and subscriptions; `None` keeps the manual order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_sort: Option<NodeSort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_sort: Option<SubscriptionSort>,
+    pub pause_updates_on_metered: bool,
+    /// Reject VLESS/VMess links whose user ID is not a UUID instead of
+    /// importing them with a warning.
+    pub strict_uuid: bool,
+    /// URL requested through a proxy to tell whether it actually works.
+    pub health_check_url: String,
+    pub health_check_expected_status: u16,
+    /// Warn this many days before a subscription expires.
+    pub expiry_warning_days: u32,
+    /// Backend events buffered per reader before log lines start dropping.
+    /// State changes are never dropped.
+    pub process_event_capacity: usize,
     pub auto_update_geodata: bool,
     pub geodata_update_interval_secs: u64,
     pub language: Language,
     pub minimize_to_tray: bool,
     pub notifications_enabled: bool,
     pub onboarding_complete: bool,
+    pub window_width: Option<i32>,
+    pub window_height: Option<i32>,
+    pub window_maximized: bool,
+    pub paned_position: Option<i32>,
+    /// Keep the logs page scrolled to the newest line.
+    pub log_auto_scroll: bool,
+    pub log_wrap_lines: bool,
 }
 
 impl Default for AppSettings {
@@ -58,18 +143,70 @@ impl Default for AppSettings {
             backend: BackendConfig::default(),
             socks_port: 1080,
             http_port: 1081,
+            enable_socks: true,
+            enable_http: true,
+            socks_udp: true,
+            ipv6: true,
+            upstream_proxy: None,
+            default_action: RuleAction::Proxy,
             auto_update_subscriptions: true,
             subscription_update_interval_secs: 86400,
+            subscription_proxy: FetchProxy::Direct,
+            subscription_groups: false,
+            clash_api_port: DEFAULT_CLASH_API_PORT,
+            node_sort: None,
+            subscription_sort: None,
+            pause_updates_on_metered: true,
+            strict_uuid: false,
+            health_check_url: DEFAULT_HEALTH_CHECK_URL.to_owned(),
+            health_check_expected_status: DEFAULT_HEALTH_CHECK_STATUS,
+            expiry_warning_days: 3,
+            process_event_capacity: DEFAULT_PROCESS_EVENT_CAPACITY,
             auto_update_geodata: true,
             geodata_update_interval_secs: 604800,
             language: Language::English,
             minimize_to_tray: true,
             notifications_enabled: true,
             onboarding_complete: false,
+            window_width: None,
+            window_height: None,
+            window_maximized: false,
+            paned_position: None,
+            log_auto_scroll: true,
+            log_wrap_lines: true,
+        }
+    }
+}
+
+impl AppSettings {
+    /// Whether the backend serves the Clash API, for switching groups.
+    pub fn serves_clash_api(&self) -> bool {
+        self.subscription_groups && self.backend.backend_type == BackendType::SingBox
+    }
+
+    /// Ports the backend listens on: the inbounds that are switched on and
+    /// the Clash API when it is served.
+    pub fn inbound_ports(&self) -> Vec<u16> {
+        let mut ports = Vec::new();
+        if self.enable_socks {
+            ports.push(self.socks_port);
         }
+        if self.enable_http && !ports.contains(&self.http_port) {
+            ports.push(self.http_port);
+        }
+        if self.serves_clash_api() && !ports.contains(&self.clash_api_port) {
+            ports.push(self.clash_api_port);
+        }
+        ports
     }
 }
 
+/// Whether a scheduled background update should be skipped for now,
+/// given the detected metered state of the current network.
+pub fn should_defer_auto_update(pause_on_metered: bool, metered: bool) -> bool {
+    pause_on_metered && metered
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,6 +223,49 @@ mod tests {
         assert!(!settings.onboarding_complete);
     }
 
+    #[test]
+    fn test_inbound_ports_skip_disabled_inbounds() {
+        let mut settings = AppSettings::default();
+        assert!(settings.enable_socks && settings.enable_http);
+        assert_eq!(settings.inbound_ports(), [1080, 1081]);
+        settings.enable_socks = false;
+        assert_eq!(settings.inbound_ports(), [1081]);
+    }
+
+    #[test]
+    fn test_inbound_ports_include_clash_api_for_singbox_groups() {
+        let mut settings = AppSettings {
+            subscription_groups: true,
+            ..AppSettings::default()
+        };
+        assert_eq!(settings.inbound_ports(), [1080, 1081]);
+        settings.backend.backend_type = BackendType::SingBox;
+        assert_eq!(
+            settings.inbound_ports(),
+            [1080, 1081, DEFAULT_CLASH_API_PORT]
+        );
+    }
+
+    #[test]
+    fn test_defer_auto_update_on_metered() {
+        assert!(should_defer_auto_update(true, true));
+        assert!(!should_defer_auto_update(true, false));
+    }
+
+    #[test]
+    fn test_defer_auto_update_disabled() {
+        assert!(!should_defer_auto_update(false, true));
+        assert!(!should_defer_auto_update(false, false));
+        assert!(AppSettings::default().pause_updates_on_metered);
+    }
+
+    #[test]
+    fn test_default_health_check() {
+        let settings = AppSettings::default();
+        assert_eq!(settings.health_check_url, DEFAULT_HEALTH_CHECK_URL);
+        assert_eq!(settings.health_check_expected_status, 204);
+    }
+
     #[test]
     fn test_default_backend() {
         let backend = BackendConfig::default();
@@ -102,6 +282,61 @@ mod tests {
         assert_eq!(settings, deserialized);
     }
 
+    #[test]
+    fn test_settings_missing_fields_use_defaults() {
+        let toml_str = r#"
+version = 1
+socks_port = 2080
+
+[backend]
+backend_type = "v2ray"
+"#;
+        let settings: AppSettings = toml::from_str(toml_str).unwrap();
+        assert_eq!(settings.socks_port, 2080);
+        assert_eq!(settings.http_port, 1081);
+        assert_eq!(settings.backend.backend_type, BackendType::V2ray);
+        assert!(settings.window_width.is_none());
+        assert!(!settings.window_maximized);
+    }
+
+    #[test]
+    fn test_settings_window_geometry_roundtrip() {
+        let settings = AppSettings {
+            window_width: Some(1200),
+            window_height: Some(800),
+            window_maximized: true,
+            paned_position: Some(420),
+            ..Default::default()
+        };
+        let toml_str = toml::to_string(&settings).unwrap();
+        let deserialized: AppSettings = toml::from_str(&toml_str).unwrap();
+        assert_eq!(settings, deserialized);
+    }
+
+    #[test]
+    fn test_upstream_proxy_toml_roundtrip() {
+        let settings = AppSettings {
+            upstream_proxy: Some(UpstreamProxy {
+                protocol: UpstreamProtocol::Http,
+                host: "proxy.corp.example".into(),
+                port: 3128,
+                username: Some("alice".into()),
+                password: None,
+            }),
+            ..Default::default()
+        };
+        let toml_str = toml::to_string(&settings).unwrap();
+        assert!(toml_str.contains("protocol = \"http\""));
+        let deserialized: AppSettings = toml::from_str(&toml_str).unwrap();
+        assert_eq!(settings, deserialized);
+    }
+
+    #[test]
+    fn test_language_serializes_lowercase() {
+        let settings: AppSettings = toml::from_str("language = \"chinese\"").unwrap();
+        assert_eq!(settings.language, Language::Chinese);
+    }
+
     #[test]
     fn test_settings_json_roundtrip() {
         let settings = AppSettings::default();
@@ -1,8 +1,10 @@
-use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::ProxyNode;
+use super::{ProxyNode, TlsOverride};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Subscription {
@@ -13,13 +15,46 @@ pub struct Subscription {
     pub last_updated: Option<DateTime<Utc>>,
     pub auto_update_interval_secs: Option<u64>,
     pub enabled: bool,
+    /// Alternative URLs tried in order when the primary URL fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirror_urls: Vec<String>,
+    /// Why the most recent refresh failed; cleared by the next successful one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_at: Option<DateTime<Utc>>,
+    /// Traffic and expiry reported by the provider in `Subscription-Userinfo`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<SubscriptionUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry_notified_at: Option<DateTime<Utc>>,
+    /// Folder the subscription is listed under; `None` is ungrouped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubscriptionUsage {
+    pub upload: u64,
+    pub download: u64,
+    /// Data cap in bytes; `None` when unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum SubscriptionSource {
-    Url { url: String },
-    File { path: String },
+    Url {
+        url: String,
+    },
+    File {
+        path: String,
+    },
+    /// Nodes added one by one (e.g. scanned from a QR code); never refreshed.
+    Manual,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -28,6 +63,55 @@ pub struct SubscriptionNode {
     pub enabled: bool,
     #[serde(skip_serializing, default)]
     pub last_latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub latency_history: Vec<(DateTime<Utc>, Option<u64>)>,
+    /// Hand-edited TLS settings, already applied to `node`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_override: Option<TlsOverride>,
+    /// Pinned by the user; kept across refreshes and sorted first.
+    #[serde(default)]
+    pub favorite: bool,
+    /// The node proxied traffic goes to, whatever its position among the
+    /// enabled nodes. At most one node across all subscriptions has it set.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+    /// Set when routing rules may send traffic to this node; kept across
+    /// reorders and refreshes so rules keep pointing at it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_target: Option<Uuid>,
+}
+
+pub const LATENCY_HISTORY_CAP: usize = 10;
+
+/// Minimum gap between two expiry warnings for the same subscription.
+const EXPIRY_NOTIFY_INTERVAL: TimeDelta = TimeDelta::days(1);
+
+/// Latency changes within this fraction of the previous sample count as flat.
+const LATENCY_TREND_TOLERANCE: f64 = 0.1;
+
+/// Order kept for the nodes of every subscription. Nodes are used in list
+/// order, so this also decides which enabled node becomes the primary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSort {
+    Name,
+    Address,
+    Latency,
+    Protocol,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionSort {
+    Name,
+    LastUpdated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyTrend {
+    Better,
+    Worse,
+    Flat,
 }
 
 impl Subscription {
@@ -40,6 +124,12 @@ impl Subscription {
             last_updated: None,
             auto_update_interval_secs: Some(86400),
             enabled: true,
+            mirror_urls: Vec::new(),
+            last_error: None,
+            last_error_at: None,
+            usage: None,
+            expiry_notified_at: None,
+            group: None,
         }
     }
 
@@ -52,6 +142,30 @@ impl Subscription {
             last_updated: None,
             auto_update_interval_secs: None,
             enabled: true,
+            mirror_urls: Vec::new(),
+            last_error: None,
+            last_error_at: None,
+            usage: None,
+            expiry_notified_at: None,
+            group: None,
+        }
+    }
+
+    pub fn new_manual(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            source: SubscriptionSource::Manual,
+            nodes: Vec::new(),
+            last_updated: None,
+            auto_update_interval_secs: None,
+            enabled: true,
+            mirror_urls: Vec::new(),
+            last_error: None,
+            last_error_at: None,
+            usage: None,
+            expiry_notified_at: None,
+            group: None,
         }
     }
 
@@ -63,3 +177,732 @@ impl Subscription {
         self.enabled && self.nodes.iter().any(|n| n.enabled)
     }
 }
+
+impl SubscriptionUsage {
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+
+    pub fn over_cap(&self) -> bool {
+        self.total.is_some_and(|total| self.used() >= total)
+    }
+
+    /// Whether the subscription has run out of data or expires within
+    /// `threshold_days` of `now` (already expired included).
+    pub fn needs_attention(&self, threshold_days: u32, now: DateTime<Utc>) -> bool {
+        self.over_cap()
+            || self
+                .expire
+                .is_some_and(|expire| expire - now <= TimeDelta::days(threshold_days.into()))
+    }
+}
+
+/// Decides whether to warn about `usage` now, warning at most once a day.
+pub fn should_notify_expiry(
+    usage: &SubscriptionUsage,
+    last_notified: Option<DateTime<Utc>>,
+    threshold_days: u32,
+    now: DateTime<Utc>,
+) -> bool {
+    usage.needs_attention(threshold_days, now)
+        && last_notified.is_none_or(|last| now - last >= EXPIRY_NOTIFY_INTERVAL)
+}
+
+impl SubscriptionNode {
+    /// An enabled node with no history or user choices yet.
+    pub fn new(node: ProxyNode) -> Self {
+        Self {
+            node,
+            enabled: true,
+            last_latency_ms: None,
+            latency_history: Vec::new(),
+            tls_override: None,
+            favorite: false,
+            primary: false,
+            route_target: None,
+        }
+    }
+
+    pub fn record_latency(&mut self, latency: Option<u64>) {
+        self.last_latency_ms = latency;
+        self.latency_history.push((Utc::now(), latency));
+        let excess = self
+            .latency_history
+            .len()
+            .saturating_sub(LATENCY_HISTORY_CAP);
+        self.latency_history.drain(..excess);
+    }
+
+    /// Compares the latest sample with the most recent earlier successful one.
+    /// Returns `None` if the latest test failed or there is nothing to compare.
+    pub fn latency_trend(&self) -> Option<LatencyTrend> {
+        let (latest, earlier) = self.latency_history.split_last()?;
+        let latest = latest.1?;
+        let previous = earlier.iter().rev().find_map(|(_, l)| *l)?;
+
+        let tolerance = (previous as f64 * LATENCY_TREND_TOLERANCE).max(1.0);
+        let diff = latest as f64 - previous as f64;
+        Some(if diff.abs() <= tolerance {
+            LatencyTrend::Flat
+        } else if diff < 0.0 {
+            LatencyTrend::Better
+        } else {
+            LatencyTrend::Worse
+        })
+    }
+}
+
+fn node_name(node: &SubscriptionNode) -> String {
+    node.node
+        .remark()
+        .unwrap_or_else(|| node.node.address())
+        .to_lowercase()
+}
+
+/// Favorites come first under every key. By latency, untested and
+/// unreachable nodes go after every measured one.
+pub fn compare_nodes(a: &SubscriptionNode, b: &SubscriptionNode, key: NodeSort) -> Ordering {
+    let by_address = |a: &SubscriptionNode, b: &SubscriptionNode| {
+        (a.node.address(), a.node.port()).cmp(&(b.node.address(), b.node.port()))
+    };
+    let by_key = match key {
+        NodeSort::Name => node_name(a).cmp(&node_name(b)),
+        NodeSort::Address => by_address(a, b),
+        NodeSort::Latency => a
+            .last_latency_ms
+            .unwrap_or(u64::MAX)
+            .cmp(&b.last_latency_ms.unwrap_or(u64::MAX)),
+        NodeSort::Protocol => a
+            .node
+            .protocol()
+            .cmp(b.node.protocol())
+            .then_with(|| node_name(a).cmp(&node_name(b))),
+    };
+    b.favorite.cmp(&a.favorite).then(by_key)
+}
+
+/// Most recently updated first; never-updated subscriptions go last.
+pub fn compare_subscriptions(
+    a: &Subscription,
+    b: &Subscription,
+    key: SubscriptionSort,
+) -> Ordering {
+    match key {
+        SubscriptionSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SubscriptionSort::LastUpdated => b.last_updated.cmp(&a.last_updated),
+    }
+}
+
+/// Makes node `idx` of subscription `id` the only primary node, or clears
+/// the choice with `None`. Returns whether anything changed.
+pub fn set_primary_node(subscriptions: &mut [Subscription], target: Option<(Uuid, usize)>) -> bool {
+    let mut changed = false;
+    for sub in subscriptions {
+        for (idx, node) in sub.nodes.iter_mut().enumerate() {
+            let primary = target == Some((sub.id, idx));
+            changed |= node.primary != primary;
+            node.primary = primary;
+        }
+    }
+    changed
+}
+
+/// Enables the nodes last measured faster than `max_ms` and disables the
+/// rest, untested and unreachable ones included. Favorites are never
+/// disabled. Returns how many are enabled.
+pub fn enable_by_latency(nodes: &mut [SubscriptionNode], max_ms: u64) -> usize {
+    let mut enabled = 0;
+    for node in nodes {
+        node.enabled = node.favorite || node.last_latency_ms.is_some_and(|ms| ms < max_ms);
+        enabled += usize::from(node.enabled);
+    }
+    enabled
+}
+
+/// Enabled nodes of enabled subscriptions that rules can route to, with a
+/// label for each: the remark, or the address when there is none.
+pub fn route_targets(subscriptions: &[Subscription]) -> Vec<(Uuid, String)> {
+    subscriptions
+        .iter()
+        .filter(|s| s.enabled)
+        .flat_map(|s| s.nodes.iter().filter(|n| n.enabled))
+        .filter_map(|n| {
+            let label = match n.node.remark() {
+                Some(remark) => remark.to_owned(),
+                None => format!("{}:{}", n.node.address(), n.node.port()),
+            };
+            n.route_target.map(|id| (id, label))
+        })
+        .collect()
+}
+
+/// Enabled subscriptions with an enabled node, by name: the groups sing-box
+/// rules can route to when subscription groups are on.
+pub fn group_targets(subscriptions: &[Subscription]) -> Vec<(Uuid, String)> {
+    subscriptions
+        .iter()
+        .filter(|s| s.enabled && s.nodes.iter().any(|n| n.enabled))
+        .map(|s| (s.id, s.name.clone()))
+        .collect()
+}
+
+/// Copies which subscriptions and nodes are enabled (and the primary node)
+/// from `shown` onto the matching entries of `stored`. Nodes are matched by
+/// `ProxyNode::key`, so a refresh that reordered or replaced them since
+/// leaves the new nodes alone. Returns whether anything changed.
+pub fn apply_selection(stored: &mut [Subscription], shown: &[Subscription]) -> bool {
+    let mut changed = false;
+    for sub in stored {
+        let Some(shown) = shown.iter().find(|s| s.id == sub.id) else {
+            continue;
+        };
+        if sub.enabled != shown.enabled {
+            sub.enabled = shown.enabled;
+            changed = true;
+        }
+        let shown_keys: Vec<_> = shown.nodes.iter().map(|n| n.node.key()).collect();
+        let mut taken = vec![false; shown_keys.len()];
+        for (i, node) in sub.nodes.iter_mut().enumerate() {
+            let key = node.node.key();
+            let free = |j: &usize| !taken[*j] && shown_keys[*j] == key;
+            // Prefer the same position, for lists holding a node twice.
+            let Some(j) = Some(i)
+                .filter(|j| *j < shown_keys.len() && free(j))
+                .or_else(|| (0..shown_keys.len()).find(free))
+            else {
+                continue;
+            };
+            taken[j] = true;
+            let shown = &shown.nodes[j];
+            if (node.enabled, node.primary) != (shown.enabled, shown.primary) {
+                node.enabled = shown.enabled;
+                node.primary = shown.primary;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// The group to store for a user-entered name; blank means ungrouped.
+pub fn group_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+/// Subscriptions as listed: each named group in the order its first member
+/// is stored, then the ungrouped ones. Members keep their stored order.
+pub fn group_subscriptions(
+    subscriptions: &[Subscription],
+) -> Vec<(Option<&str>, Vec<&Subscription>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&Subscription>)> = Vec::new();
+    let mut ungrouped = Vec::new();
+    for sub in subscriptions {
+        let Some(name) = sub.group.as_deref() else {
+            ungrouped.push(sub);
+            continue;
+        };
+        match groups.iter_mut().find(|(group, _)| *group == Some(name)) {
+            Some((_, members)) => members.push(sub),
+            None => groups.push((Some(name), vec![sub])),
+        }
+    }
+    if !ungrouped.is_empty() {
+        groups.push((None, ungrouped));
+    }
+    groups
+}
+
+/// Moves subscription `from` to the position of `to`, into `to`'s group, so
+/// dropping onto a member of another group moves it there. Returns whether
+/// anything changed.
+pub fn move_subscription(subscriptions: &mut Vec<Subscription>, from: Uuid, to: Uuid) -> bool {
+    let from_pos = subscriptions.iter().position(|s| s.id == from);
+    let to_pos = subscriptions.iter().position(|s| s.id == to);
+    let (Some(from_pos), Some(to_pos)) = (from_pos, to_pos) else {
+        return false;
+    };
+    if from_pos == to_pos {
+        return false;
+    }
+    let mut sub = subscriptions.remove(from_pos);
+    sub.group = subscriptions[if from_pos < to_pos {
+        to_pos - 1
+    } else {
+        to_pos
+    }]
+    .group
+    .clone();
+    subscriptions.insert(to_pos, sub);
+    true
+}
+
+/// `url` in the form used to spot duplicates: trimmed, with the host
+/// lowercased, default port and trailing slashes dropped. Unparseable input is
+/// only trimmed.
+pub fn normalize_subscription_url(url: &str) -> String {
+    let url = url.trim();
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return url.to_owned();
+    };
+    let path = parsed.path().trim_end_matches('/').to_owned();
+    parsed.set_path(&path);
+    parsed.as_str().trim_end_matches('/').to_owned()
+}
+
+/// The URL subscription already fetching from `url`, if any.
+pub fn find_subscription_by_url<'a>(
+    subscriptions: &'a [Subscription],
+    url: &str,
+) -> Option<&'a Subscription> {
+    let url = normalize_subscription_url(url);
+    subscriptions.iter().find(|sub| match &sub.source {
+        SubscriptionSource::Url { url: existing } => normalize_subscription_url(existing) == url,
+        _ => false,
+    })
+}
+
+/// Stable sort; returns whether the order changed.
+pub fn sort_nodes(nodes: &mut [SubscriptionNode], key: NodeSort) -> bool {
+    if nodes.is_sorted_by(|a, b| compare_nodes(a, b, key).is_le()) {
+        return false;
+    }
+    nodes.sort_by(|a, b| compare_nodes(a, b, key));
+    true
+}
+
+/// Stable sort; returns whether the order changed.
+pub fn sort_subscriptions(subscriptions: &mut [Subscription], key: SubscriptionSort) -> bool {
+    if subscriptions.is_sorted_by(|a, b| compare_subscriptions(a, b, key).is_le()) {
+        return false;
+    }
+    subscriptions.sort_by(|a, b| compare_subscriptions(a, b, key));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ShadowsocksConfig, TransportSettings, TrojanConfig};
+
+    fn node_with_history(samples: &[Option<u64>]) -> SubscriptionNode {
+        SubscriptionNode {
+            latency_history: samples.iter().map(|l| (Utc::now(), *l)).collect(),
+            ..SubscriptionNode::new(ProxyNode::Trojan(TrojanConfig {
+                address: "example.com".into(),
+                port: 443,
+                password: "pw".into(),
+                transport: TransportSettings::Tcp,
+                tls: None,
+                remark: None,
+            }))
+        }
+    }
+
+    #[test]
+    fn record_latency_trims_to_cap() {
+        let mut node = node_with_history(&[]);
+        for ms in 0..(LATENCY_HISTORY_CAP as u64 + 5) {
+            node.record_latency(Some(ms));
+        }
+
+        assert_eq!(node.latency_history.len(), LATENCY_HISTORY_CAP);
+        assert_eq!(node.latency_history[0].1, Some(5));
+        assert_eq!(node.last_latency_ms, Some(LATENCY_HISTORY_CAP as u64 + 4));
+    }
+
+    #[test]
+    fn latency_trend_compares_latest_samples() {
+        assert_eq!(node_with_history(&[]).latency_trend(), None);
+        assert_eq!(node_with_history(&[Some(100)]).latency_trend(), None);
+        assert_eq!(
+            node_with_history(&[Some(100), Some(200)]).latency_trend(),
+            Some(LatencyTrend::Worse)
+        );
+        assert_eq!(
+            node_with_history(&[Some(200), Some(100)]).latency_trend(),
+            Some(LatencyTrend::Better)
+        );
+        assert_eq!(
+            node_with_history(&[Some(100), Some(105)]).latency_trend(),
+            Some(LatencyTrend::Flat)
+        );
+    }
+
+    #[test]
+    fn latency_trend_skips_none_gaps() {
+        assert_eq!(
+            node_with_history(&[Some(100), None, None, Some(300)]).latency_trend(),
+            Some(LatencyTrend::Worse)
+        );
+        assert_eq!(node_with_history(&[Some(100), None]).latency_trend(), None);
+        assert_eq!(node_with_history(&[None, Some(100)]).latency_trend(), None);
+    }
+
+    fn usage(expire_in: Option<TimeDelta>, used: u64, total: Option<u64>) -> SubscriptionUsage {
+        SubscriptionUsage {
+            upload: 0,
+            download: used,
+            total,
+            expire: expire_in.map(|d| now() + d),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn expiry_notification_threshold_boundaries() {
+        let exactly = usage(Some(TimeDelta::days(3)), 0, None);
+        assert!(should_notify_expiry(&exactly, None, 3, now()));
+
+        let just_outside = usage(Some(TimeDelta::days(3) + TimeDelta::seconds(1)), 0, None);
+        assert!(!should_notify_expiry(&just_outside, None, 3, now()));
+
+        let expired = usage(Some(TimeDelta::days(-2)), 0, None);
+        assert!(should_notify_expiry(&expired, None, 3, now()));
+
+        let no_expiry = usage(None, 10, Some(100));
+        assert!(!should_notify_expiry(&no_expiry, None, 3, now()));
+    }
+
+    #[test]
+    fn expiry_notification_over_data_cap() {
+        assert!(should_notify_expiry(
+            &usage(None, 100, Some(100)),
+            None,
+            3,
+            now()
+        ));
+        assert!(!should_notify_expiry(
+            &usage(None, 100, None),
+            None,
+            3,
+            now()
+        ));
+    }
+
+    #[test]
+    fn expiry_notification_once_per_day() {
+        let expiring = usage(Some(TimeDelta::days(1)), 0, None);
+        let hour_ago = now() - TimeDelta::hours(1);
+        let day_ago = now() - TimeDelta::days(1);
+        assert!(!should_notify_expiry(&expiring, Some(hour_ago), 3, now()));
+        assert!(should_notify_expiry(&expiring, Some(day_ago), 3, now()));
+    }
+
+    fn named_node(remark: Option<&str>, address: &str, port: u16) -> SubscriptionNode {
+        let mut node = node_with_history(&[]);
+        let ProxyNode::Trojan(c) = &mut node.node else {
+            unreachable!()
+        };
+        c.remark = remark.map(str::to_owned);
+        c.address = address.into();
+        c.port = port;
+        node
+    }
+
+    fn remarks(nodes: &[SubscriptionNode]) -> Vec<&str> {
+        nodes
+            .iter()
+            .map(|n| n.node.remark().unwrap_or(n.node.address()))
+            .collect()
+    }
+
+    #[test]
+    fn sort_nodes_by_name_and_address() {
+        let mut nodes = vec![
+            named_node(Some("beta"), "b.com", 443),
+            named_node(None, "c.com", 80),
+            named_node(Some("Alpha"), "a.com", 8443),
+            named_node(Some("gamma"), "a.com", 443),
+        ];
+        assert!(sort_nodes(&mut nodes, NodeSort::Name));
+        assert_eq!(remarks(&nodes), ["Alpha", "beta", "c.com", "gamma"]);
+        assert!(!sort_nodes(&mut nodes, NodeSort::Name));
+
+        assert!(sort_nodes(&mut nodes, NodeSort::Address));
+        assert_eq!(remarks(&nodes), ["gamma", "Alpha", "beta", "c.com"]);
+    }
+
+    #[test]
+    fn sort_nodes_by_latency_puts_untested_last() {
+        let mut nodes: Vec<_> = [None, Some(300), Some(50)]
+            .into_iter()
+            .map(|latency| SubscriptionNode {
+                last_latency_ms: latency,
+                ..node_with_history(&[])
+            })
+            .collect();
+        sort_nodes(&mut nodes, NodeSort::Latency);
+        let latencies: Vec<_> = nodes.iter().map(|n| n.last_latency_ms).collect();
+        assert_eq!(latencies, [Some(50), Some(300), None]);
+    }
+
+    #[test]
+    fn sort_nodes_by_protocol_then_name() {
+        let ss = SubscriptionNode {
+            node: ProxyNode::Shadowsocks(ShadowsocksConfig {
+                address: "ss.com".into(),
+                port: 8388,
+                method: "aes-256-gcm".into(),
+                password: "pw".into(),
+                remark: Some("zulu".into()),
+            }),
+            ..node_with_history(&[])
+        };
+        let mut nodes = vec![
+            named_node(Some("b"), "b.com", 443),
+            ss,
+            named_node(Some("a"), "a.com", 443),
+        ];
+        sort_nodes(&mut nodes, NodeSort::Protocol);
+        assert_eq!(remarks(&nodes), ["zulu", "a", "b"]);
+    }
+
+    #[test]
+    fn sort_nodes_puts_favorites_first() {
+        let mut nodes = vec![
+            named_node(Some("a"), "a.com", 443),
+            SubscriptionNode {
+                favorite: true,
+                ..named_node(Some("c"), "c.com", 443)
+            },
+            named_node(Some("b"), "b.com", 443),
+        ];
+        sort_nodes(&mut nodes, NodeSort::Name);
+        assert_eq!(remarks(&nodes), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn set_primary_node_keeps_a_single_primary() {
+        let mut a = Subscription::new_manual("a");
+        a.nodes = vec![named_node(Some("a1"), "a.com", 443)];
+        let mut b = Subscription::new_manual("b");
+        b.nodes = vec![
+            named_node(Some("b1"), "b.com", 443),
+            named_node(Some("b2"), "b.com", 444),
+        ];
+        let (a_id, b_id) = (a.id, b.id);
+        let mut subs = vec![a, b];
+        let primaries = |subs: &[Subscription]| {
+            subs.iter()
+                .flat_map(|s| &s.nodes)
+                .filter(|n| n.primary)
+                .filter_map(|n| n.node.remark().map(str::to_owned))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(set_primary_node(&mut subs, Some((a_id, 0))));
+        assert_eq!(primaries(&subs), ["a1"]);
+        assert!(set_primary_node(&mut subs, Some((b_id, 1))));
+        assert_eq!(primaries(&subs), ["b2"]);
+        assert!(!set_primary_node(&mut subs, Some((b_id, 1))));
+        assert!(set_primary_node(&mut subs, None));
+        assert!(primaries(&subs).is_empty());
+    }
+
+    #[test]
+    fn sort_subscriptions_by_name_and_last_updated() {
+        let mut stale = Subscription::new_from_url("beta", "https://b.example/sub");
+        stale.last_updated = Some(now() - TimeDelta::days(2));
+        let mut fresh = Subscription::new_from_url("Gamma", "https://g.example/sub");
+        fresh.last_updated = Some(now());
+        let never = Subscription::new_manual("alpha");
+        let mut subs = vec![stale, never, fresh];
+        let names = |subs: &[Subscription]| subs.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+
+        assert!(sort_subscriptions(&mut subs, SubscriptionSort::Name));
+        assert_eq!(names(&subs), ["alpha", "beta", "Gamma"]);
+
+        assert!(sort_subscriptions(&mut subs, SubscriptionSort::LastUpdated));
+        assert_eq!(names(&subs), ["Gamma", "beta", "alpha"]);
+        assert!(!sort_subscriptions(
+            &mut subs,
+            SubscriptionSort::LastUpdated
+        ));
+    }
+
+    #[test]
+    fn enable_by_latency_keeps_only_fast_tested_nodes() {
+        let mut nodes: Vec<SubscriptionNode> = [Some(80), Some(300), None, Some(299)]
+            .into_iter()
+            .map(|latency| {
+                let mut node = node_with_history(&[]);
+                node.last_latency_ms = latency;
+                node.enabled = latency.is_none();
+                node
+            })
+            .collect();
+
+        let mut favorite = node_with_history(&[]);
+        favorite.favorite = true;
+        favorite.enabled = false;
+        nodes.push(favorite);
+
+        assert_eq!(enable_by_latency(&mut nodes, 300), 3);
+        let enabled: Vec<bool> = nodes.iter().map(|n| n.enabled).collect();
+        assert_eq!(enabled, [true, false, false, true, true]);
+        assert_eq!(enable_by_latency(&mut nodes, 0), 1);
+        let enabled: Vec<bool> = nodes.iter().map(|n| n.enabled).collect();
+        assert_eq!(enabled, [false, false, false, false, true]);
+    }
+
+    fn grouped(group: Option<&str>, name: &str, remarks: &[&str]) -> Subscription {
+        let mut sub = Subscription::new_manual(name);
+        sub.group = group.map(str::to_owned);
+        sub.nodes = remarks
+            .iter()
+            .map(|remark| {
+                let mut node = node_with_history(&[]);
+                if let ProxyNode::Trojan(c) = &mut node.node {
+                    c.remark = Some((*remark).to_owned());
+                }
+                node
+            })
+            .collect();
+        sub
+    }
+
+    #[test]
+    fn group_subscriptions_keeps_stored_and_node_order() {
+        let subs = vec![
+            grouped(None, "loose", &["l1"]),
+            grouped(Some("Work"), "w1", &["b", "a", "c"]),
+            grouped(Some("Home"), "h1", &["x"]),
+            grouped(Some("Work"), "w2", &["z", "y"]),
+        ];
+        let groups = group_subscriptions(&subs);
+        let layout: Vec<(Option<&str>, Vec<&str>)> = groups
+            .iter()
+            .map(|(group, members)| (*group, members.iter().map(|s| s.name.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                (Some("Work"), vec!["w1", "w2"]),
+                (Some("Home"), vec!["h1"]),
+                (None, vec!["loose"]),
+            ]
+        );
+        let remarks: Vec<&str> = groups[0].1[0]
+            .nodes
+            .iter()
+            .filter_map(|n| n.node.remark())
+            .collect();
+        assert_eq!(remarks, ["b", "a", "c"]);
+
+        let json = serde_json::to_string(&subs).unwrap();
+        let restored: Vec<Subscription> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, subs);
+        assert!(!serde_json::to_string(&subs[0]).unwrap().contains("group"));
+    }
+
+    #[test]
+    fn move_subscription_adopts_the_target_group() {
+        let mut subs = vec![
+            grouped(None, "loose", &["l1", "l2"]),
+            grouped(Some("Work"), "w1", &[]),
+            grouped(Some("Work"), "w2", &[]),
+        ];
+        let (loose, w2) = (subs[0].id, subs[2].id);
+        assert!(move_subscription(&mut subs, loose, w2));
+        let names: Vec<&str> = subs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["w1", "w2", "loose"]);
+        assert_eq!(subs[2].group.as_deref(), Some("Work"));
+        assert_eq!(subs[2].nodes.len(), 2);
+        assert!(!move_subscription(&mut subs, loose, loose));
+
+        assert_eq!(group_name("  Home "), Some("Home".to_owned()));
+        assert_eq!(group_name("   "), None);
+    }
+
+    #[test]
+    fn route_targets_lists_enabled_target_nodes() {
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let mut a = grouped(None, "a", &["Tokyo", "Paris", "Oslo"]);
+        let mut off = grouped(None, "off", &["Rome"]);
+        off.enabled = false;
+        for (node, id) in a.nodes.iter_mut().chain(&mut off.nodes).zip(&ids) {
+            node.route_target = Some(*id);
+        }
+        a.nodes[1].enabled = false;
+        a.nodes[2].route_target = None;
+        a.nodes.push(node_with_history(&[]));
+        a.nodes[3].route_target = Some(ids[2]);
+
+        assert_eq!(
+            route_targets(&[a, off]),
+            [
+                (ids[0], "Tokyo".to_owned()),
+                (ids[2], "example.com:443".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn group_targets_list_subscriptions_with_enabled_nodes() {
+        let a = grouped(None, "a", &["Tokyo"]);
+        let mut off = grouped(None, "off", &["Rome"]);
+        off.enabled = false;
+        let mut empty = grouped(None, "empty", &["Oslo"]);
+        empty.nodes[0].enabled = false;
+
+        assert_eq!(group_targets(&[a.clone(), off, empty]), [(a.id, a.name)]);
+    }
+
+    #[test]
+    fn duplicate_urls_are_found_after_normalizing() {
+        let subs = vec![
+            Subscription::new_manual("manual"),
+            Subscription::new_from_url("sub", "https://example.com/sub"),
+            Subscription::new_from_url("api", "https://Api.Example.com:443/v1?token=A"),
+        ];
+        let dup = |url| find_subscription_by_url(&subs, url).map(|s| s.name.as_str());
+
+        assert_eq!(dup("https://example.com/sub/"), Some("sub"));
+        assert_eq!(dup(" https://EXAMPLE.com/sub// "), Some("sub"));
+        assert_eq!(dup("https://example.com/Sub"), None);
+        assert_eq!(dup("https://api.example.com/v1/?token=A"), Some("api"));
+        assert_eq!(dup("https://api.example.com/v1?token=B"), None);
+        assert_eq!(normalize_subscription_url("  not a url "), "not a url");
+    }
+
+    #[test]
+    fn apply_selection_follows_nodes_a_refresh_reordered() {
+        let mut shown = Subscription::new_manual("Mine");
+        shown.nodes = vec![
+            SubscriptionNode {
+                enabled: false,
+                ..named_node(Some("a"), "a.com", 443)
+            },
+            SubscriptionNode {
+                primary: true,
+                ..named_node(Some("b"), "b.com", 443)
+            },
+        ];
+        let mut stored = shown.clone();
+        stored.nodes = vec![
+            named_node(Some("c"), "c.com", 443),
+            named_node(Some("b"), "b.com", 443),
+            named_node(Some("a"), "a.com", 443),
+        ];
+
+        let mut stored = vec![stored];
+        assert!(apply_selection(&mut stored, &[shown]));
+        let flags: Vec<_> = stored[0]
+            .nodes
+            .iter()
+            .map(|n| (n.enabled, n.primary))
+            .collect();
+        assert_eq!(flags, [(true, false), (true, true), (false, false)]);
+    }
+
+    #[test]
+    fn history_is_optional_when_deserializing() {
+        let json = r#"{"node":{"protocol":"trojan","address":"a.com","port":443,"password":"pw"},"enabled":true}"#;
+        let node: SubscriptionNode = serde_json::from_str(json).unwrap();
+        assert!(node.latency_history.is_empty());
+    }
+}
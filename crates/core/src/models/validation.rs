@@ -1,5 +1,7 @@
-use super::RuleMatch;
+use super::{AppSettings, RuleMatch, TlsOverride, UpstreamProxy};
 use ipnet::IpNet;
+use std::collections::BTreeSet;
+use std::sync::RwLock;
 use thiserror::Error;
 
 #[derive(Debug, Error, PartialEq)]
@@ -12,8 +14,28 @@ pub enum ValidationError {
     InvalidDomainPattern(String),
     #[error("invalid geosite category: {0}")]
     InvalidGeoSiteCategory(String),
+    #[error("unsupported shadowsocks method: {0}")]
+    InvalidShadowsocksMethod(String),
     #[error("index out of bounds: {0}")]
     IndexOutOfBounds(usize),
+    #[error("server name must not be empty")]
+    EmptyServerName,
+    #[error("unknown tls fingerprint: {0}")]
+    InvalidFingerprint(String),
+    #[error("node has no tls settings")]
+    NoTlsSettings,
+    #[error("invalid health check url: {0}")]
+    InvalidHealthCheckUrl(String),
+    #[error("invalid proxy url: {0}")]
+    InvalidProxyUrl(String),
+    #[error("invalid host:port: {0}")]
+    InvalidHostPort(String),
+    #[error("socks and http inbounds are both disabled")]
+    NoInbounds,
+    #[error("clash api port {0} is also used by an inbound")]
+    ClashApiPortInUse(u16),
+    #[error("unsupported vless encryption: {0}")]
+    InvalidVlessEncryption(String),
 }
 
 const VALID_COUNTRY_CODES: &[&str] = &[
@@ -40,6 +62,9 @@ const VALID_GEOSITE_CATEGORIES: &[&str] = &[
     "facebook",
     "twitter",
     "amazon",
+    "anthropic",
+    "openai",
+    "discord",
     "apple",
     "microsoft",
     "netflix",
@@ -57,6 +82,7 @@ const VALID_GEOSITE_CATEGORIES: &[&str] = &[
     "ir",
     "category-ads",
     "category-ads-all",
+    "win-spy",
     "category-porn",
     "geolocation-cn",
     "geolocation-!cn",
@@ -64,16 +90,116 @@ const VALID_GEOSITE_CATEGORIES: &[&str] = &[
     "tld-ru",
 ];
 
+const TLS_FINGERPRINTS: &[&str] = &[
+    "chrome",
+    "firefox",
+    "safari",
+    "ios",
+    "android",
+    "edge",
+    "360",
+    "qq",
+    "random",
+    "randomized",
+];
+
+const SHADOWSOCKS_METHODS: &[&str] = &[
+    "2022-blake3-aes-128-gcm",
+    "2022-blake3-aes-256-gcm",
+    "2022-blake3-chacha20-poly1305",
+    "aes-128-gcm",
+    "aes-192-gcm",
+    "aes-256-gcm",
+    "chacha20-poly1305",
+    "chacha20-ietf-poly1305",
+    "xchacha20-poly1305",
+    "xchacha20-ietf-poly1305",
+    "aes-128-ctr",
+    "aes-192-ctr",
+    "aes-256-ctr",
+    "aes-128-cfb",
+    "aes-192-cfb",
+    "aes-256-cfb",
+    "chacha20-ietf",
+    "xchacha20",
+    "rc4-md5",
+    "none",
+    "plain",
+];
+
+pub fn validate_ss_method(method: &str) -> Result<(), ValidationError> {
+    if SHADOWSOCKS_METHODS.contains(&method) {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidShadowsocksMethod(
+            method.to_string(),
+        ))
+    }
+}
+
+/// Xray's post-quantum VLESS encryption suite (ML-KEM-768 with X25519).
+const VLESS_MLKEM_SUITE: &str = "mlkem768x25519plus";
+const VLESS_MLKEM_MODES: &[&str] = &["native", "xorpub", "random"];
+const VLESS_MLKEM_HANDSHAKES: &[&str] = &["0rtt", "1rtt"];
+
+/// Accepts `none` and client-side `mlkem768x25519plus.<mode>.<rtt>.<…>`
+/// strings, whose trailing dot-separated parts (paddings and keys) carry
+/// everything the backend needs.
+pub fn validate_vless_encryption(encryption: &str) -> Result<(), ValidationError> {
+    if encryption == "none" {
+        return Ok(());
+    }
+    let invalid = || ValidationError::InvalidVlessEncryption(encryption.to_string());
+    let mut parts = encryption.split('.');
+    if parts.next() != Some(VLESS_MLKEM_SUITE)
+        || !parts.next().is_some_and(|m| VLESS_MLKEM_MODES.contains(&m))
+        || !parts
+            .next()
+            .is_some_and(|h| VLESS_MLKEM_HANDSHAKES.contains(&h))
+    {
+        return Err(invalid());
+    }
+    let rest: Vec<&str> = parts.collect();
+    let well_formed = |part: &&str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if rest.is_empty() || !rest.iter().all(well_formed) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Whether a VLESS node uses an encryption suite rather than plain `none`.
+pub fn is_vless_encrypted(encryption: Option<&str>) -> bool {
+    encryption.is_some_and(|e| !e.is_empty() && e != "none")
+}
+
+pub fn is_ss2022_method(method: &str) -> bool {
+    method.starts_with("2022-blake3-")
+}
+
+/// Key length in bytes that an SS2022 method expects its base64 PSK to decode to.
+pub fn ss2022_key_len(method: &str) -> Option<usize> {
+    match method {
+        "2022-blake3-aes-128-gcm" => Some(16),
+        "2022-blake3-aes-256-gcm" | "2022-blake3-chacha20-poly1305" => Some(32),
+        _ => None,
+    }
+}
+
 pub fn validate_country_code(code: &str) -> Result<(), ValidationError> {
     if code.len() != 2 {
         return Err(ValidationError::InvalidCountryCode(code.to_string()));
     }
 
-    if !code.chars().all(|c| c.is_ascii_uppercase()) {
+    if !code.chars().all(|c| c.is_ascii_alphabetic()) {
         return Err(ValidationError::InvalidCountryCode(code.to_string()));
     }
 
-    if !VALID_COUNTRY_CODES.contains(&code) {
+    if !VALID_COUNTRY_CODES.contains(&code.to_ascii_uppercase().as_str()) {
         return Err(ValidationError::InvalidCountryCode(code.to_string()));
     }
 
@@ -115,7 +241,54 @@ pub fn validate_domain_pattern(pattern: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+/// Categories accepted on top of the built-in list, e.g. read from a downloaded geosite file.
+static EXTRA_GEOSITE_CATEGORIES: RwLock<BTreeSet<String>> = RwLock::new(BTreeSet::new());
+
+pub fn register_geosite_categories<I, S>(categories: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let Ok(mut extra) = EXTRA_GEOSITE_CATEGORIES.write() else {
+        return;
+    };
+    extra.extend(
+        categories
+            .into_iter()
+            .map(|c| c.as_ref().trim().to_ascii_lowercase())
+            .filter(|c| !c.is_empty()),
+    );
+}
+
+fn is_known_geosite_category(category: &str) -> bool {
+    VALID_GEOSITE_CATEGORIES.contains(&category)
+        || EXTRA_GEOSITE_CATEGORIES
+            .read()
+            .is_ok_and(|extra| extra.contains(category))
+}
+
+/// Categories a rule may negate. v2ray and Xray cannot invert a matcher and
+/// use the `geolocation-!cn` list instead, the only such complement shipped.
+const NEGATABLE_GEOSITE_CATEGORIES: &[&str] = &["cn", "geolocation-cn"];
+
+/// Splits a leading `!` off a geosite category, returning whether it is negated.
+/// `!` inside a name (as in `geolocation-!cn`) is part of the name, not a negation.
+pub fn split_geosite_negation(category: &str) -> (bool, &str) {
+    match category.strip_prefix('!') {
+        Some(base) => (true, base),
+        None => (false, category),
+    }
+}
+
 pub fn validate_geosite_category(category: &str) -> Result<(), ValidationError> {
+    let (negated, base) = split_geosite_negation(category);
+    if base.is_empty() || (negated && !NEGATABLE_GEOSITE_CATEGORIES.contains(&base)) {
+        return Err(ValidationError::InvalidGeoSiteCategory(
+            category.to_string(),
+        ));
+    }
+    let category = base;
+
     if category.is_empty() {
         return Err(ValidationError::InvalidGeoSiteCategory(
             category.to_string(),
@@ -130,7 +303,7 @@ pub fn validate_geosite_category(category: &str) -> Result<(), ValidationError>
         }
     }
 
-    if !VALID_GEOSITE_CATEGORIES.contains(&category) {
+    if !is_known_geosite_category(category) {
         return Err(ValidationError::InvalidGeoSiteCategory(
             category.to_string(),
         ));
@@ -139,6 +312,86 @@ pub fn validate_geosite_category(category: &str) -> Result<(), ValidationError>
     Ok(())
 }
 
+pub fn validate_tls_override(tls: &TlsOverride) -> Result<(), ValidationError> {
+    if tls.server_name.trim().is_empty() {
+        return Err(ValidationError::EmptyServerName);
+    }
+    if let Some(fp) = &tls.fingerprint
+        && !TLS_FINGERPRINTS.contains(&fp.as_str())
+    {
+        return Err(ValidationError::InvalidFingerprint(fp.clone()));
+    }
+    Ok(())
+}
+
+pub fn validate_health_check_url(url: &str) -> Result<(), ValidationError> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Ok(()),
+        _ => Err(ValidationError::InvalidHealthCheckUrl(url.to_string())),
+    }
+}
+
+/// Subscription fetches go through HTTP proxies only; reqwest is built
+/// without SOCKS support.
+pub fn validate_proxy_url(url: &str) -> Result<(), ValidationError> {
+    match url::Url::parse(url.trim()) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Ok(()),
+        _ => Err(ValidationError::InvalidProxyUrl(url.to_string())),
+    }
+}
+
+fn is_valid_host(host: &str) -> bool {
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return true;
+    }
+    !host.is_empty()
+        && !host.starts_with(['.', '-'])
+        && !host.ends_with(['.', '-'])
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+/// Splits `host:port`, with IPv6 hosts in brackets (`[::1]:1080`).
+pub fn parse_host_port(input: &str) -> Result<(String, u16), ValidationError> {
+    let invalid = || ValidationError::InvalidHostPort(input.to_string());
+    let (host, port) = input.trim().rsplit_once(':').ok_or_else(invalid)?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    if port == 0 || !is_valid_host(host) {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+pub fn validate_upstream_proxy(proxy: &UpstreamProxy) -> Result<(), ValidationError> {
+    if proxy.port == 0 || !is_valid_host(&proxy.host) {
+        return Err(ValidationError::InvalidHostPort(format!(
+            "{}:{}",
+            proxy.host, proxy.port
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_inbounds(settings: &AppSettings) -> Result<(), ValidationError> {
+    if !settings.enable_socks && !settings.enable_http {
+        return Err(ValidationError::NoInbounds);
+    }
+    let port = settings.clash_api_port;
+    let inbounds = [
+        (settings.enable_socks, settings.socks_port),
+        (settings.enable_http, settings.http_port),
+    ];
+    if settings.serves_clash_api() && inbounds.contains(&(true, port)) {
+        return Err(ValidationError::ClashApiPortInUse(port));
+    }
+    Ok(())
+}
+
 pub fn validate_rule_match(m: &RuleMatch) -> Result<(), ValidationError> {
     match m {
         RuleMatch::GeoIp { country_code } => validate_country_code(country_code),
@@ -152,13 +405,72 @@ pub fn validate_rule_match(m: &RuleMatch) -> Result<(), ValidationError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_ss_method() {
+        assert!(validate_ss_method("aes-256-gcm").is_ok());
+        assert!(validate_ss_method("2022-blake3-aes-256-gcm").is_ok());
+        assert!(validate_ss_method("chacha20-ietf-poly1305").is_ok());
+        assert_eq!(
+            validate_ss_method("aes-999-gcm"),
+            Err(ValidationError::InvalidShadowsocksMethod(
+                "aes-999-gcm".into()
+            ))
+        );
+        assert!(validate_ss_method("").is_err());
+    }
+
+    #[test]
+    fn test_vless_encryption() {
+        assert!(validate_vless_encryption("none").is_ok());
+        assert!(
+            validate_vless_encryption(
+                "mlkem768x25519plus.native.0rtt.100-111-1111.75-0-111.50-0-3333.ptjHQxBQxTJ9MWr2cd5qWIflBSACHOevTauCQwa_71U"
+            )
+            .is_ok()
+        );
+        assert!(validate_vless_encryption("mlkem768x25519plus.xorpub.1rtt.KEY").is_ok());
+        for bad in [
+            "",
+            "aes-128-gcm",
+            "mlkem768x25519plus",
+            "mlkem768x25519plus.native.0rtt",
+            "mlkem768x25519plus.native.600s.KEY",
+            "mlkem768x25519plus.plain.0rtt.KEY",
+            "mlkem768x25519plus.native.0rtt..KEY",
+            "mlkem768x25519plus.native.0rtt.K=Y",
+        ] {
+            assert_eq!(
+                validate_vless_encryption(bad),
+                Err(ValidationError::InvalidVlessEncryption(bad.into())),
+                "{bad}"
+            );
+        }
+        assert!(!is_vless_encrypted(None));
+        assert!(!is_vless_encrypted(Some("none")));
+        assert!(!is_vless_encrypted(Some("")));
+        assert!(is_vless_encrypted(Some(
+            "mlkem768x25519plus.native.0rtt.KEY"
+        )));
+    }
+
+    #[test]
+    fn test_ss2022_key_len() {
+        assert!(is_ss2022_method("2022-blake3-aes-128-gcm"));
+        assert!(!is_ss2022_method("aes-128-gcm"));
+        assert_eq!(ss2022_key_len("2022-blake3-aes-128-gcm"), Some(16));
+        assert_eq!(ss2022_key_len("2022-blake3-aes-256-gcm"), Some(32));
+        assert_eq!(ss2022_key_len("2022-blake3-chacha20-poly1305"), Some(32));
+        assert_eq!(ss2022_key_len("aes-256-gcm"), None);
+    }
+
     #[test]
     fn test_validate_country_code() {
         let tests = vec![
             ("US", true),
             ("CN", true),
             ("RU", true),
-            ("us", false),
+            ("us", true),
+            ("Ru", true),
             ("USA", false),
             ("U", false),
             ("ZZ", false),
@@ -251,6 +563,11 @@ mod tests {
             ("", false),
             ("category with spaces", false),
             ("category_underscore", false),
+            ("!cn", true),
+            ("!geolocation-!cn", false),
+            ("!", false),
+            ("!!cn", false),
+            ("!unknown-category", false),
         ];
 
         for (category, expected_valid) in tests {
@@ -266,6 +583,154 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_geosite_negation_limited_to_geolocation_pair() {
+        assert!(validate_geosite_category("!cn").is_ok());
+        assert!(validate_geosite_category("!geolocation-cn").is_ok());
+        for category in ["!google", "!ru", "!category-ads-all", "!geolocation-!cn"] {
+            assert_eq!(
+                validate_geosite_category(category),
+                Err(ValidationError::InvalidGeoSiteCategory(category.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_registered_geosite_categories_validate() {
+        assert!(validate_geosite_category("my-company-intranet").is_err());
+
+        register_geosite_categories(["MY-COMPANY-INTRANET", " "]);
+
+        assert!(validate_geosite_category("my-company-intranet").is_ok());
+        assert!(validate_geosite_category("MY-COMPANY-INTRANET").is_err());
+        assert!(validate_geosite_category("").is_err());
+    }
+
+    #[test]
+    fn test_validate_tls_override() {
+        let mut tls = TlsOverride {
+            server_name: "example.com".into(),
+            fingerprint: Some("chrome".into()),
+            alpn: vec![],
+            verify: true,
+        };
+        assert!(validate_tls_override(&tls).is_ok());
+
+        tls.fingerprint = Some("netscape".into());
+        assert_eq!(
+            validate_tls_override(&tls),
+            Err(ValidationError::InvalidFingerprint("netscape".into()))
+        );
+
+        tls.fingerprint = None;
+        tls.server_name = String::new();
+        assert_eq!(
+            validate_tls_override(&tls),
+            Err(ValidationError::EmptyServerName)
+        );
+    }
+
+    #[test]
+    fn test_parse_host_port() {
+        assert_eq!(
+            parse_host_port("proxy.corp.example:3128"),
+            Ok(("proxy.corp.example".into(), 3128))
+        );
+        assert_eq!(
+            parse_host_port("10.0.0.1:1080"),
+            Ok(("10.0.0.1".into(), 1080))
+        );
+        assert_eq!(parse_host_port("[::1]:1080"), Ok(("::1".into(), 1080)));
+        for bad in [
+            "",
+            "proxy",
+            "proxy:",
+            "proxy:0",
+            "proxy:70000",
+            ":1080",
+            "bad host:80",
+        ] {
+            assert_eq!(
+                parse_host_port(bad),
+                Err(ValidationError::InvalidHostPort(bad.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_upstream_proxy() {
+        let mut proxy = UpstreamProxy {
+            protocol: crate::models::UpstreamProtocol::Socks,
+            host: "127.0.0.1".into(),
+            port: 1080,
+            username: None,
+            password: None,
+        };
+        assert!(validate_upstream_proxy(&proxy).is_ok());
+        proxy.port = 0;
+        assert!(validate_upstream_proxy(&proxy).is_err());
+        proxy.port = 1080;
+        proxy.host = "-bad".into();
+        assert!(validate_upstream_proxy(&proxy).is_err());
+    }
+
+    #[test]
+    fn test_validate_inbounds() {
+        let mut settings = AppSettings::default();
+        assert!(validate_inbounds(&settings).is_ok());
+        settings.enable_http = false;
+        assert!(validate_inbounds(&settings).is_ok());
+        settings.enable_socks = false;
+        assert_eq!(
+            validate_inbounds(&settings),
+            Err(ValidationError::NoInbounds)
+        );
+        settings.enable_http = true;
+        assert!(validate_inbounds(&settings).is_ok());
+    }
+
+    #[test]
+    fn test_validate_inbounds_clash_api_port_conflict() {
+        let mut settings = AppSettings {
+            subscription_groups: true,
+            clash_api_port: 1081,
+            ..AppSettings::default()
+        };
+        // Only sing-box serves the Clash API.
+        assert!(validate_inbounds(&settings).is_ok());
+        settings.backend.backend_type = crate::models::BackendType::SingBox;
+        assert_eq!(
+            validate_inbounds(&settings),
+            Err(ValidationError::ClashApiPortInUse(1081))
+        );
+        settings.enable_http = false;
+        assert!(validate_inbounds(&settings).is_ok());
+    }
+
+    #[test]
+    fn test_validate_health_check_url() {
+        assert!(validate_health_check_url("http://cp.cloudflare.com/generate_204").is_ok());
+        assert!(validate_health_check_url("https://www.gstatic.com/generate_204").is_ok());
+        for bad in ["", "not a url", "ftp://example.com/", "http://"] {
+            assert_eq!(
+                validate_health_check_url(bad),
+                Err(ValidationError::InvalidHealthCheckUrl(bad.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_proxy_url() {
+        assert!(validate_proxy_url("http://proxy.corp:3128").is_ok());
+        assert!(validate_proxy_url("https://user:pass@[::1]:8443").is_ok());
+        for bad in ["", "proxy.corp:3128", "socks5://127.0.0.1:1080", "http://"] {
+            assert_eq!(
+                validate_proxy_url(bad),
+                Err(ValidationError::InvalidProxyUrl(bad.into()))
+            );
+        }
+    }
+
     #[test]
     fn test_validate_rule_match() {
         let valid_cases = vec![
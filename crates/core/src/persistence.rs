@@ -2,13 +2,17 @@ use std::fs;
 use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use directories::ProjectDirs;
 use thiserror::Error;
 
 use uuid::Uuid;
 
-use crate::models::{AppSettings, Preset, RoutingRuleSet, Subscription};
+use crate::models::{
+    AppSettings, BackendConfig, CONNECTION_HISTORY_CAP, ConnectionEvent, Preset, RoutingRuleSet,
+    Subscription, apply_selection,
+};
 
 #[derive(Error, Debug)]
 pub enum PersistenceError {
@@ -24,27 +28,60 @@ pub enum PersistenceError {
     Json(#[from] serde_json::Error),
     #[error("corrupt config file, using defaults: {0}")]
     CorruptConfig(String),
+    #[error("a preset with a conflicting name already exists: {0}")]
+    PresetNameTaken(String),
+    #[error("invalid preset: {0}")]
+    InvalidPreset(String),
+    #[error("invalid profile name: {0:?}")]
+    InvalidProfileName(String),
+    #[error("profile already exists: {0}")]
+    ProfileExists(String),
 }
 
-#[derive(Clone)]
+/// Profile that lives directly in the base directories, so existing installs keep their data.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone)]
 pub struct AppPaths {
     config_dir: PathBuf,
     data_dir: PathBuf,
+    runtime_dir: PathBuf,
 }
 
+/// Overrides both the config and data directories, e.g. for portable installs.
+pub const HOME_ENV_VAR: &str = "V2RAY_RS_HOME";
+
 impl AppPaths {
+    /// Uses `$V2RAY_RS_HOME/{config,data}` when the variable is set, otherwise XDG dirs.
+    pub fn from_env() -> Result<Self, PersistenceError> {
+        match std::env::var_os(HOME_ENV_VAR) {
+            Some(home) if !home.is_empty() => Ok(Self::from_home(Path::new(&home))),
+            _ => Self::new(),
+        }
+    }
+
+    pub fn from_home(home: &Path) -> Self {
+        Self {
+            config_dir: home.join("config"),
+            data_dir: home.join("data"),
+            runtime_dir: home.join("run"),
+        }
+    }
+
     pub fn new() -> Result<Self, PersistenceError> {
         let dirs =
             ProjectDirs::from("com", "v2ray-rs", "v2ray-rs").ok_or(PersistenceError::NoDirs)?;
         Ok(Self {
             config_dir: dirs.config_dir().to_path_buf(),
             data_dir: dirs.data_dir().to_path_buf(),
+            runtime_dir: dirs.runtime_dir().unwrap_or(dirs.data_dir()).to_path_buf(),
         })
     }
 
     #[cfg(any(test, feature = "test-utils"))]
     pub fn from_paths(config_dir: PathBuf, data_dir: PathBuf) -> Self {
         Self {
+            runtime_dir: data_dir.clone(),
             config_dir,
             data_dir,
         }
@@ -78,6 +115,38 @@ impl AppPaths {
         self.data_dir.join("presets")
     }
 
+    /// Connection state for scripts and status bar widgets.
+    pub fn status_path(&self) -> PathBuf {
+        self.data_dir.join("status.json")
+    }
+
+    /// Socket of the running instance; call on the base paths, since there is
+    /// one instance whatever the profile.
+    pub fn control_socket_path(&self) -> PathBuf {
+        self.runtime_dir.join("control.sock")
+    }
+
+    pub fn history_path(&self) -> PathBuf {
+        self.data_dir.join("history.json")
+    }
+
+    pub fn profiles_dir(&self) -> PathBuf {
+        self.data_dir.join("profiles")
+    }
+
+    pub fn current_profile_path(&self) -> PathBuf {
+        self.config_dir.join("current_profile")
+    }
+
+    /// Paths for a profile; `self` must be the base (default profile) paths.
+    pub fn for_profile(&self, name: &str) -> Self {
+        if name == DEFAULT_PROFILE {
+            self.clone()
+        } else {
+            Self::from_home(&self.profiles_dir().join(name))
+        }
+    }
+
     pub fn ensure_dirs(&self) -> Result<(), PersistenceError> {
         create_dir_with_permissions(&self.config_dir)?;
         create_dir_with_permissions(&self.data_dir)?;
@@ -103,6 +172,7 @@ fn atomic_write(path: &Path, data: &[u8]) -> Result<(), PersistenceError> {
     let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
     tmp.write_all(data)?;
     tmp.flush()?;
+    tmp.as_file().sync_all()?;
     tmp.persist(path)
         .map_err(|e| PersistenceError::Io(e.error))?;
     Ok(())
@@ -114,6 +184,24 @@ pub fn save_settings(paths: &AppPaths, settings: &AppSettings) -> Result<(), Per
     atomic_write(&paths.settings_path(), toml_str.as_bytes())
 }
 
+/// Overwrites the settings with the defaults and returns them. Onboarding
+/// stays done and the backend binary stays chosen, so a reset does not bring
+/// the setup wizard back. Subscriptions and routing rules are left alone.
+pub fn reset_settings(paths: &AppPaths) -> Result<AppSettings, PersistenceError> {
+    let current = load_settings(paths).unwrap_or_default();
+    let settings = AppSettings {
+        onboarding_complete: current.onboarding_complete,
+        backend: BackendConfig {
+            backend_type: current.backend.backend_type,
+            binary_path: current.backend.binary_path,
+            ..BackendConfig::default()
+        },
+        ..AppSettings::default()
+    };
+    save_settings(paths, &settings)?;
+    Ok(settings)
+}
+
 pub fn load_settings(paths: &AppPaths) -> Result<AppSettings, PersistenceError> {
     let path = paths.settings_path();
     if !path.exists() {
@@ -140,9 +228,21 @@ pub fn load_settings_or_default(paths: &AppPaths) -> AppSettings {
     }
 }
 
+/// Serialises read-modify-write cycles on the subscriptions file so two
+/// writers in the same process cannot each load and then clobber the other.
+static SUBSCRIPTIONS_LOCK: Mutex<()> = Mutex::new(());
+
 pub fn save_subscriptions(
     paths: &AppPaths,
     subscriptions: &[Subscription],
+) -> Result<(), PersistenceError> {
+    let _guard = SUBSCRIPTIONS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    write_subscriptions(paths, subscriptions)
+}
+
+fn write_subscriptions(
+    paths: &AppPaths,
+    subscriptions: &[Subscription],
 ) -> Result<(), PersistenceError> {
     paths.ensure_dirs()?;
     let json = serde_json::to_string_pretty(subscriptions)?;
@@ -159,13 +259,38 @@ pub fn load_subscriptions(paths: &AppPaths) -> Result<Vec<Subscription>, Persist
     Ok(subs)
 }
 
+/// Loads the stored subscriptions, lets `f` mutate them and atomically writes
+/// the result back, all under one lock. The file is left untouched when `f`
+/// returns `None`.
+pub fn with_subscriptions<R>(
+    paths: &AppPaths,
+    f: impl FnOnce(&mut Vec<Subscription>) -> Option<R>,
+) -> Result<Option<R>, PersistenceError> {
+    let _guard = SUBSCRIPTIONS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut subs = load_subscriptions(paths)?;
+    let result = f(&mut subs);
+    if result.is_some() {
+        write_subscriptions(paths, &subs)?;
+    }
+    Ok(result)
+}
+
+/// Makes the stored node selection match `shown`, the copy the user sees.
+/// Returns `true` when they differed, i.e. an earlier save was lost.
+pub fn sync_selection(paths: &AppPaths, shown: &[Subscription]) -> Result<bool, PersistenceError> {
+    let synced = with_subscriptions(paths, |stored| apply_selection(stored, shown).then_some(()))?;
+    Ok(synced.is_some())
+}
+
 pub fn add_subscription(
     paths: &AppPaths,
     subscription: Subscription,
 ) -> Result<(), PersistenceError> {
-    let mut subs = load_subscriptions(paths)?;
-    subs.push(subscription);
-    save_subscriptions(paths, &subs)
+    with_subscriptions(paths, |subs| {
+        subs.push(subscription);
+        Some(())
+    })?;
+    Ok(())
 }
 
 pub fn get_subscription(
@@ -180,27 +305,35 @@ pub fn update_subscription(
     paths: &AppPaths,
     subscription: Subscription,
 ) -> Result<bool, PersistenceError> {
-    let mut subs = load_subscriptions(paths)?;
-    match subs.iter_mut().find(|s| s.id == subscription.id) {
-        Some(existing) => {
-            *existing = subscription;
-            save_subscriptions(paths, &subs)?;
-            Ok(true)
-        }
-        None => Ok(false),
-    }
+    let updated = with_subscriptions(paths, |subs| {
+        let existing = subs.iter_mut().find(|s| s.id == subscription.id)?;
+        *existing = subscription;
+        Some(())
+    })?;
+    Ok(updated.is_some())
+}
+
+/// Applies `edit` to the stored subscription with `id` in one transaction,
+/// leaving every other field and subscription as currently on disk.
+pub fn edit_subscription(
+    paths: &AppPaths,
+    id: &Uuid,
+    edit: impl FnOnce(&mut Subscription),
+) -> Result<bool, PersistenceError> {
+    let edited = with_subscriptions(paths, |subs| {
+        edit(subs.iter_mut().find(|s| &s.id == id)?);
+        Some(())
+    })?;
+    Ok(edited.is_some())
 }
 
 pub fn remove_subscription(paths: &AppPaths, id: &Uuid) -> Result<bool, PersistenceError> {
-    let mut subs = load_subscriptions(paths)?;
-    let initial_len = subs.len();
-    subs.retain(|s| &s.id != id);
-    if subs.len() < initial_len {
-        save_subscriptions(paths, &subs)?;
-        Ok(true)
-    } else {
-        Ok(false)
-    }
+    let removed = with_subscriptions(paths, |subs| {
+        let initial_len = subs.len();
+        subs.retain(|s| &s.id != id);
+        (subs.len() < initial_len).then_some(())
+    })?;
+    Ok(removed.is_some())
 }
 
 pub fn save_routing_rules(
@@ -212,6 +345,11 @@ pub fn save_routing_rules(
     atomic_write(&paths.routing_rules_path(), json.as_bytes())
 }
 
+/// Replaces the routing rules with the empty set a fresh install starts with.
+pub fn reset_routing_rules(paths: &AppPaths) -> Result<(), PersistenceError> {
+    save_routing_rules(paths, &RoutingRuleSet::new())
+}
+
 pub fn load_routing_rules(paths: &AppPaths) -> Result<RoutingRuleSet, PersistenceError> {
     let path = paths.routing_rules_path();
     if !path.exists() {
@@ -222,6 +360,100 @@ pub fn load_routing_rules(paths: &AppPaths) -> Result<RoutingRuleSet, Persistenc
     Ok(rules)
 }
 
+pub fn load_connection_history(paths: &AppPaths) -> Result<Vec<ConnectionEvent>, PersistenceError> {
+    let path = paths.history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Appends an event to the history file, dropping the oldest beyond the cap.
+pub fn append_connection_event(
+    paths: &AppPaths,
+    event: ConnectionEvent,
+) -> Result<(), PersistenceError> {
+    paths.ensure_dirs()?;
+    let mut events = load_connection_history(paths)?;
+    events.push(event);
+    let excess = events.len().saturating_sub(CONNECTION_HISTORY_CAP);
+    events.drain(..excess);
+    let json = serde_json::to_string_pretty(&events)?;
+    atomic_write(&paths.history_path(), json.as_bytes())
+}
+
+fn validate_profile_name(name: &str) -> Result<(), PersistenceError> {
+    let valid = !name.is_empty()
+        && name.trim() == name
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(PersistenceError::InvalidProfileName(name.to_owned()))
+    }
+}
+
+/// Profile names, with the default profile first.
+pub fn list_profiles(base: &AppPaths) -> Result<Vec<String>, PersistenceError> {
+    let mut names = Vec::new();
+    let dir = base.profiles_dir();
+    if dir.exists() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str()
+                && validate_profile_name(name).is_ok()
+                && name != DEFAULT_PROFILE
+            {
+                names.push(name.to_owned());
+            }
+        }
+    }
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_owned());
+    Ok(names)
+}
+
+/// Creates a profile whose settings start as a copy of `settings`.
+pub fn create_profile(
+    base: &AppPaths,
+    name: &str,
+    settings: &AppSettings,
+) -> Result<AppPaths, PersistenceError> {
+    validate_profile_name(name)?;
+    if name == DEFAULT_PROFILE || base.profiles_dir().join(name).exists() {
+        return Err(PersistenceError::ProfileExists(name.to_owned()));
+    }
+    base.ensure_dirs()?;
+    create_dir_with_permissions(&base.profiles_dir())?;
+    let paths = base.for_profile(name);
+    save_settings(&paths, settings)?;
+    Ok(paths)
+}
+
+/// Name of the selected profile; falls back to the default if it no longer exists.
+pub fn load_current_profile(base: &AppPaths) -> String {
+    let name = fs::read_to_string(base.current_profile_path()).unwrap_or_default();
+    let name = name.trim();
+    if validate_profile_name(name).is_ok() && base.profiles_dir().join(name).is_dir() {
+        name.to_owned()
+    } else {
+        DEFAULT_PROFILE.to_owned()
+    }
+}
+
+pub fn save_current_profile(base: &AppPaths, name: &str) -> Result<(), PersistenceError> {
+    validate_profile_name(name)?;
+    base.ensure_dirs()?;
+    atomic_write(&base.current_profile_path(), name.as_bytes())
+}
+
 fn slugify(name: &str) -> String {
     name.to_lowercase()
         .chars()
@@ -241,6 +473,52 @@ pub fn save_preset(paths: &AppPaths, preset: &Preset) -> Result<(), PersistenceE
     atomic_write(&dir.join(filename), json.as_bytes())
 }
 
+/// Re-saves a custom preset, moving its file when a rename changes the slug.
+pub fn update_preset(
+    paths: &AppPaths,
+    old_name: &str,
+    preset: &Preset,
+) -> Result<(), PersistenceError> {
+    let dir = paths.presets_dir();
+    let old_path = dir.join(format!("{}.json", slugify(old_name)));
+    let new_path = dir.join(format!("{}.json", slugify(&preset.name)));
+    if new_path != old_path && new_path.exists() {
+        return Err(PersistenceError::PresetNameTaken(preset.name.clone()));
+    }
+
+    save_preset(paths, preset)?;
+    if new_path != old_path && old_path.exists() {
+        fs::remove_file(&old_path)?;
+    }
+    Ok(())
+}
+
+pub fn export_preset(preset: &Preset, path: &Path) -> Result<(), PersistenceError> {
+    let json = serde_json::to_string_pretty(preset)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Reads a shared preset file and saves it as a custom preset.
+/// The whole file is rejected if any rule fails validation.
+pub fn import_preset(paths: &AppPaths, path: &Path) -> Result<Preset, PersistenceError> {
+    let contents = fs::read_to_string(path)?;
+    let preset: Preset = serde_json::from_str(&contents)?;
+
+    let invalid = preset.invalid_rules();
+    if !invalid.is_empty() {
+        let details = invalid
+            .iter()
+            .map(|(i, e)| format!("rule {}: {e}", i + 1))
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(PersistenceError::InvalidPreset(details));
+    }
+
+    save_preset(paths, &preset)?;
+    Ok(preset)
+}
+
 pub fn load_custom_presets(paths: &AppPaths) -> Result<Vec<Preset>, PersistenceError> {
     let dir = paths.presets_dir();
     if !dir.exists() {
@@ -274,6 +552,7 @@ pub fn delete_preset(paths: &AppPaths, name: &str) -> Result<bool, PersistenceEr
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
     use crate::models::*;
@@ -286,6 +565,16 @@ mod tests {
         (tmp, paths)
     }
 
+    fn sample_node() -> SubscriptionNode {
+        SubscriptionNode::new(ProxyNode::Shadowsocks(ShadowsocksConfig {
+            address: "ss.example.com".into(),
+            port: 8388,
+            method: "aes-256-gcm".into(),
+            password: "secret".into(),
+            remark: None,
+        }))
+    }
+
     #[test]
     fn test_ensure_dirs_creates_directories() {
         let (_tmp, paths) = test_paths();
@@ -297,6 +586,113 @@ mod tests {
         assert_eq!(config_perms.mode() & 0o777, 0o700);
     }
 
+    #[test]
+    fn test_from_env_honors_home_override() {
+        let tmp = TempDir::new().unwrap();
+        // SAFETY: no other test reads or writes this variable.
+        unsafe { std::env::set_var(HOME_ENV_VAR, tmp.path()) };
+        let paths = AppPaths::from_env();
+        unsafe { std::env::remove_var(HOME_ENV_VAR) };
+
+        let paths = paths.unwrap();
+        assert_eq!(paths.config_dir(), tmp.path().join("config"));
+        assert_eq!(paths.data_dir(), tmp.path().join("data"));
+        assert_eq!(
+            paths.settings_path(),
+            tmp.path().join("config/settings.toml")
+        );
+        assert_eq!(
+            paths.subscriptions_path(),
+            tmp.path().join("data/subscriptions.json")
+        );
+    }
+
+    #[test]
+    fn test_profiles_are_isolated() {
+        let (_tmp, base) = test_paths();
+        let work = create_profile(&base, "work", &AppSettings::default()).unwrap();
+
+        save_subscriptions(
+            &work,
+            &[Subscription::new_from_url("Work", "https://work.example")],
+        )
+        .unwrap();
+        let mut rules = RoutingRuleSet::new();
+        rules.add(RoutingRule {
+            id: Uuid::new_v4(),
+            match_condition: RuleMatch::GeoIp {
+                country_code: "RU".into(),
+            },
+            action: RuleAction::Direct,
+            enabled: true,
+            target: None,
+        });
+        save_routing_rules(&work, &rules).unwrap();
+
+        assert_eq!(load_subscriptions(&work).unwrap().len(), 1);
+        assert_eq!(load_routing_rules(&work).unwrap().rules().len(), 1);
+        assert!(load_subscriptions(&base).unwrap().is_empty());
+        assert!(load_routing_rules(&base).unwrap().rules().is_empty());
+        assert_ne!(work.settings_path(), base.settings_path());
+        assert!(work.settings_path().exists());
+    }
+
+    #[test]
+    fn test_create_profile_copies_settings() {
+        let (_tmp, base) = test_paths();
+        let settings = AppSettings {
+            socks_port: 3080,
+            ..Default::default()
+        };
+        let work = create_profile(&base, "work", &settings).unwrap();
+        assert_eq!(load_settings(&work).unwrap().socks_port, 3080);
+        assert!(matches!(
+            create_profile(&base, "work", &settings),
+            Err(PersistenceError::ProfileExists(_))
+        ));
+        assert!(matches!(
+            create_profile(&base, DEFAULT_PROFILE, &settings),
+            Err(PersistenceError::ProfileExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_profile_name_validation() {
+        let (_tmp, base) = test_paths();
+        for name in ["", "../escape", "a/b", ".hidden", " padded"] {
+            assert!(matches!(
+                create_profile(&base, name, &AppSettings::default()),
+                Err(PersistenceError::InvalidProfileName(_))
+            ));
+        }
+        assert!(create_profile(&base, "Work Laptop", &AppSettings::default()).is_ok());
+    }
+
+    #[test]
+    fn test_list_profiles_default_first() {
+        let (_tmp, base) = test_paths();
+        assert_eq!(list_profiles(&base).unwrap(), vec![DEFAULT_PROFILE]);
+        create_profile(&base, "personal", &AppSettings::default()).unwrap();
+        create_profile(&base, "home", &AppSettings::default()).unwrap();
+        assert_eq!(
+            list_profiles(&base).unwrap(),
+            vec![DEFAULT_PROFILE, "home", "personal"]
+        );
+    }
+
+    #[test]
+    fn test_current_profile_pointer() {
+        let (_tmp, base) = test_paths();
+        assert_eq!(load_current_profile(&base), DEFAULT_PROFILE);
+
+        create_profile(&base, "work", &AppSettings::default()).unwrap();
+        save_current_profile(&base, "work").unwrap();
+        assert_eq!(load_current_profile(&base), "work");
+
+        fs::remove_dir_all(base.profiles_dir().join("work")).unwrap();
+        assert_eq!(load_current_profile(&base), DEFAULT_PROFILE);
+    }
+
     #[test]
     fn test_settings_save_load_roundtrip() {
         let (_tmp, paths) = test_paths();
@@ -317,6 +713,62 @@ mod tests {
         assert_eq!(loaded, AppSettings::default());
     }
 
+    #[test]
+    fn test_reset_writes_default_settings_and_keeps_subscriptions() {
+        let (_tmp, paths) = test_paths();
+        let settings = AppSettings {
+            socks_port: 9999,
+            language: Language::Chinese,
+            ..Default::default()
+        };
+        save_settings(&paths, &settings).unwrap();
+        add_subscription(&paths, Subscription::new_manual("Mine")).unwrap();
+
+        assert_eq!(reset_settings(&paths).unwrap(), AppSettings::default());
+        assert_eq!(
+            fs::read_to_string(paths.settings_path()).unwrap(),
+            toml::to_string_pretty(&AppSettings::default()).unwrap()
+        );
+        assert_eq!(load_settings(&paths).unwrap(), AppSettings::default());
+        assert_eq!(load_subscriptions(&paths).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reset_keeps_onboarding_and_backend_binary() {
+        let (_tmp, paths) = test_paths();
+        let mut settings = AppSettings {
+            socks_port: 9999,
+            onboarding_complete: true,
+            ..Default::default()
+        };
+        settings.backend.backend_type = BackendType::SingBox;
+        settings.backend.binary_path = Some("/usr/bin/sing-box".into());
+        settings.backend.config_output_dir = Some("/tmp/out".into());
+        save_settings(&paths, &settings).unwrap();
+
+        let reset = reset_settings(&paths).unwrap();
+        assert_eq!(reset.socks_port, AppSettings::default().socks_port);
+        assert!(reset.onboarding_complete);
+        assert_eq!(reset.backend.backend_type, BackendType::SingBox);
+        assert_eq!(
+            reset.backend.binary_path.as_deref(),
+            Some(Path::new("/usr/bin/sing-box"))
+        );
+        assert_eq!(reset.backend.config_output_dir, None);
+        assert_eq!(load_settings(&paths).unwrap(), reset);
+    }
+
+    #[test]
+    fn test_reset_routing_rules_empties_the_set() {
+        let (_tmp, paths) = test_paths();
+        let mut rules = RoutingRuleSet::new();
+        rules.apply_preset(&builtin_presets()[0]);
+        save_routing_rules(&paths, &rules).unwrap();
+
+        reset_routing_rules(&paths).unwrap();
+        assert!(load_routing_rules(&paths).unwrap().rules().is_empty());
+    }
+
     #[test]
     fn test_corrupt_config_falls_back() {
         let (_tmp, paths) = test_paths();
@@ -342,6 +794,49 @@ mod tests {
         assert_eq!(subs[0].name, loaded[0].name);
     }
 
+    #[test]
+    fn test_node_tls_override_persists() {
+        let (_tmp, paths) = test_paths();
+        let mut sub = Subscription::new_from_url("Test Sub", "https://example.com/sub");
+        sub.nodes
+            .push(SubscriptionNode::new(ProxyNode::Vless(VlessConfig {
+                address: "example.com".into(),
+                port: 443,
+                uuid: "550e8400-e29b-41d4-a716-446655440000".into(),
+                encryption: None,
+                flow: None,
+                transport: TransportSettings::Tcp,
+                tls: Some(TlsSettings {
+                    server_name: Some("blocked.example.com".into()),
+                    alpn: vec![],
+                    verify: true,
+                    fingerprint: None,
+                    reality: None,
+                }),
+                remark: None,
+            })));
+        add_subscription(&paths, sub.clone()).unwrap();
+
+        let tls = TlsOverride {
+            server_name: "open.example.com".into(),
+            fingerprint: Some("chrome".into()),
+            alpn: vec!["h2".into()],
+            verify: false,
+        };
+        let node = &mut sub.nodes[0];
+        node.node.apply_tls_override(&tls).unwrap();
+        node.tls_override = Some(tls.clone());
+        assert!(update_subscription(&paths, sub.clone()).unwrap());
+
+        let loaded = get_subscription(&paths, &sub.id).unwrap().unwrap();
+        let applied = loaded.nodes[0].node.tls().unwrap();
+        assert_eq!(applied.server_name.as_deref(), Some("open.example.com"));
+        assert_eq!(applied.fingerprint.as_deref(), Some("chrome"));
+        assert_eq!(applied.alpn, vec!["h2".to_string()]);
+        assert!(!applied.verify);
+        assert_eq!(loaded.nodes[0].tls_override, Some(tls));
+    }
+
     #[test]
     fn test_routing_rules_save_load_roundtrip() {
         let (_tmp, paths) = test_paths();
@@ -353,6 +848,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         });
 
         save_routing_rules(&paths, &rules).unwrap();
@@ -457,6 +953,87 @@ mod tests {
         assert_eq!(loaded[0].id, sub2.id);
     }
 
+    #[test]
+    fn test_with_subscriptions_skips_write_on_none() {
+        let (_tmp, paths) = test_paths();
+        let result = with_subscriptions(&paths, |subs| {
+            subs.push(Subscription::new_from_url("Dropped", "https://example.com"));
+            None::<()>
+        })
+        .unwrap();
+        assert!(result.is_none());
+        assert!(!paths.subscriptions_path().exists());
+    }
+
+    #[test]
+    fn test_interleaved_edits_are_all_kept() {
+        let (_tmp, paths) = test_paths();
+        let subs: Vec<Subscription> = (0..4)
+            .map(|i| Subscription::new_from_url(format!("Sub {i}"), "https://example.com"))
+            .collect();
+        save_subscriptions(&paths, &subs).unwrap();
+
+        std::thread::scope(|scope| {
+            for (i, sub) in subs.iter().enumerate() {
+                let paths = &paths;
+                scope.spawn(move || {
+                    for round in 0..10 {
+                        edit_subscription(paths, &sub.id, |s| {
+                            s.name = format!("Sub {i} round {round}");
+                            s.enabled = round % 2 == 0;
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        let loaded = load_subscriptions(&paths).unwrap();
+        assert_eq!(loaded.len(), 4);
+        for (i, sub) in loaded.iter().enumerate() {
+            assert_eq!(sub.name, format!("Sub {i} round 9"));
+            assert!(!sub.enabled);
+        }
+    }
+
+    #[test]
+    fn test_sync_selection_restores_a_lost_save() {
+        let (_tmp, paths) = test_paths();
+        let mut sub = Subscription::new_from_url("Sub", "https://example.com/sub");
+        sub.nodes = vec![sample_node(), sample_node()];
+        add_subscription(&paths, sub.clone()).unwrap();
+        assert!(!sync_selection(&paths, std::slice::from_ref(&sub)).unwrap());
+
+        // Toggled in the UI while the save failed; meanwhile a refresh stored
+        // an error on the same subscription.
+        sub.nodes[1].enabled = false;
+        sub.nodes[0].primary = true;
+        edit_subscription(&paths, &sub.id, |s| s.last_error = Some("timeout".into())).unwrap();
+
+        assert!(sync_selection(&paths, std::slice::from_ref(&sub)).unwrap());
+        let stored = get_subscription(&paths, &sub.id).unwrap().unwrap();
+        assert!(!stored.nodes[1].enabled);
+        assert!(stored.nodes[0].primary);
+        assert_eq!(stored.last_error.as_deref(), Some("timeout"));
+        assert!(!sync_selection(&paths, &[sub]).unwrap());
+    }
+
+    #[test]
+    fn test_edit_subscription_keeps_other_fields() {
+        let (_tmp, paths) = test_paths();
+        let sub = Subscription::new_from_url("Original", "https://example.com/sub");
+        add_subscription(&paths, sub.clone()).unwrap();
+
+        // A stale in-memory copy renames while another writer toggled it off.
+        edit_subscription(&paths, &sub.id, |s| s.enabled = false).unwrap();
+        assert!(edit_subscription(&paths, &sub.id, |s| s.name = "Renamed".into()).unwrap());
+        assert!(!edit_subscription(&paths, &Uuid::new_v4(), |_| {}).unwrap());
+
+        let loaded = get_subscription(&paths, &sub.id).unwrap().unwrap();
+        assert_eq!(loaded.name, "Renamed");
+        assert!(!loaded.enabled);
+    }
+
     #[test]
     fn test_remove_nonexistent() {
         let (_tmp, paths) = test_paths();
@@ -477,6 +1054,150 @@ mod tests {
         assert_eq!(loaded[0].description, preset.description);
     }
 
+    #[test]
+    fn test_update_preset_rename_moves_file() {
+        let (_tmp, paths) = test_paths();
+        let mut preset = crate::models::builtin_presets()[0].clone();
+        preset.name = "Work Rules".into();
+        save_preset(&paths, &preset).unwrap();
+
+        let old_name = preset.name.clone();
+        preset.name = "Home Rules".into();
+        preset.description = "Renamed".into();
+        update_preset(&paths, &old_name, &preset).unwrap();
+
+        assert!(!paths.presets_dir().join("work-rules.json").exists());
+        assert!(paths.presets_dir().join("home-rules.json").exists());
+        let loaded = load_custom_presets(&paths).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "Home Rules");
+        assert_eq!(loaded[0].description, "Renamed");
+    }
+
+    #[test]
+    fn test_update_preset_same_slug_overwrites() {
+        let (_tmp, paths) = test_paths();
+        let mut preset = crate::models::builtin_presets()[0].clone();
+        preset.name = "Work Rules".into();
+        save_preset(&paths, &preset).unwrap();
+
+        preset.name = "work  rules!".into();
+        update_preset(&paths, "Work Rules", &preset).unwrap();
+
+        let loaded = load_custom_presets(&paths).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "work  rules!");
+    }
+
+    #[test]
+    fn test_update_preset_rejects_slug_collision() {
+        let (_tmp, paths) = test_paths();
+        let presets = crate::models::builtin_presets();
+        let mut first = presets[0].clone();
+        first.name = "First".into();
+        let mut second = presets[1].clone();
+        second.name = "Second One".into();
+        save_preset(&paths, &first).unwrap();
+        save_preset(&paths, &second).unwrap();
+
+        first.name = "second-one".into();
+        let err = update_preset(&paths, "First", &first).unwrap_err();
+        assert!(matches!(err, PersistenceError::PresetNameTaken(_)));
+        assert!(paths.presets_dir().join("first.json").exists());
+        assert_eq!(load_custom_presets(&paths).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_export_import_preset_roundtrip() {
+        let (tmp, paths) = test_paths();
+        let preset = crate::models::builtin_presets()[1].clone();
+        let file = tmp.path().join("shared.json");
+
+        export_preset(&preset, &file).unwrap();
+        let imported = import_preset(&paths, &file).unwrap();
+
+        assert_eq!(imported.name, preset.name);
+        assert_eq!(imported.description, preset.description);
+        let conditions = |p: &Preset| {
+            p.rules()
+                .into_iter()
+                .map(|r| (r.match_condition, r.action))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(conditions(&imported), conditions(&preset));
+        assert_eq!(load_custom_presets(&paths).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_import_preset_rejects_invalid_rules() {
+        let (tmp, paths) = test_paths();
+        let rules = vec![RoutingRule {
+            id: Uuid::new_v4(),
+            match_condition: RuleMatch::GeoIp {
+                country_code: "XX".into(),
+            },
+            action: RuleAction::Proxy,
+            enabled: true,
+            target: None,
+        }];
+        let preset = Preset::from_rules("Broken", "", &rules);
+        let file = tmp.path().join("broken.json");
+        export_preset(&preset, &file).unwrap();
+
+        let err = import_preset(&paths, &file).unwrap_err();
+        match err {
+            PersistenceError::InvalidPreset(msg) => assert!(msg.contains("rule 1"), "{msg}"),
+            other => panic!("unexpected error: {other}"),
+        }
+        assert!(load_custom_presets(&paths).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_connection_history_appends_events() {
+        let (_tmp, paths) = test_paths();
+        assert!(load_connection_history(&paths).unwrap().is_empty());
+
+        for action in [
+            ConnectionAction::Connected,
+            ConnectionAction::Disconnected,
+            ConnectionAction::Error,
+        ] {
+            append_connection_event(&paths, ConnectionEvent::now(action, Some("A".into())))
+                .unwrap();
+        }
+
+        let actions: Vec<_> = load_connection_history(&paths)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ConnectionAction::Connected,
+                ConnectionAction::Disconnected,
+                ConnectionAction::Error,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connection_history_trims_oldest() {
+        let (_tmp, paths) = test_paths();
+        for i in 0..CONNECTION_HISTORY_CAP + 3 {
+            let event = ConnectionEvent::now(ConnectionAction::Connected, Some(i.to_string()));
+            append_connection_event(&paths, event).unwrap();
+        }
+
+        let events = load_connection_history(&paths).unwrap();
+        assert_eq!(events.len(), CONNECTION_HISTORY_CAP);
+        assert_eq!(events[0].node_remark.as_deref(), Some("3"));
+        assert_eq!(
+            events.last().unwrap().node_remark,
+            Some((CONNECTION_HISTORY_CAP + 2).to_string())
+        );
+    }
+
     #[test]
     fn test_delete_preset() {
         let (_tmp, paths) = test_paths();
@@ -118,6 +118,7 @@ mod tests {
             },
             action,
             enabled: true,
+            target: None,
         }
     }
 
@@ -141,6 +142,7 @@ mod tests {
             },
             action: RuleAction::Direct,
             enabled: true,
+            target: None,
         };
         assert!(mgr.add_rule(rule).is_err());
         assert!(mgr.rules().rules().is_empty());
@@ -0,0 +1,301 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::status::Status;
+
+/// Longest request or response line accepted, so a stray client cannot make
+/// the instance buffer without bound.
+const MAX_FRAME_LEN: u64 = 64 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command for the running instance, sent as one JSON line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ControlRequest {
+    Connect,
+    Disconnect,
+    Status,
+    /// Regenerate the config and restart the backend if it is running.
+    Reload,
+    /// Raise the main window; a second GUI launch sends this before exiting.
+    Show,
+}
+
+/// The instance's answer, sent as one JSON line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum ControlResponse {
+    Ok { message: String },
+    Status(Status),
+    Error { message: String },
+}
+
+impl ControlResponse {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self::Ok {
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error {
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads one newline-terminated JSON frame; `None` when the peer closed the
+/// connection before sending anything.
+pub async fn read_frame<T, R>(reader: &mut R) -> io::Result<Option<T>>
+where
+    T: DeserializeOwned,
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    if reader.take(MAX_FRAME_LEN).read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too long or not newline-terminated",
+        ));
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub async fn write_frame<T, W>(writer: &mut W, value: &T) -> io::Result<()>
+where
+    T: Serialize,
+    W: AsyncWrite + Unpin,
+{
+    let mut json = serde_json::to_vec(value).map_err(io::Error::other)?;
+    json.push(b'\n');
+    writer.write_all(&json).await?;
+    writer.flush().await
+}
+
+/// Sends `request` to the instance listening on `path`. Fails with
+/// `NotFound` or `ConnectionRefused` when no instance is running.
+pub async fn send_request(path: &Path, request: ControlRequest) -> io::Result<ControlResponse> {
+    let exchange = async {
+        let stream = UnixStream::connect(path).await?;
+        let mut stream = BufReader::new(stream);
+        write_frame(stream.get_mut(), &request).await?;
+        read_frame(&mut stream).await?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "instance closed the connection",
+            )
+        })
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "instance did not answer"))?
+}
+
+/// The listening end, held by whichever GUI or CLI instance owns the backend.
+/// Removes the socket file when dropped.
+#[derive(Debug)]
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Fails with `AddrInUse` while another instance is listening; a socket
+    /// left behind by one that died is replaced.
+    pub fn bind(path: PathBuf) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)?;
+        }
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another instance is listening on {}", path.display()),
+            ));
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        Ok(Self { listener, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits for the next well-formed request. Clients that send garbage or
+    /// nothing are answered with an error (if possible) and skipped.
+    pub async fn accept(&self) -> io::Result<(ControlRequest, Responder)> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let mut stream = BufReader::new(stream);
+            let request = tokio::time::timeout(REQUEST_TIMEOUT, read_frame(&mut stream)).await;
+            match request {
+                Ok(Ok(Some(request))) => {
+                    return Ok((
+                        request,
+                        Responder {
+                            stream: stream.into_inner(),
+                        },
+                    ));
+                }
+                Ok(Err(e)) => {
+                    let response = ControlResponse::error(format!("bad request: {e}"));
+                    write_frame(stream.get_mut(), &response).await.ok();
+                }
+                Ok(Ok(None)) | Err(_) => {}
+            }
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Answers the request it was accepted with.
+#[derive(Debug)]
+pub struct Responder {
+    stream: UnixStream,
+}
+
+impl Responder {
+    pub async fn send(mut self, response: &ControlResponse) -> io::Result<()> {
+        write_frame(&mut self.stream, response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_frames_are_single_json_lines() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &ControlRequest::Disconnect)
+            .await
+            .unwrap();
+        write_frame(&mut buf, &ControlResponse::Status(Status::stopped()))
+            .await
+            .unwrap();
+        let text = String::from_utf8(buf.clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], r#"{"command":"disconnect"}"#);
+        assert!(lines[1].starts_with(r#"{"result":"status","state":"stopped""#));
+
+        let mut reader = BufReader::new(buf.as_slice());
+        let request: Option<ControlRequest> = read_frame(&mut reader).await.unwrap();
+        assert_eq!(request, Some(ControlRequest::Disconnect));
+        let response: Option<ControlResponse> = read_frame(&mut reader).await.unwrap();
+        assert_eq!(response, Some(ControlResponse::Status(Status::stopped())));
+        let end: Option<ControlResponse> = read_frame(&mut reader).await.unwrap();
+        assert_eq!(end, None);
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_or_oversized_frames_are_rejected() {
+        let mut reader = BufReader::new(&br#"{"command":"status"}"#[..]);
+        let err = read_frame::<ControlRequest, _>(&mut reader)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let huge = vec![b' '; MAX_FRAME_LEN as usize + 1];
+        let mut reader = BufReader::new(huge.as_slice());
+        let err = read_frame::<ControlRequest, _>(&mut reader)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_request_response_over_socket() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run/control.sock");
+        let server = ControlServer::bind(path.clone()).unwrap();
+
+        let serve = tokio::spawn(async move {
+            let (request, responder) = server.accept().await.unwrap();
+            assert_eq!(request, ControlRequest::Reload);
+            responder
+                .send(&ControlResponse::ok("reloaded"))
+                .await
+                .unwrap();
+            server
+        });
+        let response = send_request(&path, ControlRequest::Reload).await.unwrap();
+        assert_eq!(response, ControlResponse::ok("reloaded"));
+
+        let server = serve.await.unwrap();
+        let err = ControlServer::bind(path.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        drop(server);
+        assert!(!path.exists());
+        let err = send_request(&path, ControlRequest::Status)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_request_gets_error_and_server_keeps_serving() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("control.sock");
+        let server = ControlServer::bind(path.clone()).unwrap();
+        let serve = tokio::spawn(async move { server.accept().await.unwrap().0 });
+
+        let mut stream = BufReader::new(UnixStream::connect(&path).await.unwrap());
+        stream
+            .get_mut()
+            .write_all(b"{\"command\":\"explode\"}\n")
+            .await
+            .unwrap();
+        let response: Option<ControlResponse> = read_frame(&mut stream).await.unwrap();
+        assert!(matches!(response, Some(ControlResponse::Error { .. })));
+
+        // The reply is never read, so only the request half matters here.
+        let client = tokio::spawn({
+            let path = path.clone();
+            async move { send_request(&path, ControlRequest::Status).await }
+        });
+        assert_eq!(serve.await.unwrap(), ControlRequest::Status);
+        client.abort();
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("control.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+        let server = ControlServer::bind(path.clone()).unwrap();
+        assert_eq!(server.path(), path);
+    }
+}
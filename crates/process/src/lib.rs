@@ -1,9 +1,18 @@
+mod control;
 mod log_buffer;
 mod manager;
 mod pid;
+mod resources;
 mod state;
+mod status;
 
+pub use control::{
+    ControlRequest, ControlResponse, ControlServer, Responder, read_frame, send_request,
+    write_frame,
+};
 pub use log_buffer::{LogBuffer, LogLine, LogSource};
 pub use manager::{ProcessError, ProcessManager};
 pub use pid::PidFile;
-pub use state::{ProcessEvent, ProcessState};
+pub use resources::ResourceStats;
+pub use state::{ProcessEvent, ProcessFailure, ProcessState, next_log_line};
+pub use status::{Status, StatusFile};
@@ -1,9 +1,21 @@
 use std::collections::VecDeque;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LogSource {
     Stdout,
     Stderr,
+    App,
+}
+
+impl LogSource {
+    /// Tag written before each line of a saved log.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+            Self::App => "app",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,6 +38,18 @@ impl LogLine {
             content: content.into(),
         }
     }
+
+    pub fn app(content: impl Into<String>) -> Self {
+        Self {
+            source: LogSource::App,
+            content: content.into(),
+        }
+    }
+
+    /// Marker shown in place of output a slow reader missed.
+    pub fn dropped(count: u64) -> Self {
+        Self::app(format!("[{count} lines dropped]"))
+    }
 }
 
 pub struct LogBuffer {
@@ -47,11 +71,15 @@ impl LogBuffer {
         }
     }
 
-    pub fn push(&mut self, line: LogLine) {
-        if self.lines.len() >= self.capacity {
-            self.lines.pop_front();
-        }
+    /// Appends `line`, returning the oldest line when it had to make room.
+    pub fn push(&mut self, line: LogLine) -> Option<LogLine> {
+        let evicted = if self.lines.len() >= self.capacity {
+            self.lines.pop_front()
+        } else {
+            None
+        };
         self.lines.push_back(line);
+        evicted
     }
 
     pub fn last_n(&self, n: usize) -> Vec<&LogLine> {
@@ -67,6 +95,13 @@ impl LogBuffer {
             .collect()
     }
 
+    pub fn by_source(&self, source: LogSource) -> Vec<&LogLine> {
+        self.lines
+            .iter()
+            .filter(|line| line.source == source)
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         self.lines.len()
     }
@@ -78,6 +113,19 @@ impl LogBuffer {
     pub fn clear(&mut self) {
         self.lines.clear();
     }
+
+    /// Every buffered line, oldest first, under a header saying when the log
+    /// was saved, as written by "Save Logs".
+    pub fn to_text(&self, saved_at: &str) -> String {
+        let mut text = format!(
+            "# v2ray-rs log saved {saved_at}, {} lines\n",
+            self.lines.len()
+        );
+        for line in &self.lines {
+            text.push_str(&format!("[{}] {}\n", line.source.label(), line.content));
+        }
+        text
+    }
 }
 
 impl Default for LogBuffer {
@@ -126,6 +174,35 @@ mod tests {
         assert_eq!(lines[2].content, "line 5");
     }
 
+    #[test]
+    fn push_reports_the_evicted_line() {
+        let mut buffer = LogBuffer::with_capacity(2);
+        assert_eq!(buffer.push(LogLine::stdout("line 1")), None);
+        assert_eq!(buffer.push(LogLine::stdout("line 2")), None);
+        assert_eq!(
+            buffer.push(LogLine::stdout("line 3")),
+            Some(LogLine::stdout("line 1"))
+        );
+    }
+
+    #[test]
+    fn saved_text_has_header_and_all_lines_in_order() {
+        let mut buffer = LogBuffer::with_capacity(3);
+        buffer.push(LogLine::stdout("dropped"));
+        buffer.push(LogLine::app("Backend running"));
+        buffer.push(LogLine::stdout("accepted tcp:example.com:443"));
+        buffer.push(LogLine::stderr("failed to dial"));
+
+        let text = buffer.to_text("2026-10-15 12:00:00 +02:00");
+        assert_eq!(
+            text,
+            "# v2ray-rs log saved 2026-10-15 12:00:00 +02:00, 3 lines\n\
+             [app] Backend running\n\
+             [stdout] accepted tcp:example.com:443\n\
+             [stderr] failed to dial\n"
+        );
+    }
+
     #[test]
     fn last_n_returns_requested_lines() {
         let mut buffer = LogBuffer::new();
@@ -189,6 +266,25 @@ mod tests {
         assert_eq!(results_upper.len(), 3);
     }
 
+    #[test]
+    fn by_source_separates_streams() {
+        let mut buffer = LogBuffer::new();
+        buffer.push(LogLine::stdout("started"));
+        buffer.push(LogLine::stderr("warning: deprecated option"));
+        buffer.push(LogLine::app("connecting"));
+        buffer.push(LogLine::stdout("accepted"));
+
+        let stderr = buffer.by_source(LogSource::Stderr);
+        assert_eq!(stderr.len(), 1);
+        assert_eq!(stderr[0].content, "warning: deprecated option");
+
+        let stdout = buffer.by_source(LogSource::Stdout);
+        assert_eq!(stdout.len(), 2);
+        assert!(stdout.iter().all(|l| l.source == LogSource::Stdout));
+
+        assert_eq!(buffer.by_source(LogSource::App).len(), 1);
+    }
+
     #[test]
     fn search_no_matches() {
         let mut buffer = LogBuffer::new();
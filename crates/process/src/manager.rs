@@ -1,4 +1,6 @@
+use std::os::unix::process::ExitStatusExt;
 use std::path::PathBuf;
+use std::process::ExitStatus;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -7,17 +9,22 @@ use nix::unistd::Pid;
 use thiserror::Error;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time::sleep;
 
 use crate::log_buffer::{LogBuffer, LogLine};
-use crate::pid::PidFile;
-use crate::state::{ProcessEvent, ProcessState, StateManager, TransitionError};
+use crate::pid::{PidFile, PortOwner, port_owner};
+use crate::resources::{ResourceStats, read_resource_stats};
+use crate::state::{ProcessEvent, ProcessFailure, ProcessState, StateManager, TransitionError};
+use crate::status::StatusFile;
 
-const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
 const CRASH_RESTART_DELAY: Duration = Duration::from_secs(2);
 const MAX_CRASHES: usize = 3;
 const CRASH_WINDOW: Duration = Duration::from_secs(60);
+const STARTUP_GRACE: Duration = Duration::from_millis(500);
+const STARTUP_LOG_TAIL: usize = 20;
+const LOG_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Error)]
 pub enum ProcessError {
@@ -25,8 +32,19 @@ pub enum ProcessError {
     BinaryNotFound(PathBuf),
     #[error("config file missing: {0}")]
     ConfigMissing(PathBuf),
+    #[error("backend already running (pid {0})")]
+    AlreadyRunning(u32),
+    #[error("port {port} is held by a previous backend (pid {pid})")]
+    PortHeldByPrevious { port: u16, pid: u32 },
+    #[error("port {0} is already in use by another program")]
+    PortInUse(u16),
     #[error("spawn process: {0}")]
     Spawn(#[from] std::io::Error),
+    #[error("backend exited during startup: {reason}")]
+    StartupFailed {
+        reason: String,
+        log_tail: Vec<String>,
+    },
     #[error("{0}")]
     Transition(#[from] TransitionError),
 }
@@ -40,7 +58,10 @@ pub struct ProcessManager {
     config_path: PathBuf,
     crash_times: Vec<Instant>,
     auto_restart: bool,
+    stop_timeout: Duration,
     log_handles: Vec<tokio::task::JoinHandle<()>>,
+    ports: Vec<u16>,
+    status_file: Option<StatusFile>,
 }
 
 impl ProcessManager {
@@ -54,7 +75,10 @@ impl ProcessManager {
             config_path,
             crash_times: Vec::new(),
             auto_restart: true,
+            stop_timeout: DEFAULT_STOP_TIMEOUT,
             log_handles: Vec::new(),
+            ports: Vec::new(),
+            status_file: None,
         }
     }
 
@@ -66,6 +90,16 @@ impl ProcessManager {
         self.state.subscribe()
     }
 
+    pub fn subscribe_lifecycle(&self) -> mpsc::UnboundedReceiver<ProcessEvent> {
+        self.state.subscribe_lifecycle()
+    }
+
+    /// Sets how many events [`Self::subscribe`] readers may fall behind by.
+    /// Call before subscribing.
+    pub fn set_event_capacity(&mut self, capacity: usize) {
+        self.state.set_capacity(capacity);
+    }
+
     pub fn log_buffer(&self) -> &Arc<Mutex<LogBuffer>> {
         &self.log_buffer
     }
@@ -74,6 +108,45 @@ impl ProcessManager {
         self.auto_restart = enabled;
     }
 
+    pub fn resource_usage(&self) -> Option<ResourceStats> {
+        let pid = self.child.as_ref()?.id()?;
+        read_resource_stats(pid)
+    }
+
+    pub fn report_resource_usage(&self) -> Option<ResourceStats> {
+        let stats = self.resource_usage()?;
+        self.state.emit(ProcessEvent::ResourceUsage(stats));
+        Some(stats)
+    }
+
+    pub fn set_stop_timeout(&mut self, timeout: Duration) {
+        self.stop_timeout = timeout;
+    }
+
+    /// Ports the backend will listen on; `start` checks they are free.
+    pub fn set_ports(&mut self, ports: Vec<u16>) {
+        self.ports = ports;
+    }
+
+    /// Keeps `file` up to date with every state change from now on.
+    pub fn set_status_file(&mut self, file: StatusFile) {
+        self.status_file = Some(file);
+        self.write_status();
+    }
+
+    fn transition(&mut self, state: ProcessState) -> Result<(), TransitionError> {
+        self.state.transition(state)?;
+        self.write_status();
+        Ok(())
+    }
+
+    fn write_status(&self) {
+        if let Some(file) = &self.status_file {
+            let pid = self.child.as_ref().and_then(Child::id);
+            file.write(&self.state.state(), pid).ok();
+        }
+    }
+
     pub async fn start(&mut self) -> Result<(), ProcessError> {
         if !self.binary_path.exists() {
             return Err(ProcessError::BinaryNotFound(self.binary_path.clone()));
@@ -81,19 +154,71 @@ impl ProcessManager {
         if !self.config_path.exists() {
             return Err(ProcessError::ConfigMissing(self.config_path.clone()));
         }
-
-        self.state.transition(ProcessState::Starting)?;
-
-        match self.spawn_process().await {
-            Ok(()) => {
-                self.state.transition(ProcessState::Running)?;
-                Ok(())
+        if self.child.is_none() {
+            let previous = self.pid_file.running_instance(&self.binary_path)?;
+            for &port in &self.ports {
+                match port_owner(port, previous) {
+                    PortOwner::Free => {}
+                    PortOwner::Previous(pid) => {
+                        return Err(ProcessError::PortHeldByPrevious { port, pid });
+                    }
+                    PortOwner::Other => return Err(ProcessError::PortInUse(port)),
+                }
             }
-            Err(e) => {
-                let _ = self.state.transition(ProcessState::Error(e.to_string()));
-                Err(e)
+            if let Some(pid) = previous {
+                return Err(ProcessError::AlreadyRunning(pid));
             }
         }
+
+        self.transition(ProcessState::Starting)?;
+
+        if let Err(e) = self.spawn_process().await {
+            let _ = self.transition(ProcessState::Error(e.to_string().into()));
+            return Err(e);
+        }
+
+        if let Some(exit_code) = self.wait_startup().await {
+            let reason = exit_reason(exit_code);
+            let log_tail = self.log_tail(STARTUP_LOG_TAIL);
+            let err = ProcessError::StartupFailed {
+                reason: reason.clone(),
+                log_tail: log_tail.clone(),
+            };
+            let _ = self.transition(ProcessState::Error(ProcessFailure {
+                summary: err.to_string(),
+                log_tail: log_tail.clone(),
+            }));
+            self.state
+                .emit(ProcessEvent::StartupFailed { reason, log_tail });
+            return Err(err);
+        }
+
+        self.transition(ProcessState::Running)?;
+        Ok(())
+    }
+
+    fn log_tail(&self, n: usize) -> Vec<String> {
+        self.log_buffer
+            .lock()
+            .map(|buf| buf.last_n(n).iter().map(|l| l.content.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    async fn wait_startup(&mut self) -> Option<Option<i32>> {
+        let child = self.child.as_mut()?;
+        let status = tokio::time::timeout(STARTUP_GRACE, child.wait())
+            .await
+            .ok()?
+            .ok()?;
+
+        self.child = None;
+        self.pid_file.remove().ok();
+
+        for handle in self.log_handles.drain(..) {
+            let _ = tokio::time::timeout(LOG_DRAIN_TIMEOUT, handle).await;
+        }
+
+        Some(status.code())
     }
 
     pub async fn stop(&mut self) -> Result<(), ProcessError> {
@@ -101,9 +226,9 @@ impl ProcessManager {
             return Ok(());
         }
 
-        self.state.transition(ProcessState::Stopping)?;
+        self.transition(ProcessState::Stopping)?;
         self.graceful_stop().await;
-        self.state.transition(ProcessState::Stopped)?;
+        self.transition(ProcessState::Stopped)?;
         self.pid_file.remove().ok();
         Ok(())
     }
@@ -116,10 +241,11 @@ impl ProcessManager {
     }
 
     pub async fn shutdown(&mut self) {
-        if self.child.is_some() {
-            self.auto_restart = false;
-            let _ = self.stop().await;
+        self.auto_restart = false;
+        if self.child.is_some() && self.stop().await.is_err() {
+            self.graceful_stop().await;
         }
+        self.pid_file.remove().ok();
     }
 
     pub fn check_orphaned(&self) -> std::io::Result<bool> {
@@ -127,14 +253,30 @@ impl ProcessManager {
     }
 
     pub async fn wait_and_handle_exit(&mut self) -> Option<i32> {
+        let status = self.wait_exit().await?;
+        self.handle_exit(status).await
+    }
+
+    /// Waits for the backend to exit without acting on it, so unlike
+    /// [`Self::wait_and_handle_exit`] it can race other branches of a
+    /// `select!`. Pass the status to [`Self::handle_exit`] afterwards.
+    pub async fn wait_exit(&mut self) -> Option<ExitStatus> {
         let child = self.child.as_mut()?;
-        let status = child.wait().await.ok()?;
+        child.wait().await.ok()
+    }
+
+    /// Records an exit seen by [`Self::wait_exit`] and restarts the backend
+    /// if it crashed; this includes the restart delay.
+    pub async fn handle_exit(&mut self, status: ExitStatus) -> Option<i32> {
         let exit_code = status.code();
 
         self.child = None;
         self.pid_file.remove().ok();
 
-        self.state.emit(ProcessEvent::ProcessExited { exit_code });
+        self.state.emit(ProcessEvent::ProcessExited {
+            exit_code,
+            signal: status.signal(),
+        });
 
         if self.state.state() == ProcessState::Running {
             self.handle_unexpected_exit(exit_code).await;
@@ -224,25 +366,37 @@ impl ProcessManager {
             let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
         }
 
-        let wait_result = tokio::time::timeout(STOP_TIMEOUT, child.wait()).await;
-
-        if wait_result.is_err() {
-            child.kill().await.ok();
-            child.wait().await.ok();
-        }
+        let status = match tokio::time::timeout(self.stop_timeout, child.wait()).await {
+            Ok(result) => result.ok(),
+            Err(_) => {
+                child.start_kill().ok();
+                child.wait().await.ok()
+            }
+        };
 
         for handle in self.log_handles.drain(..) {
             handle.abort();
         }
 
         self.child = None;
+
+        if let Some(status) = status {
+            self.state.emit(ProcessEvent::ProcessExited {
+                exit_code: status.code(),
+                signal: status.signal(),
+            });
+        }
+    }
+
+    /// Records an exit nobody asked for as a stop; Running may only reach
+    /// Stopped through Stopping.
+    fn mark_stopped(&mut self) {
+        let _ = self.transition(ProcessState::Stopping);
+        let _ = self.transition(ProcessState::Stopped);
     }
 
     async fn handle_unexpected_exit(&mut self, exit_code: Option<i32>) {
-        let msg = match exit_code {
-            Some(code) => format!("process exited with code {code}"),
-            None => "process killed by signal".into(),
-        };
+        let msg = exit_reason(exit_code);
 
         let is_signal_exit =
             exit_code.is_none() || matches!(exit_code, Some(130) | Some(137) | Some(143));
@@ -252,29 +406,34 @@ impl ProcessManager {
             self.crash_times.retain(|t| t.elapsed() < CRASH_WINDOW);
 
             if self.crash_times.len() >= MAX_CRASHES {
-                let _ = self.state.transition(ProcessState::Error(format!(
-                    "{MAX_CRASHES} crashes within {CRASH_WINDOW:?}: {msg}"
-                )));
+                let _ = self.transition(ProcessState::Error(
+                    format!("{MAX_CRASHES} crashes within {CRASH_WINDOW:?}: {msg}").into(),
+                ));
                 return;
             }
         }
 
-        if !self.auto_restart || is_signal_exit {
-            let _ = self.state.transition(if is_signal_exit {
-                ProcessState::Stopped
-            } else {
-                ProcessState::Error(msg)
-            });
+        if is_signal_exit {
+            self.mark_stopped();
+            return;
+        }
+        if !self.auto_restart {
+            let _ = self.transition(ProcessState::Error(msg.into()));
             return;
         }
 
-        let _ = self.state.transition(ProcessState::Stopped);
+        self.mark_stopped();
         sleep(CRASH_RESTART_DELAY).await;
 
         if let Err(e) = self.start().await {
-            let _ = self
-                .state
-                .transition(ProcessState::Error(format!("restart failed: {e}")));
+            let _ = self.transition(ProcessState::Error(format!("restart failed: {e}").into()));
         }
     }
 }
+
+fn exit_reason(exit_code: Option<i32>) -> String {
+    match exit_code {
+        Some(code) => format!("process exited with code {code}"),
+        None => "process killed by signal".into(),
+    }
+}
@@ -1,7 +1,12 @@
 use nix::sys::signal::{Signal, kill};
 use nix::unistd::Pid;
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Linux truncates /proc/<pid>/comm to TASK_COMM_LEN - 1 bytes.
+const COMM_MAX_LEN: usize = 15;
 
 fn is_backend_process(pid: u32) -> bool {
     let cmdline_path = format!("/proc/{pid}/cmdline");
@@ -12,6 +17,94 @@ fn is_backend_process(pid: u32) -> bool {
     cmdline.contains("v2ray") || cmdline.contains("xray") || cmdline.contains("sing-box")
 }
 
+fn process_comm(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|s| s.trim_end().to_owned())
+}
+
+fn is_same_binary(pid: u32, binary: &Path) -> bool {
+    let Some(comm) = process_comm(pid) else {
+        return false;
+    };
+    let Some(name) = binary.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy();
+    let expected: String = name.bytes().take(COMM_MAX_LEN).map(char::from).collect();
+    comm == expected
+}
+
+/// Who is listening on a TCP port the backend wants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortOwner {
+    Free,
+    /// The given process, which is the backend named in our PID file.
+    Previous(u32),
+    Other,
+}
+
+/// Socket inodes of TCP listeners bound to `port`, from /proc/net/tcp{,6}.
+fn listening_inodes(port: u16) -> HashSet<u64> {
+    const TCP_LISTEN: &str = "0A";
+    let mut inodes = HashSet::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(text) = fs::read_to_string(table) else {
+            continue;
+        };
+        for line in text.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(local), Some(state), Some(inode)) =
+                (fields.get(1), fields.get(3), fields.get(9))
+            else {
+                continue;
+            };
+            let local_port = local
+                .rsplit_once(':')
+                .and_then(|(_, p)| u16::from_str_radix(p, 16).ok());
+            if *state == TCP_LISTEN
+                && local_port == Some(port)
+                && let Ok(inode) = inode.parse()
+            {
+                inodes.insert(inode);
+            }
+        }
+    }
+    inodes
+}
+
+fn holds_socket(pid: u32, inodes: &HashSet<u64>) -> bool {
+    let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+        return false;
+    };
+    fds.flatten().any(|fd| {
+        fs::read_link(fd.path())
+            .ok()
+            .and_then(|target| {
+                target
+                    .to_str()?
+                    .strip_prefix("socket:[")?
+                    .strip_suffix(']')?
+                    .parse()
+                    .ok()
+            })
+            .is_some_and(|inode: u64| inodes.contains(&inode))
+    })
+}
+
+/// Finds out whether `port` is taken and, if so, whether `previous` (our
+/// earlier backend, if one is still alive) is the one holding it.
+pub fn port_owner(port: u16, previous: Option<u32>) -> PortOwner {
+    let inodes = listening_inodes(port);
+    if inodes.is_empty() {
+        return PortOwner::Free;
+    }
+    match previous {
+        Some(pid) if holds_socket(pid, &inodes) => PortOwner::Previous(pid),
+        _ => PortOwner::Other,
+    }
+}
+
 pub struct PidFile {
     path: PathBuf,
 }
@@ -50,6 +143,46 @@ impl PidFile {
         }
     }
 
+    pub fn running_instance(&self, binary: &Path) -> std::io::Result<Option<u32>> {
+        let pid = match self.read() {
+            Ok(Some(pid)) => pid,
+            Ok(None) => return Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                self.remove()?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let alive = kill(Pid::from_raw(pid as i32), None).is_ok();
+        if alive && is_same_binary(pid, binary) {
+            return Ok(Some(pid));
+        }
+
+        self.remove()?;
+        Ok(None)
+    }
+
+    /// Stops the backend named in the PID file, escalating to SIGKILL after
+    /// `timeout`. Returns false when there was nothing to stop.
+    pub fn terminate_instance(&self, binary: &Path, timeout: Duration) -> std::io::Result<bool> {
+        let Some(pid) = self.running_instance(binary)? else {
+            return Ok(false);
+        };
+        let nix_pid = Pid::from_raw(pid as i32);
+        let _ = kill(nix_pid, Signal::SIGTERM);
+        let deadline = Instant::now() + timeout;
+        while kill(nix_pid, None).is_ok() {
+            if Instant::now() >= deadline {
+                let _ = kill(nix_pid, Signal::SIGKILL);
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        self.remove()?;
+        Ok(true)
+    }
+
     pub fn check_and_kill_orphaned(&self) -> std::io::Result<bool> {
         let Some(pid) = self.read()? else {
             return Ok(false);
@@ -74,6 +207,7 @@ impl PidFile {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
@@ -186,4 +320,89 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
     }
+
+    fn current_binary() -> PathBuf {
+        PathBuf::from(process_comm(std::process::id()).unwrap())
+    }
+
+    #[test]
+    fn running_instance_with_no_file() {
+        let dir = TempDir::new().unwrap();
+        let pid_file = PidFile::new(test_pid_path(&dir));
+
+        assert_eq!(pid_file.running_instance(&current_binary()).unwrap(), None);
+    }
+
+    #[test]
+    fn running_instance_clears_dead_pid() {
+        let dir = TempDir::new().unwrap();
+        let pid_file = PidFile::new(test_pid_path(&dir));
+        pid_file.write(999999).unwrap();
+
+        assert_eq!(pid_file.running_instance(&current_binary()).unwrap(), None);
+        assert_eq!(pid_file.read().unwrap(), None);
+    }
+
+    #[test]
+    fn running_instance_detects_live_process() {
+        let dir = TempDir::new().unwrap();
+        let pid_file = PidFile::new(test_pid_path(&dir));
+        let pid = std::process::id();
+        pid_file.write(pid).unwrap();
+
+        assert_eq!(
+            pid_file.running_instance(&current_binary()).unwrap(),
+            Some(pid)
+        );
+        assert_eq!(pid_file.read().unwrap(), Some(pid));
+    }
+
+    #[test]
+    fn running_instance_clears_reused_pid() {
+        let dir = TempDir::new().unwrap();
+        let pid_file = PidFile::new(test_pid_path(&dir));
+        pid_file.write(std::process::id()).unwrap();
+
+        let other = PathBuf::from("/usr/bin/xray");
+        assert_eq!(pid_file.running_instance(&other).unwrap(), None);
+        assert_eq!(pid_file.read().unwrap(), None);
+    }
+
+    #[test]
+    fn running_instance_clears_invalid_file() {
+        let dir = TempDir::new().unwrap();
+        let pid_file = PidFile::new(test_pid_path(&dir));
+        fs::write(&pid_file.path, "garbage").unwrap();
+
+        assert_eq!(pid_file.running_instance(&current_binary()).unwrap(), None);
+        assert!(!pid_file.path.exists());
+    }
+
+    #[test]
+    fn port_owner_free_port() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert_eq!(port_owner(port, None), PortOwner::Free);
+    }
+
+    #[test]
+    fn port_owner_stale_own_process() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pid = std::process::id();
+
+        assert_eq!(port_owner(port, Some(pid)), PortOwner::Previous(pid));
+    }
+
+    #[test]
+    fn port_owner_foreign_process() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        assert_eq!(port_owner(port, None), PortOwner::Other);
+        assert_eq!(port_owner(port, Some(999999)), PortOwner::Other);
+    }
 }
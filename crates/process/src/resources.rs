@@ -0,0 +1,77 @@
+use std::fs;
+use std::time::Duration;
+
+// USER_HZ is fixed at 100 on all mainstream Linux architectures.
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceStats {
+    pub rss_bytes: u64,
+    pub cpu_time: Duration,
+}
+
+pub fn read_resource_stats(pid: u32) -> Option<ResourceStats> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    Some(ResourceStats {
+        rss_bytes: parse_status_rss(&status)?,
+        cpu_time: parse_stat_cpu_time(&stat)?,
+    })
+}
+
+fn parse_stat_cpu_time(stat: &str) -> Option<Duration> {
+    // comm (field 2) may contain spaces and parentheses, so skip past the last ')'
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(Duration::from_millis(
+        (utime + stime) * 1000 / CLOCK_TICKS_PER_SEC,
+    ))
+}
+
+fn parse_status_rss(status: &str) -> Option<u64> {
+    let value = status.lines().find_map(|l| l.strip_prefix("VmRSS:"))?;
+    let kb: u64 = value.split_whitespace().next()?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_STAT: &str = "4242 (xray (run)) S 1 4242 4242 0 -1 4194560 12345 0 0 0 \
+        250 130 0 0 20 0 12 0 987654 1234567890 10240 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0";
+
+    const SAMPLE_STATUS: &str = "Name:\txray\nState:\tS (sleeping)\nPid:\t4242\n\
+        VmPeak:\t 1300000 kB\nVmSize:\t 1250000 kB\nVmRSS:\t   40960 kB\nThreads:\t12\n";
+
+    #[test]
+    fn parse_stat_sums_user_and_system_time() {
+        let cpu = parse_stat_cpu_time(SAMPLE_STAT).unwrap();
+        assert_eq!(cpu, Duration::from_millis(3800));
+    }
+
+    #[test]
+    fn parse_stat_rejects_truncated_input() {
+        assert!(parse_stat_cpu_time("4242 (xray) S 1 2").is_none());
+        assert!(parse_stat_cpu_time("garbage").is_none());
+    }
+
+    #[test]
+    fn parse_status_reads_rss() {
+        assert_eq!(parse_status_rss(SAMPLE_STATUS), Some(40960 * 1024));
+        assert_eq!(parse_status_rss("Name:\tkthreadd\n"), None);
+    }
+
+    #[test]
+    fn read_current_process() {
+        let stats = read_resource_stats(std::process::id()).unwrap();
+        assert!(stats.rss_bytes > 0);
+    }
+
+    #[test]
+    fn read_missing_process() {
+        assert!(read_resource_stats(999_999_999).is_none());
+    }
+}
@@ -1,7 +1,13 @@
+use std::sync::Mutex;
+
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 
 use crate::log_buffer::LogLine;
+use crate::resources::ResourceStats;
+
+/// Room for a burst of backend output before slow readers start lagging.
+const DEFAULT_EVENT_CAPACITY: usize = 1024;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ProcessState {
@@ -9,10 +15,50 @@ pub enum ProcessState {
     Starting,
     Running,
     Stopping,
-    Error(String),
+    Error(ProcessFailure),
+}
+
+/// Why the backend is not running: a one-line summary, and the last lines it
+/// logged when it exited while starting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessFailure {
+    pub summary: String,
+    pub log_tail: Vec<String>,
+}
+
+impl From<String> for ProcessFailure {
+    fn from(summary: String) -> Self {
+        Self {
+            summary,
+            log_tail: Vec::new(),
+        }
+    }
+}
+
+impl From<&str> for ProcessFailure {
+    fn from(summary: &str) -> Self {
+        summary.to_owned().into()
+    }
+}
+
+impl std::fmt::Display for ProcessFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.summary)
+    }
 }
 
 impl ProcessState {
+    /// Lowercase name, as reported in the status file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Stopped => "stopped",
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Stopping => "stopping",
+            Self::Error(_) => "error",
+        }
+    }
+
     pub fn can_transition_to(&self, target: &ProcessState) -> bool {
         use ProcessState::*;
         matches!(
@@ -50,7 +96,26 @@ pub enum ProcessEvent {
     LogLine(LogLine),
     ProcessExited {
         exit_code: Option<i32>,
+        signal: Option<i32>,
+    },
+    StartupFailed {
+        reason: String,
+        log_tail: Vec<String>,
     },
+    ResourceUsage(ResourceStats),
+}
+
+impl ProcessEvent {
+    /// Events that change what the process is doing, as opposed to output
+    /// and periodic stats. These go to lifecycle subscribers without loss.
+    pub fn is_lifecycle(&self) -> bool {
+        matches!(
+            self,
+            ProcessEvent::StateChanged { .. }
+                | ProcessEvent::ProcessExited { .. }
+                | ProcessEvent::StartupFailed { .. }
+        )
+    }
 }
 
 #[derive(Debug, Error)]
@@ -62,42 +127,87 @@ pub enum TransitionError {
     },
 }
 
+/// Waits for the next log line on `rx`.
+///
+/// When the receiver fell behind, the missed events come back as a single
+/// [`LogLine::dropped`] marker so the gap is visible. The count includes any
+/// non-log events that were skipped along with the lines.
+pub async fn next_log_line(rx: &mut broadcast::Receiver<ProcessEvent>) -> Option<LogLine> {
+    loop {
+        match rx.recv().await {
+            Ok(ProcessEvent::LogLine(line)) => return Some(line),
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                return Some(LogLine::dropped(count));
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
 pub struct StateManager {
     state: ProcessState,
     tx: broadcast::Sender<ProcessEvent>,
+    lifecycle: Mutex<Vec<mpsc::UnboundedSender<ProcessEvent>>>,
 }
 
 impl StateManager {
     pub fn new() -> Self {
-        let (tx, _rx) = broadcast::channel(64);
+        Self::with_capacity(DEFAULT_EVENT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity.max(1));
         Self {
             state: ProcessState::Stopped,
             tx,
+            lifecycle: Mutex::new(Vec::new()),
         }
     }
 
+    /// Replaces the broadcast channel. Receivers from [`Self::subscribe`]
+    /// taken before this stop getting events; lifecycle subscribers are kept.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.tx = broadcast::channel(capacity.max(1)).0;
+    }
+
     pub fn state(&self) -> ProcessState {
         self.state.clone()
     }
 
     pub fn transition(&mut self, target: ProcessState) -> Result<ProcessState, TransitionError> {
         let old = self.state.transition(target.clone())?;
-        let _ = self.tx.send(ProcessEvent::StateChanged {
+        self.emit(ProcessEvent::StateChanged {
             from: old.clone(),
             to: target,
         });
         Ok(old)
     }
 
+    /// All events, bounded: a reader that falls behind gets `Lagged`.
     pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
         self.tx.subscribe()
     }
 
+    /// Lifecycle events only, unbounded, so none are ever skipped.
+    pub fn subscribe_lifecycle(&self) -> mpsc::UnboundedReceiver<ProcessEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        if let Ok(mut subscribers) = self.lifecycle.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
     pub fn sender(&self) -> &broadcast::Sender<ProcessEvent> {
         &self.tx
     }
 
     pub fn emit(&self, event: ProcessEvent) {
+        if event.is_lifecycle()
+            && let Ok(mut subscribers) = self.lifecycle.lock()
+        {
+            subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        }
         let _ = self.tx.send(event);
     }
 }
@@ -212,6 +322,87 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn lagged_log_reader_gets_dropped_marker() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for i in 0..5 {
+            tx.send(ProcessEvent::LogLine(LogLine::stdout(format!("line {i}"))))
+                .unwrap();
+        }
+        drop(tx);
+
+        assert_eq!(next_log_line(&mut rx).await, Some(LogLine::dropped(3)));
+        assert_eq!(
+            next_log_line(&mut rx).await,
+            Some(LogLine::stdout("line 3"))
+        );
+        assert_eq!(
+            next_log_line(&mut rx).await,
+            Some(LogLine::stdout("line 4"))
+        );
+        assert_eq!(next_log_line(&mut rx).await, None);
+    }
+
+    #[tokio::test]
+    async fn log_reader_skips_other_events() {
+        let mgr = StateManager::new();
+        let mut rx = mgr.subscribe();
+        mgr.emit(ProcessEvent::ProcessExited {
+            exit_code: Some(0),
+            signal: None,
+        });
+        mgr.emit(ProcessEvent::LogLine(LogLine::stderr("boom")));
+
+        assert_eq!(next_log_line(&mut rx).await, Some(LogLine::stderr("boom")));
+    }
+
+    #[test]
+    fn rapid_transitions_all_reach_lifecycle_subscriber() {
+        let mut mgr = StateManager::with_capacity(2);
+        let mut lagging = mgr.subscribe();
+        let mut rx = mgr.subscribe_lifecycle();
+
+        let cycle = [
+            ProcessState::Starting,
+            ProcessState::Running,
+            ProcessState::Error("crashed".into()),
+            ProcessState::Stopped,
+        ];
+        for _ in 0..100 {
+            for state in &cycle {
+                mgr.emit(ProcessEvent::LogLine(LogLine::stdout("noise")));
+                mgr.transition(state.clone()).unwrap();
+            }
+        }
+
+        let mut from = ProcessState::Stopped;
+        for _ in 0..100 {
+            for state in &cycle {
+                match rx.try_recv().unwrap() {
+                    ProcessEvent::StateChanged { from: f, to } => {
+                        assert_eq!(f, from);
+                        assert_eq!(&to, state);
+                    }
+                    other => panic!("expected StateChanged, got {other:?}"),
+                }
+                from = state.clone();
+            }
+        }
+        assert!(rx.try_recv().is_err());
+        assert!(matches!(
+            lagging.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(_))
+        ));
+    }
+
+    #[test]
+    fn closed_lifecycle_subscribers_are_dropped() {
+        let mut mgr = StateManager::new();
+        drop(mgr.subscribe_lifecycle());
+        mgr.transition(ProcessState::Starting).unwrap();
+        assert!(mgr.lifecycle.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn state_manager_starts_stopped() {
         let mgr = StateManager::new();
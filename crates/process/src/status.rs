@@ -0,0 +1,122 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::ProcessState;
+
+/// What `status.json` tells scripts and status bar widgets about the proxy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub state: String,
+    pub active_node: Option<String>,
+    pub socks_port: Option<u16>,
+    pub http_port: Option<u16>,
+    pub pid: Option<u32>,
+}
+
+impl Status {
+    /// What to report when no status file has been written yet.
+    pub fn stopped() -> Self {
+        Self {
+            state: ProcessState::Stopped.name().to_owned(),
+            active_node: None,
+            socks_port: None,
+            http_port: None,
+            pid: None,
+        }
+    }
+}
+
+/// The status file plus the details of the connection it describes.
+#[derive(Debug, Clone)]
+pub struct StatusFile {
+    path: PathBuf,
+    active_node: Option<String>,
+    socks_port: Option<u16>,
+    http_port: Option<u16>,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            active_node: None,
+            socks_port: None,
+            http_port: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_active_node(&mut self, node: Option<String>) {
+        self.active_node = node;
+    }
+
+    pub fn set_ports(&mut self, socks_port: Option<u16>, http_port: Option<u16>) {
+        self.socks_port = socks_port;
+        self.http_port = http_port;
+    }
+
+    /// Atomically replaces the file with `state`; the node and ports are only
+    /// reported while the backend is up.
+    pub fn write(&self, state: &ProcessState, pid: Option<u32>) -> io::Result<()> {
+        let up = matches!(
+            state,
+            ProcessState::Starting | ProcessState::Running | ProcessState::Stopping
+        );
+        let status = Status {
+            state: state.name().to_owned(),
+            active_node: self.active_node.clone().filter(|_| up),
+            socks_port: self.socks_port.filter(|_| up),
+            http_port: self.http_port.filter(|_| up),
+            pid: pid.filter(|_| up),
+        };
+        let json = serde_json::to_vec_pretty(&status).map_err(io::Error::other)?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    pub fn read(&self) -> io::Result<Status> {
+        let json = fs::read(&self.path)?;
+        serde_json::from_slice(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_details_only_reported_while_up() {
+        let dir = TempDir::new().unwrap();
+        let mut file = StatusFile::new(dir.path().join("status.json"));
+        file.set_active_node(Some("Tokyo".into()));
+        file.set_ports(Some(1080), None);
+
+        file.write(&ProcessState::Running, Some(42)).unwrap();
+        assert_eq!(
+            file.read().unwrap(),
+            Status {
+                state: "running".into(),
+                active_node: Some("Tokyo".into()),
+                socks_port: Some(1080),
+                http_port: None,
+                pid: Some(42),
+            }
+        );
+
+        file.write(&ProcessState::Error("boom".into()), Some(42))
+            .unwrap();
+        let status = file.read().unwrap();
+        assert_eq!(status.state, "error");
+        assert_eq!(status.active_node, None);
+        assert_eq!(status.pid, None);
+        assert!(!dir.path().join("status.json.tmp").exists());
+    }
+}
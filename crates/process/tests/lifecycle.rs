@@ -3,8 +3,12 @@ use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
 use tempfile::TempDir;
-use v2ray_rs_process::{ProcessManager, ProcessState};
+use v2ray_rs_process::{
+    LogSource, ProcessError, ProcessEvent, ProcessManager, ProcessState, StatusFile,
+};
 
 fn setup_dir() -> TempDir {
     TempDir::new().unwrap()
@@ -47,6 +51,40 @@ async fn start_and_stop() {
     assert_eq!(mgr.state(), ProcessState::Stopped);
 }
 
+#[tokio::test]
+async fn status_file_follows_connection() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+    let mut status = StatusFile::new(dir.path().join("status.json"));
+    status.set_active_node(Some("Tokyo".into()));
+    status.set_ports(Some(1080), Some(1081));
+    let reader = status.clone();
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.set_status_file(status);
+    assert_eq!(reader.read().unwrap().state, "stopped");
+
+    mgr.start().await.unwrap();
+    let running = reader.read().unwrap();
+    assert_eq!(running.state, "running");
+    assert_eq!(running.active_node.as_deref(), Some("Tokyo"));
+    assert_eq!(running.socks_port, Some(1080));
+    assert_eq!(running.http_port, Some(1081));
+    let pid: u32 = fs::read_to_string(pid_path(&dir))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    assert_eq!(running.pid, Some(pid));
+
+    mgr.stop().await.unwrap();
+    let stopped = reader.read().unwrap();
+    assert_eq!(stopped.state, "stopped");
+    assert_eq!(stopped.pid, None);
+    assert_eq!(stopped.active_node, None);
+}
+
 #[tokio::test]
 async fn restart_transitions() {
     let dir = setup_dir();
@@ -132,6 +170,60 @@ async fn log_capture() {
     mgr.stop().await.unwrap();
 }
 
+#[tokio::test]
+async fn startup_lines_arrive_before_running() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\necho 'banner' >&2\nsleep 60\n");
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    let mut rx = mgr.subscribe();
+    mgr.start().await.unwrap();
+
+    let mut saw_banner = false;
+    loop {
+        match rx.recv().await.unwrap() {
+            ProcessEvent::LogLine(line) if line.content == "banner" => saw_banner = true,
+            ProcessEvent::StateChanged {
+                to: ProcessState::Running,
+                ..
+            } => break,
+            _ => {}
+        }
+    }
+    assert!(saw_banner, "banner should be delivered while Starting");
+
+    mgr.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn log_capture_tags_sources() {
+    let dir = setup_dir();
+    let binary = create_script(
+        &dir,
+        "backend",
+        "#!/bin/sh\necho 'hello stdout'\necho 'hello stderr' >&2\nsleep 60\n",
+    );
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.start().await.unwrap();
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    {
+        let buf = mgr.log_buffer().lock().unwrap();
+        let stderr = buf.by_source(LogSource::Stderr);
+        let stdout = buf.by_source(LogSource::Stdout);
+        assert_eq!(stderr.len(), 1);
+        assert_eq!(stderr[0].content, "hello stderr");
+        assert_eq!(stdout.len(), 1);
+        assert_eq!(stdout[0].content, "hello stdout");
+    }
+
+    mgr.stop().await.unwrap();
+}
+
 #[tokio::test]
 async fn shutdown_disables_auto_restart() {
     let dir = setup_dir();
@@ -145,6 +237,69 @@ async fn shutdown_disables_auto_restart() {
     assert_eq!(mgr.state(), ProcessState::Stopped);
 }
 
+#[tokio::test]
+async fn shutdown_removes_pid_file() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+    let pid = pid_path(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid.clone());
+    mgr.start().await.unwrap();
+    assert!(pid.exists());
+
+    mgr.shutdown().await;
+
+    assert_eq!(mgr.state(), ProcessState::Stopped);
+    assert!(!pid.exists());
+}
+
+#[tokio::test]
+async fn start_clears_stale_pid_file() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+    let pid = pid_path(&dir);
+
+    fs::write(&pid, "999999").unwrap();
+    let mut mgr = ProcessManager::new(binary.clone(), config.clone(), pid.clone());
+    mgr.start().await.unwrap();
+    assert_ne!(fs::read_to_string(&pid).unwrap(), "999999");
+    mgr.stop().await.unwrap();
+
+    fs::write(&pid, std::process::id().to_string()).unwrap();
+    let mut mgr = ProcessManager::new(binary, config, pid.clone());
+    mgr.start().await.unwrap();
+    assert_ne!(
+        fs::read_to_string(&pid).unwrap(),
+        std::process::id().to_string()
+    );
+    mgr.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn start_refuses_when_instance_running() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+    let pid = pid_path(&dir);
+
+    let mut first = ProcessManager::new(binary.clone(), config.clone(), pid.clone());
+    first.start().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let mut second = ProcessManager::new(binary, config, pid.clone());
+    let result = second.start().await;
+    assert!(matches!(
+        result,
+        Err(v2ray_rs_process::ProcessError::AlreadyRunning(_))
+    ));
+    assert_eq!(second.state(), ProcessState::Stopped);
+    assert!(pid.exists());
+
+    first.stop().await.unwrap();
+}
+
 #[tokio::test]
 async fn stop_when_already_stopped() {
     let dir = setup_dir();
@@ -161,7 +316,7 @@ async fn stop_when_already_stopped() {
 #[tokio::test]
 async fn crash_detection() {
     let dir = setup_dir();
-    let binary = create_script(&dir, "backend", "#!/bin/sh\nexit 1\n");
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nsleep 1\nexit 1\n");
     let config = create_config(&dir);
 
     let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
@@ -176,3 +331,220 @@ async fn crash_detection() {
         other => panic!("expected Error state, got {other:?}"),
     }
 }
+
+#[tokio::test]
+async fn crash_restart_survives_events_during_delay() {
+    let dir = setup_dir();
+    let marker = dir.path().join("crashed");
+    let binary = create_script(
+        &dir,
+        "backend",
+        &format!(
+            "#!/bin/sh\nif [ -e {0} ]; then exec sleep 60; fi\ntouch {0}\nsleep 1\nexit 1\n",
+            marker.display()
+        ),
+    );
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.start().await.unwrap();
+
+    // Ticks far more often than the restart delay, like the UI's usage poll.
+    let mut tick = tokio::time::interval(std::time::Duration::from_millis(50));
+    let mut ticks = 0;
+    let status = loop {
+        tokio::select! {
+            _ = tick.tick() => ticks += 1,
+            status = mgr.wait_exit() => break status.unwrap(),
+        }
+    };
+    assert!(ticks > 1);
+    assert_eq!(mgr.handle_exit(status).await, Some(1));
+
+    assert_eq!(mgr.state(), ProcessState::Running);
+    mgr.shutdown().await;
+}
+
+#[tokio::test]
+async fn startup_failure_carries_log_tail() {
+    let dir = setup_dir();
+    let binary = create_script(
+        &dir,
+        "backend",
+        "#!/bin/sh\necho 'loading config' \necho 'listen tcp 127.0.0.1:1080: bind: address already in use' >&2\nexit 23\n",
+    );
+    let config = create_config(&dir);
+    let pid = pid_path(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid.clone());
+    let mut events = mgr.subscribe();
+    let result = mgr.start().await;
+
+    match result {
+        Err(ProcessError::StartupFailed { reason, log_tail }) => {
+            assert!(reason.contains("23"));
+            assert!(
+                log_tail
+                    .iter()
+                    .any(|l| l.contains("address already in use"))
+            );
+        }
+        other => panic!("expected StartupFailed, got {other:?}"),
+    }
+
+    match mgr.state() {
+        ProcessState::Error(failure) => {
+            assert!(failure.summary.contains("23"));
+            assert!(
+                failure
+                    .log_tail
+                    .iter()
+                    .any(|l| l.contains("address already in use"))
+            );
+        }
+        other => panic!("expected Error state, got {other:?}"),
+    }
+    assert!(!pid.exists());
+
+    let mut saw_event = false;
+    while let Ok(event) = events.try_recv() {
+        if let ProcessEvent::StartupFailed { log_tail, .. } = event {
+            saw_event = log_tail
+                .iter()
+                .any(|l| l.contains("address already in use"));
+        }
+    }
+    assert!(saw_event);
+}
+
+#[tokio::test]
+async fn stop_escalates_to_sigkill_after_timeout() {
+    let dir = setup_dir();
+    let binary = create_script(
+        &dir,
+        "backend",
+        "#!/bin/sh\ntrap '' TERM\nwhile true; do sleep 1; done\n",
+    );
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.set_stop_timeout(std::time::Duration::from_millis(300));
+    mgr.start().await.unwrap();
+    let mut events = mgr.subscribe();
+
+    let started = std::time::Instant::now();
+    mgr.shutdown().await;
+    let elapsed = started.elapsed();
+
+    assert_eq!(mgr.state(), ProcessState::Stopped);
+    assert!(elapsed >= std::time::Duration::from_millis(300));
+    assert!(elapsed < std::time::Duration::from_secs(3));
+
+    let mut exit_signal = None;
+    while let Ok(event) = events.try_recv() {
+        if let ProcessEvent::ProcessExited { signal, .. } = event {
+            exit_signal = signal;
+        }
+    }
+    assert_eq!(exit_signal, Some(9));
+}
+
+#[tokio::test]
+async fn external_sigterm_reports_stopped() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nexec sleep 60\n");
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.start().await.unwrap();
+    let pid: i32 = fs::read_to_string(pid_path(&dir))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    kill(Pid::from_raw(pid), Signal::SIGTERM).unwrap();
+
+    assert_eq!(mgr.wait_and_handle_exit().await, None);
+    assert_eq!(mgr.state(), ProcessState::Stopped);
+    assert!(!pid_path(&dir).exists());
+}
+
+#[tokio::test]
+async fn stop_reports_sigterm_exit() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nexec sleep 60\n");
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.start().await.unwrap();
+    let mut events = mgr.subscribe();
+
+    mgr.stop().await.unwrap();
+
+    let mut exit_signal = None;
+    while let Ok(event) = events.try_recv() {
+        if let ProcessEvent::ProcessExited { signal, .. } = event {
+            exit_signal = signal;
+        }
+    }
+    assert_eq!(exit_signal, Some(15));
+}
+
+#[tokio::test]
+async fn resource_usage_only_while_running() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    assert!(mgr.resource_usage().is_none());
+
+    mgr.start().await.unwrap();
+    let mut events = mgr.subscribe();
+    let stats = mgr.report_resource_usage().unwrap();
+    assert!(stats.rss_bytes > 0);
+    assert!(matches!(
+        events.try_recv(),
+        Ok(ProcessEvent::ResourceUsage(_))
+    ));
+
+    mgr.stop().await.unwrap();
+    assert!(mgr.resource_usage().is_none());
+}
+
+#[tokio::test]
+async fn start_reports_port_held_by_other_program() {
+    let dir = setup_dir();
+    let binary = create_script(&dir, "backend", "#!/bin/sh\nwhile true; do sleep 1; done\n");
+    let config = create_config(&dir);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut mgr = ProcessManager::new(binary, config, pid_path(&dir));
+    mgr.set_ports(vec![port]);
+    let result = mgr.start().await;
+    assert!(matches!(result, Err(ProcessError::PortInUse(p)) if p == port));
+    assert_eq!(mgr.state(), ProcessState::Stopped);
+}
+
+#[tokio::test]
+async fn start_reports_port_held_by_previous_backend() {
+    let dir = setup_dir();
+    let config = create_config(&dir);
+    let pid = pid_path(&dir);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // The test binary stands in for a backend left over from an earlier run.
+    let binary = std::env::current_exe().unwrap();
+    fs::write(&pid, std::process::id().to_string()).unwrap();
+
+    let mut mgr = ProcessManager::new(binary, config, pid);
+    mgr.set_ports(vec![port]);
+    let result = mgr.start().await;
+    assert!(matches!(
+        result,
+        Err(ProcessError::PortHeldByPrevious { port: p, pid })
+            if p == port && pid == std::process::id()
+    ));
+}
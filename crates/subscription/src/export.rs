@@ -0,0 +1,379 @@
+use std::fmt::Write;
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use v2ray_rs_core::models::{
+    ProxyNode, ShadowsocksConfig, Subscription, TlsSettings, TransportSettings, VmessConfig,
+};
+
+/// Enabled nodes packed as a standard base64 subscription body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeExport {
+    pub blob: String,
+    pub exported: usize,
+    /// Nodes left out because no share link can describe them.
+    pub skipped: usize,
+}
+
+/// Encodes the enabled nodes of enabled subscriptions, one share link per
+/// line, the way subscription servers serve them.
+pub fn export_enabled_nodes(subscriptions: &[Subscription]) -> NodeExport {
+    let mut links = Vec::new();
+    let mut skipped = 0;
+    for node in subscriptions
+        .iter()
+        .filter(|s| s.enabled)
+        .flat_map(Subscription::enabled_nodes)
+    {
+        match to_uri(node) {
+            Some(link) => links.push(link),
+            None => skipped += 1,
+        }
+    }
+    NodeExport {
+        blob: STANDARD.encode(links.join("\n")),
+        exported: links.len(),
+        skipped,
+    }
+}
+
+/// The share link for `node`, in the form [`crate::parser::parse_uri`]
+/// reads back. `None` when the link format cannot carry the node's settings:
+/// REALITY on VMess, or an HTTP/2 host list on VLESS and Trojan.
+pub fn to_uri(node: &ProxyNode) -> Option<String> {
+    match node {
+        ProxyNode::Vless(c) => {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            if let Some(encryption) = &c.encryption {
+                query.append_pair("encryption", encryption);
+            }
+            if let Some(flow) = c.flow.as_deref().filter(|f| !f.is_empty()) {
+                query.append_pair("flow", flow);
+            }
+            append_transport(&mut query, &c.transport)?;
+            append_tls(&mut query, c.tls.as_ref());
+            Some(url_link(
+                "vless",
+                &c.uuid,
+                &c.address,
+                c.port,
+                query.finish(),
+                c.remark.as_deref(),
+            ))
+        }
+        ProxyNode::Trojan(c) => {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            append_transport(&mut query, &c.transport)?;
+            match &c.tls {
+                Some(tls) => append_tls(&mut query, Some(tls)),
+                None => {
+                    query.append_pair("security", "none");
+                }
+            }
+            Some(url_link(
+                "trojan",
+                &c.password,
+                &c.address,
+                c.port,
+                query.finish(),
+                c.remark.as_deref(),
+            ))
+        }
+        ProxyNode::Vmess(c) => vmess_link(c),
+        ProxyNode::Shadowsocks(c) => Some(ss_link(c)),
+    }
+}
+
+fn url_link(
+    scheme: &str,
+    user: &str,
+    address: &str,
+    port: u16,
+    query: String,
+    remark: Option<&str>,
+) -> String {
+    let mut link = format!(
+        "{scheme}://{}@{}:{port}",
+        encode_userinfo(user),
+        url_host(address)
+    );
+    if !query.is_empty() {
+        link.push('?');
+        link.push_str(&query);
+    }
+    if let Some(remark) = remark {
+        link.push('#');
+        link.push_str(&percent_encode(remark));
+    }
+    link
+}
+
+fn append_transport(
+    query: &mut url::form_urlencoded::Serializer<String>,
+    transport: &TransportSettings,
+) -> Option<()> {
+    match transport {
+        TransportSettings::Tcp => {
+            query.append_pair("type", "tcp");
+        }
+        TransportSettings::Ws(ws) => {
+            query.append_pair("type", "ws");
+            if !ws.path.is_empty() {
+                query.append_pair("path", &ws.path);
+            }
+            if let Some(host) = &ws.host {
+                query.append_pair("host", host);
+            }
+            if !ws.headers.is_empty() {
+                query.append_pair("headers", &serde_json::to_string(&ws.headers).ok()?);
+            }
+        }
+        TransportSettings::Grpc(grpc) => {
+            query.append_pair("type", "grpc");
+            query.append_pair("serviceName", &grpc.service_name);
+            if grpc.multi_mode {
+                query.append_pair("mode", "multi");
+            }
+            if let Some(authority) = &grpc.authority {
+                query.append_pair("authority", authority);
+            }
+        }
+        TransportSettings::H2(h2) => {
+            query.append_pair("type", "h2");
+            match h2.host.as_slice() {
+                [] => {}
+                [host] => {
+                    query.append_pair("host", host);
+                }
+                _ => return None,
+            }
+            if !h2.path.is_empty() {
+                query.append_pair("path", &h2.path);
+            }
+        }
+        TransportSettings::Mkcp(kcp) => {
+            query.append_pair("type", "kcp");
+            if let Some(header_type) = &kcp.header_type {
+                query.append_pair("headerType", header_type);
+            }
+            if let Some(seed) = &kcp.seed {
+                query.append_pair("seed", seed);
+            }
+        }
+    }
+    Some(())
+}
+
+fn append_tls(query: &mut url::form_urlencoded::Serializer<String>, tls: Option<&TlsSettings>) {
+    let Some(tls) = tls else {
+        return;
+    };
+    let security = if tls.reality.is_some() {
+        "reality"
+    } else {
+        "tls"
+    };
+    query.append_pair("security", security);
+    if let Some(sni) = &tls.server_name {
+        query.append_pair("sni", sni);
+    }
+    if !tls.alpn.is_empty() {
+        query.append_pair("alpn", &tls.alpn.join(","));
+    }
+    if let Some(fp) = &tls.fingerprint {
+        query.append_pair("fp", fp);
+    }
+    if !tls.verify {
+        query.append_pair("allowInsecure", "1");
+    }
+    if let Some(reality) = &tls.reality {
+        query.append_pair("pbk", &reality.public_key);
+        if let Some(sid) = &reality.short_id {
+            query.append_pair("sid", sid);
+        }
+        if let Some(spx) = &reality.spider_x {
+            query.append_pair("spx", spx);
+        }
+    }
+}
+
+/// v2rayN's `vmess://` form: base64 of a flat JSON object, where `type` and
+/// `path` change meaning with the transport.
+fn vmess_link(c: &VmessConfig) -> Option<String> {
+    let mut json = serde_json::json!({
+        "v": "2",
+        "add": c.address,
+        "port": c.port,
+        "id": c.uuid,
+        "aid": c.alter_id,
+        "scy": c.security,
+    });
+    let (net, kind, host, path) = match &c.transport {
+        TransportSettings::Tcp => ("tcp", "none", None, ""),
+        TransportSettings::Ws(ws) => {
+            if !ws.headers.is_empty() {
+                json["headers"] = serde_json::to_value(&ws.headers).ok()?;
+            }
+            ("ws", "none", ws.host.clone(), ws.path.as_str())
+        }
+        TransportSettings::Grpc(grpc) => {
+            if let Some(authority) = &grpc.authority {
+                json["authority"] = authority.as_str().into();
+            }
+            let mode = if grpc.multi_mode { "multi" } else { "gun" };
+            ("grpc", mode, None, grpc.service_name.as_str())
+        }
+        TransportSettings::H2(h2) => (
+            "h2",
+            "none",
+            (!h2.host.is_empty()).then(|| h2.host.join(",")),
+            h2.path.as_str(),
+        ),
+        TransportSettings::Mkcp(kcp) => (
+            "kcp",
+            kcp.header_type.as_deref().unwrap_or(""),
+            None,
+            kcp.seed.as_deref().unwrap_or(""),
+        ),
+    };
+    if let Some(remark) = &c.remark {
+        json["ps"] = remark.as_str().into();
+    }
+    json["net"] = net.into();
+    json["type"] = kind.into();
+    json["path"] = path.into();
+    if let Some(host) = host {
+        json["host"] = host.into();
+    }
+
+    match &c.tls {
+        Some(tls) if tls.reality.is_some() => return None,
+        Some(tls) => {
+            json["tls"] = "tls".into();
+            json["sni"] = tls.server_name.as_deref().unwrap_or("").into();
+            json["alpn"] = tls.alpn.join(",").into();
+            json["fp"] = tls.fingerprint.as_deref().unwrap_or("").into();
+            if !tls.verify {
+                json["allowInsecure"] = true.into();
+            }
+        }
+        None => json["tls"] = "".into(),
+    }
+    Some(format!("vmess://{}", STANDARD.encode(json.to_string())))
+}
+
+/// SIP002 link. 2022 ciphers keep a percent-encoded plain userinfo, as the
+/// spec asks, since their keys are base64 already.
+fn ss_link(c: &ShadowsocksConfig) -> String {
+    let userinfo = format!("{}:{}", c.method, c.password);
+    let userinfo = if c.method.starts_with("2022-") {
+        percent_encode(&userinfo).replace("%3A", ":")
+    } else {
+        URL_SAFE_NO_PAD.encode(userinfo)
+    };
+    let mut link = format!("ss://{userinfo}@{}:{}", url_host(&c.address), c.port);
+    if let Some(remark) = &c.remark {
+        link.push('#');
+        link.push_str(&percent_encode(remark));
+    }
+    link
+}
+
+fn url_host(address: &str) -> String {
+    if address.contains(':') {
+        format!("[{address}]")
+    } else {
+        address.to_owned()
+    }
+}
+
+/// Share-link parsers keep the user part as the URL spells it, so existing
+/// escapes are left alone and only what would break the URL is escaped.
+fn encode_userinfo(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=%".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+    out
+}
+
+/// Escapes everything but RFC 3986 unreserved characters, so the result
+/// reads the same to strict percent-decoders and to form decoders.
+fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::decode_subscription_content;
+    use crate::parser::parse_uri;
+    use v2ray_rs_core::models::{RealitySettings, SubscriptionNode};
+
+    const LINKS: &[&str] = &[
+        "vless://550e8400-e29b-41d4-a716-446655440000@example.com:443?type=ws&path=%2Fws&host=cdn.example.com&security=tls&sni=example.com&alpn=h2,http/1.1&fp=chrome#Frankfurt%20%231",
+        "vless://550e8400-e29b-41d4-a716-446655440000@[2001:db8::1]:443?flow=xtls-rprx-vision&security=reality&sni=www.example.com&fp=chrome&pbk=abc123&sid=6ba8&spx=%2F#Reality",
+        "vmess://eyJ2IjoiMiIsInBzIjoiVG9reW8gJiBNb3JlIiwiYWRkIjoidm1lc3MuZXhhbXBsZS5jb20iLCJwb3J0IjoiNDQzIiwiaWQiOiI1NTBlODQwMC1lMjliLTQxZDQtYTcxNi00NDY2NTU0NDAwMDAiLCJhaWQiOiIwIiwibmV0IjoiZ3JwYyIsInBhdGgiOiJzdmMiLCJ0eXBlIjoibXVsdGkiLCJ0bHMiOiJ0bHMiLCJzbmkiOiJ2bWVzcy5leGFtcGxlLmNvbSJ9",
+        "ss://YWVzLTI1Ni1nY206c2VjcmV0@ss.example.com:8388#Plain+Node",
+        "ss://2022-blake3-aes-128-gcm:AAAAAAAAAAAAAAAAAAAAAA%3D%3D@1.2.3.4:8388#SS2022",
+        "trojan://p%40ss@trojan.example.com:443?type=kcp&headerType=wechat-video&seed=s33d&allowInsecure=1#Trojan",
+        "trojan://secret@plain.example.com:8080?security=none",
+    ];
+
+    fn node(link: &str) -> SubscriptionNode {
+        SubscriptionNode::new(parse_uri(link).unwrap())
+    }
+
+    #[test]
+    fn test_blob_decodes_to_the_same_nodes() {
+        let mut sub = Subscription::new_manual("Mine");
+        sub.nodes = LINKS.iter().map(|link| node(link)).collect();
+        let mut disabled = node(LINKS[0]);
+        disabled.enabled = false;
+        sub.nodes.push(disabled);
+        let mut off = Subscription::new_manual("Off");
+        off.enabled = false;
+        off.nodes = vec![node(LINKS[3])];
+
+        let export = export_enabled_nodes(&[sub.clone(), off]);
+        assert_eq!(export.exported, LINKS.len());
+        assert_eq!(export.skipped, 0);
+
+        let decoded: Vec<ProxyNode> = decode_subscription_content(&export.blob)
+            .iter()
+            .map(|link| parse_uri(link).unwrap())
+            .collect();
+        let expected: Vec<ProxyNode> = sub.enabled_nodes().cloned().collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_inexpressible_nodes_are_skipped() {
+        let mut reality_vmess = node(LINKS[2]);
+        if let ProxyNode::Vmess(c) = &mut reality_vmess.node {
+            c.tls.as_mut().unwrap().reality = Some(RealitySettings {
+                public_key: "abc".into(),
+                short_id: None,
+                spider_x: None,
+            });
+        }
+        assert_eq!(to_uri(&reality_vmess.node), None);
+
+        let mut sub = Subscription::new_manual("Mine");
+        sub.nodes = vec![reality_vmess, node(LINKS[3])];
+        let export = export_enabled_nodes(&[sub]);
+        assert_eq!((export.exported, export.skipped), (1, 1));
+    }
+}
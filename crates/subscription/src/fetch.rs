@@ -1,14 +1,22 @@
 use std::time::Duration;
 
 use base64::Engine;
-use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use chrono::DateTime;
 use thiserror::Error;
+use url::Url;
+use v2ray_rs_core::models::{FetchProxy, SubscriptionUsage};
+
+use crate::parser::percent_decode;
 
 pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
-pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 pub(crate) const USER_AGENT: &str = concat!("v2ray-rs/", env!("CARGO_PKG_VERSION"));
+/// Enough for a provider bouncing through a CDN or two, short enough to end
+/// a redirect loop quickly.
+pub(crate) const MAX_REDIRECTS: usize = 5;
+const USERINFO_HEADER: &str = "subscription-userinfo";
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum FetchError {
     #[error("network error: {0}")]
     NetworkError(String),
@@ -18,23 +26,162 @@ pub enum FetchError {
     FileError(String),
     #[error("request timed out")]
     Timeout,
+    #[error("too many redirects (more than {MAX_REDIRECTS})")]
+    TooManyRedirects,
+    #[error("response larger than {limit} bytes")]
+    TooLarge { limit: usize },
+    #[error("no valid proxy links in subscription content")]
+    NoValidLinks,
 }
 
-pub async fn fetch_from_url(url: &str) -> Result<String, FetchError> {
-    let client = reqwest::Client::builder()
+/// Proxies for plain and TLS fetches, and the hosts that skip them.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ProxyPlan {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+/// Resolves `setting` against `env`, the first non-empty of each lowercase
+/// and uppercase variable winning as curl does.
+pub(crate) fn proxy_plan(setting: &FetchProxy, env: impl Fn(&str) -> Option<String>) -> ProxyPlan {
+    match setting {
+        FetchProxy::Direct => ProxyPlan::default(),
+        FetchProxy::Custom { url } => ProxyPlan {
+            http: Some(url.trim().to_owned()),
+            https: Some(url.trim().to_owned()),
+            no_proxy: None,
+        },
+        FetchProxy::System => {
+            let var = |names: [&str; 2]| {
+                names
+                    .into_iter()
+                    .find_map(|name| env(name).filter(|v| !v.trim().is_empty()))
+            };
+            let all = var(["all_proxy", "ALL_PROXY"]);
+            ProxyPlan {
+                http: var(["http_proxy", "HTTP_PROXY"]).or_else(|| all.clone()),
+                https: var(["https_proxy", "HTTPS_PROXY"]).or(all),
+                no_proxy: var(["no_proxy", "NO_PROXY"]),
+            }
+        }
+    }
+}
+
+/// Client settings shared by every subscription fetch. Proxies come only
+/// from `proxy`, never from reqwest's own environment lookup.
+pub(crate) fn client_builder(proxy: &FetchProxy) -> Result<reqwest::ClientBuilder, FetchError> {
+    let mut builder = reqwest::Client::builder()
         .connect_timeout(CONNECT_TIMEOUT)
-        .timeout(REQUEST_TIMEOUT)
         .user_agent(USER_AGENT)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .no_proxy();
+
+    let plan = proxy_plan(proxy, |name| std::env::var(name).ok());
+    let no_proxy = plan
+        .no_proxy
+        .as_deref()
+        .and_then(reqwest::NoProxy::from_string);
+    let invalid = |e: reqwest::Error| FetchError::NetworkError(format!("invalid proxy: {e}"));
+    if let Some(url) = &plan.http {
+        let proxy = reqwest::Proxy::http(url).map_err(invalid)?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+    }
+    if let Some(url) = &plan.https {
+        let proxy = reqwest::Proxy::https(url).map_err(invalid)?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy));
+    }
+    Ok(builder)
+}
+
+pub async fn fetch_from_url(url: &str) -> Result<String, FetchError> {
+    let client = client_builder(&FetchProxy::Direct)?
         .build()
         .map_err(|e| FetchError::NetworkError(e.to_string()))?;
 
     fetch_with_client(&client, url).await
 }
 
+/// Basic-auth user and optional password.
+type Credentials = (String, Option<String>);
+
+/// Splits `user:pass@` off `url`, percent-decoded, so it can be sent as basic
+/// auth instead of leaking into logs and error messages with the URL.
+fn split_credentials(url: &str) -> Result<(Url, Option<Credentials>), FetchError> {
+    let mut url =
+        Url::parse(url).map_err(|e| FetchError::NetworkError(format!("invalid URL: {e}")))?;
+    if url.username().is_empty() && url.password().is_none() {
+        return Ok((url, None));
+    }
+    let user = percent_decode(url.username());
+    let password = url.password().map(percent_decode);
+    // Only fails for URLs that cannot carry credentials in the first place.
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    Ok((url, Some((user, password))))
+}
+
+/// Subscription body along with the usage info from its response headers.
+#[derive(Debug, Clone)]
+pub struct FetchedContent {
+    pub body: String,
+    pub usage: Option<SubscriptionUsage>,
+}
+
 pub async fn fetch_with_client(client: &reqwest::Client, url: &str) -> Result<String, FetchError> {
-    let response = client.get(url).send().await.map_err(|e| {
+    fetch_content(client, url).await.map(|fetched| fetched.body)
+}
+
+/// Bounds on a single download, so a stalled or endless endpoint fails
+/// instead of holding up a refresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchLimits {
+    /// For the whole exchange, from connecting to the last byte.
+    pub timeout: Duration,
+    pub max_bytes: usize,
+}
+
+impl Default for FetchLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+pub async fn fetch_content(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<FetchedContent, FetchError> {
+    fetch_content_with_limits(client, url, FetchLimits::default()).await
+}
+
+pub async fn fetch_content_with_limits(
+    client: &reqwest::Client,
+    url: &str,
+    limits: FetchLimits,
+) -> Result<FetchedContent, FetchError> {
+    tokio::time::timeout(limits.timeout, fetch_limited(client, url, limits.max_bytes))
+        .await
+        .map_err(|_| FetchError::Timeout)?
+}
+
+async fn fetch_limited(
+    client: &reqwest::Client,
+    url: &str,
+    max_bytes: usize,
+) -> Result<FetchedContent, FetchError> {
+    let (url, credentials) = split_credentials(url)?;
+    let mut request = client.get(url);
+    if let Some((user, password)) = credentials {
+        request = request.basic_auth(user, password);
+    }
+    let response = request.send().await.map_err(|e| {
         if e.is_timeout() {
             FetchError::Timeout
+        } else if e.is_redirect() {
+            FetchError::TooManyRedirects
         } else {
             FetchError::NetworkError(e.to_string())
         }
@@ -42,45 +189,409 @@ pub async fn fetch_with_client(client: &reqwest::Client, url: &str) -> Result<St
 
     let status = response.status();
     if !status.is_success() {
-        let body = response.text().await.unwrap_or_default();
+        let body = read_body(response, max_bytes).await.unwrap_or_default();
         return Err(FetchError::HttpError {
             status: status.as_u16(),
             body,
         });
     }
 
-    response
-        .text()
+    let usage = response
+        .headers()
+        .get(USERINFO_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_subscription_userinfo);
+    let body = read_body(response, max_bytes).await?;
+    Ok(FetchedContent { body, usage })
+}
+
+/// Reads the body, giving up as soon as it is known to exceed `max_bytes`
+/// rather than buffering all of it first.
+async fn read_body(
+    mut response: reqwest::Response,
+    max_bytes: usize,
+) -> Result<String, FetchError> {
+    let too_large = FetchError::TooLarge { limit: max_bytes };
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(too_large);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
         .await
-        .map_err(|e| FetchError::NetworkError(e.to_string()))
+        .map_err(|e| FetchError::NetworkError(e.to_string()))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Parses `upload=…; download=…; total=…; expire=…` as sent by most panels.
+/// A zero `total` or `expire` means unlimited.
+pub fn parse_subscription_userinfo(header: &str) -> Option<SubscriptionUsage> {
+    let mut usage = SubscriptionUsage {
+        upload: 0,
+        download: 0,
+        total: None,
+        expire: None,
+    };
+    let mut recognized = false;
+    for pair in header.split(';') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        // Some panels send floats such as "1.073741824e+10".
+        let Some(value) = value.trim().parse::<f64>().ok().filter(|v| *v >= 0.0) else {
+            continue;
+        };
+        let value = value as u64;
+        match key.trim().to_ascii_lowercase().as_str() {
+            "upload" => usage.upload = value,
+            "download" => usage.download = value,
+            "total" => usage.total = (value > 0).then_some(value),
+            "expire" => {
+                usage.expire = (value > 0)
+                    .then(|| DateTime::from_timestamp(value as i64, 0))
+                    .flatten()
+            }
+            _ => continue,
+        }
+        recognized = true;
+    }
+    recognized.then_some(usage)
 }
 
 pub fn fetch_from_file(path: &str) -> Result<String, FetchError> {
     std::fs::read_to_string(path).map_err(|e| FetchError::FileError(e.to_string()))
 }
 
-pub fn decode_subscription_content(raw: &str) -> Vec<String> {
-    let trimmed = raw.trim();
+const BOM: char = '\u{feff}';
 
-    let decoded = STANDARD
-        .decode(trimmed)
-        .or_else(|_| URL_SAFE_NO_PAD.decode(trimmed));
+pub fn decode_subscription_content(raw: &str) -> Vec<String> {
+    let trimmed = raw.trim_start_matches(BOM).trim();
 
-    let text = match decoded {
-        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
-        Err(_) => trimmed.to_owned(),
-    };
+    let text = decode_base64_body(trimmed)
+        .filter(|decoded| looks_like_uri_list(decoded))
+        .unwrap_or_else(|| trimmed.to_owned());
 
-    text.lines()
+    text.trim_start_matches(BOM)
+        .lines()
         .map(|l| l.trim().to_owned())
         .filter(|l| !l.is_empty())
         .collect()
 }
 
+fn decode_base64_body(body: &str) -> Option<String> {
+    let compact: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.is_empty() {
+        return None;
+    }
+    let bytes = [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(&compact).ok())?;
+    String::from_utf8(bytes).ok()
+}
+
+fn looks_like_uri_list(text: &str) -> bool {
+    let mut has_uri = false;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.chars().any(|c| c.is_control() && c != '\t') {
+            return false;
+        }
+        if has_scheme(line) {
+            has_uri = true;
+        }
+    }
+    has_uri
+}
+
+fn has_scheme(line: &str) -> bool {
+    line.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Serves `respond(path, lowercased request head)` on a local port;
+    /// returns the base URL.
+    async fn serve(respond: fn(&str, &str) -> String) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let path = head.split(' ').nth(1).unwrap_or("/").to_owned();
+                let response = respond(&path, &head);
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    fn reply(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn client() -> reqwest::Client {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        client_builder(&FetchProxy::Direct)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_proxy_plan_per_mode() {
+        let env = |name: &str| match name {
+            "HTTPS_PROXY" => Some("http://secure.corp:3128".to_owned()),
+            "http_proxy" => Some(" ".to_owned()),
+            "HTTP_PROXY" => Some("http://plain.corp:3128".to_owned()),
+            "ALL_PROXY" => Some("http://all.corp:3128".to_owned()),
+            "no_proxy" => Some("localhost,.internal".to_owned()),
+            _ => None,
+        };
+
+        assert_eq!(proxy_plan(&FetchProxy::Direct, env), ProxyPlan::default());
+        assert_eq!(
+            proxy_plan(&FetchProxy::System, env),
+            ProxyPlan {
+                http: Some("http://plain.corp:3128".into()),
+                https: Some("http://secure.corp:3128".into()),
+                no_proxy: Some("localhost,.internal".into()),
+            }
+        );
+        assert_eq!(
+            proxy_plan(&FetchProxy::System, |name| {
+                (name == "all_proxy").then(|| "http://all.corp:3128".to_owned())
+            }),
+            ProxyPlan {
+                http: Some("http://all.corp:3128".into()),
+                https: Some("http://all.corp:3128".into()),
+                no_proxy: None,
+            }
+        );
+        let custom = FetchProxy::Custom {
+            url: "http://mine:8080 ".into(),
+        };
+        assert_eq!(
+            proxy_plan(&custom, env),
+            ProxyPlan {
+                http: Some("http://mine:8080".into()),
+                https: Some("http://mine:8080".into()),
+                no_proxy: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_proxy_receives_fetch() {
+        // A proxy sees the absolute URL in the request line.
+        let proxy = serve(|path, _| {
+            if path == "http://sub.invalid/list" {
+                reply("200 OK", "", "via proxy")
+            } else {
+                reply("400 Bad Request", "", "")
+            }
+        })
+        .await;
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = client_builder(&FetchProxy::Custom { url: proxy })
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let body = fetch_with_client(&client, "http://sub.invalid/list")
+            .await
+            .unwrap();
+        assert_eq!(body, "via proxy");
+
+        let err = client_builder(&FetchProxy::Custom {
+            url: "http://[::1".into(),
+        })
+        .unwrap_err();
+        assert!(matches!(err, FetchError::NetworkError(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_redirect_chain() {
+        let base = serve(|path, _| match path {
+            "/sub" => reply("302 Found", "Location: /cdn/1\r\n", ""),
+            "/cdn/1" => reply("301 Moved Permanently", "Location: /cdn/2\r\n", ""),
+            "/cdn/2" => reply("200 OK", "", "trojan://pw@example.com:443#T\n"),
+            "/loop" => reply("302 Found", "Location: /loop\r\n", ""),
+            _ => reply("404 Not Found", "", ""),
+        })
+        .await;
+        let client = client();
+
+        let body = fetch_with_client(&client, &format!("{base}/sub"))
+            .await
+            .unwrap();
+        assert_eq!(body, "trojan://pw@example.com:443#T\n");
+
+        let err = fetch_with_client(&client, &format!("{base}/loop"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::TooManyRedirects), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sends_url_credentials_as_basic_auth() {
+        let base = serve(|_, head| {
+            if head.contains("\r\nauthorization: basic dxnlcjpwqhnz\r\n") {
+                reply("200 OK", "", "ok")
+            } else {
+                reply("401 Unauthorized", "", "login required")
+            }
+        })
+        .await;
+        let client = client();
+
+        let with_auth = base.replace("http://", "http://user:p%40ss@");
+        let body = fetch_with_client(&client, &format!("{with_auth}/sub"))
+            .await
+            .unwrap();
+        assert_eq!(body, "ok");
+
+        let err = fetch_with_client(&client, &format!("{base}/sub"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::HttpError { status: 401, .. }));
+    }
+
+    /// Sends headers without a length, then `body` in 1 KiB chunks, then
+    /// holds the connection open for `stall`.
+    async fn serve_stream(body: usize, stall: Duration) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let _ = stream.read(&mut buf).await;
+                    let head = "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n";
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    for _ in 0..body / 1024 {
+                        if stream.write_all(&[b'a'; 1024]).await.is_err() {
+                            return;
+                        }
+                    }
+                    tokio::time::sleep(stall).await;
+                });
+            }
+        });
+        format!("http://{addr}/sub")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_aborts_past_size_limit() {
+        let limits = FetchLimits {
+            max_bytes: 4096,
+            ..FetchLimits::default()
+        };
+        let client = client();
+
+        let url = serve_stream(64 * 1024, Duration::ZERO).await;
+        let err = fetch_content_with_limits(&client, &url, limits)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, FetchError::TooLarge { limit: 4096 }),
+            "{err:?}"
+        );
+
+        let big = "a".repeat(8192);
+        let declared = serve(|_, _| reply("200 OK", "", &"a".repeat(8192))).await;
+        let err = fetch_content_with_limits(&client, &format!("{declared}/sub"), limits)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::TooLarge { .. }), "{err:?}");
+
+        let exact = FetchLimits {
+            max_bytes: big.len(),
+            ..limits
+        };
+        let fetched = fetch_content_with_limits(&client, &format!("{declared}/sub"), exact)
+            .await
+            .unwrap();
+        assert_eq!(fetched.body, big);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_times_out_on_stalled_body() {
+        let limits = FetchLimits {
+            timeout: Duration::from_millis(300),
+            ..FetchLimits::default()
+        };
+        let url = serve_stream(1024, Duration::from_secs(30)).await;
+        let started = std::time::Instant::now();
+        let err = fetch_content_with_limits(&client(), &url, limits)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::Timeout), "{err:?}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_split_credentials_strips_userinfo() {
+        let (url, credentials) = split_credentials("https://a%3Ab:c@host.example/sub").unwrap();
+        assert_eq!(url.as_str(), "https://host.example/sub");
+        assert_eq!(credentials, Some(("a:b".to_owned(), Some("c".to_owned()))));
+
+        let (url, credentials) = split_credentials("https://host.example/sub").unwrap();
+        assert_eq!(url.as_str(), "https://host.example/sub");
+        assert_eq!(credentials, None);
+    }
+
+    #[test]
+    fn test_parse_subscription_userinfo() {
+        let usage = parse_subscription_userinfo(
+            "upload=455727941; download=6174315083; total=1073741824000; expire=1700000000",
+        )
+        .unwrap();
+        assert_eq!(usage.upload, 455727941);
+        assert_eq!(usage.download, 6174315083);
+        assert_eq!(usage.used(), 455727941 + 6174315083);
+        assert_eq!(usage.total, Some(1073741824000));
+        assert_eq!(usage.expire.unwrap().timestamp(), 1700000000);
+    }
+
+    #[test]
+    fn test_parse_subscription_userinfo_unlimited() {
+        let usage =
+            parse_subscription_userinfo("upload=0;download=1.5e3;total=0;expire=0").unwrap();
+        assert_eq!(usage.download, 1500);
+        assert_eq!(usage.total, None);
+        assert_eq!(usage.expire, None);
+    }
+
+    #[test]
+    fn test_parse_subscription_userinfo_garbage() {
+        assert!(parse_subscription_userinfo("").is_none());
+        assert!(parse_subscription_userinfo("foo=bar; baz").is_none());
+    }
+
     #[test]
     fn test_decode_base64_content() {
         let uris = "vmess://example1\nvless://example2\nss://example3";
@@ -119,6 +630,51 @@ mod tests {
         assert_eq!(plain_result, vec!["vmess://a", "vless://b", "ss://c"]);
     }
 
+    #[test]
+    fn test_decode_single_plain_uri() {
+        let result = decode_subscription_content("trojan://pass@host.example.com:443#Node\n");
+        assert_eq!(result, vec!["trojan://pass@host.example.com:443#Node"]);
+    }
+
+    #[test]
+    fn test_decode_strips_bom_and_crlf() {
+        let plain = "\u{feff}vmess://a\r\nvless://b\r\n";
+        assert_eq!(
+            decode_subscription_content(plain),
+            vec!["vmess://a", "vless://b"]
+        );
+
+        let encoded = format!("\u{feff}{}", STANDARD.encode("vmess://a\r\nvless://b\r\n"));
+        assert_eq!(
+            decode_subscription_content(&encoded),
+            vec!["vmess://a", "vless://b"]
+        );
+    }
+
+    #[test]
+    fn test_decode_wrapped_base64_blob() {
+        let encoded = STANDARD.encode("vmess://example1\nvless://example2\nss://example3");
+        let (head, tail) = encoded.split_at(20);
+        let wrapped = format!("{head}\r\n{tail}\r\n");
+
+        assert_eq!(
+            decode_subscription_content(&wrapped),
+            vec!["vmess://example1", "vless://example2", "ss://example3"]
+        );
+    }
+
+    #[test]
+    fn test_decode_base64_garbage_falls_back_to_plain() {
+        // Valid base64 alphabet, but decodes to bytes that are not a URI list.
+        assert_eq!(decode_subscription_content("abcd"), vec!["abcd"]);
+
+        let not_uris = STANDARD.encode("just some text\nwithout links");
+        assert_eq!(
+            decode_subscription_content(&not_uris),
+            vec![not_uris.clone()]
+        );
+    }
+
     #[test]
     fn test_fetch_from_file() {
         let dir = tempfile::tempdir().unwrap();
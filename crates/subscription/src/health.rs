@@ -0,0 +1,239 @@
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+
+use v2ray_rs_core::config::{ConfigError, LISTEN_ADDRESS, generator_for};
+use v2ray_rs_core::models::{AppSettings, ProxyNode};
+use v2ray_rs_process::{ProcessError, ProcessManager};
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const PROXY_READY_TIMEOUT: Duration = Duration::from_secs(5);
+const PROXY_READY_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum HealthError {
+    #[error("generate config: {0}")]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Process(#[from] ProcessError),
+    #[error("backend did not open its proxy port")]
+    NotReady,
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected status {0}")]
+    UnexpectedStatus(u16),
+}
+
+/// Config routing everything through `node`, with both inbounds on the given
+/// ports so it can run next to the main instance.
+pub fn single_node_config(
+    node: &ProxyNode,
+    settings: &AppSettings,
+    socks_port: u16,
+    http_port: u16,
+) -> Result<serde_json::Value, ConfigError> {
+    let settings = AppSettings {
+        socks_port,
+        http_port,
+        enable_socks: true,
+        enable_http: true,
+        ..settings.clone()
+    };
+    generator_for(settings.backend.backend_type).generate(
+        std::slice::from_ref(node),
+        &[],
+        &settings,
+        None,
+    )
+}
+
+/// Requests `url` through the HTTP proxy on `http_port` and expects `expected_status` back.
+pub async fn check_through_proxy(
+    http_port: u16,
+    url: &str,
+    expected_status: u16,
+) -> Result<Duration, HealthError> {
+    let proxy = reqwest::Proxy::all(format!("http://{LISTEN_ADDRESS}:{http_port}"))?;
+    let client = reqwest::Client::builder()
+        .proxy(proxy)
+        .timeout(HEALTH_CHECK_TIMEOUT)
+        .build()?;
+
+    let start = Instant::now();
+    let response = client.get(url).send().await?;
+    let status = response.status().as_u16();
+    if status != expected_status {
+        return Err(HealthError::UnexpectedStatus(status));
+    }
+    Ok(start.elapsed())
+}
+
+/// Runs `node` alone in a throwaway backend instance and checks that traffic
+/// actually gets through it. `work_dir` holds the temporary config and pid file.
+pub async fn test_node(
+    node: &ProxyNode,
+    settings: &AppSettings,
+    binary: PathBuf,
+    work_dir: &Path,
+) -> Result<Duration, HealthError> {
+    let (socks_port, http_port) = (free_port()?, free_port()?);
+    let config = single_node_config(node, settings, socks_port, http_port)?;
+
+    std::fs::create_dir_all(work_dir)?;
+    let config_path = work_dir.join("node-test.json");
+    std::fs::write(
+        &config_path,
+        serde_json::to_vec_pretty(&config).map_err(ConfigError::from)?,
+    )?;
+
+    let mut mgr = ProcessManager::new(binary, config_path.clone(), work_dir.join("node-test.pid"));
+    mgr.set_auto_restart(false);
+    let result = match mgr.start().await {
+        Ok(()) => match wait_for_port(http_port).await {
+            Ok(()) => {
+                check_through_proxy(
+                    http_port,
+                    &settings.health_check_url,
+                    settings.health_check_expected_status,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+    mgr.shutdown().await;
+    let _ = std::fs::remove_file(&config_path);
+    result
+}
+
+fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind((LISTEN_ADDRESS, 0))?.local_addr()?.port())
+}
+
+async fn wait_for_port(port: u16) -> Result<(), HealthError> {
+    let deadline = Instant::now() + PROXY_READY_TIMEOUT;
+    while Instant::now() < deadline {
+        if TcpStream::connect((LISTEN_ADDRESS, port)).await.is_ok() {
+            return Ok(());
+        }
+        sleep(PROXY_READY_POLL).await;
+    }
+    Err(HealthError::NotReady)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v2ray_rs_core::models::{
+        BackendType, DEFAULT_HEALTH_CHECK_STATUS, DEFAULT_HEALTH_CHECK_URL, TransportSettings,
+        TrojanConfig,
+    };
+
+    fn trojan_node() -> ProxyNode {
+        ProxyNode::Trojan(TrojanConfig {
+            address: "trojan.example.com".into(),
+            port: 443,
+            password: "secret".into(),
+            transport: TransportSettings::Tcp,
+            tls: None,
+            remark: Some("T".into()),
+        })
+    }
+
+    /// Minimal HTTP proxy stand-in that answers every request with `status`.
+    async fn fake_proxy(status: &'static str) -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let response =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_single_node_config_uses_given_ports() {
+        let mut settings = AppSettings::default();
+        settings.backend.backend_type = BackendType::V2ray;
+        let config = single_node_config(&trojan_node(), &settings, 20001, 20002).unwrap();
+
+        let inbounds = config["inbounds"].as_array().unwrap();
+        assert_eq!(inbounds[0]["port"], 20001);
+        assert_eq!(inbounds[1]["port"], 20002);
+        let proxies: Vec<_> = config["outbounds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|o| o["protocol"] == "trojan")
+            .collect();
+        assert_eq!(proxies.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_through_proxy_passes_on_expected_status() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let port = fake_proxy("204 No Content").await;
+        assert!(
+            check_through_proxy(port, DEFAULT_HEALTH_CHECK_URL, DEFAULT_HEALTH_CHECK_STATUS)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_through_proxy_custom_expected_status() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let url = "http://example.com/health";
+
+        let ok = fake_proxy("200 OK").await;
+        assert!(check_through_proxy(ok, url, 200).await.is_ok());
+
+        let missing = fake_proxy("404 Not Found").await;
+        assert!(matches!(
+            check_through_proxy(missing, url, 200).await,
+            Err(HealthError::UnexpectedStatus(404))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_through_proxy_rejects_other_status() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let port = fake_proxy("403 Forbidden").await;
+        assert!(matches!(
+            check_through_proxy(port, DEFAULT_HEALTH_CHECK_URL, DEFAULT_HEALTH_CHECK_STATUS).await,
+            Err(HealthError::UnexpectedStatus(403))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_node_without_binary_fails() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let result = test_node(
+            &trojan_node(),
+            &AppSettings::default(),
+            dir.path().join("missing-backend"),
+            dir.path(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(HealthError::Process(ProcessError::BinaryNotFound(_)))
+        ));
+        assert!(!dir.path().join("node-test.json").exists());
+    }
+}
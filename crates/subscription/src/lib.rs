@@ -1,5 +1,8 @@
+pub mod export;
 pub mod fetch;
+pub mod health;
 pub mod manager;
 pub mod parser;
 pub mod ping;
+pub mod qr;
 pub mod update;
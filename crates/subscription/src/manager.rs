@@ -1,10 +1,11 @@
 use thiserror::Error;
 use uuid::Uuid;
-use v2ray_rs_core::models::Subscription;
+use v2ray_rs_core::models::{FetchProxy, Subscription, SubscriptionSource};
 use v2ray_rs_core::persistence::{self, AppPaths, PersistenceError};
 
-use crate::fetch::{CONNECT_TIMEOUT, FetchError, REQUEST_TIMEOUT, USER_AGENT};
-use crate::update::{self, UpdateResult};
+use crate::fetch::{FetchError, FetchLimits, client_builder};
+use crate::parser::ParseOptions;
+use crate::update::{self, DEFAULT_MAX_RETRIES, FetchedNodes, UpdateResult};
 
 #[derive(Debug, Error)]
 pub enum SubscriptionError {
@@ -16,22 +17,122 @@ pub enum SubscriptionError {
     Storage(#[from] PersistenceError),
 }
 
+/// What kind of problem stopped a refresh, which decides the remedy offered:
+/// retrying for `Network` and `Timeout`, fixing the URL for `Parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Network,
+    Timeout,
+    /// The source answered, but with no usable subscription, or refused the
+    /// URL outright (an HTTP client error).
+    Parse,
+    /// Local files: a file subscription's source or the app's own storage.
+    Io,
+}
+
+impl FailureKind {
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Network | Self::Timeout)
+    }
+}
+
+impl SubscriptionError {
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            Self::Fetch(FetchError::Timeout) => FailureKind::Timeout,
+            // 408 and 429 are the server asking to try again later.
+            Self::Fetch(FetchError::HttpError { status, .. })
+                if (400..500).contains(status) && !matches!(status, 408 | 429) =>
+            {
+                FailureKind::Parse
+            }
+            Self::Fetch(
+                FetchError::NetworkError(_)
+                | FetchError::HttpError { .. }
+                | FetchError::TooManyRedirects,
+            ) => FailureKind::Network,
+            Self::Fetch(FetchError::NoValidLinks | FetchError::TooLarge { .. }) => {
+                FailureKind::Parse
+            }
+            Self::Fetch(FetchError::FileError(_)) | Self::Storage(_) | Self::NotFound(_) => {
+                FailureKind::Io
+            }
+        }
+    }
+}
+
+/// How a newly added subscription's first refresh went, for telling the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Imported(usize),
+    /// The source answered, but nothing in it parsed as a node.
+    NoNodes,
+    Failed(String),
+}
+
+pub fn import_outcome(result: Result<&Subscription, &SubscriptionError>) -> ImportOutcome {
+    match result {
+        Ok(sub) if sub.nodes.is_empty() => ImportOutcome::NoNodes,
+        Ok(sub) => ImportOutcome::Imported(sub.nodes.len()),
+        Err(SubscriptionError::Fetch(FetchError::NoValidLinks)) => ImportOutcome::NoNodes,
+        Err(e) => ImportOutcome::Failed(e.to_string()),
+    }
+}
+
+fn build_client(proxy: &FetchProxy) -> Result<reqwest::Client, FetchError> {
+    client_builder(proxy)?
+        .build()
+        .map_err(|e| FetchError::NetworkError(e.to_string()))
+}
+
 #[derive(Clone)]
 pub struct SubscriptionService {
     client: reqwest::Client,
+    /// Why the chosen fetch proxy is unusable; URL fetches fail with it.
+    proxy_error: Option<FetchError>,
     paths: AppPaths,
+    fetch_limits: FetchLimits,
+    parse_options: ParseOptions,
 }
 
 impl SubscriptionService {
     pub fn new(paths: AppPaths) -> Self {
-        let client = reqwest::Client::builder()
-            .connect_timeout(CONNECT_TIMEOUT)
-            .timeout(REQUEST_TIMEOUT)
-            .user_agent(USER_AGENT)
-            .build()
+        let client = build_client(&FetchProxy::Direct)
             .expect("reqwest HTTP client build failed — is TLS available on this system?");
 
-        Self { client, paths }
+        Self {
+            client,
+            proxy_error: None,
+            paths,
+            fetch_limits: FetchLimits::default(),
+            parse_options: ParseOptions::default(),
+        }
+    }
+
+    /// Rebuilds the client to fetch through `proxy`. If it cannot be used,
+    /// URL fetches fail with the returned error until a usable one is set.
+    pub fn set_fetch_proxy(&mut self, proxy: &FetchProxy) -> Result<(), FetchError> {
+        match build_client(proxy) {
+            Ok(client) => {
+                self.client = client;
+                self.proxy_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.proxy_error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn with_fetch_limits(mut self, fetch_limits: FetchLimits) -> Self {
+        self.fetch_limits = fetch_limits;
+        self
+    }
+
+    pub fn with_parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
     }
 
     pub async fn add_and_fetch(
@@ -39,68 +140,351 @@ impl SubscriptionService {
         name: String,
         url: String,
     ) -> Result<Subscription, SubscriptionError> {
-        let mut sub = Subscription::new_from_url(name, url);
-        persistence::add_subscription(&self.paths, sub.clone())?;
+        let sub = Subscription::new_from_url(name, url);
+        let id = sub.id;
+        persistence::add_subscription(&self.paths, sub)?;
 
-        match update::update_subscription(&self.client, &mut sub).await {
-            Ok(_) => {
-                persistence::update_subscription(&self.paths, sub.clone())?;
-            }
-            Err(e) => {
-                log::warn!("initial fetch failed for {}: {e}", sub.id);
+        match self.refresh(id).await {
+            Ok((sub, _)) => Ok(sub),
+            Err(SubscriptionError::Fetch(e)) => {
+                log::warn!("initial fetch failed for {id}: {e}");
+                persistence::get_subscription(&self.paths, &id)?
+                    .ok_or(SubscriptionError::NotFound(id))
             }
+            Err(e) => Err(e),
         }
-
-        Ok(sub)
     }
 
     pub async fn refresh(
         &self,
         id: Uuid,
     ) -> Result<(Subscription, UpdateResult), SubscriptionError> {
-        let mut sub = persistence::get_subscription(&self.paths, &id)?
-            .ok_or(SubscriptionError::NotFound(id))?;
-
-        let result = update::update_subscription(&self.client, &mut sub).await?;
-        persistence::update_subscription(&self.paths, sub.clone())?;
-
-        Ok((sub, result))
+        let fetched = self.fetch(id).await?;
+        self.apply(id, fetched)
     }
 
-    pub async fn refresh_all_overdue(
+    async fn fetch(
         &self,
-        global_interval_secs: u64,
-    ) -> Vec<(Uuid, Result<UpdateResult, SubscriptionError>)> {
-        let subs = match persistence::load_subscriptions(&self.paths) {
-            Ok(subs) => subs,
-            Err(e) => {
-                log::error!("failed to load subscriptions: {e}");
-                return vec![];
-            }
-        };
+        id: Uuid,
+    ) -> Result<Result<Option<FetchedNodes>, FetchError>, SubscriptionError> {
+        let sub = persistence::get_subscription(&self.paths, &id)?
+            .ok_or(SubscriptionError::NotFound(id))?;
+        if let (SubscriptionSource::Url { .. }, Some(e)) = (&sub.source, &self.proxy_error) {
+            return Ok(Err(e.clone()));
+        }
+        Ok(update::fetch_nodes(
+            &self.client,
+            &sub,
+            DEFAULT_MAX_RETRIES,
+            self.fetch_limits,
+            self.parse_options,
+        )
+        .await)
+    }
 
-        let now = chrono::Utc::now();
-        let mut results = Vec::new();
+    /// Merges a finished fetch into the subscription as stored now rather than
+    /// the copy read before fetching, so edits made meanwhile are kept.
+    fn apply(
+        &self,
+        id: Uuid,
+        fetched: Result<Option<FetchedNodes>, FetchError>,
+    ) -> Result<(Subscription, UpdateResult), SubscriptionError> {
+        let (sub, result) = persistence::with_subscriptions(&self.paths, |subs| {
+            let sub = subs.iter_mut().find(|s| s.id == id)?;
+            let result = update::apply_fetched(sub, fetched);
+            Some((sub.clone(), result))
+        })?
+        .ok_or(SubscriptionError::NotFound(id))?;
+        Ok((sub, result?))
+    }
+}
 
-        for sub in subs.iter().filter(|s| s.enabled) {
+/// Enabled fetched subscriptions due for an automatic refresh at `now`,
+/// each by its own interval or else `global_interval_secs`.
+pub fn overdue_subscriptions(
+    subs: &[Subscription],
+    global_interval_secs: u64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<Uuid> {
+    subs.iter()
+        .filter(|s| s.enabled && s.source != SubscriptionSource::Manual)
+        .filter(|sub| {
             let interval = sub
                 .auto_update_interval_secs
                 .unwrap_or(global_interval_secs);
-
-            let overdue = match sub.last_updated {
-                Some(last) => {
-                    let elapsed = (now - last).num_seconds().max(0) as u64;
-                    elapsed >= interval
-                }
+            match sub.last_updated {
+                Some(last) => (now - last).num_seconds().max(0) as u64 >= interval,
                 None => true,
-            };
+            }
+        })
+        .map(|sub| sub.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn service_with_file(nodes: &str) -> (TempDir, SubscriptionService, Subscription) {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("nodes.txt");
+        std::fs::write(&file, nodes).unwrap();
+        let paths = AppPaths::from_paths(dir.path().join("config"), dir.path().join("data"));
+        let sub = Subscription::new_from_file("Local", file.to_string_lossy().into_owned());
+        persistence::add_subscription(&paths, sub.clone()).unwrap();
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        (dir, SubscriptionService::new(paths), sub)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_edits_made_during_fetch() {
+        let (_dir, svc, sub) = service_with_file(
+            "trojan://secret@a.example.com:443#A\nvless://uuid@b.example.com:443#B\n",
+        );
+        svc.refresh(sub.id).await.unwrap();
+
+        let fetched = svc.fetch(sub.id).await.unwrap();
+        // The user disables a node and renames the subscription mid-fetch.
+        persistence::edit_subscription(&svc.paths, &sub.id, |s| {
+            s.name = "Renamed".into();
+            s.nodes[0].enabled = false;
+        })
+        .unwrap();
+        let (returned, result) = svc.apply(sub.id, fetched).unwrap();
+
+        assert_eq!(result.unchanged, 2);
+        let stored = persistence::get_subscription(&svc.paths, &sub.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, returned);
+        assert_eq!(stored.name, "Renamed");
+        assert!(!stored.nodes[0].enabled);
+        assert!(stored.nodes[1].enabled);
+    }
 
-            if overdue {
-                let result = self.refresh(sub.id).await.map(|(_, r)| r);
-                results.push((sub.id, result));
+    #[tokio::test]
+    async fn test_cancelled_refresh_leaves_subscription_unchanged() {
+        let (_dir, svc, _) = service_with_file("trojan://secret@a.example.com:443#A\n");
+        // A server that accepts and then never answers, like a hung panel.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sub", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
             }
-        }
+        });
+        let sub = Subscription::new_from_url("Hung", url);
+        persistence::add_subscription(&svc.paths, sub.clone()).unwrap();
+
+        let task = tokio::spawn({
+            let svc = svc.clone();
+            async move { svc.refresh(sub.id).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!task.is_finished());
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+        server.abort();
+
+        let stored = persistence::get_subscription(&svc.paths, &sub.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, sub);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_fetch_applies_parse_options() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (_dir, svc, _) = service_with_file("");
+        let svc = svc.with_parse_options(ParseOptions { strict_uuid: true });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sub", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let body =
+                "vless://not-a-uuid@a.example.com:443#A\ntrojan://secret@b.example.com:443#B\n";
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.read(&mut [0u8; 4096]).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let sub = svc.add_and_fetch("Strict".into(), url).await.unwrap();
+
+        assert_eq!(sub.nodes.len(), 1);
+        assert_eq!(sub.nodes[0].node.protocol(), "trojan");
+        assert!(sub.last_updated.is_some());
+        let stored = persistence::get_subscription(&svc.paths, &sub.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored, sub);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_applies_fetch_limits() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (_dir, svc, _) = service_with_file("");
+        let svc = svc.with_fetch_limits(FetchLimits {
+            max_bytes: 16,
+            ..FetchLimits::default()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sub", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let body = "trojan://secret@a.example.com:443#A\n";
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.read(&mut [0u8; 4096]).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        let sub = Subscription::new_from_url("Large", url);
+        persistence::add_subscription(&svc.paths, sub.clone()).unwrap();
+
+        let fetched = svc.fetch(sub.id).await.unwrap();
+        assert!(matches!(fetched, Err(FetchError::TooLarge { limit: 16 })));
+    }
+
+    #[tokio::test]
+    async fn test_unusable_fetch_proxy_fails_url_fetches() {
+        let (_dir, mut svc, file_sub) = service_with_file("trojan://secret@a.example.com:443#A\n");
+        let sub = Subscription::new_from_url("Remote", "http://127.0.0.1:9/sub");
+        persistence::add_subscription(&svc.paths, sub.clone()).unwrap();
+
+        let bad = FetchProxy::Custom {
+            url: "http://[::1".into(),
+        };
+        assert!(svc.set_fetch_proxy(&bad).is_err());
+        let fetched = svc.fetch(sub.id).await.unwrap();
+        let err = fetched.unwrap_err();
+        assert!(err.to_string().contains("invalid proxy"), "{err}");
+        // Local files never went through the proxy.
+        assert!(svc.fetch(file_sub.id).await.unwrap().is_ok());
+
+        svc.set_fetch_proxy(&FetchProxy::Direct).unwrap();
+        assert!(svc.proxy_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_of_subscription_deleted_during_fetch() {
+        let (_dir, svc, sub) = service_with_file("trojan://secret@a.example.com:443#A\n");
+        let fetched = svc.fetch(sub.id).await.unwrap();
+        persistence::remove_subscription(&svc.paths, &sub.id).unwrap();
+
+        let err = svc.apply(sub.id, fetched).unwrap_err();
+        assert!(matches!(err, SubscriptionError::NotFound(id) if id == sub.id));
+        assert!(
+            persistence::load_subscriptions(&svc.paths)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_without_nodes_is_reported() {
+        let (_dir, svc, sub) = service_with_file("<html>not a subscription</html>\n");
+        let result = svc.refresh(sub.id).await;
+        assert_eq!(
+            import_outcome(result.as_ref().map(|(s, _)| s)),
+            ImportOutcome::NoNodes
+        );
+        let stored = persistence::get_subscription(&svc.paths, &sub.id)
+            .unwrap()
+            .unwrap();
+        assert!(stored.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_import_outcome_counts_nodes_or_reports_failure() {
+        let (_dir, svc, sub) = service_with_file("trojan://secret@a.example.com:443#A\n");
+        let result = svc.refresh(sub.id).await;
+        assert_eq!(
+            import_outcome(result.as_ref().map(|(s, _)| s)),
+            ImportOutcome::Imported(1)
+        );
+
+        let missing = Uuid::new_v4();
+        let result = svc.refresh(missing).await;
+        assert_eq!(
+            import_outcome(result.as_ref().map(|(s, _)| s)),
+            ImportOutcome::Failed(format!("subscription not found: {missing}"))
+        );
+    }
+
+    #[test]
+    fn test_overdue_subscriptions_follow_their_interval() {
+        let now = chrono::Utc::now();
+        let hours_ago = |h| Some(now - chrono::Duration::hours(h));
+        let mut never = Subscription::new_from_url("Never", "https://a.example.com");
+        never.last_updated = None;
+        let mut stale = Subscription::new_from_url("Stale", "https://b.example.com");
+        stale.last_updated = hours_ago(25);
+        let mut fresh = Subscription::new_from_url("Fresh", "https://c.example.com");
+        fresh.last_updated = hours_ago(1);
+        let mut hourly = fresh.clone();
+        hourly.id = Uuid::new_v4();
+        hourly.auto_update_interval_secs = Some(1800);
+        let mut disabled = stale.clone();
+        disabled.id = Uuid::new_v4();
+        disabled.enabled = false;
+        let manual = Subscription::new_manual("Manual");
+
+        let subs = [
+            never.clone(),
+            stale.clone(),
+            fresh,
+            hourly.clone(),
+            disabled,
+            manual,
+        ];
+        assert_eq!(
+            overdue_subscriptions(&subs, 86400, now),
+            [never.id, stale.id, hourly.id]
+        );
+    }
+
+    #[test]
+    fn test_errors_map_to_their_kind() {
+        let fetch = |e| SubscriptionError::Fetch(e).kind();
+        let http = |status| {
+            fetch(FetchError::HttpError {
+                status,
+                body: String::new(),
+            })
+        };
+        assert_eq!(
+            fetch(FetchError::NetworkError("refused".into())),
+            FailureKind::Network
+        );
+        assert_eq!(fetch(FetchError::TooManyRedirects), FailureKind::Network);
+        assert_eq!(http(502), FailureKind::Network);
+        assert_eq!(http(429), FailureKind::Network);
+        assert_eq!(fetch(FetchError::Timeout), FailureKind::Timeout);
+        assert_eq!(http(404), FailureKind::Parse);
+        assert_eq!(fetch(FetchError::NoValidLinks), FailureKind::Parse);
+        assert_eq!(fetch(FetchError::TooLarge { limit: 1 }), FailureKind::Parse);
+        assert_eq!(fetch(FetchError::FileError("gone".into())), FailureKind::Io);
+        let storage = PersistenceError::Io(std::io::Error::other("disk full"));
+        assert_eq!(SubscriptionError::Storage(storage).kind(), FailureKind::Io);
+        assert!(http(503).is_retryable());
+        assert!(!http(404).is_retryable());
+    }
 
-        results
+    #[tokio::test]
+    async fn test_refresh_reports_missing_file_as_io() {
+        let (dir, svc, sub) = service_with_file("vless://x");
+        std::fs::remove_file(dir.path().join("nodes.txt")).unwrap();
+        let err = svc.refresh(sub.id).await.unwrap_err();
+        assert_eq!(err.kind(), FailureKind::Io);
     }
 }
@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use thiserror::Error;
 use v2ray_rs_core::models::{
-    GrpcSettings, H2Settings, ProxyNode, TlsSettings, TransportSettings, WsSettings,
+    GrpcSettings, H2Settings, MkcpSettings, ProxyNode, RealitySettings, TlsSettings,
+    TransportSettings, WsSettings, bare_host,
 };
 
 #[derive(Debug, Error)]
@@ -13,12 +14,26 @@ pub enum ParseError {
     InvalidFormat(String),
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject VLESS/VMess user IDs that are not UUIDs instead of importing
+    /// them with a warning.
+    pub strict_uuid: bool,
+}
+
+/// Longest non-UUID user ID Xray accepts; it maps such IDs onto a UUIDv5.
+const MAX_CUSTOM_ID_LEN: usize = 30;
+
 pub fn parse_uri(uri: &str) -> Result<ProxyNode, ParseError> {
+    parse_uri_with(uri, ParseOptions::default())
+}
+
+pub fn parse_uri_with(uri: &str, options: ParseOptions) -> Result<ProxyNode, ParseError> {
     let scheme = uri.split("://").next().unwrap_or("").to_lowercase();
 
     match scheme.as_str() {
-        "vless" => parse_vless(uri),
-        "vmess" => parse_vmess(uri),
+        "vless" => parse_vless(uri, options),
+        "vmess" => parse_vmess(uri, options),
         "ss" => parse_ss(uri),
         "trojan" => parse_trojan(uri),
         other => Err(ParseError::UnsupportedScheme(other.to_owned())),
@@ -33,14 +48,18 @@ fn parse_url_transport(params: &HashMap<String, String>) -> TransportSettings {
             TransportSettings::Ws(WsSettings {
                 path,
                 host,
-                headers: Default::default(),
+                headers: params
+                    .get("headers")
+                    .map(|h| parse_headers(h))
+                    .unwrap_or_default(),
             })
         }
         Some("grpc") => {
             let service_name = params.get("serviceName").cloned().unwrap_or_default();
             TransportSettings::Grpc(GrpcSettings {
                 service_name,
-                multi_mode: false,
+                multi_mode: params.get("mode").map(String::as_str) == Some("multi"),
+                authority: params.get("authority").filter(|a| !a.is_empty()).cloned(),
             })
         }
         Some("h2") => {
@@ -51,32 +70,98 @@ fn parse_url_transport(params: &HashMap<String, String>) -> TransportSettings {
             let path = params.get("path").cloned().unwrap_or_default();
             TransportSettings::H2(H2Settings { host, path })
         }
+        Some("kcp") => TransportSettings::Mkcp(MkcpSettings {
+            header_type: params.get("headerType").filter(|h| !h.is_empty()).cloned(),
+            seed: params.get("seed").filter(|s| !s.is_empty()).cloned(),
+        }),
         _ => TransportSettings::Tcp,
     }
 }
 
+/// Extra WebSocket headers from a `headers` parameter, given either as a JSON
+/// object (`{"User-Agent":"x"}`) or as `Name: value` pairs separated by `|`.
+/// Entries without a name are dropped.
+fn parse_headers(raw: &str) -> HashMap<String, String> {
+    if raw.trim_start().starts_with('{') {
+        return serde_json::from_str::<serde_json::Value>(raw)
+            .map(|json| json_headers(&json))
+            .unwrap_or_default();
+    }
+    raw.split('|')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+fn json_headers(value: &serde_json::Value) -> HashMap<String, String> {
+    match value {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(name, _)| !name.trim().is_empty())
+            .filter_map(|(name, value)| Some((name.trim().to_owned(), value.as_str()?.to_owned())))
+            .collect(),
+        serde_json::Value::String(s) => parse_headers(s),
+        _ => HashMap::new(),
+    }
+}
+
+/// `allowInsecure=1` (v2rayN and most panels) or `insecure=1` (Clash-style
+/// exporters) marks a node whose certificate cannot be verified.
+fn allows_insecure(params: &HashMap<String, String>) -> bool {
+    ["allowInsecure", "insecure"].iter().any(|key| {
+        params
+            .get(*key)
+            .is_some_and(|v| matches!(v.as_str(), "1" | "true"))
+    })
+}
+
 fn parse_url_tls(params: &HashMap<String, String>) -> Option<TlsSettings> {
     match params.get("security").map(|s| s.as_str()) {
         Some("tls") | Some("reality") => {
             let server_name = params.get("sni").cloned();
             let alpn = params
                 .get("alpn")
-                .map(|a| a.split(',').map(|s| s.to_owned()).collect())
+                .map(|a| split_list(a))
                 .unwrap_or_default();
             let fingerprint = params.get("fp").cloned();
+            let reality = match (
+                params.get("security").map(String::as_str),
+                params.get("pbk"),
+            ) {
+                (Some("reality"), Some(public_key)) => Some(RealitySettings {
+                    public_key: public_key.clone(),
+                    short_id: params.get("sid").filter(|s| !s.is_empty()).cloned(),
+                    spider_x: params.get("spx").filter(|s| !s.is_empty()).cloned(),
+                }),
+                _ => None,
+            };
             Some(TlsSettings {
                 server_name,
                 alpn,
-                verify: true,
+                verify: !allows_insecure(params),
                 fingerprint,
+                reality,
             })
         }
         _ => None,
     }
 }
 
-fn parse_vless(uri: &str) -> Result<ProxyNode, ParseError> {
-    use v2ray_rs_core::models::VlessConfig;
+fn check_user_id(id: &str, options: ParseOptions) -> Result<(), ParseError> {
+    if uuid::Uuid::parse_str(id).is_ok() {
+        return Ok(());
+    }
+    if options.strict_uuid || id.is_empty() || id.len() > MAX_CUSTOM_ID_LEN {
+        return Err(ParseError::InvalidFormat("invalid UUID".into()));
+    }
+    log::warn!("user ID {id:?} is not a UUID; only Xray accepts custom IDs");
+    Ok(())
+}
+
+fn parse_vless(uri: &str, options: ParseOptions) -> Result<ProxyNode, ParseError> {
+    use v2ray_rs_core::models::{VlessConfig, validate_vless_encryption};
 
     let url = url::Url::parse(uri).map_err(|e| ParseError::InvalidFormat(e.to_string()))?;
 
@@ -84,9 +169,11 @@ fn parse_vless(uri: &str) -> Result<ProxyNode, ParseError> {
     if uuid.is_empty() {
         return Err(ParseError::InvalidFormat("missing UUID".into()));
     }
+    check_user_id(&uuid, options)?;
 
     let address = url
         .host_str()
+        .map(bare_host)
         .ok_or_else(|| ParseError::InvalidFormat("missing host".into()))?
         .to_owned();
     let port = url
@@ -104,13 +191,20 @@ fn parse_vless(uri: &str) -> Result<ProxyNode, ParseError> {
     let tls = parse_url_tls(&params);
 
     let flow = params.get("flow").cloned();
-    let encryption = params.get("encryption").cloned();
+    let encryption = match params.get("encryption").map(String::as_str) {
+        None | Some("") => "none".to_owned(),
+        Some(value) => {
+            validate_vless_encryption(value)
+                .map_err(|e| ParseError::InvalidFormat(e.to_string()))?;
+            value.to_owned()
+        }
+    };
 
     Ok(ProxyNode::Vless(VlessConfig {
         address,
         port,
         uuid,
-        encryption,
+        encryption: Some(encryption),
         flow,
         transport,
         tls,
@@ -118,9 +212,9 @@ fn parse_vless(uri: &str) -> Result<ProxyNode, ParseError> {
     }))
 }
 
-fn parse_vmess(uri: &str) -> Result<ProxyNode, ParseError> {
+fn parse_vmess(uri: &str, options: ParseOptions) -> Result<ProxyNode, ParseError> {
     use base64::Engine;
-    use base64::engine::general_purpose::STANDARD;
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
     use v2ray_rs_core::models::{
         GrpcSettings, H2Settings, TlsSettings, TransportSettings, VmessConfig, WsSettings,
     };
@@ -129,14 +223,19 @@ fn parse_vmess(uri: &str) -> Result<ProxyNode, ParseError> {
         .strip_prefix("vmess://")
         .ok_or_else(|| ParseError::InvalidFormat("missing vmess:// prefix".into()))?;
 
+    let encoded = encoded.trim();
     let decoded = STANDARD
-        .decode(encoded.trim())
+        .decode(encoded)
+        .or_else(|_| STANDARD_NO_PAD.decode(encoded))
+        .or_else(|_| URL_SAFE.decode(encoded))
+        .or_else(|_| URL_SAFE_NO_PAD.decode(encoded))
         .map_err(|e| ParseError::InvalidFormat(format!("base64 decode failed: {e}")))?;
     let json: serde_json::Value = serde_json::from_slice(&decoded)
         .map_err(|e| ParseError::InvalidFormat(format!("invalid JSON: {e}")))?;
 
     let address = json["add"]
         .as_str()
+        .map(bare_host)
         .ok_or_else(|| ParseError::InvalidFormat("missing 'add' field".into()))?
         .to_owned();
     let port = json["port"]
@@ -148,6 +247,7 @@ fn parse_vmess(uri: &str) -> Result<ProxyNode, ParseError> {
         .as_str()
         .ok_or_else(|| ParseError::InvalidFormat("missing 'id' field".into()))?
         .to_owned();
+    check_user_id(&uuid, options)?;
     let remark = json["ps"].as_str().map(|s| s.to_owned());
 
     let transport = match json["net"].as_str() {
@@ -157,37 +257,62 @@ fn parse_vmess(uri: &str) -> Result<ProxyNode, ParseError> {
             TransportSettings::Ws(WsSettings {
                 path,
                 host,
-                headers: Default::default(),
+                headers: json_headers(&json["headers"]),
             })
         }
         Some("grpc") => {
-            let service_name = json["path"].as_str().unwrap_or("").to_owned();
+            let service_name = json["path"]
+                .as_str()
+                .filter(|p| !p.is_empty())
+                .or_else(|| json["serviceName"].as_str())
+                .unwrap_or("")
+                .to_owned();
             TransportSettings::Grpc(GrpcSettings {
                 service_name,
-                multi_mode: false,
+                multi_mode: json["type"].as_str() == Some("multi"),
+                authority: json["authority"]
+                    .as_str()
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_owned),
             })
         }
         Some("h2") => {
-            let host = json["host"]
-                .as_str()
-                .map(|h| vec![h.to_owned()])
-                .unwrap_or_default();
+            let host = json_string_list(&json["host"]);
             let path = json["path"].as_str().unwrap_or("").to_owned();
             TransportSettings::H2(H2Settings { host, path })
         }
+        // vmess links carry the header type in `type` and the seed in `path`.
+        Some("kcp") => TransportSettings::Mkcp(MkcpSettings {
+            header_type: json["type"]
+                .as_str()
+                .filter(|h| !h.is_empty())
+                .map(str::to_owned),
+            seed: json["path"]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned),
+        }),
         _ => TransportSettings::Tcp,
     };
 
     let tls = if json["tls"].as_str() == Some("tls") {
         let server_name = json["sni"]
             .as_str()
-            .or_else(|| json["host"].as_str())
+            .filter(|s| !s.is_empty())
+            .or_else(|| json["host"].as_str().and_then(|h| h.split(',').next()))
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty());
+        let fingerprint = json["fp"]
+            .as_str()
+            .filter(|s| !s.is_empty())
             .map(|s| s.to_owned());
+        let insecure = json_flag(&json["allowInsecure"]) || json_flag(&json["skip-cert-verify"]);
         Some(TlsSettings {
             server_name,
-            alpn: vec![],
-            verify: true,
-            fingerprint: None,
+            alpn: json_string_list(&json["alpn"]),
+            verify: !insecure,
+            fingerprint,
+            reality: None,
         })
     } else {
         None
@@ -205,51 +330,138 @@ fn parse_vmess(uri: &str) -> Result<ProxyNode, ParseError> {
     }))
 }
 
+/// Comma-separated values such as `h2, http/1.1`, trimmed and without the
+/// empty entries left by stray or trailing commas.
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn json_string_list(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(s) => split_list(s),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|item| item.to_owned())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn json_flag(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_u64() == Some(1),
+        serde_json::Value::String(s) => matches!(s.as_str(), "1" | "true"),
+        _ => false,
+    }
+}
+
 fn parse_ss(uri: &str) -> Result<ProxyNode, ParseError> {
-    use base64::Engine;
-    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
     use v2ray_rs_core::models::ShadowsocksConfig;
 
     let without_scheme = uri
         .strip_prefix("ss://")
         .ok_or_else(|| ParseError::InvalidFormat("missing ss:// prefix".into()))?;
 
-    let (userinfo_part, host_part) = without_scheme
-        .split_once('@')
-        .ok_or_else(|| ParseError::InvalidFormat("missing '@' separator".into()))?;
+    let (body, fragment) = without_scheme.split_once('#').unzip();
+    let body = body.unwrap_or(without_scheme);
 
-    let decoded = URL_SAFE_NO_PAD
-        .decode(userinfo_part.trim())
-        .or_else(|_| STANDARD.decode(userinfo_part.trim()))
-        .map_err(|e| ParseError::InvalidFormat(format!("base64 decode failed: {e}")))?;
-    let userinfo = String::from_utf8(decoded)
-        .map_err(|e| ParseError::InvalidFormat(format!("invalid UTF-8: {e}")))?;
+    let (userinfo, host_port) = match body.rsplit_once('@') {
+        Some((userinfo_part, host_port)) => {
+            (decode_ss_userinfo(userinfo_part)?, host_port.to_owned())
+        }
+        None => {
+            // Legacy format: ss://BASE64(method:password@host:port)#tag
+            let decoded = decode_ss_base64(body).map_err(|_| {
+                ParseError::InvalidFormat(
+                    "missing '@' separator and body is not a legacy base64 link".into(),
+                )
+            })?;
+            let (userinfo, host_port) = decoded.rsplit_once('@').ok_or_else(|| {
+                ParseError::InvalidFormat("missing '@' separator in decoded link".into())
+            })?;
+            (userinfo.to_owned(), host_port.to_owned())
+        }
+    };
 
     let (method, password) = userinfo
         .split_once(':')
         .ok_or_else(|| ParseError::InvalidFormat("missing method:password".into()))?;
 
-    let (host_port, fragment) = host_part.split_once('#').unzip();
-    let host_port = host_port.unwrap_or(host_part);
-
     let (address, port_str) = host_port
         .rsplit_once(':')
         .ok_or_else(|| ParseError::InvalidFormat("missing host:port".into()))?;
+    if address.contains(':') && !address.starts_with('[') {
+        return Err(ParseError::InvalidFormat(
+            "IPv6 host must be in brackets".into(),
+        ));
+    }
+    let address = bare_host(address);
     let port: u16 = port_str
         .parse()
         .map_err(|_| ParseError::InvalidFormat("invalid port".into()))?;
 
+    let method = method.trim().to_ascii_lowercase();
+    if let Err(e) = check_ss_credentials(&method, password) {
+        log::warn!("shadowsocks node {address}:{port}: {e}");
+    }
+
     let remark = percent_decode_fragment(fragment);
 
     Ok(ProxyNode::Shadowsocks(ShadowsocksConfig {
         address: address.to_owned(),
         port,
-        method: method.to_owned(),
+        method,
         password: password.to_owned(),
         remark,
     }))
 }
 
+// SIP002 allows SS2022 userinfo as percent-encoded plain `method:password`
+// instead of base64; base64 output never contains ':' so the check is unambiguous.
+fn decode_ss_userinfo(userinfo: &str) -> Result<String, ParseError> {
+    let plain = percent_decode(userinfo);
+    if plain.contains(':') {
+        return Ok(plain);
+    }
+    decode_ss_base64(userinfo)
+}
+
+fn check_ss_credentials(method: &str, password: &str) -> Result<(), String> {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use v2ray_rs_core::models::{ss2022_key_len, validate_ss_method};
+
+    validate_ss_method(method).map_err(|e| e.to_string())?;
+    let Some(key_len) = ss2022_key_len(method) else {
+        return Ok(());
+    };
+    // Multi-user servers take `server_psk:user_psk`; each part is a separate key.
+    for key in password.split(':') {
+        match STANDARD.decode(key) {
+            Ok(bytes) if bytes.len() == key_len => {}
+            _ => return Err(format!("{method} expects a base64 {key_len}-byte key")),
+        }
+    }
+    Ok(())
+}
+
+fn decode_ss_base64(input: &str) -> Result<String, ParseError> {
+    use base64::Engine;
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+
+    let decoded = URL_SAFE_NO_PAD
+        .decode(input.trim())
+        .or_else(|_| STANDARD.decode(input.trim()))
+        .map_err(|e| ParseError::InvalidFormat(format!("base64 decode failed: {e}")))?;
+    String::from_utf8(decoded).map_err(|e| ParseError::InvalidFormat(format!("invalid UTF-8: {e}")))
+}
+
 fn parse_trojan(uri: &str) -> Result<ProxyNode, ParseError> {
     use v2ray_rs_core::models::TrojanConfig;
 
@@ -262,6 +474,7 @@ fn parse_trojan(uri: &str) -> Result<ProxyNode, ParseError> {
 
     let address = url
         .host_str()
+        .map(bare_host)
         .ok_or_else(|| ParseError::InvalidFormat("missing host".into()))?
         .to_owned();
     let port = url
@@ -270,23 +483,24 @@ fn parse_trojan(uri: &str) -> Result<ProxyNode, ParseError> {
 
     let remark = percent_decode_fragment(url.fragment());
 
-    let params: HashMap<String, String> = url
+    let mut params: HashMap<String, String> = url
         .query_pairs()
         .map(|(k, v)| (k.into_owned(), v.into_owned()))
         .collect();
 
     let transport = parse_url_transport(&params);
-    let tls = parse_url_tls(&params).or_else(|| {
-        if port == 443 {
-            Some(TlsSettings {
-                server_name: Some(address.clone()),
-                alpn: vec![],
-                verify: true,
-                fingerprint: None,
-            })
-        } else {
-            None
+    // Trojan runs over TLS by definition, so links from trojan-gfw and
+    // trojan-go often leave `security` out whatever the port; only an explicit
+    // `security=none` turns it off.
+    let implied = !params.contains_key("security");
+    if implied {
+        params.insert("security".into(), "tls".into());
+    }
+    let tls = parse_url_tls(&params).map(|mut tls| {
+        if implied {
+            tls.server_name.get_or_insert_with(|| address.clone());
         }
+        tls
     });
 
     Ok(ProxyNode::Trojan(TrojanConfig {
@@ -311,11 +525,7 @@ pub fn parse_subscription_uris(uris: &[String]) -> ImportResult {
     for uri in uris {
         match parse_uri(uri) {
             Ok(proxy_node) => {
-                nodes.push(v2ray_rs_core::models::SubscriptionNode {
-                    node: proxy_node,
-                    enabled: true,
-                    last_latency_ms: None,
-                });
+                nodes.push(v2ray_rs_core::models::SubscriptionNode::new(proxy_node));
             }
             Err(e) => {
                 errors.push((uri.clone(), e));
@@ -326,13 +536,17 @@ pub fn parse_subscription_uris(uris: &[String]) -> ImportResult {
     ImportResult { nodes, errors }
 }
 
+/// Remarks are form-encoded by most clients, so `+` stands for a space.
 fn percent_decode_fragment(fragment: Option<&str>) -> Option<String> {
-    fragment.map(|f| {
-        url::form_urlencoded::parse(f.as_bytes())
-            .next()
-            .map(|(k, _)| k.into_owned())
-            .unwrap_or_else(|| f.to_owned())
-    })
+    fragment.map(|f| percent_decode(&f.replace('+', " ")))
+}
+
+/// Percent-decodes `input` with the decoder `url` itself uses, leaving `+`
+/// alone as userinfo and base64 keys need.
+pub(crate) fn percent_decode(input: &str) -> String {
+    percent_encoding::percent_decode_str(input)
+        .decode_utf8_lossy()
+        .into_owned()
 }
 
 #[cfg(test)]
@@ -388,6 +602,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_vless_encryption() {
+        let parse =
+            |query: &str| match parse_uri(&format!("vless://uuid@example.com:443?{query}#E")) {
+                Ok(ProxyNode::Vless(cfg)) => Ok(cfg.encryption),
+                Ok(_) => panic!("expected VLESS config"),
+                Err(e) => Err(e.to_string()),
+            };
+        assert_eq!(parse("type=tcp"), Ok(Some("none".into())));
+        assert_eq!(parse("encryption=none"), Ok(Some("none".into())));
+        assert_eq!(parse("encryption="), Ok(Some("none".into())));
+
+        let suite = "mlkem768x25519plus.native.0rtt.100-111-1111.ptjHQxBQxTJ9MWr2cd5qWIflBSACHOevTauCQwa_71U";
+        assert_eq!(
+            parse(&format!("encryption={suite}")),
+            Ok(Some(suite.into()))
+        );
+
+        let err = parse("encryption=aes-128-gcm").unwrap_err();
+        assert!(err.contains("unsupported vless encryption"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_vless_reality() {
+        let uri = "vless://uuid@example.com:443?type=tcp&security=reality&sni=www.microsoft.com&fp=firefox&pbk=PUBKEY&sid=6ba8&spx=%2F&flow=xtls-rprx-vision#R";
+        let ProxyNode::Vless(cfg) = parse_uri(uri).unwrap() else {
+            panic!("expected VLESS config");
+        };
+
+        assert_eq!(cfg.flow.as_deref(), Some("xtls-rprx-vision"));
+        let tls = cfg.tls.unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("www.microsoft.com"));
+        assert_eq!(tls.fingerprint.as_deref(), Some("firefox"));
+        let reality = tls.reality.unwrap();
+        assert_eq!(reality.public_key, "PUBKEY");
+        assert_eq!(reality.short_id.as_deref(), Some("6ba8"));
+        assert_eq!(reality.spider_x.as_deref(), Some("/"));
+    }
+
     #[test]
     fn test_parse_vless_with_grpc() {
         let uri = "vless://uuid@example.com:443?type=grpc&serviceName=MyService&security=tls";
@@ -452,6 +705,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_vmess_tls_alpn_fingerprint() {
+        let vmess_json = r#"{"add":"example.com","port":"443","id":"uuid","net":"tcp","tls":"tls","sni":"sni.example.com","alpn":"h2,http/1.1","fp":"chrome","allowInsecure":"1"}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(vmess_json);
+        let uri = format!("vmess://{}", encoded);
+
+        match parse_uri(&uri).unwrap() {
+            ProxyNode::Vmess(cfg) => {
+                let tls = cfg.tls.unwrap();
+                assert_eq!(tls.server_name, Some("sni.example.com".to_string()));
+                assert_eq!(tls.alpn, vec!["h2".to_string(), "http/1.1".to_string()]);
+                assert_eq!(tls.fingerprint, Some("chrome".to_string()));
+                assert!(!tls.verify);
+            }
+            _ => panic!("expected VMess config"),
+        }
+    }
+
+    #[test]
+    fn test_parse_vmess_h2_host_list_and_grpc_service() {
+        let h2_json = r#"{"add":"example.com","port":"443","id":"uuid","net":"h2","host":"a.example.com,b.example.com","path":"/h2","tls":"tls"}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(h2_json);
+
+        match parse_uri(&format!("vmess://{}", encoded)).unwrap() {
+            ProxyNode::Vmess(cfg) => {
+                match cfg.transport {
+                    TransportSettings::H2(h2) => {
+                        assert_eq!(h2.host, vec!["a.example.com", "b.example.com"]);
+                        assert_eq!(h2.path, "/h2");
+                    }
+                    _ => panic!("expected H2 transport"),
+                }
+                let tls = cfg.tls.unwrap();
+                assert_eq!(tls.server_name, Some("a.example.com".to_string()));
+                assert!(tls.verify);
+            }
+            _ => panic!("expected VMess config"),
+        }
+
+        let grpc_json = r#"{"add":"example.com","port":"443","id":"uuid","net":"grpc","path":"","serviceName":"svc","type":"multi"}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(grpc_json);
+
+        match parse_uri(&format!("vmess://{}", encoded)).unwrap() {
+            ProxyNode::Vmess(cfg) => match cfg.transport {
+                TransportSettings::Grpc(grpc) => {
+                    assert_eq!(grpc.service_name, "svc");
+                    assert!(grpc.multi_mode);
+                }
+                _ => panic!("expected gRPC transport"),
+            },
+            _ => panic!("expected VMess config"),
+        }
+    }
+
+    #[test]
+    fn test_parse_vmess_url_safe_no_pad() {
+        // "?" and ">" in the remark force '/' and '+' in standard base64
+        let vmess_json = r#"{"v":"2","ps":"Node??>>","add":"example.com","port":"443","id":"uuid-1234","net":"ws","path":"/ws","host":"example.com","tls":"tls"}"#;
+        let standard = base64::engine::general_purpose::STANDARD.encode(vmess_json);
+        let url_safe = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(vmess_json);
+        assert_ne!(standard, url_safe);
+
+        let expected = parse_uri(&format!("vmess://{}", standard)).unwrap();
+        let result = parse_uri(&format!("vmess://{}", url_safe)).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn test_parse_ss_sip002() {
         let userinfo = "aes-256-gcm:password";
@@ -472,6 +793,105 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_ss2022_plain_userinfo() {
+        let psk = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+        let uri = format!(
+            "ss://2022-blake3-aes-256-gcm:{}@example.com:8388#SS2022",
+            psk.replace('=', "%3D")
+        );
+
+        match parse_uri(&uri).unwrap() {
+            ProxyNode::Shadowsocks(cfg) => {
+                assert_eq!(cfg.method, "2022-blake3-aes-256-gcm");
+                assert_eq!(cfg.password, psk);
+                assert_eq!(cfg.port, 8388);
+            }
+            _ => panic!("expected Shadowsocks config"),
+        }
+    }
+
+    #[test]
+    fn test_parse_ss_unknown_method_is_kept() {
+        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("AES-999-GCM:pw");
+        let uri = format!("ss://{encoded}@example.com:8388");
+
+        match parse_uri(&uri).unwrap() {
+            ProxyNode::Shadowsocks(cfg) => assert_eq!(cfg.method, "aes-999-gcm"),
+            _ => panic!("expected Shadowsocks config"),
+        }
+        assert!(check_ss_credentials("aes-999-gcm", "pw").is_err());
+    }
+
+    #[test]
+    fn test_check_ss2022_key_length() {
+        let key16 = "MDEyMzQ1Njc4OWFiY2RlZg==";
+        let key32 = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+        assert!(check_ss_credentials("2022-blake3-aes-128-gcm", key16).is_ok());
+        assert!(check_ss_credentials("2022-blake3-aes-256-gcm", key32).is_ok());
+        assert!(check_ss_credentials("2022-blake3-aes-256-gcm", key16).is_err());
+        assert!(check_ss_credentials("2022-blake3-aes-256-gcm", "not-base64").is_err());
+        let multi_user = format!("{key32}:{key32}");
+        assert!(check_ss_credentials("2022-blake3-aes-256-gcm", &multi_user).is_ok());
+    }
+
+    #[test]
+    fn test_ss2022_generators_keep_method_and_key() {
+        use v2ray_rs_core::config::{ConfigGenerator, SingboxGenerator, V2rayGenerator};
+        use v2ray_rs_core::models::AppSettings;
+
+        let psk = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+        let uri = format!("ss://2022-blake3-aes-256-gcm:{psk}@example.com:8388#SS2022");
+        let node = parse_uri(&uri).unwrap();
+        let settings = AppSettings::default();
+
+        let v2ray = V2rayGenerator
+            .generate(std::slice::from_ref(&node), &[], &settings, None)
+            .unwrap();
+        let server = &v2ray["outbounds"][0]["settings"]["servers"][0];
+        assert_eq!(server["method"], "2022-blake3-aes-256-gcm");
+        assert_eq!(server["password"], psk);
+
+        let singbox = SingboxGenerator
+            .generate(std::slice::from_ref(&node), &[], &settings, None)
+            .unwrap();
+        let outbound = &singbox["outbounds"][0];
+        assert_eq!(outbound["type"], "shadowsocks");
+        assert_eq!(outbound["method"], "2022-blake3-aes-256-gcm");
+        assert_eq!(outbound["password"], psk);
+    }
+
+    #[test]
+    fn test_parse_ss_legacy_base64() {
+        let sip002 = format!(
+            "ss://{}@example.com:8388#Test",
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("aes-256-gcm:password")
+        );
+        let legacy = format!(
+            "ss://{}#Test",
+            base64::engine::general_purpose::STANDARD
+                .encode("aes-256-gcm:password@example.com:8388")
+        );
+
+        let expected = parse_uri(&sip002).unwrap();
+        let result = parse_uri(&legacy).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_ss_legacy_decoded_without_at() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode("aes-256-gcm:password");
+        let uri = format!("ss://{}", encoded);
+
+        match parse_uri(&uri) {
+            Err(ParseError::InvalidFormat(msg)) => {
+                assert!(msg.contains("@"));
+            }
+            _ => panic!("expected InvalidFormat error"),
+        }
+    }
+
     #[test]
     fn test_parse_trojan_basic() {
         let uri = "trojan://password@example.com:443#Test";
@@ -504,6 +924,134 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_trojan_ws_defaults_to_tls_on_any_port() {
+        let uri = "trojan://password@example.com:8443?type=ws&path=%2Ftj&host=cdn.example.com&sni=cdn.example.com#WS";
+        let ProxyNode::Trojan(cfg) = parse_uri(uri).unwrap() else {
+            panic!("expected Trojan config");
+        };
+        match &cfg.transport {
+            TransportSettings::Ws(ws) => {
+                assert_eq!(ws.path, "/tj");
+                assert_eq!(ws.host.as_deref(), Some("cdn.example.com"));
+            }
+            other => panic!("expected WS transport, got {other:?}"),
+        }
+        let tls = cfg.tls.unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("cdn.example.com"));
+
+        let uri = "trojan://password@example.com:8080?security=none&type=grpc&serviceName=tj#Plain";
+        let ProxyNode::Trojan(cfg) = parse_uri(uri).unwrap() else {
+            panic!("expected Trojan config");
+        };
+        assert!(matches!(cfg.transport, TransportSettings::Grpc(ref g) if g.service_name == "tj"));
+        assert_eq!(cfg.tls, None);
+    }
+
+    #[test]
+    fn test_parse_ws_headers() {
+        let ws = |uri: &str| match parse_uri(uri).unwrap() {
+            ProxyNode::Vless(cfg) => match cfg.transport {
+                TransportSettings::Ws(ws) => ws,
+                other => panic!("Expected WS, got {other:?}"),
+            },
+            _ => panic!("Expected VLESS"),
+        };
+
+        let piped = ws(
+            "vless://uuid@example.com:443?type=ws&host=cdn.example.com&headers=User-Agent%3A%20Mozilla%2F5.0%7CX-Token%3A%20abc%7C%3Anameless#V",
+        );
+        assert_eq!(piped.host.as_deref(), Some("cdn.example.com"));
+        assert_eq!(piped.headers.len(), 2);
+        assert_eq!(piped.headers["User-Agent"], "Mozilla/5.0");
+        assert_eq!(piped.headers["X-Token"], "abc");
+
+        let json =
+            ws("vless://uuid@example.com:443?type=ws&headers=%7B%22X-Token%22%3A%22abc%22%7D#V");
+        assert_eq!(json.headers["X-Token"], "abc");
+
+        let vmess_json = r#"{"add":"example.com","port":"443","id":"uuid","net":"ws","host":"cdn.example.com","headers":{"X-Token":"abc"}}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(vmess_json);
+        match parse_uri(&format!("vmess://{encoded}")).unwrap() {
+            ProxyNode::Vmess(cfg) => match cfg.transport {
+                TransportSettings::Ws(ws) => assert_eq!(ws.headers["X-Token"], "abc"),
+                other => panic!("Expected WS, got {other:?}"),
+            },
+            _ => panic!("Expected VMess"),
+        }
+    }
+
+    #[test]
+    fn test_parse_mkcp() {
+        match parse_uri("vless://uuid@example.com:443?type=kcp&headerType=srtp&seed=abc#K").unwrap()
+        {
+            ProxyNode::Vless(cfg) => assert_eq!(
+                cfg.transport,
+                TransportSettings::Mkcp(MkcpSettings {
+                    header_type: Some("srtp".into()),
+                    seed: Some("abc".into()),
+                })
+            ),
+            _ => panic!("Expected VLESS"),
+        }
+
+        let vmess_json = r#"{"add":"example.com","port":"443","id":"uuid","net":"kcp","type":"wechat-video","path":"s"}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(vmess_json);
+        match parse_uri(&format!("vmess://{encoded}")).unwrap() {
+            ProxyNode::Vmess(cfg) => assert_eq!(
+                cfg.transport,
+                TransportSettings::Mkcp(MkcpSettings {
+                    header_type: Some("wechat-video".into()),
+                    seed: Some("s".into()),
+                })
+            ),
+            _ => panic!("Expected VMess"),
+        }
+    }
+
+    #[test]
+    fn test_parse_grpc_mode_and_authority() {
+        let grpc = |uri: &str| match parse_uri(uri).unwrap() {
+            ProxyNode::Vless(cfg) => match cfg.transport {
+                TransportSettings::Grpc(grpc) => grpc,
+                other => panic!("Expected gRPC, got {other:?}"),
+            },
+            _ => panic!("Expected VLESS"),
+        };
+
+        let multi = grpc(
+            "vless://uuid@example.com:443?type=grpc&serviceName=svc&mode=multi&authority=cdn.example.com#V",
+        );
+        assert_eq!(multi.service_name, "svc");
+        assert!(multi.multi_mode);
+        assert_eq!(multi.authority.as_deref(), Some("cdn.example.com"));
+
+        let gun = grpc("vless://uuid@example.com:443?type=grpc&serviceName=svc&mode=gun#V");
+        assert!(!gun.multi_mode);
+        assert_eq!(gun.authority, None);
+    }
+
+    #[test]
+    fn test_parse_encoded_alpn() {
+        let uri = "vless://uuid@example.com:443?security=tls&alpn=h2%2C%20http%2F1.1%2C%2C#V";
+        let tls = parse_uri(uri).unwrap().tls().cloned().unwrap();
+        assert_eq!(tls.alpn, vec!["h2", "http/1.1"]);
+
+        let uri = "trojan://pw@example.com:443?alpn=+h2+,http/1.1,#T";
+        let tls = parse_uri(uri).unwrap().tls().cloned().unwrap();
+        assert_eq!(tls.alpn, vec!["h2", "http/1.1"]);
+    }
+
+    #[test]
+    fn test_parse_allow_insecure() {
+        let tls = |uri: &str| parse_uri(uri).unwrap().tls().cloned().unwrap();
+        assert!(!tls("trojan://pw@example.com:443?security=tls&allowInsecure=1#T").verify);
+        assert!(!tls("trojan://pw@example.com:8443?insecure=true#T").verify);
+        assert!(!tls("vless://uuid@example.com:443?security=tls&allowInsecure=1#V").verify);
+        assert!(tls("vless://uuid@example.com:443?security=tls&allowInsecure=0#V").verify);
+        assert!(tls("trojan://pw@example.com:443#T").verify);
+    }
+
     #[test]
     fn test_parse_unknown_scheme() {
         let uri = "http://foo";
@@ -517,6 +1065,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_user_id_validation() {
+        let valid = "vless://550e8400-e29b-41d4-a716-446655440000@example.com:443";
+        let custom = "vless://my-custom-id@example.com:443";
+        let malformed = "vless://550e8400-e29b-41d4-a716-44665544000g-and-more@example.com:443";
+        let strict = ParseOptions { strict_uuid: true };
+
+        assert!(parse_uri_with(valid, strict).is_ok());
+        assert!(parse_uri(custom).is_ok());
+        for (uri, options) in [(custom, strict), (malformed, ParseOptions::default())] {
+            match parse_uri_with(uri, options) {
+                Err(ParseError::InvalidFormat(msg)) => assert_eq!(msg, "invalid UUID"),
+                other => panic!("expected invalid UUID for {uri}, got {other:?}"),
+            }
+        }
+
+        let vmess = |id: &str| {
+            let json = format!(r#"{{"add":"example.com","port":"443","id":"{id}"}}"#);
+            let encoded = base64::engine::general_purpose::STANDARD.encode(json);
+            format!("vmess://{encoded}")
+        };
+        assert!(parse_uri_with(&vmess("550e8400-e29b-41d4-a716-446655440000"), strict).is_ok());
+        assert!(parse_uri_with(&vmess("uuid"), strict).is_err());
+        assert!(parse_uri(&vmess("")).is_err());
+    }
+
+    #[test]
+    fn test_ipv6_hosts_are_stored_bare() {
+        let ss = "ss://YWVzLTI1Ni1nY206cGFzcw@[2001:db8::1]:8388#v6";
+        match parse_uri(ss).unwrap() {
+            ProxyNode::Shadowsocks(cfg) => {
+                assert_eq!(cfg.address, "2001:db8::1");
+                assert_eq!(cfg.port, 8388);
+                assert_eq!(cfg.method, "aes-256-gcm");
+            }
+            other => panic!("expected shadowsocks, got {other:?}"),
+        }
+
+        let vless = "vless://550e8400-e29b-41d4-a716-446655440000@[2001:db8::2]:443?security=tls";
+        let node = parse_uri(vless).unwrap();
+        assert_eq!(node.address(), "2001:db8::2");
+        assert_eq!(node.port(), 443);
+
+        let trojan = parse_uri("trojan://secret@[::1]:443").unwrap();
+        assert_eq!(trojan.address(), "::1");
+
+        let json = r#"{"add":"[2001:db8::3]","port":"443","id":"uuid"}"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(json);
+        let vmess = parse_uri(&format!("vmess://{encoded}")).unwrap();
+        assert_eq!(vmess.address(), "2001:db8::3");
+
+        assert!(parse_uri("ss://YWVzLTI1Ni1nY206cGFzcw@2001:db8::1:8388").is_err());
+    }
+
     #[test]
     fn test_parse_vless_missing_uuid() {
         let uri = "vless://@host:443";
@@ -20,29 +20,56 @@ const PING_TIMEOUT: Duration = Duration::from_secs(5);
 const MAX_CONCURRENT_PINGS: usize = 50;
 
 pub async fn tcp_ping(addr: &str, port: u16) -> Result<Duration, PingError> {
-    let target = format!("{addr}:{port}");
     let start = Instant::now();
-    timeout(PING_TIMEOUT, TcpStream::connect(&target))
+    timeout(PING_TIMEOUT, TcpStream::connect((addr, port)))
         .await
         .map_err(|_| PingError::Timeout)?
         .map_err(PingError::ConnectionFailed)?;
     Ok(start.elapsed())
 }
 
+/// Caps concurrent pings; clones share the cap so parallel tests stay within it.
+#[derive(Clone)]
+pub struct PingLimiter(Arc<Semaphore>);
+
+impl Default for PingLimiter {
+    fn default() -> Self {
+        Self(Arc::new(Semaphore::new(MAX_CONCURRENT_PINGS)))
+    }
+}
+
 pub async fn ping_nodes(nodes: &[SubscriptionNode]) -> Vec<Option<u64>> {
-    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_PINGS));
+    ping_nodes_with(nodes, &PingLimiter::default(), |_, _| {}).await
+}
+
+/// Pings `nodes` under `limiter`, calling `on_result(index, latency)` as each ping finishes.
+pub async fn ping_nodes_with<F>(
+    nodes: &[SubscriptionNode],
+    limiter: &PingLimiter,
+    on_result: F,
+) -> Vec<Option<u64>>
+where
+    F: Fn(usize, Option<u64>) + Send + Sync + 'static,
+{
+    let on_result = Arc::new(on_result);
     let handles: Vec<_> = nodes
         .iter()
-        .map(|node| {
+        .enumerate()
+        .map(|(idx, node)| {
             let addr = node.node.address().to_string();
             let port = node.node.port();
-            let permit = Arc::clone(&semaphore);
+            let permit = Arc::clone(&limiter.0);
+            let on_result = Arc::clone(&on_result);
             tokio::spawn(async move {
-                let _permit = permit.acquire().await.ok()?;
-                tcp_ping(&addr, port)
-                    .await
-                    .ok()
-                    .map(|d| d.as_millis() as u64)
+                let latency = match permit.acquire().await {
+                    Ok(_permit) => tcp_ping(&addr, port)
+                        .await
+                        .ok()
+                        .map(|d| d.as_millis() as u64),
+                    Err(_) => None,
+                };
+                on_result(idx, latency);
+                latency
             })
         })
         .collect();
@@ -53,3 +80,46 @@ pub async fn ping_nodes(nodes: &[SubscriptionNode]) -> Vec<Option<u64>> {
     }
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use v2ray_rs_core::models::{ProxyNode, TrojanConfig};
+
+    fn node(port: u16) -> SubscriptionNode {
+        SubscriptionNode::new(ProxyNode::Trojan(TrojanConfig {
+            address: "127.0.0.1".into(),
+            port,
+            password: "pw".into(),
+            transport: Default::default(),
+            tls: None,
+            remark: None,
+        }))
+    }
+
+    #[tokio::test]
+    async fn ping_nodes_with_reports_each_result() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let closed_port = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap().port()
+        };
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_cb = Arc::clone(&seen);
+        let results = ping_nodes_with(
+            &[node(open_port), node(closed_port)],
+            &PingLimiter::default(),
+            move |idx, latency| seen_cb.lock().unwrap().push((idx, latency.is_some())),
+        )
+        .await;
+
+        assert!(results[0].is_some());
+        assert!(results[1].is_none());
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, vec![(0, true), (1, false)]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::Cursor;
+
+/// Decodes the first QR code in a PNG image that holds a link.
+///
+/// Returns `None` when the image can't be read or contains no readable code
+/// with a `scheme://` payload; with several codes the first such one wins.
+pub fn decode_qr_to_uri(png_bytes: &[u8]) -> Option<String> {
+    let (width, height, luma) = decode_png_luma(png_bytes)?;
+    let mut image =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| luma[y * width + x]);
+
+    image
+        .detect_grids()
+        .iter()
+        .filter_map(|grid| grid.decode().ok())
+        .map(|(_, content)| content.trim().to_owned())
+        .find(|content| content.contains("://"))
+}
+
+fn decode_png_luma(png_bytes: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(Cursor::new(png_bytes));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().ok()?;
+    let mut buf = vec![0; reader.output_buffer_size()?];
+    let info = reader.next_frame(&mut buf).ok()?;
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return None,
+    };
+
+    let mut luma = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &buf[y * info.line_size..][..width * channels];
+        for px in row.chunks_exact(channels) {
+            let value = match channels {
+                1 | 2 => px[0],
+                _ => ((px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000) as u8,
+            };
+            // Treat transparent pixels as white background.
+            let alpha = if channels % 2 == 0 {
+                px[channels - 1]
+            } else {
+                255
+            };
+            luma.push(255 - ((255 - value as u32) * alpha as u32 / 255) as u8);
+        }
+    }
+    Some((width, height, luma))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: usize = 4;
+    const QUIET_ZONE: usize = 4;
+
+    fn qr_modules(content: &str) -> (usize, Vec<bool>) {
+        let code = qrcode::QrCode::new(content.as_bytes()).unwrap();
+        let dark = code
+            .to_colors()
+            .into_iter()
+            .map(|c| c == qrcode::Color::Dark)
+            .collect();
+        (code.width(), dark)
+    }
+
+    /// Renders codes side by side into a greyscale PNG.
+    fn qr_png(contents: &[&str]) -> Vec<u8> {
+        let codes: Vec<_> = contents.iter().map(|c| qr_modules(c)).collect();
+        let cell = codes.iter().map(|(w, _)| w + 2 * QUIET_ZONE).max().unwrap() * SCALE;
+        let (width, height) = (cell * codes.len(), cell);
+
+        let mut pixels = vec![255u8; width * height];
+        for (i, (modules, dark)) in codes.iter().enumerate() {
+            for y in 0..*modules {
+                for x in 0..*modules {
+                    if !dark[y * modules + x] {
+                        continue;
+                    }
+                    for dy in 0..SCALE {
+                        for dx in 0..SCALE {
+                            let px = i * cell + (x + QUIET_ZONE) * SCALE + dx;
+                            let py = (y + QUIET_ZONE) * SCALE + dy;
+                            pixels[py * width + px] = 0;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn test_decode_single_code() {
+        let uri = "trojan://secret@a.example.com:443#Node";
+        assert_eq!(decode_qr_to_uri(&qr_png(&[uri])).as_deref(), Some(uri));
+    }
+
+    #[test]
+    fn test_decoded_uri_parses() {
+        let uri = "vless://b831381d-6324-4d53-ad4f-8cda48b30811@example.com:443?security=tls&type=ws&path=%2Fws#Q";
+        let decoded = decode_qr_to_uri(&qr_png(&[uri])).unwrap();
+        assert!(crate::parser::parse_uri(&decoded).is_ok());
+    }
+
+    #[test]
+    fn test_multiple_codes_picks_first_link() {
+        let png = qr_png(&[
+            "just some text",
+            "ss://YWVzLTI1Ni1nY206cGFzcw@b.example.com:8388",
+        ]);
+        assert_eq!(
+            decode_qr_to_uri(&png).as_deref(),
+            Some("ss://YWVzLTI1Ni1nY206cGFzcw@b.example.com:8388")
+        );
+    }
+
+    #[test]
+    fn test_image_without_code() {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, 32, 32);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[255u8; 32 * 32]).unwrap();
+        writer.finish().unwrap();
+
+        assert!(decode_qr_to_uri(&out).is_none());
+    }
+
+    #[test]
+    fn test_not_an_image() {
+        assert!(decode_qr_to_uri(b"definitely not a png").is_none());
+    }
+}
@@ -1,20 +1,35 @@
-use std::mem::discriminant;
 use std::time::Duration;
 
 use chrono::Utc;
 use uuid::Uuid;
-use v2ray_rs_core::models::{ProxyNode, Subscription, SubscriptionNode, SubscriptionSource};
+use v2ray_rs_core::models::{
+    NodeKey, ProxyNode, Subscription, SubscriptionNode, SubscriptionSource, SubscriptionUsage,
+};
 
-use crate::fetch::{FetchError, fetch_from_file, fetch_with_client};
-use crate::parser::parse_uri;
+use crate::fetch::{
+    FetchError, FetchLimits, FetchedContent, fetch_content_with_limits, fetch_from_file,
+};
+use crate::parser::{ParseOptions, parse_uri_with};
 
-const DEFAULT_MAX_RETRIES: u32 = 3;
+pub(crate) const DEFAULT_MAX_RETRIES: u32 = 3;
 
 #[derive(Debug, Clone)]
 pub struct UpdateResult {
     pub added: usize,
     pub removed: usize,
     pub unchanged: usize,
+    /// Old nodes the update dropped, for telling the user what went away.
+    pub removed_nodes: Vec<SubscriptionNode>,
+    /// URL the content came from, when it was fetched over the network.
+    pub source_url: Option<String>,
+}
+
+/// Nodes fetched for a subscription, not yet merged into its stored copy.
+#[derive(Debug, Clone)]
+pub struct FetchedNodes {
+    pub nodes: Vec<ProxyNode>,
+    pub source_url: Option<String>,
+    pub usage: Option<SubscriptionUsage>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,13 +66,33 @@ pub fn reconcile_with_counts(
     let mut unchanged = 0;
     let mut result = Vec::new();
 
-    for new_node in new_parsed {
-        let matched = old_nodes.iter().find(|old| {
-            let old_node = &old.node;
-            old_node.address() == new_node.address()
-                && old_node.port() == new_node.port()
-                && discriminant(old_node) == discriminant(&new_node)
-        });
+    let old_keys: Vec<StoredKey> = old_nodes.iter().map(StoredKey::new).collect();
+    let new_keys: Vec<NewKey> = new_parsed.iter().map(NewKey::new).collect();
+
+    let removed_nodes: Vec<SubscriptionNode> = old_nodes
+        .iter()
+        .zip(&old_keys)
+        .filter(|(_, old)| !new_keys.iter().any(|new| old.matches(new)))
+        .map(|(node, _)| node.clone())
+        .collect();
+
+    // Providers sometimes list a node twice. Each old node's primary flag
+    // and route target go to the first copy only, so they stay unique.
+    let mut claimed = vec![false; old_nodes.len()];
+    for (mut new_node, new_key) in new_parsed.into_iter().zip(&new_keys) {
+        let matching: Vec<usize> = (0..old_keys.len())
+            .filter(|&i| old_keys[i].matches(new_key))
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&i| !claimed[i])
+            .or(matching.first().copied());
+        let claims = index.is_some_and(|i| !claimed[i]);
+        if let Some(i) = index {
+            claimed[i] = true;
+        }
+        let matched = index.map(|i| &old_nodes[i]);
 
         if matched.is_some() {
             unchanged += 1;
@@ -66,33 +101,96 @@ pub fn reconcile_with_counts(
         }
 
         let enabled = matched.map(|m| m.enabled).unwrap_or(true);
+        let favorite = matched.is_some_and(|m| m.favorite);
+        let primary = claims && matched.is_some_and(|m| m.primary);
+        let route_target = matched.filter(|_| claims).and_then(|m| m.route_target);
+        // The server is the same, so its last test still holds.
+        let last_latency_ms = matched.and_then(|m| m.last_latency_ms);
+        let latency_history = matched
+            .map(|m| m.latency_history.clone())
+            .unwrap_or_default();
+        // Keep hand-edited TLS settings across refreshes unless the provider
+        // dropped TLS from the node altogether.
+        let tls_override = matched
+            .and_then(|m| m.tls_override.clone())
+            .filter(|tls| new_node.apply_tls_override(tls).is_ok());
         result.push(SubscriptionNode {
             node: new_node,
             enabled,
-            last_latency_ms: None,
+            last_latency_ms,
+            latency_history,
+            tls_override,
+            favorite,
+            primary,
+            route_target,
         });
     }
 
-    let removed = old_nodes.len().saturating_sub(unchanged);
-
     let update_result = UpdateResult {
         added,
-        removed,
+        removed: removed_nodes.len(),
         unchanged,
+        removed_nodes,
+        source_url: None,
     };
 
     (result, update_result)
 }
 
+/// Key of a stored node. A TLS override replaced the provider's SNI, so an
+/// overridden node matches on everything else.
+struct StoredKey {
+    key: NodeKey,
+    overridden: bool,
+}
+
+impl StoredKey {
+    fn new(old: &SubscriptionNode) -> Self {
+        let mut key = old.node.key();
+        let overridden = old.tls_override.is_some();
+        if overridden {
+            key.sni = None;
+        }
+        Self { key, overridden }
+    }
+
+    fn matches(&self, new: &NewKey) -> bool {
+        if self.overridden {
+            new.without_sni.as_ref() == Some(&self.key)
+        } else {
+            self.key == new.key
+        }
+    }
+}
+
+/// Key of a fetched node, plus its key without SNI when it uses TLS and so
+/// can take an override.
+struct NewKey {
+    key: NodeKey,
+    without_sni: Option<NodeKey>,
+}
+
+impl NewKey {
+    fn new(node: &ProxyNode) -> Self {
+        let key = node.key();
+        let without_sni = node.tls().map(|_| NodeKey {
+            sni: None,
+            ..key.clone()
+        });
+        Self { key, without_sni }
+    }
+}
+
 pub async fn fetch_with_retry(
     client: &reqwest::Client,
     url: &str,
     max_retries: u32,
-) -> Result<String, FetchError> {
+    limits: FetchLimits,
+) -> Result<FetchedContent, FetchError> {
     let mut last_error = None;
 
     for attempt in 0..=max_retries {
-        match fetch_with_client(client, url).await {
+        match fetch_content_with_limits(client, url, limits).await {
             Ok(content) => return Ok(content),
             Err(e) => {
                 last_error = Some(e);
@@ -107,32 +205,122 @@ pub async fn fetch_with_retry(
     Err(last_error.unwrap())
 }
 
+/// Tries each URL in order, returning the first content fetched and its URL.
+/// Fails with the last URL's error if none succeeds.
+pub async fn fetch_first_available(
+    client: &reqwest::Client,
+    urls: &[&str],
+    max_retries: u32,
+    limits: FetchLimits,
+) -> Result<(FetchedContent, String), FetchError> {
+    let mut last_error = FetchError::NetworkError("no URLs to fetch".into());
+    for url in urls {
+        match fetch_with_retry(client, url, max_retries, limits).await {
+            Ok(content) => return Ok((content, (*url).to_owned())),
+            Err(e) => {
+                log::warn!("fetch {url} failed: {e}");
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Refreshes the node list, recording the outcome in `last_error`.
 pub async fn update_subscription(
     client: &reqwest::Client,
     subscription: &mut Subscription,
 ) -> Result<UpdateResult, FetchError> {
-    let raw_content = match &subscription.source {
+    update_subscription_with_retries(client, subscription, DEFAULT_MAX_RETRIES).await
+}
+
+async fn update_subscription_with_retries(
+    client: &reqwest::Client,
+    subscription: &mut Subscription,
+    max_retries: u32,
+) -> Result<UpdateResult, FetchError> {
+    let fetched = fetch_nodes(
+        client,
+        subscription,
+        max_retries,
+        FetchLimits::default(),
+        ParseOptions::default(),
+    )
+    .await;
+    apply_fetched(subscription, fetched)
+}
+
+/// Fetches and parses a subscription's nodes without touching it, so the
+/// result can be applied to whatever copy is current once the fetch is done.
+/// Manual subscriptions have nothing to fetch and yield `None`.
+pub async fn fetch_nodes(
+    client: &reqwest::Client,
+    subscription: &Subscription,
+    max_retries: u32,
+    limits: FetchLimits,
+    options: ParseOptions,
+) -> Result<Option<FetchedNodes>, FetchError> {
+    let (raw_content, source_url, usage) = match &subscription.source {
         SubscriptionSource::Url { url } => {
-            fetch_with_retry(client, url, DEFAULT_MAX_RETRIES).await?
+            let urls: Vec<&str> = std::iter::once(url.as_str())
+                .chain(subscription.mirror_urls.iter().map(String::as_str))
+                .collect();
+            let (fetched, used) = fetch_first_available(client, &urls, max_retries, limits).await?;
+            (fetched.body, Some(used), fetched.usage)
         }
-        SubscriptionSource::File { path } => fetch_from_file(path)?,
+        SubscriptionSource::File { path } => (fetch_from_file(path)?, None, None),
+        SubscriptionSource::Manual => return Ok(None),
     };
 
     let uris = crate::fetch::decode_subscription_content(&raw_content);
 
     let mut parsed_nodes = Vec::new();
     for uri in uris {
-        if let Ok(node) = parse_uri(&uri) {
+        if let Ok(node) = parse_uri_with(&uri, options) {
             parsed_nodes.push(node);
         }
     }
+    if parsed_nodes.is_empty() {
+        return Err(FetchError::NoValidLinks);
+    }
 
-    let (new_nodes, result) = reconcile_with_counts(&subscription.nodes, parsed_nodes);
-
-    subscription.nodes = new_nodes;
-    subscription.last_updated = Some(Utc::now());
+    Ok(Some(FetchedNodes {
+        nodes: parsed_nodes,
+        source_url,
+        usage,
+    }))
+}
 
-    Ok(result)
+/// Reconciles a fetch against `subscription`'s current nodes, recording the
+/// outcome in `last_error`.
+pub fn apply_fetched(
+    subscription: &mut Subscription,
+    fetched: Result<Option<FetchedNodes>, FetchError>,
+) -> Result<UpdateResult, FetchError> {
+    match fetched {
+        Ok(Some(fetched)) => {
+            let (new_nodes, mut result) = reconcile_with_counts(&subscription.nodes, fetched.nodes);
+            result.source_url = fetched.source_url;
+            subscription.nodes = new_nodes;
+            subscription.last_updated = Some(Utc::now());
+            subscription.usage = fetched.usage;
+            subscription.last_error = None;
+            subscription.last_error_at = None;
+            Ok(result)
+        }
+        Ok(None) => Ok(UpdateResult {
+            added: 0,
+            removed: 0,
+            unchanged: subscription.nodes.len(),
+            removed_nodes: Vec::new(),
+            source_url: None,
+        }),
+        Err(e) => {
+            subscription.last_error = Some(e.to_string());
+            subscription.last_error_at = Some(Utc::now());
+            Err(e)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -179,9 +367,11 @@ mod tests {
     #[test]
     fn test_reconcile_preserves_enabled() {
         let old = vec![SubscriptionNode {
-            node: vless_node("example.com", 443),
             enabled: false,
-            last_latency_ms: None,
+            favorite: true,
+            primary: true,
+            route_target: Some(uuid::Uuid::nil()),
+            ..SubscriptionNode::new(vless_node("example.com", 443))
         }];
 
         let new_parsed = vec![vless_node("example.com", 443)];
@@ -190,16 +380,37 @@ mod tests {
 
         assert_eq!(result.len(), 1);
         assert!(!result[0].enabled);
+        assert!(result[0].favorite);
+        assert!(result[0].primary);
+        assert_eq!(result[0].route_target, Some(uuid::Uuid::nil()));
     }
 
     #[test]
-    fn test_reconcile_adds_new_nodes() {
+    fn test_reconcile_keeps_latency() {
+        let tested = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let history = vec![(tested, Some(120)), (tested, Some(87))];
         let old = vec![SubscriptionNode {
-            node: vless_node("a.com", 443),
-            enabled: true,
-            last_latency_ms: None,
+            last_latency_ms: Some(87),
+            latency_history: history.clone(),
+            ..SubscriptionNode::new(vless_node("example.com", 443))
         }];
 
+        let (result, counts) = reconcile_with_counts(
+            &old,
+            vec![vless_node("example.com", 443), vless_node("b.com", 443)],
+        );
+
+        assert_eq!(counts.unchanged, 1);
+        assert_eq!(result[0].last_latency_ms, Some(87));
+        assert_eq!(result[0].latency_history, history);
+        assert_eq!(result[1].last_latency_ms, None);
+        assert!(result[1].latency_history.is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_adds_new_nodes() {
+        let old = vec![SubscriptionNode::new(vless_node("a.com", 443))];
+
         let new_parsed = vec![vless_node("a.com", 443), vless_node("b.com", 443)];
 
         let result = reconcile_nodes(&old, new_parsed);
@@ -208,37 +419,31 @@ mod tests {
         assert_eq!(result[0].node.address(), "a.com");
         assert_eq!(result[1].node.address(), "b.com");
         assert!(result[1].enabled);
+        assert!(!result[1].favorite);
     }
 
     #[test]
     fn test_reconcile_removes_missing() {
         let old = vec![
-            SubscriptionNode {
-                node: vless_node("a.com", 443),
-                enabled: true,
-                last_latency_ms: None,
-            },
-            SubscriptionNode {
-                node: vless_node("b.com", 443),
-                enabled: true,
-                last_latency_ms: None,
-            },
+            SubscriptionNode::new(vless_node("a.com", 443)),
+            SubscriptionNode::new(vless_node("b.com", 443)),
         ];
 
         let new_parsed = vec![vless_node("a.com", 443)];
 
-        let result = reconcile_nodes(&old, new_parsed);
+        let (result, counts) = reconcile_with_counts(&old, new_parsed);
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].node.address(), "a.com");
+        assert_eq!(counts.removed, 1);
+        assert_eq!(counts.removed_nodes, [old[1].clone()]);
     }
 
     #[test]
     fn test_reconcile_all_replaced() {
         let old = vec![SubscriptionNode {
-            node: vless_node("a.com", 443),
             enabled: false,
-            last_latency_ms: None,
+            ..SubscriptionNode::new(vless_node("a.com", 443))
         }];
 
         let new_parsed = vec![vless_node("b.com", 443)];
@@ -264,11 +469,7 @@ mod tests {
 
     #[test]
     fn test_reconcile_empty_new() {
-        let old = vec![SubscriptionNode {
-            node: vless_node("a.com", 443),
-            enabled: true,
-            last_latency_ms: None,
-        }];
+        let old = vec![SubscriptionNode::new(vless_node("a.com", 443))];
 
         let new_parsed = vec![];
 
@@ -277,18 +478,97 @@ mod tests {
         assert_eq!(result.len(), 0);
     }
 
+    #[test]
+    fn test_reconcile_reapplies_tls_override() {
+        let with_tls = |sni: &str| {
+            let mut node = vless_node("a.com", 443);
+            if let ProxyNode::Vless(c) = &mut node {
+                c.tls = Some(v2ray_rs_core::models::TlsSettings {
+                    server_name: Some(sni.into()),
+                    alpn: vec![],
+                    verify: true,
+                    fingerprint: None,
+                    reality: None,
+                });
+            }
+            node
+        };
+        let tls = v2ray_rs_core::models::TlsOverride {
+            server_name: "custom.example.com".into(),
+            fingerprint: None,
+            alpn: vec![],
+            verify: false,
+        };
+        let mut edited = with_tls("custom.example.com");
+        edited.apply_tls_override(&tls).unwrap();
+        let old = vec![SubscriptionNode {
+            tls_override: Some(tls.clone()),
+            ..SubscriptionNode::new(edited)
+        }];
+
+        let result = reconcile_nodes(&old, vec![with_tls("provider.example.com")]);
+        assert_eq!(result[0].tls_override, Some(tls));
+        let applied = result[0].node.tls().unwrap();
+        assert_eq!(applied.server_name.as_deref(), Some("custom.example.com"));
+        assert!(!applied.verify);
+
+        let result = reconcile_nodes(&old, vec![vless_node("a.com", 443)]);
+        assert_eq!(result[0].tls_override, None);
+    }
+
+    #[test]
+    fn test_reconcile_tells_apart_users_of_one_server() {
+        let user = |uuid: &str| {
+            let mut node = vless_node("multi.example.com", 443);
+            if let ProxyNode::Vless(c) = &mut node {
+                c.uuid = uuid.into();
+            }
+            node
+        };
+        let stored = |node: ProxyNode, enabled: bool| SubscriptionNode {
+            enabled,
+            ..SubscriptionNode::new(node)
+        };
+        let old = vec![stored(user("alice"), false), stored(user("bob"), true)];
+
+        let (result, counts) = reconcile_with_counts(&old, vec![user("bob"), user("alice")]);
+        assert_eq!(result[0].node, user("bob"));
+        assert!(result[0].enabled);
+        assert_eq!(result[1].node, user("alice"));
+        assert!(!result[1].enabled);
+        assert_eq!(counts.unchanged, 2);
+
+        let (result, counts) = reconcile_with_counts(&old, vec![user("carol")]);
+        assert!(result[0].enabled);
+        assert_eq!((counts.added, counts.removed), (1, 2));
+    }
+
+    #[test]
+    fn test_reconcile_duplicates_do_not_share_primary_or_target() {
+        let target = uuid::Uuid::new_v4();
+        let old = vec![SubscriptionNode {
+            primary: true,
+            route_target: Some(target),
+            enabled: false,
+            ..SubscriptionNode::new(vless_node("a.com", 443))
+        }];
+
+        let result = reconcile_nodes(&old, vec![vless_node("a.com", 443); 2]);
+
+        assert_eq!(result[0].route_target, Some(target));
+        assert!(result[0].primary);
+        assert_eq!(result[1].route_target, None);
+        assert!(!result[1].primary);
+        assert!(!result[1].enabled);
+    }
+
     #[test]
     fn test_update_result_counts() {
         let old = vec![
+            SubscriptionNode::new(vless_node("a.com", 443)),
             SubscriptionNode {
-                node: vless_node("a.com", 443),
-                enabled: true,
-                last_latency_ms: None,
-            },
-            SubscriptionNode {
-                node: vmess_node("b.com", 8443),
                 enabled: false,
-                last_latency_ms: None,
+                ..SubscriptionNode::new(vmess_node("b.com", 8443))
             },
         ];
 
@@ -300,4 +580,117 @@ mod tests {
         assert_eq!(result.removed, 1);
         assert_eq!(result.unchanged, 1);
     }
+
+    #[tokio::test]
+    async fn test_update_subscription_from_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nodes.txt");
+        std::fs::write(
+            &path,
+            "trojan://secret@a.example.com:443#A\nvless://uuid@b.example.com:443#B\n",
+        )
+        .unwrap();
+
+        let mut sub = Subscription::new_from_file("Local", path.to_string_lossy().into_owned());
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = reqwest::Client::new();
+        let result = update_subscription(&client, &mut sub).await.unwrap();
+
+        assert_eq!(result.added, 2);
+        assert_eq!(sub.nodes.len(), 2);
+        assert!(sub.last_updated.is_some());
+    }
+
+    async fn serve(status: &'static str, body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}/sub")
+    }
+
+    #[tokio::test]
+    async fn test_update_falls_back_to_mirror() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let primary = serve("503 Service Unavailable", "blocked").await;
+        let mirror = serve("200 OK", "trojan://secret@mirror.example.com:443#M\n").await;
+
+        let mut sub = Subscription::new_from_url("Mirrored", primary);
+        sub.mirror_urls = vec![mirror.clone()];
+        let client = reqwest::Client::new();
+        let result = update_subscription_with_retries(&client, &mut sub, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(result.source_url.as_deref(), Some(mirror.as_str()));
+        assert_eq!(sub.nodes.len(), 1);
+        assert_eq!(sub.nodes[0].node.address(), "mirror.example.com");
+        assert!(sub.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_fails_when_all_mirrors_fail() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let primary = serve("503 Service Unavailable", "").await;
+        let mirror = serve("404 Not Found", "").await;
+
+        let mut sub = Subscription::new_from_url("Broken", primary);
+        sub.mirror_urls = vec![mirror];
+        let client = reqwest::Client::new();
+        let err = update_subscription_with_retries(&client, &mut sub, 0)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, FetchError::HttpError { status: 404, .. }));
+        assert!(sub.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_manual_subscription_keeps_nodes() {
+        let mut sub = Subscription::new_manual("Imported");
+        sub.nodes
+            .push(SubscriptionNode::new(vless_node("a.com", 443)));
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = reqwest::Client::new();
+
+        let result = update_subscription(&client, &mut sub).await.unwrap();
+        assert_eq!(result.unchanged, 1);
+        assert_eq!(sub.nodes.len(), 1);
+        assert!(sub.last_updated.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_update_sets_last_error_until_success() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nodes.txt");
+        let mut sub = Subscription::new_from_file("Local", path.to_string_lossy().into_owned());
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = reqwest::Client::new();
+
+        assert!(update_subscription(&client, &mut sub).await.is_err());
+        assert!(sub.last_error.as_deref().unwrap().contains("file error"));
+        assert!(sub.last_error_at.is_some());
+
+        std::fs::write(&path, "not a proxy link\n").unwrap();
+        let err = update_subscription(&client, &mut sub).await.unwrap_err();
+        assert!(matches!(err, FetchError::NoValidLinks));
+        assert_eq!(sub.last_error, Some(err.to_string()));
+
+        std::fs::write(&path, "trojan://secret@a.example.com:443#A\n").unwrap();
+        update_subscription(&client, &mut sub).await.unwrap();
+        assert!(sub.last_error.is_none());
+        assert!(sub.last_error_at.is_none());
+        assert_eq!(sub.nodes.len(), 1);
+    }
 }
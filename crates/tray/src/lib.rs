@@ -2,5 +2,5 @@ mod icons;
 mod notification;
 mod tray;
 
-pub use notification::Notifier;
+pub use notification::{NotificationSink, Notifier};
 pub use tray::{TrayAction, TrayHandle, TrayService};
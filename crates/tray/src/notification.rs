@@ -1,33 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
 use notify_rust::{Notification, Timeout};
 use v2ray_rs_process::ProcessState;
 
 const NOTIFICATION_TIMEOUT_MS: u32 = 5000;
 
+/// Where notifications end up; the desktop in the app, a recorder in tests.
+pub trait NotificationSink: Send + Sync {
+    fn send(&self, summary: &str, body: &str);
+}
+
+struct DesktopSink;
+
+impl NotificationSink for DesktopSink {
+    fn send(&self, summary: &str, body: &str) {
+        let _ = Notification::new()
+            .appname("V2Ray Manager")
+            .summary(summary)
+            .body(body)
+            .icon("network-vpn")
+            .timeout(Timeout::Milliseconds(NOTIFICATION_TIMEOUT_MS))
+            .show();
+    }
+}
+
+/// Clones share the enabled flag and active node, so toggling the setting
+/// takes effect for notifications already on their way.
 #[derive(Clone)]
 pub struct Notifier {
-    enabled: bool,
+    enabled: Arc<AtomicBool>,
+    active_node: Arc<Mutex<Option<String>>>,
+    sink: Arc<dyn NotificationSink>,
 }
 
 impl Notifier {
     pub fn new(enabled: bool) -> Self {
-        Self { enabled }
+        Self::with_sink(enabled, Arc::new(DesktopSink))
     }
 
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+    pub fn with_sink(enabled: bool, sink: Arc<dyn NotificationSink>) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            active_node: Arc::new(Mutex::new(None)),
+            sink,
+        }
     }
 
-    pub fn on_state_change(&self, from: &ProcessState, to: &ProcessState) {
-        if !self.enabled {
-            return;
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Node named in the next "connected" notification.
+    pub fn set_active_node(&self, node: Option<String>) {
+        if let Ok(mut active) = self.active_node.lock() {
+            *active = node;
         }
+    }
 
+    pub fn on_state_change(&self, from: &ProcessState, to: &ProcessState) {
         match to {
             ProcessState::Running => {
-                self.send("Proxy Connected", "Backend process started successfully");
+                let node = self.active_node.lock().ok().and_then(|n| n.clone());
+                let body = match node {
+                    Some(node) => format!("Connected to {node}"),
+                    None => "Backend process started successfully".to_owned(),
+                };
+                self.send("Proxy Connected", &body);
             }
             ProcessState::Error(msg) => {
-                self.send("Proxy Error", msg);
+                self.send("Proxy Error", &format!("Connection failed: {msg}"));
             }
             ProcessState::Stopped if matches!(from, ProcessState::Running) => {
                 self.send("Proxy Disconnected", "Backend process stopped unexpectedly");
@@ -36,13 +82,113 @@ impl Notifier {
         }
     }
 
+    /// Reports the outcome of a background subscription update; silent if
+    /// nothing was due.
+    pub fn on_subscriptions_updated(&self, updated: usize, failed: usize) {
+        let body = match (updated, failed) {
+            (0, 0) => return,
+            (updated, 0) => format!("{updated} subscription(s) updated"),
+            (0, failed) => format!("{failed} subscription(s) failed to update"),
+            (updated, failed) => format!("{updated} updated, {failed} failed"),
+        };
+        self.send("Subscriptions Updated", &body);
+    }
+
+    pub fn on_subscription_expiring(&self, name: &str, detail: &str) {
+        self.send("Subscription Expiring", &format!("{name}: {detail}"));
+    }
+
     fn send(&self, summary: &str, body: &str) {
-        let _ = Notification::new()
-            .appname("V2Ray Manager")
-            .summary(summary)
-            .body(body)
-            .icon("network-vpn")
-            .timeout(Timeout::Milliseconds(NOTIFICATION_TIMEOUT_MS))
-            .show();
+        if self.is_enabled() {
+            self.sink.send(summary, body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<(String, String)>>);
+
+    impl NotificationSink for RecordingSink {
+        fn send(&self, summary: &str, body: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((summary.to_owned(), body.to_owned()));
+        }
+    }
+
+    impl RecordingSink {
+        fn take(&self) -> Vec<(String, String)> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    fn fire_all(notifier: &Notifier) {
+        notifier.on_state_change(&ProcessState::Starting, &ProcessState::Running);
+        notifier.on_state_change(
+            &ProcessState::Starting,
+            &ProcessState::Error("exit code 1".into()),
+        );
+        notifier.on_subscriptions_updated(2, 1);
+    }
+
+    #[test]
+    fn test_disabled_notifier_is_silent() {
+        let sink = Arc::new(RecordingSink::default());
+        let notifier = Notifier::with_sink(false, sink.clone());
+        fire_all(&notifier);
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn test_enabled_notifier_records_messages() {
+        let sink = Arc::new(RecordingSink::default());
+        let notifier = Notifier::with_sink(true, sink.clone());
+        notifier.set_active_node(Some("Tokyo 1".into()));
+        fire_all(&notifier);
+
+        let expected = [
+            ("Proxy Connected", "Connected to Tokyo 1"),
+            ("Proxy Error", "Connection failed: exit code 1"),
+            ("Subscriptions Updated", "2 updated, 1 failed"),
+        ];
+        let sent = sink.take();
+        assert_eq!(sent.len(), expected.len());
+        for ((summary, body), (want_summary, want_body)) in sent.iter().zip(expected) {
+            assert_eq!(summary, want_summary);
+            assert_eq!(body, want_body);
+        }
+    }
+
+    #[test]
+    fn test_toggle_applies_to_existing_clones() {
+        let sink = Arc::new(RecordingSink::default());
+        let notifier = Notifier::with_sink(true, sink.clone());
+        let clone = notifier.clone();
+
+        notifier.set_enabled(false);
+        clone.on_state_change(&ProcessState::Starting, &ProcessState::Running);
+        assert!(sink.take().is_empty());
+
+        notifier.set_enabled(true);
+        clone.on_subscriptions_updated(1, 0);
+        assert_eq!(
+            sink.take(),
+            vec![(
+                "Subscriptions Updated".to_owned(),
+                "1 subscription(s) updated".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_nothing_due_sends_nothing() {
+        let sink = Arc::new(RecordingSink::default());
+        Notifier::with_sink(true, sink.clone()).on_subscriptions_updated(0, 0);
+        assert!(sink.take().is_empty());
     }
 }
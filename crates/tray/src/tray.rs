@@ -2,7 +2,6 @@ use std::sync::mpsc;
 
 use ksni::menu::{MenuItem, StandardItem};
 use ksni::{Handle, Tray, TrayMethods};
-use tokio::sync::broadcast;
 use v2ray_rs_process::{ProcessEvent, ProcessState};
 
 use crate::icons;
@@ -94,7 +93,9 @@ impl Tray for AppTray {
             ProcessState::Starting => "Status: Connecting...",
             ProcessState::Running => "Status: Connected",
             ProcessState::Stopping => "Status: Disconnecting...",
-            ProcessState::Error(msg) => return self.menu_with_error(toggle, msg),
+            ProcessState::Error(failure) => {
+                return self.menu_with_error(toggle, &failure.summary);
+            }
         };
 
         let show_window = {
@@ -191,7 +192,7 @@ pub struct TrayService;
 
 impl TrayService {
     pub async fn spawn(
-        mut event_rx: broadcast::Receiver<ProcessEvent>,
+        mut event_rx: tokio::sync::mpsc::UnboundedReceiver<ProcessEvent>,
         notifier: Notifier,
     ) -> Result<TrayHandle, ksni::Error> {
         let (action_tx, action_rx) = mpsc::channel();
@@ -205,24 +206,18 @@ impl TrayService {
         let update_handle = handle.clone();
 
         tokio::spawn(async move {
-            loop {
-                match event_rx.recv().await {
-                    Ok(event) => {
-                        if let ProcessEvent::StateChanged { from, to } = event {
-                            let state = to.clone();
-                            update_handle
-                                .update(move |tray| {
-                                    tray.process_state = state;
-                                })
-                                .await;
-                            let n = notifier.clone();
-                            tokio::task::spawn_blocking(move || {
-                                n.on_state_change(&from, &to);
-                            });
-                        }
-                    }
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                    Err(broadcast::error::RecvError::Closed) => break,
+            while let Some(event) = event_rx.recv().await {
+                if let ProcessEvent::StateChanged { from, to } = event {
+                    let state = to.clone();
+                    update_handle
+                        .update(move |tray| {
+                            tray.process_state = state;
+                        })
+                        .await;
+                    let n = notifier.clone();
+                    tokio::task::spawn_blocking(move || {
+                        n.on_state_change(&from, &to);
+                    });
                 }
             }
         });
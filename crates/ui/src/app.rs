@@ -1,34 +1,63 @@
-use std::sync::Mutex;
-use std::time::Duration;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use adw::prelude::*;
 use gtk::glib;
 use relm4::adw;
 use relm4::prelude::*;
-use tokio::sync::broadcast;
-
-use v2ray_rs_core::config::ConfigWriter;
-use v2ray_rs_core::models::AppSettings;
-use v2ray_rs_core::persistence::{self, AppPaths};
-use v2ray_rs_process::{ProcessEvent, ProcessState};
-use v2ray_rs_tray::{TrayAction, TrayHandle};
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use v2ray_rs_core::backend::{Revalidation, revalidate_backend};
+use v2ray_rs_core::config::{
+    ConfigDiff, ConfigWriter, DiffLine, NodeGroup, NodeSupport, RouteTarget, active_nodes,
+    proxies_nothing, proxy_env_exports, unrouted_rules,
+};
+use v2ray_rs_core::geodata::GeodataManager;
+use v2ray_rs_core::models::{
+    AppSettings, ConnectionAction, ConnectionEvent, Language, NodeKey, NodeSort, ProxyNode,
+    RoutingRule, SubscriptionSort, connection_sessions,
+};
+use v2ray_rs_core::persistence::{self, AppPaths, DEFAULT_PROFILE};
+use v2ray_rs_process::{
+    ControlRequest, ControlResponse, ControlServer, LogLine, PidFile, ProcessError, ProcessEvent,
+    ProcessState, ResourceStats, Status, StatusFile, next_log_line, send_request,
+};
+use v2ray_rs_subscription::export::export_enabled_nodes;
+use v2ray_rs_subscription::health::test_node;
+use v2ray_rs_subscription::manager::FailureKind;
+use v2ray_rs_tray::{Notifier, TrayAction, TrayHandle};
 
 static TRAY_HANDLE: Mutex<Option<TrayHandle>> = Mutex::new(None);
-static TRAY_EVENT_TX: Mutex<Option<broadcast::Sender<ProcessEvent>>> = Mutex::new(None);
+static TRAY_EVENT_TX: Mutex<Option<mpsc::UnboundedSender<ProcessEvent>>> = Mutex::new(None);
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+static CONTROL_SERVER: Mutex<Option<ControlServer>> = Mutex::new(None);
 
 const APP_ICON_PNG: &[u8] = include_bytes!("../../../assets/v2ray-rs.png");
 const DEFAULT_WINDOW_WIDTH: i32 = 900;
 const DEFAULT_WINDOW_HEIGHT: i32 = 650;
+const MIN_WINDOW_WIDTH: i32 = 480;
+const MIN_WINDOW_HEIGHT: i32 = 360;
+const DEFAULT_PANED_POSITION: i32 = 380;
+const MIN_PANED_POSITION: i32 = 120;
 const TRAY_POLL_INTERVAL: Duration = Duration::from_millis(200);
-const EVENT_CHANNEL_CAPACITY: usize = 16;
-
-use crate::logs::{LogsMsg, LogsPage};
-use crate::subscriptions::{SubscriptionsMsg, SubscriptionsOutput, SubscriptionsPage};
+const QUIT_TIMEOUT: Duration = Duration::from_secs(7);
+const RESOURCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const STOP_PREVIOUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+use crate::i18n::{tr, trf};
+use crate::logs::{LogsMsg, LogsOutput, LogsPage};
+use crate::subscriptions::{
+    SubscriptionsMsg, SubscriptionsOutput, SubscriptionsPage, parse_options,
+};
 use crate::wizard::OnboardingWizard;
 
 pub struct App {
     settings: AppSettings,
     paths: AppPaths,
+    base_paths: AppPaths,
+    profile: String,
+    pending_profile: Option<String>,
     subscriptions_page: Controller<SubscriptionsPage>,
     logs_page: Controller<LogsPage>,
     show_wizard: bool,
@@ -37,20 +66,39 @@ pub struct App {
     process_handle: Option<ProcessHandle>,
     process_state: ProcessState,
     reconnect_pending: bool,
+    quitting: bool,
     connected: bool,
     button_sensitive: bool,
     has_active_nodes: bool,
     toast_overlay: adw::ToastOverlay,
+    paned: gtk::Paned,
+    main_menu: gtk::gio::Menu,
+    toggle_action: gtk::gio::SimpleAction,
+    refresh_action: gtk::gio::SimpleAction,
+    proxy_env_action: gtk::gio::SimpleAction,
+    review_action: gtk::gio::SimpleAction,
+    active_node: String,
+    connected_since: Option<Instant>,
+    uptime: Duration,
+    uptime_timer: Option<glib::SourceId>,
 }
 
 struct ProcessHandle {
-    cmd_tx: tokio::sync::mpsc::Sender<ProcessCmd>,
+    cmd_tx: mpsc::Sender<ProcessCmd>,
 }
 
 enum ProcessCmd {
     Stop,
 }
 
+/// What the config is generated from.
+struct ActiveConfig {
+    support: NodeSupport,
+    groups: Vec<NodeGroup>,
+    targets: Vec<RouteTarget>,
+    rules: Vec<RoutingRule>,
+}
+
 #[derive(Debug)]
 pub enum AppMsg {
     OnboardingComplete(AppSettings, Option<(String, String)>),
@@ -61,18 +109,480 @@ pub enum AppMsg {
     CloseRequested,
     TrayShowWindow,
     TrayQuit,
+    QuitConfirmed,
+    ForceQuit,
     ActiveNodesChanged(bool),
     ProcessStateChanged(ProcessState),
-    ProcessLogLine(String),
+    ProcessLogLine(LogLine),
+    ResourceUsage(ResourceStats),
     OpenPreferences,
+    RefreshSubscriptions,
+    FocusSearch,
+    ViewConfig,
+    ViewHistory,
+    ReviewChanges,
+    Reconnect,
+    ShowProfiles,
+    SwitchProfile(String),
+    CreateProfile(String),
+    CopyProxyEnv,
+    ExportNodes,
+    LogViewChanged(bool, bool),
+    LogsSaved(Result<std::path::PathBuf, String>),
+    TestNode(Uuid, Box<ProxyNode>),
+    NodeTestDone(Uuid, NodeKey, String, Result<Duration, String>),
+    SubscriptionsAutoUpdated {
+        updated: usize,
+        failed: usize,
+    },
+    SubscriptionExpiring {
+        name: String,
+        detail: String,
+    },
+    SortChanged(Option<NodeSort>, Option<SubscriptionSort>),
+    SubscriptionUpdated(String),
+    SubscriptionRefreshFailed {
+        id: Uuid,
+        message: String,
+        kind: FailureKind,
+    },
+    PreviousBackendRunning(u32),
+    StopPreviousBackend,
+    PreviousBackendStopped(Result<(), String>),
+    /// Re-checks the stored backend binary, e.g. after it was uninstalled.
+    CheckBackend,
+    UptimeTick,
+    Control(ControlRequest, oneshot::Sender<ControlResponse>),
 }
 
 impl App {
+    fn sync_actions(&self) {
+        self.toggle_action
+            .set_enabled(self.button_sensitive && (self.connected || self.has_active_nodes));
+        let locked = matches!(
+            self.process_state,
+            ProcessState::Running | ProcessState::Starting
+        );
+        self.refresh_action.set_enabled(!locked);
+        self.proxy_env_action
+            .set_enabled(self.process_state == ProcessState::Running);
+        self.review_action
+            .set_enabled(self.process_state == ProcessState::Running);
+    }
+
+    /// Enabled nodes, split by whether the selected backend can run them,
+    /// and the enabled routing rules.
+    fn load_active_config(&self) -> ActiveConfig {
+        let subscriptions = persistence::load_subscriptions(&self.paths).unwrap_or_default();
+        let (support, groups, targets) =
+            active_nodes(self.settings.backend.backend_type, &subscriptions);
+        let rules = persistence::load_routing_rules(&self.paths).unwrap_or_default();
+        ActiveConfig {
+            support,
+            groups,
+            targets,
+            rules: rules.enabled_rules().cloned().collect(),
+        }
+    }
+
+    fn config_writer(&self, groups: Vec<NodeGroup>, targets: Vec<RouteTarget>) -> ConfigWriter {
+        ConfigWriter::new(&self.settings, &self.paths)
+            .with_groups(groups)
+            .with_targets(targets)
+    }
+
+    /// Makes sure the stored node selection is the one on screen before it is
+    /// used to connect. Returns false if it could not be saved.
+    fn sync_selection(&self) -> bool {
+        let shown = self.subscriptions_page.model().subscriptions().to_vec();
+        match persistence::sync_selection(&self.paths, &shown) {
+            Ok(false) => true,
+            Ok(true) => {
+                log::warn!("stored node selection was out of date; saved it before connecting");
+                self.show_toast(&tr("Unsaved node changes were saved before connecting"));
+                true
+            }
+            Err(e) => {
+                log::error!("save subscriptions: {e}");
+                self.show_toast(&trf(
+                    "Node changes could not be saved, not connecting: {error}",
+                    &[("error", &e)],
+                ));
+                false
+            }
+        }
+    }
+
+    fn save_window_state(&mut self) {
+        let maximized = self.window.is_maximized();
+        self.settings.window_maximized = maximized;
+        if !maximized {
+            let (width, height) = self.window.default_size();
+            self.settings.window_width = Some(width);
+            self.settings.window_height = Some(height);
+        }
+        self.settings.paned_position = Some(self.paned.position());
+        if let Err(e) = persistence::save_settings(&self.paths, &self.settings) {
+            log::error!("save settings: {e}");
+        }
+    }
+
+    fn request_quit(&mut self, sender: &ComponentSender<Self>) {
+        self.save_window_state();
+        if self.process_state != ProcessState::Running {
+            sender.input(AppMsg::QuitConfirmed);
+            return;
+        }
+
+        self.window.set_visible(true);
+        self.window.present();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(tr("Quit V2Ray Manager?"))
+            .body(tr("The proxy is connected. Quitting will disconnect it."))
+            .build();
+        dialog.add_response("cancel", &tr("Cancel"));
+        dialog.add_response("quit", &tr("Disconnect and Quit"));
+        dialog.set_response_appearance("quit", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let s = sender.input_sender().clone();
+        dialog.connect_response(None, move |_, response| {
+            if response == "quit" {
+                s.emit(AppMsg::QuitConfirmed);
+            }
+        });
+        dialog.present(Some(&self.window));
+    }
+
+    /// Offers the enabled nodes as a base64 subscription, copied or saved.
+    fn show_export_dialog(&self) {
+        let export = export_enabled_nodes(self.subscriptions_page.model().subscriptions());
+        if export.exported == 0 {
+            self.show_toast(&tr("No enabled nodes can be exported"));
+            return;
+        }
+        let mut body = trf(
+            "{count} enabled nodes as a base64 subscription, for importing into another client.",
+            &[("count", &export.exported)],
+        );
+        if export.skipped > 0 {
+            body.push_str("\n\n");
+            body.push_str(&trf(
+                "{count} nodes have settings share links cannot carry and are left out.",
+                &[("count", &export.skipped)],
+            ));
+        }
+        let dialog = adw::AlertDialog::builder()
+            .heading(tr("Export Enabled Nodes"))
+            .body(body)
+            .build();
+        dialog.add_response("cancel", &tr("Cancel"));
+        dialog.add_response("save", &tr("Save to File…"));
+        dialog.add_response("copy", &tr("Copy"));
+        dialog.set_response_appearance("copy", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("copy"));
+        dialog.set_close_response("cancel");
+
+        let window = self.window.clone();
+        let toast_overlay = self.toast_overlay.clone();
+        dialog.connect_response(None, move |_, response| match response {
+            "copy" => {
+                window.clipboard().set_text(&export.blob);
+                toast_overlay.add_toast(adw::Toast::new(&tr("Copied to clipboard")));
+            }
+            "save" => {
+                let file_dialog = gtk::FileDialog::builder()
+                    .title(tr("Export Enabled Nodes"))
+                    .initial_name("nodes.txt")
+                    .build();
+                let blob = export.blob.clone();
+                let toast_overlay = toast_overlay.clone();
+                file_dialog.save(Some(&window), gtk::gio::Cancellable::NONE, move |result| {
+                    let Some(path) = result.ok().and_then(|f| f.path()) else {
+                        return;
+                    };
+                    if let Err(e) = std::fs::write(&path, &blob) {
+                        log::error!("export nodes to {}: {e}", path.display());
+                        toast_overlay.add_toast(adw::Toast::new(&trf(
+                            "Export failed: {error}",
+                            &[("error", &e)],
+                        )));
+                    }
+                });
+            }
+            _ => {}
+        });
+        dialog.present(Some(&self.window));
+    }
+
+    fn confirm_stop_previous(&self, pid: u32, sender: &ComponentSender<Self>) {
+        let dialog = adw::AlertDialog::builder()
+            .heading(tr("Previous Backend Still Running"))
+            .body(trf(
+                "A backend from an earlier session (PID {pid}) is still running and holding the proxy ports. Stop it and connect?",
+                &[("pid", &pid)],
+            ))
+            .build();
+        dialog.add_response("cancel", &tr("Cancel"));
+        dialog.add_response("stop", &tr("Stop and Connect"));
+        dialog.set_response_appearance("stop", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let s = sender.input_sender().clone();
+        dialog.connect_response(None, move |_, response| {
+            if response == "stop" {
+                s.emit(AppMsg::StopPreviousBackend);
+            }
+        });
+        dialog.present(Some(&self.window));
+    }
+
+    /// Follows a backend that moved to where detection finds it, and asks the
+    /// user to pick another when it is gone altogether.
+    fn check_backend(&mut self, sender: &ComponentSender<Self>) {
+        match revalidate_backend(&self.settings.backend) {
+            Revalidation::Valid => {}
+            Revalidation::Replaced(found) => {
+                log::info!("stored backend missing, switching to {found}");
+                self.settings.backend.binary_path = Some(found.binary_path.clone());
+                if let Err(e) = persistence::save_settings(&self.paths, &self.settings) {
+                    log::error!("save settings: {e}");
+                }
+                self.show_toast(&trf(
+                    "Backend moved — now using {backend}",
+                    &[("backend", &found)],
+                ));
+            }
+            Revalidation::Missing(e) => {
+                log::warn!("stored backend unusable: {e}");
+                let dialog = adw::AlertDialog::builder()
+                    .heading(tr("Backend Not Found"))
+                    .body(trf(
+                        "{error}\n\nChoose a backend in Preferences to connect again.",
+                        &[("error", &e)],
+                    ))
+                    .build();
+                dialog.add_response("later", &tr("Later"));
+                dialog.add_response("preferences", &tr("Open Preferences"));
+                dialog.set_response_appearance("preferences", adw::ResponseAppearance::Suggested);
+                dialog.set_default_response(Some("preferences"));
+                dialog.set_close_response("later");
+
+                let s = sender.input_sender().clone();
+                dialog.connect_response(None, move |_, response| {
+                    if response == "preferences" {
+                        s.emit(AppMsg::OpenPreferences);
+                    }
+                });
+                dialog.present(Some(&self.window));
+            }
+        }
+    }
+
+    /// Asks before restarting a running connection on a newly chosen backend,
+    /// since the switch drops open connections and may skip nodes.
+    fn offer_backend_switch(&self, sender: &ComponentSender<Self>) {
+        let support = self.load_active_config().support;
+        let backend = v2ray_rs_core::backend::backend_name(self.settings.backend.backend_type);
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(trf("Switch to {backend}?", &[("backend", &backend)]))
+            .build();
+        if support.kept.is_empty() {
+            dialog.set_body(&trf(
+                "None of the enabled nodes work with {backend}. The current connection stays on the previous backend until you disconnect.",
+                &[("backend", &backend)],
+            ));
+            dialog.add_response("close", &tr("Close"));
+            dialog.present(Some(&self.window));
+            return;
+        }
+
+        let mut body = tr("The current connection still uses the previous backend.");
+        if !support.skipped.is_empty() {
+            body.push_str("\n\n");
+            body.push_str(&trf(
+                "{count} nodes will be skipped: not supported by {backend}",
+                &[("count", &support.skipped.len()), ("backend", &backend)],
+            ));
+        }
+        dialog.set_body(&body);
+        dialog.add_response("later", &tr("Later"));
+        dialog.add_response("reconnect", &tr("Reconnect Now"));
+        dialog.set_response_appearance("reconnect", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("reconnect"));
+        dialog.set_close_response("later");
+
+        let s = sender.input_sender().clone();
+        dialog.connect_response(None, move |_, response| {
+            if response == "reconnect" {
+                s.emit(AppMsg::Reconnect);
+            }
+        });
+        dialog.present(Some(&self.window));
+    }
+
+    fn show_error_dialog(&self, summary: &str, log_tail: &str) {
+        let label = gtk::Label::builder()
+            .label(log_tail)
+            .selectable(true)
+            .wrap(true)
+            .xalign(0.0)
+            .css_classes(["monospace"])
+            .build();
+        let scrolled = gtk::ScrolledWindow::builder()
+            .min_content_height(160)
+            .max_content_height(320)
+            .propagate_natural_height(true)
+            .child(&label)
+            .build();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(tr("Backend Failed to Start"))
+            .body(summary)
+            .extra_child(&scrolled)
+            .build();
+        dialog.add_response("close", &tr("Close"));
+        dialog.set_close_response("close");
+        dialog.present(Some(&self.window));
+    }
+
+    fn load_profile(&mut self, name: String) {
+        if let Err(e) = persistence::save_current_profile(&self.base_paths, &name) {
+            log::error!("save current profile: {e}");
+        }
+        let paths = self.base_paths.for_profile(&name);
+        let settings = persistence::load_settings_or_default(&paths);
+        if settings.language != self.settings.language {
+            self.apply_language(settings.language);
+        }
+        if let Err(e) =
+            GeodataManager::new(&paths).register_geosite_categories(settings.backend.backend_type)
+        {
+            log::warn!("read geosite categories: {e}");
+        }
+        self.subscriptions_page.emit(SubscriptionsMsg::Reload(
+            paths.clone(),
+            Box::new(settings.clone()),
+        ));
+        self.paths = paths;
+        self.settings = settings;
+        sync_notifications(&self.settings);
+        self.show_toast(&trf("Switched to profile “{name}”", &[("name", &name)]));
+        self.profile = name;
+    }
+
+    /// Switches language and refreshes everything the window owns. Dialogs
+    /// that are open at the time keep their old strings until reopened.
+    fn apply_language(&self, language: Language) {
+        crate::i18n::switch_language(language);
+        fill_main_menu(&self.main_menu);
+        self.subscriptions_page.emit(SubscriptionsMsg::Relabel);
+        self.logs_page.emit(LogsMsg::Relabel);
+    }
+
+    fn primary_node_label(&self) -> String {
+        self.load_active_config()
+            .support
+            .kept
+            .first()
+            .map(|n| match n.remark() {
+                Some(remark) => remark.to_owned(),
+                None => format!("{}:{}", n.address(), n.port()),
+            })
+            .unwrap_or_default()
+    }
+
+    fn control_response(
+        &self,
+        request: ControlRequest,
+        sender: &ComponentSender<Self>,
+    ) -> ControlResponse {
+        let connected = self.process_handle.is_some();
+        match request {
+            ControlRequest::Connect if connected => ControlResponse::ok(format!(
+                "Already connected via {}",
+                self.primary_node_label()
+            )),
+            ControlRequest::Connect => {
+                sender.input(AppMsg::Connect);
+                ControlResponse::ok("Connecting")
+            }
+            ControlRequest::Disconnect if connected => {
+                sender.input(AppMsg::Disconnect);
+                ControlResponse::ok("Disconnecting")
+            }
+            ControlRequest::Reload if connected => {
+                sender.input(AppMsg::Reconnect);
+                ControlResponse::ok("Reconnecting")
+            }
+            ControlRequest::Disconnect | ControlRequest::Reload => {
+                ControlResponse::ok("Not connected")
+            }
+            ControlRequest::Status => match StatusFile::new(self.paths.status_path()).read() {
+                Ok(status) => ControlResponse::Status(status),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    ControlResponse::Status(Status::stopped())
+                }
+                Err(e) => ControlResponse::error(e.to_string()),
+            },
+            ControlRequest::Show => {
+                sender.input(AppMsg::TrayShowWindow);
+                ControlResponse::ok("Shown")
+            }
+        }
+    }
+
+    fn record_connection_event(&self, from: &ProcessState, to: &ProcessState) {
+        let action = match (from, to) {
+            (ProcessState::Running, ProcessState::Running) => return,
+            (_, ProcessState::Running) => ConnectionAction::Connected,
+            (ProcessState::Running | ProcessState::Stopping, ProcessState::Stopped) => {
+                ConnectionAction::Disconnected
+            }
+            (_, ProcessState::Error(_)) => ConnectionAction::Error,
+            _ => return,
+        };
+        let node = match from {
+            ProcessState::Running | ProcessState::Stopping => self.active_node.clone(),
+            _ => self.primary_node_label(),
+        };
+        let event = ConnectionEvent::now(action, (!node.is_empty()).then_some(node));
+        if let Err(e) = persistence::append_connection_event(&self.paths, event) {
+            log::error!("save connection history: {e}");
+        }
+    }
+
+    fn start_uptime(&mut self, sender: &ComponentSender<Self>) {
+        self.active_node = self.primary_node_label();
+        self.connected_since = Some(Instant::now());
+        self.uptime = Duration::ZERO;
+
+        if self.uptime_timer.is_none() {
+            let s = sender.input_sender().clone();
+            self.uptime_timer = Some(glib::timeout_add_seconds_local(1, move || {
+                s.emit(AppMsg::UptimeTick);
+                glib::ControlFlow::Continue
+            }));
+        }
+    }
+
+    fn reset_uptime(&mut self) {
+        if let Some(timer) = self.uptime_timer.take() {
+            timer.remove();
+        }
+        self.connected_since = None;
+        self.uptime = Duration::ZERO;
+    }
+
     fn show_toast(&self, msg: &str) {
         self.toast_overlay.add_toast(adw::Toast::new(msg));
     }
 
-    fn apply_state(&mut self, state: &ProcessState) {
+    fn apply_state(&mut self, state: &ProcessState, sender: &ComponentSender<Self>) {
         let from = self.process_state.clone();
         match state {
             ProcessState::Stopped => {
@@ -91,13 +601,24 @@ impl App {
                 self.connected = true;
                 self.button_sensitive = false;
             }
-            ProcessState::Error(msg) => {
+            ProcessState::Error(failure) => {
                 self.connected = false;
                 self.button_sensitive = true;
-                self.show_toast(&format!("Error: {msg}"));
+                if failure.log_tail.is_empty() {
+                    self.show_toast(&trf("Error: {message}", &[("message", failure)]));
+                } else {
+                    self.show_error_dialog(&failure.summary, &failure.log_tail.join("\n"));
+                }
             }
         }
+        match state {
+            ProcessState::Running if self.connected_since.is_none() => self.start_uptime(sender),
+            ProcessState::Running | ProcessState::Stopping => {}
+            _ => self.reset_uptime(),
+        }
+        self.record_connection_event(&from, state);
         self.process_state = state.clone();
+        self.sync_actions();
 
         let locked = matches!(state, ProcessState::Running | ProcessState::Starting);
         self.subscriptions_page
@@ -124,7 +645,8 @@ impl SimpleComponent for App {
         adw::ApplicationWindow {
             set_default_width: DEFAULT_WINDOW_WIDTH,
             set_default_height: DEFAULT_WINDOW_HEIGHT,
-            set_title: Some("V2Ray Manager"),
+            #[watch]
+            set_title: Some(&tr("V2Ray Manager")),
 
             connect_close_request[sender] => move |_| {
                 sender.input(AppMsg::CloseRequested);
@@ -140,7 +662,14 @@ impl SimpleComponent for App {
                     adw::HeaderBar {
                         #[wrap(Some)]
                         set_title_widget = &adw::WindowTitle {
-                            set_title: "V2Ray Manager",
+                            #[watch]
+                            set_title: &tr("V2Ray Manager"),
+                            #[watch]
+                            set_subtitle: if model.profile == DEFAULT_PROFILE {
+                                ""
+                            } else {
+                                &model.profile
+                            },
                         },
 
                         pack_start = &gtk::Button {
@@ -153,17 +682,17 @@ impl SimpleComponent for App {
                                     "network-wired-symbolic"
                                 },
                                 #[watch]
-                                set_label: if model.connected { "Disconnect" } else { "Connect" },
+                                set_label: &if model.connected { tr("Disconnect") } else { tr("Connect") },
                             },
                             #[watch]
                             set_sensitive: model.button_sensitive && (model.connected || model.has_active_nodes),
                             #[watch]
-                            set_tooltip_text: Some(if !model.connected && !model.has_active_nodes {
-                                "No enabled proxy nodes"
+                            set_tooltip_text: Some(&if !model.connected && !model.has_active_nodes {
+                                tr("No enabled proxy nodes")
                             } else if model.connected {
-                                "Disconnect from proxy"
+                                tr("Disconnect from proxy")
                             } else {
-                                "Connect to proxy"
+                                tr("Connect to proxy")
                             }),
                             #[watch]
                             set_css_classes: &["pill", if model.connected { "destructive-action" } else { "suggested-action" }],
@@ -172,22 +701,18 @@ impl SimpleComponent for App {
 
                         pack_end = &gtk::MenuButton {
                             set_icon_name: "open-menu-symbolic",
-                            set_tooltip_text: Some("Main Menu"),
-                            #[wrap(Some)]
-                            set_popover = &gtk::PopoverMenu::from_model(Some(&{
-                                let menu = gtk::gio::Menu::new();
-                                menu.append(Some("Preferences"), Some("win.preferences"));
-                                menu
-                            })) {},
+                            #[watch]
+                            set_tooltip_text: Some(&tr("Main Menu")),
+                            set_menu_model: Some(&model.main_menu),
                         },
                     },
 
                     #[local_ref]
                     toast_overlay -> adw::ToastOverlay {
-                        gtk::Paned {
+                        #[local_ref]
+                        paned -> gtk::Paned {
                             set_orientation: gtk::Orientation::Vertical,
                             set_vexpand: true,
-                            set_position: 380,
                             set_shrink_start_child: false,
                             set_shrink_end_child: false,
 
@@ -198,29 +723,102 @@ impl SimpleComponent for App {
                             set_end_child = model.logs_page.widget(),
                         },
                     },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 12,
+                        set_margin_top: 4,
+                        set_margin_bottom: 4,
+                        set_margin_start: 12,
+                        set_margin_end: 12,
+                        #[watch]
+                        set_visible: model.connected_since.is_some(),
+
+                        gtk::Image {
+                            set_icon_name: Some("network-transmit-receive-symbolic"),
+                        },
+
+                        gtk::Label {
+                            set_hexpand: true,
+                            set_xalign: 0.0,
+                            set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            #[watch]
+                            set_label: &model.active_node,
+                        },
+
+                        gtk::Label {
+                            add_css_class: "dim-label",
+                            add_css_class: "caption",
+                            #[watch]
+                            set_label: &inbounds_label(&model.settings),
+                        },
+
+                        gtk::Label {
+                            add_css_class: "caption",
+                            add_css_class: "numeric",
+                            #[watch]
+                            set_label: &format_uptime(model.uptime),
+                        },
+                    },
                 }
             }
         }
     }
 
     fn init(
-        paths: Self::Init,
+        base_paths: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let profile = persistence::load_current_profile(&base_paths);
+        let paths = base_paths.for_profile(&profile);
         let settings = v2ray_rs_core::persistence::load_settings(&paths).unwrap_or_default();
 
         let show_wizard = !paths.settings_path().exists();
 
+        if let Err(e) =
+            GeodataManager::new(&paths).register_geosite_categories(settings.backend.backend_type)
+        {
+            log::warn!("read geosite categories: {e}");
+        }
+
         setup_tray_polling(sender.input_sender().clone());
+        if let Ok(mut guard) = CONTROL_SERVER.lock()
+            && let Some(server) = guard.take()
+        {
+            serve_control(server, sender.input_sender().clone());
+        }
 
         let subscriptions_page = SubscriptionsPage::builder()
             .launch((paths.clone(), settings.clone()))
             .forward(sender.input_sender(), |msg| match msg {
                 SubscriptionsOutput::ActiveNodesChanged(has) => AppMsg::ActiveNodesChanged(has),
+                SubscriptionsOutput::TestNode(sub_id, node) => AppMsg::TestNode(sub_id, node),
+                SubscriptionsOutput::AutoUpdated { updated, failed } => {
+                    AppMsg::SubscriptionsAutoUpdated { updated, failed }
+                }
+                SubscriptionsOutput::ExpiryWarning { name, detail } => {
+                    AppMsg::SubscriptionExpiring { name, detail }
+                }
+                SubscriptionsOutput::SortChanged(nodes, subscriptions) => {
+                    AppMsg::SortChanged(nodes, subscriptions)
+                }
+                SubscriptionsOutput::Updated(summary) => AppMsg::SubscriptionUpdated(summary),
+                SubscriptionsOutput::RefreshFailed { id, message, kind } => {
+                    AppMsg::SubscriptionRefreshFailed { id, message, kind }
+                }
             });
 
-        let logs_page = LogsPage::builder().launch(()).detach();
+        let logs_page =
+            LogsPage::builder()
+                .launch(settings.clone())
+                .forward(sender.input_sender(), |msg| match msg {
+                    LogsOutput::ViewChanged {
+                        auto_scroll,
+                        wrap_lines,
+                    } => AppMsg::LogViewChanged(auto_scroll, wrap_lines),
+                    LogsOutput::Saved(result) => AppMsg::LogsSaved(result),
+                });
 
         let wizard = OnboardingWizard::builder().launch(paths.clone()).forward(
             sender.input_sender(),
@@ -240,6 +838,9 @@ impl SimpleComponent for App {
         let model = App {
             settings,
             paths,
+            base_paths,
+            profile,
+            pending_profile: None,
             subscriptions_page,
             logs_page,
             show_wizard,
@@ -248,23 +849,81 @@ impl SimpleComponent for App {
             process_handle: None,
             process_state: ProcessState::Stopped,
             reconnect_pending: false,
+            quitting: false,
             connected: false,
             button_sensitive: true,
             has_active_nodes,
             toast_overlay: toast_overlay.clone(),
+            paned: gtk::Paned::new(gtk::Orientation::Vertical),
+            main_menu: gtk::gio::Menu::new(),
+            toggle_action: gtk::gio::SimpleAction::new("toggle-connection", None),
+            refresh_action: gtk::gio::SimpleAction::new("refresh-subscriptions", None),
+            proxy_env_action: gtk::gio::SimpleAction::new("copy-proxy-env", None),
+            review_action: gtk::gio::SimpleAction::new("review-changes", None),
+            active_node: String::new(),
+            connected_since: None,
+            uptime: Duration::ZERO,
+            uptime_timer: None,
         };
 
+        fill_main_menu(&model.main_menu);
         let toast_overlay = &model.toast_overlay;
+        let paned = &model.paned;
         let widgets = view_output!();
 
-        let prefs_action = gtk::gio::SimpleAction::new("preferences", None);
+        if let (Some(width), Some(height)) =
+            (model.settings.window_width, model.settings.window_height)
         {
-            let s = sender.input_sender().clone();
-            prefs_action.connect_activate(move |_, _| {
-                s.emit(AppMsg::OpenPreferences);
-            });
+            root.set_default_size(width.max(MIN_WINDOW_WIDTH), height.max(MIN_WINDOW_HEIGHT));
+        }
+        if model.settings.window_maximized {
+            root.maximize();
+        }
+        model.paned.set_position(
+            model
+                .settings
+                .paned_position
+                .unwrap_or(DEFAULT_PANED_POSITION)
+                .max(MIN_PANED_POSITION),
+        );
+
+        let input = sender.input_sender();
+        let prefs_action = gtk::gio::SimpleAction::new("preferences", None);
+        let search_action = gtk::gio::SimpleAction::new("search", None);
+        let quit_action = gtk::gio::SimpleAction::new("quit", None);
+        let view_config_action = gtk::gio::SimpleAction::new("view-config", None);
+        let view_history_action = gtk::gio::SimpleAction::new("view-history", None);
+        let profiles_action = gtk::gio::SimpleAction::new("profiles", None);
+        let export_action = gtk::gio::SimpleAction::new("export-nodes", None);
+        add_window_action(&root, &prefs_action, input, || AppMsg::OpenPreferences);
+        add_window_action(&root, &model.toggle_action, input, || {
+            AppMsg::ToggleConnection
+        });
+        add_window_action(&root, &model.refresh_action, input, || {
+            AppMsg::RefreshSubscriptions
+        });
+        add_window_action(&root, &search_action, input, || AppMsg::FocusSearch);
+        add_window_action(&root, &quit_action, input, || AppMsg::TrayQuit);
+        add_window_action(&root, &view_config_action, input, || AppMsg::ViewConfig);
+        add_window_action(&root, &view_history_action, input, || AppMsg::ViewHistory);
+        add_window_action(&root, &profiles_action, input, || AppMsg::ShowProfiles);
+        add_window_action(&root, &export_action, input, || AppMsg::ExportNodes);
+        add_window_action(&root, &model.proxy_env_action, input, || {
+            AppMsg::CopyProxyEnv
+        });
+        add_window_action(&root, &model.review_action, input, || AppMsg::ReviewChanges);
+
+        let app = relm4::main_application();
+        app.set_accels_for_action("win.toggle-connection", &["<Control>k"]);
+        app.set_accels_for_action("win.preferences", &["<Control>comma"]);
+        app.set_accels_for_action("win.refresh-subscriptions", &["<Control>r"]);
+        app.set_accels_for_action("win.search", &["<Control>f"]);
+        app.set_accels_for_action("win.quit", &["<Control>q"]);
+
+        model.sync_actions();
+        if !model.show_wizard {
+            sender.input(AppMsg::CheckBackend);
         }
-        root.add_action(&prefs_action);
 
         ComponentParts { model, widgets }
     }
@@ -276,6 +935,7 @@ impl SimpleComponent for App {
                     log::error!("save settings: {e}");
                 }
                 self.settings = settings;
+                sync_notifications(&self.settings);
                 self.show_wizard = false;
 
                 if let Some((name, url)) = subscription {
@@ -283,20 +943,46 @@ impl SimpleComponent for App {
                         .emit(SubscriptionsMsg::AddSubscription(name, url));
                 }
             }
-            AppMsg::SettingsChanged(settings) => {
-                crate::i18n::switch_language(settings.language);
+            AppMsg::SettingsChanged(mut settings) => {
+                // The sort orders are chosen on the subscriptions page and the
+                // log view options on the logs page, not in Preferences, whose
+                // copy of them may be stale.
+                settings.node_sort = self.settings.node_sort;
+                settings.subscription_sort = self.settings.subscription_sort;
+                settings.log_auto_scroll = self.settings.log_auto_scroll;
+                settings.log_wrap_lines = self.settings.log_wrap_lines;
+                if settings.language != self.settings.language {
+                    self.apply_language(settings.language);
+                }
+                if settings.strict_uuid != self.settings.strict_uuid {
+                    self.subscriptions_page
+                        .emit(SubscriptionsMsg::SetParseOptions(parse_options(&settings)));
+                }
+                if settings.subscription_proxy != self.settings.subscription_proxy {
+                    self.subscriptions_page
+                        .emit(SubscriptionsMsg::SetFetchProxy(
+                            settings.subscription_proxy.clone(),
+                        ));
+                }
                 if let Err(e) = v2ray_rs_core::persistence::save_settings(&self.paths, &settings) {
                     log::error!("save settings: {e}");
                 }
                 let was_connected = self.process_handle.is_some();
+                let backend_switched = settings.backend.backend_type
+                    != self.settings.backend.backend_type
+                    || settings.backend.binary_path != self.settings.backend.binary_path;
                 self.settings = settings;
-                if was_connected {
+                sync_notifications(&self.settings);
+                if was_connected && backend_switched {
+                    self.offer_backend_switch(&sender);
+                } else if was_connected {
                     self.reconnect_pending = true;
                     sender.input(AppMsg::Disconnect);
                 }
             }
             AppMsg::ActiveNodesChanged(has) => {
                 self.has_active_nodes = has;
+                self.sync_actions();
             }
             AppMsg::ToggleConnection => {
                 if self.connected {
@@ -313,73 +999,144 @@ impl SimpleComponent for App {
                 let binary_path = match &self.settings.backend.binary_path {
                     Some(p) => p.clone(),
                     None => {
-                        self.show_toast("No backend binary configured — check Preferences");
+                        self.show_toast(&tr("No backend binary configured — check Preferences"));
                         return;
                     }
                 };
 
-                let subscriptions =
-                    persistence::load_subscriptions(&self.paths).unwrap_or_default();
-                let nodes: Vec<_> = subscriptions
-                    .iter()
-                    .filter(|s| s.enabled)
-                    .flat_map(|s| s.enabled_nodes().cloned())
-                    .collect();
-
-                if nodes.is_empty() {
-                    self.show_toast("No enabled proxy nodes — add a subscription first");
+                if !self.sync_selection() {
                     return;
                 }
+                let ActiveConfig {
+                    support,
+                    groups,
+                    targets,
+                    rules: enabled_rules,
+                } = self.load_active_config();
+                let backend =
+                    v2ray_rs_core::backend::backend_name(self.settings.backend.backend_type);
+
+                if support.kept.is_empty() && support.skipped.is_empty() {
+                    self.show_toast(&tr("No enabled proxy nodes — add a subscription first"));
+                    return;
+                }
+                if support.kept.is_empty() {
+                    self.show_toast(&trf(
+                        "None of the enabled nodes work with {backend} — switch backends in Preferences",
+                        &[("backend", &backend)],
+                    ));
+                    return;
+                }
+                if !support.skipped.is_empty() {
+                    self.show_toast(&trf(
+                        "{count} nodes skipped: not supported by {backend}",
+                        &[("count", &support.skipped.len()), ("backend", &backend)],
+                    ));
+                }
+                if proxies_nothing(&enabled_rules, self.settings.default_action) {
+                    self.show_toast(&tr(
+                        "No rule sends traffic to a proxy and the default action is not Proxy — all traffic bypasses your nodes",
+                    ));
+                }
+                let offered = if self.settings.serves_clash_api() {
+                    groups.as_slice()
+                } else {
+                    &[]
+                };
+                let unrouted = unrouted_rules(&enabled_rules, &targets, offered).len();
+                if unrouted > 0 {
+                    self.show_toast(&trf(
+                        "{count} rules route to a node that is not enabled — using the primary node",
+                        &[("count", &unrouted)],
+                    ));
+                }
+                let nodes = support.kept;
 
-                let rules = persistence::load_routing_rules(&self.paths).unwrap_or_default();
-                let enabled_rules: Vec<_> = rules.enabled_rules().cloned().collect();
-
-                let writer = ConfigWriter::new(&self.settings, &self.paths);
+                let writer = self.config_writer(groups, targets);
                 let config_path = match writer.write_config(&nodes, &enabled_rules, &self.settings)
                 {
                     Ok(path) => path,
                     Err(e) => {
-                        self.show_toast(&format!("Config generation failed: {e}"));
+                        self.show_toast(&trf(
+                            "Config generation failed: {error}",
+                            &[("error", &e)],
+                        ));
                         return;
                     }
                 };
 
                 let pid_path = self.paths.data_dir().join("backend.pid");
+                if let Some(notifier) = NOTIFIER.get() {
+                    notifier.set_active_node(Some(self.primary_node_label()));
+                }
 
-                self.apply_state(&ProcessState::Starting);
+                self.apply_state(&ProcessState::Starting, &sender);
                 self.logs_page.emit(LogsMsg::SetRunning(true));
                 self.logs_page.emit(LogsMsg::Clear);
-
-                let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel::<ProcessCmd>(4);
+                self.logs_page.emit(LogsMsg::AppendLine(LogLine::app(trf(
+                    "Starting {backend} with {count} nodes ({path})",
+                    &[
+                        ("backend", &backend),
+                        ("count", &nodes.len()),
+                        ("path", &config_path.display()),
+                    ],
+                ))));
+
+                let (cmd_tx, mut cmd_rx) = mpsc::channel::<ProcessCmd>(4);
                 let input_sender = sender.input_sender().clone();
 
+                let event_capacity = self.settings.process_event_capacity;
+                let ports = self.settings.inbound_ports();
+                let mut status_file = StatusFile::new(self.paths.status_path());
+                status_file.set_active_node(Some(self.primary_node_label()));
+                status_file.set_ports(
+                    self.settings
+                        .enable_socks
+                        .then_some(self.settings.socks_port),
+                    self.settings.enable_http.then_some(self.settings.http_port),
+                );
+
                 tokio::spawn(async move {
                     let mut mgr =
                         v2ray_rs_process::ProcessManager::new(binary_path, config_path, pid_path);
+                    mgr.set_event_capacity(event_capacity);
+                    mgr.set_ports(ports);
+                    mgr.set_status_file(status_file);
+
+                    // Subscribed before starting so the startup banner and
+                    // early warnings show up while still Starting.
+                    let log_sender = input_sender.clone();
+                    let mut log_rx = mgr.subscribe();
+                    tokio::spawn(async move {
+                        while let Some(line) = next_log_line(&mut log_rx).await {
+                            log_sender.emit(AppMsg::ProcessLogLine(line));
+                        }
+                    });
 
                     match mgr.start().await {
                         Ok(()) => {
                             input_sender.emit(AppMsg::ProcessStateChanged(ProcessState::Running));
                         }
+                        Err(
+                            ProcessError::AlreadyRunning(pid)
+                            | ProcessError::PortHeldByPrevious { pid, .. },
+                        ) => {
+                            input_sender.emit(AppMsg::ProcessStateChanged(ProcessState::Stopped));
+                            input_sender.emit(AppMsg::PreviousBackendRunning(pid));
+                            return;
+                        }
                         Err(e) => {
-                            input_sender.emit(AppMsg::ProcessStateChanged(ProcessState::Error(
-                                e.to_string(),
-                            )));
+                            let state = match mgr.state() {
+                                state @ ProcessState::Error(_) => state,
+                                _ => ProcessState::Error(e.to_string().into()),
+                            };
+                            input_sender.emit(AppMsg::ProcessStateChanged(state));
                             return;
                         }
                     }
 
-                    let mut event_rx = mgr.subscribe();
-
-                    let log_sender = input_sender.clone();
-                    let mut log_rx = mgr.subscribe();
-                    tokio::spawn(async move {
-                        while let Ok(event) = log_rx.recv().await {
-                            if let ProcessEvent::LogLine(line) = event {
-                                log_sender.emit(AppMsg::ProcessLogLine(line.content));
-                            }
-                        }
-                    });
+                    let mut event_rx = mgr.subscribe_lifecycle();
+                    let mut usage_tick = tokio::time::interval(RESOURCE_POLL_INTERVAL);
 
                     loop {
                         tokio::select! {
@@ -394,26 +1151,39 @@ impl SimpleComponent for App {
                                     }
                                 }
                             }
-                            result = event_rx.recv() => {
-                                match result {
-                                    Ok(ProcessEvent::StateChanged { to, .. }) => {
+                            _ = usage_tick.tick() => {
+                                if let Some(stats) = mgr.report_resource_usage() {
+                                    input_sender.emit(AppMsg::ResourceUsage(stats));
+                                }
+                            }
+                            status = mgr.wait_exit() => {
+                                // Handled here rather than raced in the
+                                // select, so no tick can cut the restart short.
+                                if let Some(status) = status {
+                                    mgr.handle_exit(status).await;
+                                }
+                                // Only the outcome matters here; the
+                                // Stopped/Starting steps of an auto-restart
+                                // would otherwise make the UI drop its handle.
+                                while event_rx.try_recv().is_ok() {}
+                                let state = mgr.state();
+                                let running = state == ProcessState::Running;
+                                input_sender.emit(AppMsg::ProcessStateChanged(state));
+                                if !running {
+                                    break;
+                                }
+                            }
+                            event = event_rx.recv() => {
+                                match event {
+                                    Some(ProcessEvent::StateChanged { to, .. }) => {
                                         let is_error = matches!(to, ProcessState::Error(_));
                                         input_sender.emit(AppMsg::ProcessStateChanged(to));
                                         if is_error {
                                             break;
                                         }
                                     }
-                                    Ok(ProcessEvent::ProcessExited { .. }) => {
-                                        let _ = mgr.wait_and_handle_exit().await;
-                                        let state = mgr.state();
-                                        input_sender.emit(AppMsg::ProcessStateChanged(state));
-                                        if mgr.state() != ProcessState::Running {
-                                            break;
-                                        }
-                                    }
-                                    Ok(_) => {}
-                                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                                    Err(broadcast::error::RecvError::Closed) => break,
+                                    Some(_) => {}
+                                    None => break,
                                 }
                             }
                         }
@@ -424,10 +1194,16 @@ impl SimpleComponent for App {
             }
             AppMsg::Disconnect => {
                 if let Some(handle) = self.process_handle.take() {
-                    self.apply_state(&ProcessState::Stopping);
-                    let _ = handle.cmd_tx.try_send(ProcessCmd::Stop);
+                    self.apply_state(&ProcessState::Stopping, &sender);
+                    // A closed channel means the backend task already ended,
+                    // so nothing will report Stopped for it.
+                    if let Err(mpsc::error::TrySendError::Closed(_)) =
+                        handle.cmd_tx.try_send(ProcessCmd::Stop)
+                    {
+                        sender.input(AppMsg::ProcessStateChanged(ProcessState::Stopped));
+                    }
                 } else {
-                    self.show_toast("Not connected");
+                    self.show_toast(&tr("Not connected"));
                 }
             }
             AppMsg::ProcessStateChanged(state) => {
@@ -435,9 +1211,23 @@ impl SimpleComponent for App {
                 if stopped {
                     self.process_handle = None;
                     self.logs_page.emit(LogsMsg::SetRunning(false));
+                    if self.quitting {
+                        self.window.destroy();
+                        return;
+                    }
+                }
+                if state == ProcessState::Running {
+                    self.logs_page
+                        .emit(LogsMsg::AppendLine(LogLine::app(tr("Backend running"))));
+                }
+                self.apply_state(&state, &sender);
+                if stopped && let Some(profile) = self.pending_profile.take() {
+                    self.load_profile(profile);
+                    return;
                 }
-                self.apply_state(&state);
-                if matches!(state, ProcessState::Stopped) && self.reconnect_pending {
+                // An error while stopping still leaves nothing running, so a
+                // pending reconnect goes ahead rather than being lost.
+                if stopped && self.reconnect_pending {
                     self.reconnect_pending = false;
                     sender.input(AppMsg::Connect);
                 }
@@ -445,25 +1235,272 @@ impl SimpleComponent for App {
             AppMsg::ProcessLogLine(line) => {
                 self.logs_page.emit(LogsMsg::AppendLine(line));
             }
+            AppMsg::ResourceUsage(stats) => {
+                self.logs_page.emit(LogsMsg::SetResourceUsage(stats));
+            }
             AppMsg::CloseRequested => {
                 if self.settings.minimize_to_tray {
+                    self.save_window_state();
                     self.window.set_visible(false);
                 } else {
-                    if let Some(handle) = self.process_handle.take() {
-                        let _ = handle.cmd_tx.try_send(ProcessCmd::Stop);
-                    }
-                    self.window.destroy();
+                    self.request_quit(&sender);
                 }
             }
             AppMsg::TrayShowWindow => {
                 self.window.set_visible(true);
                 self.window.present();
             }
+            AppMsg::Control(request, reply) => {
+                let _ = reply.send(self.control_response(request, &sender));
+            }
             AppMsg::TrayQuit => {
-                if let Some(handle) = self.process_handle.take() {
-                    let _ = handle.cmd_tx.try_send(ProcessCmd::Stop);
+                self.request_quit(&sender);
+            }
+            AppMsg::QuitConfirmed => {
+                let stopping = match self.process_handle.take() {
+                    Some(handle) => {
+                        self.apply_state(&ProcessState::Stopping, &sender);
+                        handle.cmd_tx.try_send(ProcessCmd::Stop).is_ok()
+                    }
+                    None => self.process_state == ProcessState::Stopping,
+                };
+                if !stopping {
+                    self.window.destroy();
+                    return;
+                }
+                self.quitting = true;
+                let s = sender.input_sender().clone();
+                glib::timeout_add_local_once(QUIT_TIMEOUT, move || {
+                    s.emit(AppMsg::ForceQuit);
+                });
+            }
+            AppMsg::ForceQuit => {
+                if self.quitting {
+                    log::warn!("backend did not stop within {QUIT_TIMEOUT:?}, quitting anyway");
+                    self.window.destroy();
+                }
+            }
+            AppMsg::CopyProxyEnv => {
+                if self.process_state == ProcessState::Running {
+                    let exports = proxy_env_exports(&self.settings);
+                    self.window.clipboard().set_text(&exports);
+                    self.show_toast(&tr("Proxy environment copied to clipboard"));
+                }
+            }
+            AppMsg::ExportNodes => self.show_export_dialog(),
+            AppMsg::TestNode(sub_id, node) => {
+                let Some(binary_path) = self.settings.backend.binary_path.clone() else {
+                    self.show_toast(&tr("No backend binary configured — check Preferences"));
+                    return;
+                };
+                let name = node.remark().unwrap_or(node.address()).to_owned();
+                // The node may be reordered or refreshed away while the test
+                // runs, so the result goes back by key rather than position.
+                let key = node.key();
+                self.show_toast(&trf("Testing {name}…", &[("name", &name)]));
+                let settings = self.settings.clone();
+                let work_dir = self.paths.data_dir().join("node-test");
+                let input_sender = sender.input_sender().clone();
+                tokio::spawn(async move {
+                    let result = test_node(&node, &settings, binary_path, &work_dir)
+                        .await
+                        .map_err(|e| e.to_string());
+                    input_sender.emit(AppMsg::NodeTestDone(sub_id, key, name, result));
+                });
+            }
+            AppMsg::NodeTestDone(sub_id, key, name, result) => match result {
+                Ok(elapsed) => {
+                    let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+                    self.subscriptions_page.emit(SubscriptionsMsg::NodeTested(
+                        sub_id,
+                        key,
+                        Some(ms),
+                    ));
+                    self.show_toast(&trf(
+                        "{name} works ({ms} ms) and is now enabled",
+                        &[("name", &name), ("ms", &elapsed.as_millis())],
+                    ));
+                }
+                Err(e) => {
+                    self.subscriptions_page
+                        .emit(SubscriptionsMsg::NodeTested(sub_id, key, None));
+                    self.show_toast(&trf(
+                        "{name} failed the test: {error}",
+                        &[("name", &name), ("error", &e)],
+                    ));
+                }
+            },
+            AppMsg::SubscriptionsAutoUpdated { updated, failed } => {
+                if let Some(notifier) = NOTIFIER.get().cloned() {
+                    tokio::task::spawn_blocking(move || {
+                        notifier.on_subscriptions_updated(updated, failed);
+                    });
+                }
+            }
+            AppMsg::SubscriptionExpiring { name, detail } => {
+                if let Some(notifier) = NOTIFIER.get().cloned() {
+                    tokio::task::spawn_blocking(move || {
+                        notifier.on_subscription_expiring(&name, &detail);
+                    });
+                }
+            }
+            AppMsg::SortChanged(nodes, subscriptions) => {
+                self.settings.node_sort = nodes;
+                self.settings.subscription_sort = subscriptions;
+                if let Err(e) = persistence::save_settings(&self.paths, &self.settings) {
+                    log::error!("save settings: {e}");
+                }
+            }
+            AppMsg::LogViewChanged(auto_scroll, wrap_lines) => {
+                self.settings.log_auto_scroll = auto_scroll;
+                self.settings.log_wrap_lines = wrap_lines;
+                if let Err(e) = persistence::save_settings(&self.paths, &self.settings) {
+                    log::error!("save settings: {e}");
                 }
-                self.window.destroy();
+            }
+            AppMsg::LogsSaved(Ok(path)) => {
+                self.show_toast(&trf("Logs saved to {path}", &[("path", &path.display())]))
+            }
+            AppMsg::LogsSaved(Err(e)) => {
+                self.show_toast(&trf("Could not save logs: {error}", &[("error", &e)]));
+            }
+            AppMsg::SubscriptionUpdated(summary) => self.show_toast(&summary),
+            AppMsg::SubscriptionRefreshFailed { id, message, kind } => {
+                let toast = adw::Toast::new(&message);
+                // Failures that may pass get a retry; a wrong URL or content
+                // gets the edit dialog; local file problems get neither.
+                if kind != FailureKind::Io {
+                    let retry = kind.is_retryable();
+                    toast.set_button_label(Some(&if retry { tr("Retry") } else { tr("Edit") }));
+                    let page = self.subscriptions_page.sender().clone();
+                    toast.connect_button_clicked(move |_| {
+                        page.emit(if retry {
+                            SubscriptionsMsg::UpdateSubscription(id)
+                        } else {
+                            SubscriptionsMsg::ShowEditDialog(id)
+                        });
+                    });
+                }
+                self.toast_overlay.add_toast(toast);
+            }
+            AppMsg::PreviousBackendRunning(pid) => {
+                self.confirm_stop_previous(pid, &sender);
+            }
+            AppMsg::CheckBackend => self.check_backend(&sender),
+            AppMsg::StopPreviousBackend => {
+                let Some(binary) = self.settings.backend.binary_path.clone() else {
+                    return;
+                };
+                let pid_file = PidFile::new(self.paths.data_dir().join("backend.pid"));
+                let input = sender.input_sender().clone();
+                tokio::task::spawn_blocking(move || {
+                    let result = pid_file
+                        .terminate_instance(&binary, STOP_PREVIOUS_TIMEOUT)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string());
+                    input.emit(AppMsg::PreviousBackendStopped(result));
+                });
+            }
+            AppMsg::PreviousBackendStopped(result) => match result {
+                Ok(()) => sender.input(AppMsg::Connect),
+                Err(error) => self.show_toast(&trf(
+                    "Could not stop the previous backend: {error}",
+                    &[("error", &error)],
+                )),
+            },
+            AppMsg::UptimeTick => {
+                if let Some(since) = self.connected_since {
+                    self.uptime = since.elapsed();
+                }
+            }
+            AppMsg::RefreshSubscriptions => {
+                self.subscriptions_page.emit(SubscriptionsMsg::UpdateAll);
+            }
+            AppMsg::FocusSearch => {
+                if !self.show_wizard {
+                    self.subscriptions_page.emit(SubscriptionsMsg::FocusSearch);
+                }
+            }
+            AppMsg::ViewConfig => {
+                let active = self.load_active_config();
+                let writer = self.config_writer(active.groups, active.targets);
+                let (support, rules) = (active.support, active.rules);
+                match writer.render_config(&support.kept, &rules, &self.settings) {
+                    Ok(json) => show_config_dialog(&self.window, &json),
+                    Err(e) => {
+                        self.show_toast(&trf("Config generation failed: {error}", &[("error", &e)]))
+                    }
+                }
+            }
+            AppMsg::ReviewChanges => {
+                let active = self.load_active_config();
+                let writer = self.config_writer(active.groups, active.targets);
+                let (support, rules) = (active.support, active.rules);
+                match writer.diff_config(&support.kept, &rules, &self.settings) {
+                    Ok(diff) if diff.is_empty() => {
+                        self.show_toast(&tr("Running config is up to date"));
+                    }
+                    Ok(diff) => {
+                        let s = sender.input_sender().clone();
+                        show_diff_dialog(&self.window, &diff, move || s.emit(AppMsg::Reconnect));
+                    }
+                    Err(e) => {
+                        self.show_toast(&trf("Config generation failed: {error}", &[("error", &e)]))
+                    }
+                }
+            }
+            AppMsg::Reconnect => {
+                if self.process_handle.is_some() {
+                    self.reconnect_pending = true;
+                    sender.input(AppMsg::Disconnect);
+                } else {
+                    sender.input(AppMsg::Connect);
+                }
+            }
+            AppMsg::ShowProfiles => {
+                let profiles = persistence::list_profiles(&self.base_paths).unwrap_or_else(|e| {
+                    log::error!("list profiles: {e}");
+                    vec![DEFAULT_PROFILE.to_owned()]
+                });
+                show_profiles_dialog(
+                    &self.window,
+                    &profiles,
+                    &self.profile,
+                    sender.input_sender().clone(),
+                );
+            }
+            AppMsg::CreateProfile(name) => {
+                let name = name.trim().to_owned();
+                match persistence::create_profile(&self.base_paths, &name, &self.settings) {
+                    Ok(_) => sender.input(AppMsg::SwitchProfile(name)),
+                    Err(e) => {
+                        self.show_toast(&trf("Could not create profile: {error}", &[("error", &e)]))
+                    }
+                }
+            }
+            AppMsg::SwitchProfile(name) => {
+                if name == self.profile {
+                    return;
+                }
+                // Switching while connected stops the backend first; the
+                // switch completes once it reports Stopped.
+                if self.process_handle.is_some() {
+                    self.reconnect_pending = false;
+                    self.pending_profile = Some(name);
+                    sender.input(AppMsg::Disconnect);
+                } else if self.process_state == ProcessState::Stopping {
+                    self.pending_profile = Some(name);
+                } else {
+                    self.load_profile(name);
+                }
+            }
+            AppMsg::ViewHistory => {
+                let events =
+                    persistence::load_connection_history(&self.paths).unwrap_or_else(|e| {
+                        log::error!("load connection history: {e}");
+                        Vec::new()
+                    });
+                show_history_dialog(&self.window, &events);
             }
             AppMsg::OpenPreferences => {
                 let paths = self.paths.clone();
@@ -483,6 +1520,336 @@ impl SimpleComponent for App {
     }
 }
 
+fn show_config_dialog(window: &adw::ApplicationWindow, json: &str) {
+    let buffer = gtk::TextBuffer::new(None);
+    buffer.set_text(json);
+
+    let text_view = gtk::TextView::builder()
+        .buffer(&buffer)
+        .editable(false)
+        .monospace(true)
+        .wrap_mode(gtk::WrapMode::None)
+        .top_margin(12)
+        .bottom_margin(12)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+
+    let scrolled = gtk::ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .child(&text_view)
+        .build();
+
+    let copy_btn = gtk::Button::builder()
+        .icon_name("edit-copy-symbolic")
+        .tooltip_text(tr("Copy"))
+        .build();
+
+    let header = adw::HeaderBar::new();
+    header.pack_start(&copy_btn);
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&header);
+    toolbar.set_content(Some(&scrolled));
+
+    let toast_overlay = adw::ToastOverlay::new();
+    toast_overlay.set_child(Some(&toolbar));
+
+    {
+        let json = json.to_owned();
+        let overlay = toast_overlay.clone();
+        copy_btn.connect_clicked(move |btn| {
+            btn.clipboard().set_text(&json);
+            overlay.add_toast(adw::Toast::new(&tr("Copied to clipboard")));
+        });
+    }
+
+    let dialog = adw::Dialog::builder()
+        .title(tr("Generated Config"))
+        .content_width(700)
+        .content_height(600)
+        .child(&toast_overlay)
+        .build();
+
+    dialog.present(Some(window));
+}
+
+fn show_diff_dialog(
+    window: &adw::ApplicationWindow,
+    diff: &ConfigDiff,
+    on_reconnect: impl Fn() + 'static,
+) {
+    let buffer = gtk::TextBuffer::new(None);
+    let added = buffer.create_tag(Some("added"), &[("foreground", &"#26a269")]);
+    let removed = buffer.create_tag(Some("removed"), &[("foreground", &"#c01c28")]);
+    for line in &diff.lines {
+        let (tag, text) = match line {
+            DiffLine::Added(text) => (&added, format!("+ {text}\n")),
+            DiffLine::Removed(text) => (&removed, format!("- {text}\n")),
+        };
+        let mut end = buffer.end_iter();
+        if let Some(tag) = tag {
+            buffer.insert_with_tags(&mut end, &text, &[tag]);
+        } else {
+            buffer.insert(&mut end, &text);
+        }
+    }
+
+    let text_view = gtk::TextView::builder()
+        .buffer(&buffer)
+        .editable(false)
+        .monospace(true)
+        .wrap_mode(gtk::WrapMode::None)
+        .top_margin(12)
+        .bottom_margin(12)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+
+    let scrolled = gtk::ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .child(&text_view)
+        .build();
+
+    let reconnect_btn = gtk::Button::builder()
+        .label(tr("Reconnect"))
+        .css_classes(["suggested-action"])
+        .build();
+
+    let header = adw::HeaderBar::new();
+    header.pack_end(&reconnect_btn);
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&header);
+    toolbar.set_content(Some(&scrolled));
+
+    let dialog = adw::Dialog::builder()
+        .title(trf(
+            "Config Changes (+{added} −{removed})",
+            &[("added", &diff.added()), ("removed", &diff.removed())],
+        ))
+        .content_width(700)
+        .content_height(600)
+        .child(&toolbar)
+        .build();
+
+    {
+        let dialog = dialog.clone();
+        reconnect_btn.connect_clicked(move |_| {
+            dialog.close();
+            on_reconnect();
+        });
+    }
+
+    dialog.present(Some(window));
+}
+
+fn show_profiles_dialog(
+    window: &adw::ApplicationWindow,
+    profiles: &[String],
+    current: &str,
+    input: relm4::Sender<AppMsg>,
+) {
+    let dialog = adw::Dialog::builder()
+        .title(tr("Profiles"))
+        .content_width(420)
+        .build();
+
+    let group = adw::PreferencesGroup::builder()
+        .description(tr(
+            "Each profile keeps its own settings, subscriptions and routing rules",
+        ))
+        .build();
+    for name in profiles {
+        let row = adw::ActionRow::builder()
+            .title(name.as_str())
+            .activatable(name != current)
+            .build();
+        if name == current {
+            row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+        }
+        let dialog = dialog.clone();
+        let input = input.clone();
+        let name = name.clone();
+        row.connect_activated(move |_| {
+            dialog.close();
+            input.emit(AppMsg::SwitchProfile(name.clone()));
+        });
+        group.add(&row);
+    }
+
+    let create_group = adw::PreferencesGroup::new();
+    let new_row = adw::EntryRow::builder()
+        .title(tr("New profile"))
+        .show_apply_button(true)
+        .build();
+    {
+        let dialog = dialog.clone();
+        new_row.connect_apply(move |row| {
+            let name = row.text().to_string();
+            if name.trim().is_empty() {
+                return;
+            }
+            dialog.close();
+            input.emit(AppMsg::CreateProfile(name));
+        });
+    }
+    create_group.add(&new_row);
+
+    let page = adw::PreferencesPage::new();
+    page.add(&group);
+    page.add(&create_group);
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&adw::HeaderBar::new());
+    toolbar.set_content(Some(&page));
+    dialog.set_child(Some(&toolbar));
+
+    dialog.present(Some(window));
+}
+
+fn show_history_dialog(window: &adw::ApplicationWindow, events: &[ConnectionEvent]) {
+    let sessions = connection_sessions(events);
+
+    let content: gtk::Widget = if sessions.is_empty() {
+        adw::StatusPage::builder()
+            .icon_name("document-open-recent-symbolic")
+            .title(tr("No Connections Yet"))
+            .build()
+            .upcast()
+    } else {
+        let list = gtk::ListBox::builder()
+            .css_classes(["boxed-list"])
+            .selection_mode(gtk::SelectionMode::None)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .valign(gtk::Align::Start)
+            .build();
+        for session in &sessions {
+            let started = session
+                .started
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M");
+            let status = match session.duration() {
+                None => tr("connected now"),
+                Some(d) if session.ended_with_error && d.is_zero() => tr("failed to start"),
+                Some(d) => {
+                    let length = format_uptime(d.to_std().unwrap_or_default());
+                    if session.ended_with_error {
+                        trf("{duration}, ended with an error", &[("duration", &length)])
+                    } else {
+                        length
+                    }
+                }
+            };
+            let row = adw::ActionRow::builder()
+                .title(
+                    session
+                        .node_remark
+                        .clone()
+                        .unwrap_or_else(|| tr("Unknown node")),
+                )
+                .subtitle(format!("{started} · {status}"))
+                .build();
+            if session.ended_with_error {
+                row.add_prefix(&gtk::Image::from_icon_name("dialog-warning-symbolic"));
+            }
+            list.append(&row);
+        }
+        gtk::ScrolledWindow::builder()
+            .vexpand(true)
+            .child(&list)
+            .build()
+            .upcast()
+    };
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&adw::HeaderBar::new());
+    toolbar.set_content(Some(&content));
+
+    let dialog = adw::Dialog::builder()
+        .title(tr("Connection History"))
+        .content_width(480)
+        .content_height(520)
+        .child(&toolbar)
+        .build();
+
+    dialog.present(Some(window));
+}
+
+fn fill_main_menu(menu: &gtk::gio::Menu) {
+    menu.remove_all();
+    menu.append(
+        Some(&tr("Refresh Subscriptions")),
+        Some("win.refresh-subscriptions"),
+    );
+    menu.append(Some(&tr("View Generated Config")), Some("win.view-config"));
+    menu.append(Some(&tr("Copy Proxy Env")), Some("win.copy-proxy-env"));
+    menu.append(Some(&tr("Export Enabled Nodes")), Some("win.export-nodes"));
+    menu.append(
+        Some(&tr("Review Config Changes")),
+        Some("win.review-changes"),
+    );
+    menu.append(Some(&tr("Connection History")), Some("win.view-history"));
+    menu.append(Some(&tr("Profiles")), Some("win.profiles"));
+    menu.append(Some(&tr("Preferences")), Some("win.preferences"));
+    menu.append(Some(&tr("Quit")), Some("win.quit"));
+}
+
+fn add_window_action(
+    window: &adw::ApplicationWindow,
+    action: &gtk::gio::SimpleAction,
+    sender: &relm4::Sender<AppMsg>,
+    msg: fn() -> AppMsg,
+) {
+    let s = sender.clone();
+    action.connect_activate(move |_, _| {
+        s.emit(msg());
+    });
+    window.add_action(action);
+}
+
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+fn inbounds_label(settings: &AppSettings) -> String {
+    let mut parts = Vec::new();
+    if settings.enable_socks {
+        parts.push(format!("SOCKS {}", settings.socks_port));
+    }
+    if settings.enable_http {
+        parts.push(format!("HTTP {}", settings.http_port));
+    }
+    parts.join(" · ")
+}
+
+fn sync_notifications(settings: &AppSettings) {
+    if let Some(notifier) = NOTIFIER.get() {
+        notifier.set_enabled(settings.notifications_enabled);
+    }
+}
+
+/// Answers other invocations (the CLI or a second launch) through the
+/// control socket, so they drive this instance instead of competing with it.
+fn serve_control(server: ControlServer, sender: relm4::Sender<AppMsg>) {
+    tokio::spawn(async move {
+        while let Ok((request, responder)) = server.accept().await {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            sender.emit(AppMsg::Control(request, reply_tx));
+            let response = reply_rx
+                .await
+                .unwrap_or_else(|_| ControlResponse::error("shutting down"));
+            responder.send(&response).await.ok();
+        }
+    });
+}
+
 fn setup_tray_polling(sender: relm4::Sender<AppMsg>) {
     glib::timeout_add_local(TRAY_POLL_INTERVAL, move || {
         if let Ok(guard) = TRAY_HANDLE.lock()
@@ -527,7 +1894,8 @@ pub fn run() {
         .install_default()
         .expect("failed to install rustls crypto provider");
 
-    let paths = AppPaths::new().expect("failed to determine XDG directories");
+    let base_paths = AppPaths::from_env().expect("failed to determine XDG directories");
+    let paths = base_paths.for_profile(&persistence::load_current_profile(&base_paths));
 
     let settings = v2ray_rs_core::persistence::load_settings(&paths).unwrap_or_default();
     crate::i18n::init(settings.language);
@@ -535,14 +1903,34 @@ pub fn run() {
     let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
     let _rt_guard = rt.enter();
 
-    let (event_tx, event_rx) = broadcast::channel::<ProcessEvent>(EVENT_CHANNEL_CAPACITY);
+    let socket = base_paths.control_socket_path();
+    match ControlServer::bind(socket.clone()) {
+        Ok(server) => {
+            if let Ok(mut guard) = CONTROL_SERVER.lock() {
+                *guard = Some(server);
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            match rt.block_on(send_request(&socket, ControlRequest::Show)) {
+                Ok(ControlResponse::Error { message }) => {
+                    eprintln!("v2ray-rs is already running: {message}");
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("v2ray-rs is already running but did not answer: {e}"),
+            }
+            return;
+        }
+        Err(e) => log::error!("control socket {}: {e}", socket.display()),
+    }
+
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<ProcessEvent>();
     if let Ok(mut guard) = TRAY_EVENT_TX.lock() {
         *guard = Some(event_tx);
     }
 
+    let notifier = NOTIFIER.get_or_init(|| Notifier::new(settings.notifications_enabled));
     let tray_handle = rt.block_on(async {
-        let notifier = v2ray_rs_tray::Notifier::new(settings.notifications_enabled);
-        v2ray_rs_tray::TrayService::spawn(event_rx, notifier)
+        v2ray_rs_tray::TrayService::spawn(event_rx, notifier.clone())
             .await
             .ok()
     });
@@ -559,8 +1947,10 @@ pub fn run() {
         .application_id("com.github.v2ray-rs")
         .build();
 
-    app.connect_startup(|_| {
+    let icon_dir = base_paths.data_dir().join("icons");
+    app.connect_startup(move |_| {
         gtk::Window::set_default_icon_name("v2ray-rs");
+        crate::icons::register_fallback_icons(&icon_dir);
     });
 
     app.connect_activate(|app| {
@@ -571,7 +1961,7 @@ pub fn run() {
     });
 
     let relm_app = RelmApp::from_app(app);
-    relm_app.run::<App>(paths);
+    relm_app.run::<App>(base_paths);
 
     if let Ok(mut guard) = TRAY_HANDLE.lock()
         && let Some(handle) = guard.take()
@@ -0,0 +1,46 @@
+/// How close to the end, in pixels, still counts as being at the bottom, so
+/// rounding in the scroll adjustment does not pause following.
+const BOTTOM_SLACK: f64 = 4.0;
+
+/// Whether a view scrolled to `value` shows the end of its content.
+pub fn at_bottom(value: f64, page_size: f64, upper: f64) -> bool {
+    value + page_size >= upper - BOTTOM_SLACK
+}
+
+/// Decides whether new log lines scroll the view to the end. Following
+/// pauses while the user has scrolled up and resumes once they are back at
+/// the bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoScroll {
+    enabled: bool,
+    paused: bool,
+}
+
+impl AutoScroll {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            paused: false,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turning following on jumps to the end, so it starts unpaused.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.paused = false;
+    }
+
+    /// Records where the view was scrolled to.
+    pub fn scrolled(&mut self, at_bottom: bool) {
+        self.paused = !at_bottom;
+    }
+
+    /// Whether a newly appended line should be scrolled into view.
+    pub fn follows(&self) -> bool {
+        self.enabled && !self.paused
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+/// Coalesces a burst of values into the last one, released once none has
+/// arrived for `delay`. Times are passed in, so callers pick the clock.
+#[derive(Debug)]
+pub struct Debouncer<T> {
+    delay: Duration,
+    pending: Option<(T, Instant)>,
+}
+
+impl<T> Debouncer<T> {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+        }
+    }
+
+    /// Replaces any pending value with `value`, restarting the quiet period.
+    pub fn push(&mut self, value: T, now: Instant) {
+        self.pending = Some((value, now));
+    }
+
+    /// The pending value once it has been left alone for `delay`.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        let (_, at) = self.pending.as_ref()?;
+        if now.duration_since(*at) < self.delay {
+            return None;
+        }
+        self.pending.take().map(|(value, _)| value)
+    }
+
+    /// The pending value right away, e.g. when its source goes away.
+    pub fn flush(&mut self) -> Option<T> {
+        self.pending.take().map(|(value, _)| value)
+    }
+}
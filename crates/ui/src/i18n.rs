@@ -4,13 +4,20 @@ use v2ray_rs_core::models::Language;
 
 const DOMAIN: &str = "v2ray-rs";
 
-pub fn init(language: Language) {
-    let locale = match language {
-        Language::English => "en_US.UTF-8",
-        Language::Russian => "ru_RU.UTF-8",
-    };
+/// The catalog directory under `locale/` that holds `language`.
+pub fn locale_name(language: Language) -> &'static str {
+    match language {
+        Language::English => "en_US",
+        Language::Russian => "ru_RU",
+        Language::Chinese => "zh_CN",
+    }
+}
 
-    setlocale(LocaleCategory::LcAll, locale);
+pub fn init(language: Language) {
+    setlocale(
+        LocaleCategory::LcAll,
+        format!("{}.UTF-8", locale_name(language)),
+    );
 
     let locale_dir = locale_dir();
     if let Some(dir) = locale_dir.to_str() {
@@ -20,18 +27,36 @@ pub fn init(language: Language) {
     textdomain(DOMAIN).ok();
 }
 
+/// Switches lookups to `language` for the rest of the session.
+///
+/// Only strings looked up afterwards change. Callers re-render the widgets
+/// they own; dialogs that are already open keep the language they were built
+/// in until they are closed and reopened.
 pub fn switch_language(language: Language) {
-    let locale = match language {
-        Language::English => "en_US.UTF-8",
-        Language::Russian => "ru_RU.UTF-8",
-    };
-    setlocale(LocaleCategory::LcAll, locale);
+    setlocale(
+        LocaleCategory::LcAll,
+        format!("{}.UTF-8", locale_name(language)),
+    );
 }
 
+/// Looks up `msgid` in the active catalog.
+///
+/// Keys are the English source text, as usual for gettext, so a missing
+/// translation still reads fine. Strings with values in them use named
+/// `{placeholders}` and go through [`trf`] so translators can reorder them.
 pub fn tr(msgid: &str) -> String {
     gettext(msgid)
 }
 
+/// Translates `msgid`, then fills each `{name}` placeholder from `args`.
+pub fn trf(msgid: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+    let mut text = gettext(msgid);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
 fn locale_dir() -> std::path::PathBuf {
     let exe_dir = std::env::current_exe()
         .ok()
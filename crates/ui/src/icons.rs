@@ -0,0 +1,78 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use gtk::gdk;
+use relm4::gtk;
+
+macro_rules! icons {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../icons/", $name, ".svg")))),*]
+    };
+}
+
+/// Every themed icon the UI asks for, with a bundled SVG used when the
+/// current theme (and hicolor) lacks it, so no button renders blank.
+pub const FALLBACK_ICONS: &[(&str, &str)] = icons![
+    "application-x-executable-symbolic",
+    "dialog-error-symbolic",
+    "dialog-warning-symbolic",
+    "document-edit-symbolic",
+    "document-open-recent-symbolic",
+    "document-open-symbolic",
+    "document-save-symbolic",
+    "document-send-symbolic",
+    "edit-clear-all-symbolic",
+    "edit-copy-symbolic",
+    "edit-find-symbolic",
+    "emblem-ok-symbolic",
+    "folder-download-symbolic",
+    "go-down-symbolic",
+    "go-up-symbolic",
+    "image-x-generic-symbolic",
+    "list-add-symbolic",
+    "list-drag-handle-symbolic",
+    "network-server-symbolic",
+    "network-transmit-receive-symbolic",
+    "network-vpn-disconnected-symbolic",
+    "network-vpn-symbolic",
+    "network-wired-disconnected-symbolic",
+    "network-wired-symbolic",
+    "network-wireless-signal-good-symbolic",
+    "network-workgroup-symbolic",
+    "non-starred-symbolic",
+    "object-select-symbolic",
+    "open-menu-symbolic",
+    "pan-down-symbolic",
+    "pan-end-symbolic",
+    "preferences-system-symbolic",
+    "starred-symbolic",
+    "user-trash-symbolic",
+    "view-more-symbolic",
+    "view-sort-ascending-symbolic",
+];
+
+/// Writes the fallbacks into `dir` as unthemed icons, which GTK only uses
+/// when no theme provides the name.
+fn write_fallback_icons(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (name, svg) in FALLBACK_ICONS {
+        let path = dir.join(format!("{name}.svg"));
+        if fs::read_to_string(&path).ok().as_deref() != Some(*svg) {
+            fs::write(path, svg)?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the bundled fallbacks to the display's icon theme search path.
+/// Call once GTK is initialised.
+pub fn register_fallback_icons(dir: &Path) {
+    if let Err(e) = write_fallback_icons(dir) {
+        log::warn!("fallback icons {}: {e}", dir.display());
+        return;
+    }
+    if let Some(display) = gdk::Display::default() {
+        gtk::IconTheme::for_display(&display).add_search_path(dir);
+    }
+}
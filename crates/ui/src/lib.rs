@@ -1,5 +1,8 @@
 mod app;
+pub mod autoscroll;
+pub mod debounce;
 pub mod i18n;
+mod icons;
 mod logs;
 mod preferences;
 mod subscriptions;
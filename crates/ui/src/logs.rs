@@ -2,24 +2,118 @@ use adw::prelude::*;
 use relm4::adw;
 use relm4::prelude::*;
 
+use std::path::PathBuf;
+use v2ray_rs_core::models::AppSettings;
+
+use v2ray_rs_process::{LogBuffer, LogLine, LogSource, ResourceStats};
+
+use crate::autoscroll::{AutoScroll, at_bottom};
+use crate::i18n::{tr, trf};
+
+const STDERR_COLOR: &str = "#e66100";
+const APP_COLOR: &str = "#3584e4";
+
 pub struct LogsPage {
     running: bool,
+    usage: Option<ResourceStats>,
+    auto_scroll: AutoScroll,
+    wrap_lines: bool,
+    /// The lines shown, capped like the backend's own buffer; the text view
+    /// drops its first line whenever this evicts one, so both stay in step.
+    lines: LogBuffer,
     log_buffer: gtk::TextBuffer,
     text_view: gtk::TextView,
+    stdout_tag: gtk::TextTag,
+    stderr_tag: gtk::TextTag,
+    app_tag: gtk::TextTag,
+}
+
+impl LogsPage {
+    fn tag_for(&self, source: LogSource) -> &gtk::TextTag {
+        match source {
+            LogSource::Stdout => &self.stdout_tag,
+            LogSource::Stderr => &self.stderr_tag,
+            LogSource::App => &self.app_tag,
+        }
+    }
+
+    fn scroll_to_end(&self) {
+        if let Some(mark) = self.log_buffer.mark("insert") {
+            let end = self.log_buffer.end_iter();
+            self.log_buffer.move_mark(&mark, &end);
+            self.text_view.scroll_to_mark(&mark, 0.0, false, 0.0, 0.0);
+        }
+    }
+
+    fn save(&self, sender: &ComponentSender<Self>) {
+        let saved_at = chrono::Local::now();
+        let text = self
+            .lines
+            .to_text(&saved_at.format("%Y-%m-%d %H:%M:%S %:z").to_string());
+        let file_dialog = gtk::FileDialog::builder()
+            .title(tr("Save Logs"))
+            .initial_name(format!("v2ray-rs-{}.log", saved_at.format("%Y%m%d-%H%M%S")))
+            .build();
+        let sender = sender.clone();
+        file_dialog.save(
+            gtk::Window::NONE,
+            gtk::gio::Cancellable::NONE,
+            move |result| {
+                let Some(path) = result.ok().and_then(|f| f.path()) else {
+                    return;
+                };
+                let result = std::fs::write(&path, text)
+                    .map(|()| path.clone())
+                    .map_err(|e| {
+                        log::error!("save logs to {}: {e}", path.display());
+                        e.to_string()
+                    });
+                sender.output(LogsOutput::Saved(result)).ok();
+            },
+        );
+    }
+
+    fn view_changed(&self, sender: &ComponentSender<Self>) {
+        sender
+            .output(LogsOutput::ViewChanged {
+                auto_scroll: self.auto_scroll.enabled(),
+                wrap_lines: self.wrap_lines,
+            })
+            .ok();
+    }
 }
 
 #[derive(Debug)]
 pub enum LogsMsg {
-    AppendLine(String),
+    AppendLine(LogLine),
+    SetSourceVisible(LogSource, bool),
     Clear,
+    Save,
     SetRunning(bool),
+    SetResourceUsage(ResourceStats),
+    SetAutoScroll(bool),
+    SetWrapLines(bool),
+    /// The view was scrolled; `true` when it shows the last line.
+    Scrolled(bool),
+    /// Re-reads translated labels after a language switch.
+    Relabel,
+}
+
+#[derive(Debug)]
+pub enum LogsOutput {
+    /// The view options changed and should be saved.
+    ViewChanged {
+        auto_scroll: bool,
+        wrap_lines: bool,
+    },
+    Saved(Result<PathBuf, String>),
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for LogsPage {
-    type Init = ();
+    type Init = AppSettings;
     type Input = LogsMsg;
-    type Output = ();
+    type Output = LogsOutput;
 
     view! {
         gtk::Box {
@@ -28,16 +122,105 @@ impl SimpleComponent for LogsPage {
 
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
-                set_halign: gtk::Align::End,
                 set_margin_top: 6,
+                set_margin_start: 12,
                 set_margin_end: 6,
 
+                gtk::Label {
+                    set_hexpand: true,
+                    set_xalign: 0.0,
+                    add_css_class: "caption",
+                    add_css_class: "dim-label",
+                    #[watch]
+                    set_label: &model.usage.map(format_usage).unwrap_or_default(),
+                },
+
+                gtk::Box {
+                    add_css_class: "linked",
+                    set_margin_end: 6,
+
+                    gtk::ToggleButton {
+                        #[watch]
+                        set_label: &tr("Output"),
+                        #[watch]
+                        set_tooltip_text: Some(&tr("Show backend stdout")),
+                        set_active: true,
+                        add_css_class: "flat",
+                        connect_toggled[sender] => move |btn| {
+                            sender.input(LogsMsg::SetSourceVisible(LogSource::Stdout, btn.is_active()));
+                        },
+                    },
+                    gtk::ToggleButton {
+                        #[watch]
+                        set_label: &tr("Errors"),
+                        #[watch]
+                        set_tooltip_text: Some(&tr("Show backend stderr")),
+                        set_active: true,
+                        add_css_class: "flat",
+                        connect_toggled[sender] => move |btn| {
+                            sender.input(LogsMsg::SetSourceVisible(LogSource::Stderr, btn.is_active()));
+                        },
+                    },
+                    gtk::ToggleButton {
+                        #[watch]
+                        set_label: &tr("App"),
+                        #[watch]
+                        set_tooltip_text: Some(&tr("Show application messages")),
+                        set_active: true,
+                        add_css_class: "flat",
+                        connect_toggled[sender] => move |btn| {
+                            sender.input(LogsMsg::SetSourceVisible(LogSource::App, btn.is_active()));
+                        },
+                    },
+                },
+
                 gtk::Button {
                     set_icon_name: "edit-clear-all-symbolic",
-                    set_tooltip_text: Some("Clear logs"),
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Clear logs")),
                     add_css_class: "flat",
                     connect_clicked => LogsMsg::Clear,
                 },
+
+                gtk::Button {
+                    set_icon_name: "document-save-symbolic",
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Save logs to a file")),
+                    add_css_class: "flat",
+                    connect_clicked => LogsMsg::Save,
+                },
+
+                gtk::MenuButton {
+                    set_icon_name: "view-more-symbolic",
+                    #[watch]
+                    set_tooltip_text: Some(&tr("View options")),
+                    add_css_class: "flat",
+
+                    #[wrap(Some)]
+                    set_popover = &gtk::Popover {
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 6,
+
+                            gtk::CheckButton {
+                                #[watch]
+                                set_label: Some(&tr("Auto-scroll to bottom")),
+                                set_active: model.auto_scroll.enabled(),
+                                connect_toggled[sender] => move |check| {
+                                    sender.input(LogsMsg::SetAutoScroll(check.is_active()));
+                                },
+                            },
+                            gtk::CheckButton {
+                                #[watch]
+                                set_label: Some(&tr("Wrap long lines")),
+                                set_active: model.wrap_lines,
+                                connect_toggled[sender] => move |check| {
+                                    sender.input(LogsMsg::SetWrapLines(check.is_active()));
+                                },
+                            },
+                        },
+                    },
+                },
             },
 
             gtk::Stack {
@@ -48,11 +231,14 @@ impl SimpleComponent for LogsPage {
                 set_visible_child_name: if model.running { "logs" } else { "empty" },
 
                 add_named[Some("empty")] = &adw::StatusPage {
-                    set_title: "Process Not Running",
-                    set_description: Some("Start the proxy to see logs"),
+                    #[watch]
+                    set_title: &tr("Process Not Running"),
+                    #[watch]
+                    set_description: Some(&tr("Start the proxy to see logs")),
                     set_icon_name: Some("network-vpn-disconnected-symbolic"),
                 },
 
+                #[name = "scroller"]
                 add_named[Some("logs")] = &gtk::ScrolledWindow {
                     set_vexpand: true,
 
@@ -65,7 +251,8 @@ impl SimpleComponent for LogsPage {
                         set_right_margin: 12,
                         set_top_margin: 12,
                         set_bottom_margin: 12,
-                        set_wrap_mode: gtk::WrapMode::Word,
+                        #[watch]
+                        set_wrap_mode: if model.wrap_lines { gtk::WrapMode::Word } else { gtk::WrapMode::None },
                     },
                 },
             },
@@ -73,48 +260,122 @@ impl SimpleComponent for LogsPage {
     }
 
     fn init(
-        _init: Self::Init,
+        settings: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let log_buffer = gtk::TextBuffer::new(None::<&gtk::TextTagTable>);
         let text_view = gtk::TextView::builder().buffer(&log_buffer).build();
 
+        let stdout_tag = gtk::TextTag::builder().name("stdout").build();
+        let stderr_tag = gtk::TextTag::builder()
+            .name("stderr")
+            .foreground(STDERR_COLOR)
+            .build();
+        let app_tag = gtk::TextTag::builder()
+            .name("app")
+            .foreground(APP_COLOR)
+            .style(gtk::pango::Style::Italic)
+            .build();
+        let tags = log_buffer.tag_table();
+        tags.add(&stdout_tag);
+        tags.add(&stderr_tag);
+        tags.add(&app_tag);
+
         let model = LogsPage {
             running: false,
+            usage: None,
+            auto_scroll: AutoScroll::new(settings.log_auto_scroll),
+            wrap_lines: settings.log_wrap_lines,
+            lines: LogBuffer::new(),
             log_buffer: log_buffer.clone(),
             text_view: text_view.clone(),
+            stdout_tag,
+            stderr_tag,
+            app_tag,
         };
 
         let text_view = &model.text_view;
         let widgets = view_output!();
+        let input = sender.input_sender().clone();
+        widgets
+            .scroller
+            .vadjustment()
+            .connect_value_changed(move |adj| {
+                input.emit(LogsMsg::Scrolled(at_bottom(
+                    adj.value(),
+                    adj.page_size(),
+                    adj.upper(),
+                )));
+            });
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             LogsMsg::AppendLine(line) => {
                 let mut end_iter = self.log_buffer.end_iter();
-                if self.log_buffer.char_count() > 0 {
-                    self.log_buffer.insert(&mut end_iter, "\n");
-                    end_iter = self.log_buffer.end_iter();
+                let tag = self.tag_for(line.source).clone();
+                self.log_buffer.insert_with_tags(
+                    &mut end_iter,
+                    &format!("{}\n", line.content),
+                    &[&tag],
+                );
+                if self.lines.push(line).is_some() {
+                    let mut start = self.log_buffer.start_iter();
+                    if let Some(mut second) = self.log_buffer.iter_at_line(1) {
+                        self.log_buffer.delete(&mut start, &mut second);
+                    }
                 }
-                self.log_buffer.insert(&mut end_iter, &line);
-
-                if let Some(mark) = self.log_buffer.mark("insert") {
-                    let end = self.log_buffer.end_iter();
-                    self.log_buffer.move_mark(&mark, &end);
-                    self.text_view.scroll_to_mark(&mark, 0.0, false, 0.0, 0.0);
+                if self.auto_scroll.follows() {
+                    self.scroll_to_end();
                 }
             }
+            LogsMsg::SetSourceVisible(source, visible) => {
+                self.tag_for(source).set_invisible(!visible);
+            }
             LogsMsg::Clear => {
                 let mut start = self.log_buffer.start_iter();
                 let mut end = self.log_buffer.end_iter();
                 self.log_buffer.delete(&mut start, &mut end);
+                self.lines.clear();
             }
+            LogsMsg::Save => self.save(&sender),
             LogsMsg::SetRunning(running) => {
                 self.running = running;
+                if !running {
+                    self.usage = None;
+                }
+            }
+            LogsMsg::SetResourceUsage(stats) => {
+                self.usage = Some(stats);
             }
+            LogsMsg::SetAutoScroll(enabled) => {
+                self.auto_scroll.set_enabled(enabled);
+                if enabled {
+                    self.scroll_to_end();
+                }
+                self.view_changed(&sender);
+            }
+            LogsMsg::SetWrapLines(wrap) => {
+                self.wrap_lines = wrap;
+                self.view_changed(&sender);
+            }
+            LogsMsg::Scrolled(at_bottom) => self.auto_scroll.scrolled(at_bottom),
+            LogsMsg::Relabel => {}
         }
     }
 }
+
+fn format_usage(stats: ResourceStats) -> String {
+    let rss_mb = stats.rss_bytes as f64 / (1024.0 * 1024.0);
+    let cpu_secs = stats.cpu_time.as_secs();
+    trf(
+        "Memory {memory} MB · CPU time {minutes}m {seconds}s",
+        &[
+            ("memory", &format!("{rss_mb:.1}")),
+            ("minutes", &(cpu_secs / 60)),
+            ("seconds", &format!("{:02}", cpu_secs % 60)),
+        ],
+    )
+}
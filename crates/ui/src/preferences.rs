@@ -1,21 +1,33 @@
 use adw::prelude::*;
+use gtk::glib;
 use ipnet::IpNet;
 use relm4::adw;
 use relm4::gtk;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use v2ray_rs_core::backend::{backend_name, detect_all};
+use v2ray_rs_core::config::check_output_dir;
 use v2ray_rs_core::models::{
-    AppSettings, BackendConfig, Language, Preset, RoutingRule, RoutingRuleSet, RuleAction,
-    RuleMatch, builtin_presets,
+    AppSettings, BackendConfig, FetchProxy, Language, Preset, RoutingRule, RoutingRuleSet,
+    RuleAction, RuleMatch, UpstreamProtocol, UpstreamProxy, builtin_presets, group_targets,
+    parse_host_port, parse_rule_lines, route_targets, split_geosite_negation,
+    validate_health_check_url, validate_proxy_url,
 };
 use v2ray_rs_core::persistence::{self, AppPaths};
 
+use crate::debounce::Debouncer;
+use crate::i18n::{tr, trf};
+
 type SettingsCallback = Rc<dyn Fn(AppSettings)>;
 
+/// How long settings must stay unchanged before they are passed on (and
+/// saved), so dragging a spin row writes the file once rather than per step.
+const SETTINGS_DEBOUNCE: Duration = Duration::from_millis(300);
+
 pub fn show_preferences(
     parent: &adw::ApplicationWindow,
     paths: &AppPaths,
@@ -23,76 +35,135 @@ pub fn show_preferences(
     on_settings_changed: impl Fn(AppSettings) + 'static,
 ) {
     let dialog = adw::PreferencesDialog::new();
-    dialog.set_title("Preferences");
+    dialog.set_title(&tr("Preferences"));
 
-    let cb: SettingsCallback = Rc::new(on_settings_changed);
+    let cb = debounced(&dialog, Rc::new(on_settings_changed));
     let settings_state = Rc::new(RefCell::new(settings.clone()));
 
-    let system_page = build_system_page(&settings_state, &cb);
+    let system_page = build_system_page(&dialog, paths, &settings_state, &cb);
     dialog.add(&system_page);
 
-    let network_page = build_network_page(&settings_state, &cb);
+    let network_page = build_network_page(&dialog, &settings_state, &cb);
     dialog.add(&network_page);
 
-    let routing_page = build_routing_page(paths);
+    let routing_page = build_routing_page(paths, &settings_state, &cb);
     dialog.add(&routing_page);
 
     dialog.present(Some(parent));
 }
 
+/// Wraps `cb` so bursts of changes reach it once, as the final settings.
+/// Whatever is still pending when the dialog closes is passed on right away.
+fn debounced(dialog: &adw::PreferencesDialog, cb: SettingsCallback) -> SettingsCallback {
+    let debouncer = Rc::new(RefCell::new(Debouncer::new(SETTINGS_DEBOUNCE)));
+    {
+        let debouncer = debouncer.clone();
+        let cb = cb.clone();
+        dialog.connect_closed(move |_| {
+            let pending = debouncer.borrow_mut().flush();
+            if let Some(settings) = pending {
+                cb(settings);
+            }
+        });
+    }
+    Rc::new(move |settings| {
+        debouncer.borrow_mut().push(settings, Instant::now());
+        let debouncer = debouncer.clone();
+        let cb = cb.clone();
+        // Every change arms its own timer; only the one that finds the
+        // settings untouched for the whole delay releases them.
+        glib::timeout_add_local_once(SETTINGS_DEBOUNCE, move || {
+            let ready = debouncer.borrow_mut().poll(Instant::now());
+            if let Some(settings) = ready {
+                cb(settings);
+            }
+        });
+    })
+}
+
 fn emit(state: &Rc<RefCell<AppSettings>>, cb: &SettingsCallback) {
     cb(state.borrow().clone());
 }
 
 fn build_system_page(
+    dialog: &adw::PreferencesDialog,
+    paths: &AppPaths,
     state: &Rc<RefCell<AppSettings>>,
     cb: &SettingsCallback,
 ) -> adw::PreferencesPage {
     let page = adw::PreferencesPage::builder()
-        .title("System")
+        .title(tr("System"))
         .icon_name("preferences-system-symbolic")
         .build();
 
     let s = state.borrow();
 
-    let interface_group = adw::PreferencesGroup::builder().title("Interface").build();
+    let interface_group = adw::PreferencesGroup::builder()
+        .title(tr("Interface"))
+        .build();
 
     let lang_row = adw::ComboRow::builder()
-        .title("Language")
-        .model(&gtk::StringList::new(&["English", "Russian"]))
+        .title(tr("Language"))
+        .model(&translated_list(&["English", "Russian", "Chinese"]))
         .selected(match s.language {
             Language::English => 0,
             Language::Russian => 1,
+            Language::Chinese => 2,
         })
         .build();
     interface_group.add(&lang_row);
     page.add(&interface_group);
 
     let integration_group = adw::PreferencesGroup::builder()
-        .title("Integration")
+        .title(tr("Integration"))
         .build();
 
     let tray_row = adw::SwitchRow::builder()
-        .title("Minimize to tray")
+        .title(tr("Minimize to tray"))
         .active(s.minimize_to_tray)
         .build();
     integration_group.add(&tray_row);
 
     let notif_row = adw::SwitchRow::builder()
-        .title("Enable notifications")
+        .title(tr("Enable notifications"))
         .active(s.notifications_enabled)
         .build();
     integration_group.add(&notif_row);
     page.add(&integration_group);
 
+    let reset_group = adw::PreferencesGroup::new();
+    let reset_row = adw::ActionRow::builder()
+        .title(tr("Reset to Defaults"))
+        .subtitle(tr("Subscriptions are kept"))
+        .activatable(true)
+        .build();
+    reset_row.add_prefix(
+        &gtk::Image::builder()
+            .icon_name("edit-clear-all-symbolic")
+            .build(),
+    );
+    reset_group.add(&reset_row);
+    page.add(&reset_group);
+
     drop(s);
 
+    {
+        let d = dialog.clone();
+        let pp = paths.clone();
+        let st = state.clone();
+        let cb = cb.clone();
+        reset_row.connect_activated(move |_| {
+            show_reset_dialog(&d, &pp, &st, &cb);
+        });
+    }
+
     {
         let st = state.clone();
         let cb = cb.clone();
         lang_row.connect_selected_notify(move |row| {
             st.borrow_mut().language = match row.selected() {
                 1 => Language::Russian,
+                2 => Language::Chinese,
                 _ => Language::English,
             };
             emit(&st, &cb);
@@ -118,28 +189,85 @@ fn build_system_page(
     page
 }
 
+/// Asks before writing the default settings (and optionally empty routing
+/// rules), then closes preferences since every page shows stale values.
+fn show_reset_dialog(
+    prefs: &adw::PreferencesDialog,
+    paths: &AppPaths,
+    state: &Rc<RefCell<AppSettings>>,
+    cb: &SettingsCallback,
+) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Reset to Defaults?"))
+        .body(tr(
+            "All preferences return to their defaults. Subscriptions and the backend binary are kept.",
+        ))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("reset", &tr("Reset"));
+    dialog.set_response_appearance("reset", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let rules_check = gtk::CheckButton::builder()
+        .label(tr("Also reset routing rules"))
+        .halign(gtk::Align::Center)
+        .build();
+    dialog.set_extra_child(Some(&rules_check));
+
+    let prefs_weak = prefs.downgrade();
+    let paths = paths.clone();
+    let state = state.clone();
+    let cb = cb.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "reset" {
+            return;
+        }
+        // Rules first: the settings callback may regenerate the config.
+        if rules_check.is_active()
+            && let Err(e) = persistence::reset_routing_rules(&paths)
+        {
+            log::error!("reset routing rules: {e}");
+        }
+        match persistence::reset_settings(&paths) {
+            Ok(defaults) => {
+                *state.borrow_mut() = defaults;
+                emit(&state, &cb);
+            }
+            Err(e) => log::error!("reset settings: {e}"),
+        }
+        if let Some(prefs) = prefs_weak.upgrade() {
+            prefs.close();
+        }
+    });
+
+    dialog.present(Some(prefs));
+}
+
 fn build_network_page(
+    dialog: &adw::PreferencesDialog,
     state: &Rc<RefCell<AppSettings>>,
     cb: &SettingsCallback,
 ) -> adw::PreferencesPage {
     let page = adw::PreferencesPage::builder()
-        .title("Network")
+        .title(tr("Network"))
         .icon_name("network-server-symbolic")
         .build();
 
     let s = state.borrow();
 
     let backend_group = adw::PreferencesGroup::builder()
-        .title("Backend")
-        .description("Select proxy backend")
+        .title(tr("Backend"))
+        .description(tr("Select proxy backend"))
         .build();
 
     let detected = detect_all();
 
     if detected.is_empty() {
         let row = adw::ActionRow::builder()
-            .title("No backend found")
-            .subtitle("Install v2ray, xray, or sing-box")
+            .title(tr("No backend found"))
+            .subtitle(tr("Install v2ray, xray, or sing-box"))
             .sensitive(false)
             .build();
         backend_group.add(&row);
@@ -194,14 +322,22 @@ fn build_network_page(
             backend_group.add(&row);
         }
     }
+    backend_group.add(&build_output_dir_row(dialog, state, cb));
     page.add(&backend_group);
 
     let ports_group = adw::PreferencesGroup::builder()
-        .title("Proxy Ports")
+        .title(tr("Proxy Ports"))
+        .build();
+
+    let socks_enabled_row = adw::SwitchRow::builder()
+        .title(tr("SOCKS5 inbound"))
+        .active(s.enable_socks)
+        .sensitive(s.enable_http)
         .build();
+    ports_group.add(&socks_enabled_row);
 
     let socks_row = adw::SpinRow::builder()
-        .title("SOCKS5 Port")
+        .title(tr("SOCKS5 Port"))
         .adjustment(&gtk::Adjustment::new(
             s.socks_port as f64,
             1024.0,
@@ -210,11 +346,19 @@ fn build_network_page(
             0.0,
             0.0,
         ))
+        .sensitive(s.enable_socks)
         .build();
     ports_group.add(&socks_row);
 
+    let http_enabled_row = adw::SwitchRow::builder()
+        .title(tr("HTTP inbound"))
+        .active(s.enable_http)
+        .sensitive(s.enable_socks)
+        .build();
+    ports_group.add(&http_enabled_row);
+
     let http_row = adw::SpinRow::builder()
-        .title("HTTP Port")
+        .title(tr("HTTP Port"))
         .adjustment(&gtk::Adjustment::new(
             s.http_port as f64,
             1024.0,
@@ -223,22 +367,40 @@ fn build_network_page(
             0.0,
             0.0,
         ))
+        .sensitive(s.enable_http)
         .build();
     ports_group.add(&http_row);
+
+    let udp_row = adw::SwitchRow::builder()
+        .title(tr("UDP over SOCKS"))
+        .subtitle(tr("Turn off to make apps fall back from QUIC to TCP"))
+        .active(s.socks_udp)
+        .build();
+    ports_group.add(&udp_row);
+
+    let ipv6_row = adw::SwitchRow::builder()
+        .title(tr("IPv6"))
+        .subtitle(tr(
+            "Turn off if connections hang on networks with broken IPv6",
+        ))
+        .active(s.ipv6)
+        .build();
+    ports_group.add(&ipv6_row);
     page.add(&ports_group);
+    page.add(&build_upstream_group(state, cb));
 
     let sub_group = adw::PreferencesGroup::builder()
-        .title("Subscriptions")
+        .title(tr("Subscriptions"))
         .build();
 
     let auto_update_row = adw::SwitchRow::builder()
-        .title("Auto-update subscriptions")
+        .title(tr("Auto-update subscriptions"))
         .active(s.auto_update_subscriptions)
         .build();
     sub_group.add(&auto_update_row);
 
     let interval_row = adw::SpinRow::builder()
-        .title("Update interval (hours)")
+        .title(tr("Update interval (hours)"))
         .sensitive(s.auto_update_subscriptions)
         .adjustment(&gtk::Adjustment::new(
             (s.subscription_update_interval_secs / 3600) as f64,
@@ -250,10 +412,118 @@ fn build_network_page(
         ))
         .build();
     sub_group.add(&interval_row);
+
+    let metered_row = adw::SwitchRow::builder()
+        .title(tr("Pause on metered connections"))
+        .subtitle(tr("Skip automatic updates while the network is metered"))
+        .sensitive(s.auto_update_subscriptions)
+        .active(s.pause_updates_on_metered)
+        .build();
+    sub_group.add(&metered_row);
+    add_fetch_proxy_rows(&sub_group, state, cb);
+
+    let expiry_row = adw::SpinRow::builder()
+        .title(tr("Expiry warning (days)"))
+        .subtitle(tr(
+            "Notify when a subscription is about to expire or out of data",
+        ))
+        .adjustment(&gtk::Adjustment::new(
+            s.expiry_warning_days as f64,
+            0.0,
+            30.0,
+            1.0,
+            0.0,
+            0.0,
+        ))
+        .build();
+    sub_group.add(&expiry_row);
+
+    let strict_uuid_row = adw::SwitchRow::builder()
+        .title(tr("Strict UUID check"))
+        .subtitle(tr(
+            "Reject VLESS and VMess links whose user ID is not a UUID",
+        ))
+        .active(s.strict_uuid)
+        .build();
+    sub_group.add(&strict_uuid_row);
+
+    let groups_row = adw::SwitchRow::builder()
+        .title(tr("Subscription groups"))
+        .subtitle(tr(
+            "sing-box only: switch between a subscription's nodes at runtime through the Clash API",
+        ))
+        .active(s.subscription_groups)
+        .build();
+    sub_group.add(&groups_row);
+
+    let clash_port_row = adw::SpinRow::builder()
+        .title(tr("Clash API Port"))
+        .sensitive(s.subscription_groups)
+        .adjustment(&gtk::Adjustment::new(
+            s.clash_api_port as f64,
+            1024.0,
+            65535.0,
+            1.0,
+            0.0,
+            0.0,
+        ))
+        .build();
+    sub_group.add(&clash_port_row);
     page.add(&sub_group);
 
+    let advanced_group = adw::PreferencesGroup::builder()
+        .title(tr("Advanced"))
+        .build();
+
+    let health_url_row = adw::EntryRow::builder()
+        .title(tr("Health Check URL"))
+        .text(&s.health_check_url)
+        .show_apply_button(true)
+        .build();
+    advanced_group.add(&health_url_row);
+
+    let health_status_row = adw::SpinRow::builder()
+        .title(tr("Expected Status Code"))
+        .subtitle(tr("HTTP status the health check URL answers with"))
+        .adjustment(&gtk::Adjustment::new(
+            s.health_check_expected_status as f64,
+            100.0,
+            599.0,
+            1.0,
+            0.0,
+            0.0,
+        ))
+        .build();
+    advanced_group.add(&health_status_row);
+    page.add(&advanced_group);
+
     drop(s);
 
+    // One inbound always stays on: each switch locks the other while it is off.
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        let port = socks_row.clone();
+        let other = http_enabled_row.clone();
+        socks_enabled_row.connect_active_notify(move |row| {
+            st.borrow_mut().enable_socks = row.is_active();
+            port.set_sensitive(row.is_active());
+            other.set_sensitive(row.is_active());
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        let port = http_row.clone();
+        let other = socks_enabled_row.clone();
+        http_enabled_row.connect_active_notify(move |row| {
+            st.borrow_mut().enable_http = row.is_active();
+            port.set_sensitive(row.is_active());
+            other.set_sensitive(row.is_active());
+            emit(&st, &cb);
+        });
+    }
     {
         let st = state.clone();
         let cb = cb.clone();
@@ -270,13 +540,31 @@ fn build_network_page(
             emit(&st, &cb);
         });
     }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        udp_row.connect_active_notify(move |row| {
+            st.borrow_mut().socks_udp = row.is_active();
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        ipv6_row.connect_active_notify(move |row| {
+            st.borrow_mut().ipv6 = row.is_active();
+            emit(&st, &cb);
+        });
+    }
     {
         let st = state.clone();
         let cb = cb.clone();
         let interval = interval_row.clone();
+        let metered = metered_row.clone();
         auto_update_row.connect_active_notify(move |row| {
             st.borrow_mut().auto_update_subscriptions = row.is_active();
             interval.set_sensitive(row.is_active());
+            metered.set_sensitive(row.is_active());
             emit(&st, &cb);
         });
     }
@@ -289,12 +577,387 @@ fn build_network_page(
         });
     }
 
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        metered_row.connect_active_notify(move |row| {
+            st.borrow_mut().pause_updates_on_metered = row.is_active();
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        expiry_row.connect_changed(move |row| {
+            st.borrow_mut().expiry_warning_days = row.value() as u32;
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        strict_uuid_row.connect_active_notify(move |row| {
+            st.borrow_mut().strict_uuid = row.is_active();
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        let port = clash_port_row.clone();
+        groups_row.connect_active_notify(move |row| {
+            st.borrow_mut().subscription_groups = row.is_active();
+            port.set_sensitive(row.is_active());
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        clash_port_row.connect_changed(move |row| {
+            st.borrow_mut().clash_api_port = row.value() as u16;
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        health_url_row.connect_apply(move |row| {
+            let url = row.text().trim().to_owned();
+            if validate_health_check_url(&url).is_err() {
+                row.add_css_class("error");
+                return;
+            }
+            row.remove_css_class("error");
+            st.borrow_mut().health_check_url = url;
+            emit(&st, &cb);
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        health_status_row.connect_changed(move |row| {
+            st.borrow_mut().health_check_expected_status = row.value() as u16;
+            emit(&st, &cb);
+        });
+    }
+
     page
 }
 
-fn build_routing_page(paths: &AppPaths) -> adw::PreferencesPage {
+fn output_dir_subtitle(settings: &AppSettings) -> String {
+    match &settings.backend.config_output_dir {
+        Some(dir) => dir.display().to_string(),
+        None => tr("Default (application data directory)"),
+    }
+}
+
+/// Row choosing where generated configs are written. A directory that cannot
+/// be created or written to is rejected and the previous one kept.
+fn build_output_dir_row(
+    dialog: &adw::PreferencesDialog,
+    state: &Rc<RefCell<AppSettings>>,
+    cb: &SettingsCallback,
+) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(tr("Config Directory"))
+        .subtitle(output_dir_subtitle(&state.borrow()))
+        .build();
+
+    let choose_btn = gtk::Button::builder()
+        .icon_name("document-open-symbolic")
+        .tooltip_text(tr("Choose Directory"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .build();
+    let reset_btn = gtk::Button::builder()
+        .icon_name("edit-clear-all-symbolic")
+        .tooltip_text(tr("Use Default"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .sensitive(state.borrow().backend.config_output_dir.is_some())
+        .build();
+    row.add_suffix(&choose_btn);
+    row.add_suffix(&reset_btn);
+
+    {
+        let dialog = dialog.clone();
+        let st = state.clone();
+        let cb = cb.clone();
+        let row = row.clone();
+        let reset_btn = reset_btn.clone();
+        choose_btn.connect_clicked(move |_| {
+            let file_dialog = gtk::FileDialog::builder()
+                .title(tr("Config Directory"))
+                .build();
+            let dialog = dialog.clone();
+            let st = st.clone();
+            let cb = cb.clone();
+            let row = row.clone();
+            let reset_btn = reset_btn.clone();
+            file_dialog.select_folder(
+                gtk::Window::NONE,
+                gtk::gio::Cancellable::NONE,
+                move |result| {
+                    let Some(path) = result.ok().and_then(|f| f.path()) else {
+                        return;
+                    };
+                    if let Err(e) = check_output_dir(&path) {
+                        log::error!("config directory {}: {e}", path.display());
+                        dialog.add_toast(adw::Toast::new(&trf(
+                            "Cannot write to {path}, keeping the previous directory",
+                            &[("path", &path.display())],
+                        )));
+                        return;
+                    }
+                    st.borrow_mut().backend.config_output_dir = Some(path);
+                    row.set_subtitle(&output_dir_subtitle(&st.borrow()));
+                    reset_btn.set_sensitive(true);
+                    emit(&st, &cb);
+                },
+            );
+        });
+    }
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        let row = row.clone();
+        reset_btn.connect_clicked(move |btn| {
+            st.borrow_mut().backend.config_output_dir = None;
+            row.set_subtitle(&output_dir_subtitle(&st.borrow()));
+            btn.set_sensitive(false);
+            emit(&st, &cb);
+        });
+    }
+
+    row
+}
+
+/// Rows picking how subscriptions are downloaded. A custom URL only takes
+/// effect once it parses as an HTTP proxy.
+fn add_fetch_proxy_rows(
+    group: &adw::PreferencesGroup,
+    state: &Rc<RefCell<AppSettings>>,
+    cb: &SettingsCallback,
+) {
+    let current = state.borrow().subscription_proxy.clone();
+
+    let mode_row = adw::ComboRow::builder()
+        .title(tr("Fetch through"))
+        .model(&gtk::StringList::new(&[
+            &tr("No proxy"),
+            &tr("System proxy"),
+            &tr("Custom proxy"),
+        ]))
+        .selected(match current {
+            FetchProxy::Direct => 0,
+            FetchProxy::System => 1,
+            FetchProxy::Custom { .. } => 2,
+        })
+        .build();
+    group.add(&mode_row);
+
+    let url_row = adw::EntryRow::builder()
+        .title(tr("Proxy URL"))
+        .text(match &current {
+            FetchProxy::Custom { url } => url.as_str(),
+            _ => "",
+        })
+        .sensitive(matches!(current, FetchProxy::Custom { .. }))
+        .show_apply_button(true)
+        .build();
+    group.add(&url_row);
+
+    let sync: Rc<dyn Fn()> = {
+        let st = state.clone();
+        let cb = cb.clone();
+        let mode_row = mode_row.clone();
+        let url_row = url_row.clone();
+        Rc::new(move || {
+            let proxy = match mode_row.selected() {
+                1 => FetchProxy::System,
+                2 => {
+                    let url = url_row.text().trim().to_owned();
+                    if validate_proxy_url(&url).is_err() {
+                        url_row.add_css_class("error");
+                        return;
+                    }
+                    FetchProxy::Custom { url }
+                }
+                _ => FetchProxy::Direct,
+            };
+            url_row.remove_css_class("error");
+            if st.borrow().subscription_proxy == proxy {
+                return;
+            }
+            st.borrow_mut().subscription_proxy = proxy;
+            emit(&st, &cb);
+        })
+    };
+
+    {
+        let sync = sync.clone();
+        let url_row = url_row.clone();
+        mode_row.connect_selected_notify(move |row| {
+            url_row.set_sensitive(row.selected() == 2);
+            sync();
+        });
+    }
+    url_row.connect_apply(move |_| sync());
+}
+
+fn build_upstream_group(
+    state: &Rc<RefCell<AppSettings>>,
+    cb: &SettingsCallback,
+) -> adw::PreferencesGroup {
+    let group = adw::PreferencesGroup::builder()
+        .title(tr("Upstream Proxy"))
+        .description(tr(
+            "Send direct traffic through another proxy, such as a corporate one",
+        ))
+        .build();
+
+    let upstream = state.borrow().upstream_proxy.clone();
+
+    let enable_row = adw::SwitchRow::builder()
+        .title(tr("Use upstream proxy"))
+        .active(upstream.is_some())
+        .build();
+    group.add(&enable_row);
+
+    let protocol_row = adw::ComboRow::builder()
+        .title(tr("Protocol"))
+        .model(&gtk::StringList::new(&["SOCKS5", "HTTP"]))
+        .selected(match upstream.as_ref().map(|u| u.protocol) {
+            Some(UpstreamProtocol::Http) => 1,
+            _ => 0,
+        })
+        .build();
+    group.add(&protocol_row);
+
+    let address_row = adw::EntryRow::builder()
+        .title(tr("Address (host:port)"))
+        .text(
+            upstream
+                .as_ref()
+                .map(|u| {
+                    if u.host.contains(':') {
+                        format!("[{}]:{}", u.host, u.port)
+                    } else {
+                        format!("{}:{}", u.host, u.port)
+                    }
+                })
+                .unwrap_or_default(),
+        )
+        .show_apply_button(true)
+        .build();
+    group.add(&address_row);
+
+    let username_row = adw::EntryRow::builder()
+        .title(tr("Username"))
+        .text(
+            upstream
+                .as_ref()
+                .and_then(|u| u.username.clone())
+                .unwrap_or_default(),
+        )
+        .show_apply_button(true)
+        .build();
+    group.add(&username_row);
+
+    let password_row = adw::PasswordEntryRow::builder()
+        .title(tr("Password"))
+        .text(
+            upstream
+                .as_ref()
+                .and_then(|u| u.password.clone())
+                .unwrap_or_default(),
+        )
+        .show_apply_button(true)
+        .build();
+    group.add(&password_row);
+
+    let detail_rows: [gtk::Widget; 4] = [
+        protocol_row.clone().upcast(),
+        address_row.clone().upcast(),
+        username_row.clone().upcast(),
+        password_row.clone().upcast(),
+    ];
+    for row in &detail_rows {
+        row.set_sensitive(upstream.is_some());
+    }
+
+    let sync: Rc<dyn Fn()> = {
+        let st = state.clone();
+        let cb = cb.clone();
+        let enable_row = enable_row.clone();
+        let protocol_row = protocol_row.clone();
+        let address_row = address_row.clone();
+        let username_row = username_row.clone();
+        let password_row = password_row.clone();
+        Rc::new(move || {
+            let upstream = if enable_row.is_active() {
+                let Ok((host, port)) = parse_host_port(&address_row.text()) else {
+                    address_row.add_css_class("error");
+                    return;
+                };
+                address_row.remove_css_class("error");
+                let optional = |text: String| (!text.is_empty()).then_some(text);
+                Some(UpstreamProxy {
+                    protocol: match protocol_row.selected() {
+                        1 => UpstreamProtocol::Http,
+                        _ => UpstreamProtocol::Socks,
+                    },
+                    host,
+                    port,
+                    username: optional(username_row.text().trim().to_owned()),
+                    password: optional(password_row.text().to_string()),
+                })
+            } else {
+                address_row.remove_css_class("error");
+                None
+            };
+            if st.borrow().upstream_proxy == upstream {
+                return;
+            }
+            st.borrow_mut().upstream_proxy = upstream;
+            emit(&st, &cb);
+        })
+    };
+
+    {
+        let sync = sync.clone();
+        enable_row.connect_active_notify(move |row| {
+            for detail in &detail_rows {
+                detail.set_sensitive(row.is_active());
+            }
+            sync();
+        });
+    }
+    {
+        let sync = sync.clone();
+        protocol_row.connect_selected_notify(move |_| sync());
+    }
+    {
+        let sync = sync.clone();
+        address_row.connect_apply(move |_| sync());
+    }
+    {
+        let sync = sync.clone();
+        username_row.connect_apply(move |_| sync());
+    }
+    password_row.connect_apply(move |_| sync());
+
+    group
+}
+
+fn build_routing_page(
+    paths: &AppPaths,
+    state: &Rc<RefCell<AppSettings>>,
+    cb: &SettingsCallback,
+) -> adw::PreferencesPage {
     let page = adw::PreferencesPage::builder()
-        .title("Routing")
+        .title(tr("Routing"))
         .icon_name("network-workgroup-symbolic")
         .build();
 
@@ -314,14 +977,20 @@ fn build_routing_page(paths: &AppPaths) -> adw::PreferencesPage {
         .build();
 
     let presets_btn = gtk::Button::builder()
-        .label("Presets")
+        .label(tr("Presets"))
         .css_classes(["flat"])
         .build();
     toolbar.append(&presets_btn);
 
+    let import_btn = gtk::Button::builder()
+        .label(tr("Import Rules…"))
+        .css_classes(["flat"])
+        .build();
+    toolbar.append(&import_btn);
+
     let add_btn = gtk::Button::builder()
         .icon_name("list-add-symbolic")
-        .tooltip_text("Add Rule")
+        .tooltip_text(tr("Add Rule"))
         .css_classes(["flat"])
         .build();
     toolbar.append(&add_btn);
@@ -331,15 +1000,42 @@ fn build_routing_page(paths: &AppPaths) -> adw::PreferencesPage {
     page.add(&toolbar_group);
 
     let rules_group = adw::PreferencesGroup::builder()
-        .title("Rules")
-        .description("Rules are evaluated in order from top to bottom")
+        .title(tr("Rules"))
+        .description(tr("Rules are evaluated in order from top to bottom"))
         .build();
     page.add(&rules_group);
 
+    let default_group = adw::PreferencesGroup::new();
+    let default_row = adw::ComboRow::builder()
+        .title(tr("Default Route"))
+        .subtitle(tr("Where traffic that matches no rule goes"))
+        .model(&translated_list(&["Proxy", "Direct", "Block"]))
+        .selected(match state.borrow().default_action {
+            RuleAction::Proxy => 0,
+            RuleAction::Direct => 1,
+            RuleAction::Block => 2,
+        })
+        .build();
+    default_group.add(&default_row);
+    page.add(&default_group);
+    {
+        let st = state.clone();
+        let cb = cb.clone();
+        default_row.connect_selected_notify(move |row| {
+            st.borrow_mut().default_action = match row.selected() {
+                1 => RuleAction::Direct,
+                2 => RuleAction::Block,
+                _ => RuleAction::Proxy,
+            };
+            emit(&st, &cb);
+        });
+    }
+
     let ctx = RenderCtx {
         rules_group: rules_group.clone(),
         rule_set: rule_set.clone(),
         paths: paths.clone(),
+        settings: state.clone(),
         added_rows: Rc::new(RefCell::new(Vec::new())),
     };
 
@@ -351,6 +1047,12 @@ fn build_routing_page(paths: &AppPaths) -> adw::PreferencesPage {
             show_routing_rule_dialog(None, &ctx);
         });
     }
+    {
+        let ctx = ctx.clone();
+        import_btn.connect_clicked(move |_| {
+            show_import_rules_dialog(&ctx);
+        });
+    }
     {
         let ctx = ctx.clone();
         let p = paths.clone();
@@ -367,6 +1069,7 @@ struct RenderCtx {
     rules_group: adw::PreferencesGroup,
     rule_set: Rc<RefCell<RoutingRuleSet>>,
     paths: Rc<AppPaths>,
+    settings: Rc<RefCell<AppSettings>>,
     added_rows: Rc<RefCell<Vec<adw::ActionRow>>>,
 }
 
@@ -386,9 +1089,10 @@ fn render_routing_rules(ctx: &RenderCtx) {
     }
 
     let total = rules.len();
+    let targets = load_route_targets(ctx);
     let mut rows = ctx.added_rows.borrow_mut();
     for (idx, rule) in rules.iter().enumerate() {
-        let row = build_routing_rule_row(rule, idx, total, ctx);
+        let row = build_routing_rule_row(rule, idx, total, &targets, ctx);
         group.add(&row);
         rows.push(row);
     }
@@ -398,11 +1102,12 @@ fn build_routing_rule_row(
     rule: &RoutingRule,
     idx: usize,
     total: usize,
+    targets: &[(Uuid, String)],
     ctx: &RenderCtx,
 ) -> adw::ActionRow {
     let row = adw::ActionRow::builder()
         .title(format_match(&rule.match_condition))
-        .subtitle(format_action(&rule.action))
+        .subtitle(format_rule_action(rule, targets))
         .build();
 
     let switch = gtk::Switch::builder()
@@ -439,7 +1144,7 @@ fn build_routing_rule_row(
 
     if idx > 0 {
         let btn = gtk::Button::builder()
-            .label("Move Up")
+            .label(tr("Move Up"))
             .has_frame(false)
             .build();
         let ctx = ctx.clone();
@@ -457,7 +1162,7 @@ fn build_routing_rule_row(
 
     if idx < total - 1 {
         let btn = gtk::Button::builder()
-            .label("Move Down")
+            .label(tr("Move Down"))
             .has_frame(false)
             .build();
         let ctx = ctx.clone();
@@ -474,7 +1179,7 @@ fn build_routing_rule_row(
     }
 
     let edit_btn = gtk::Button::builder()
-        .label("Edit")
+        .label(tr("Edit"))
         .has_frame(false)
         .build();
     {
@@ -497,8 +1202,41 @@ fn build_routing_rule_row(
     }
     popover_box.append(&edit_btn);
 
+    let duplicate_btn = gtk::Button::builder()
+        .label(tr("Duplicate"))
+        .has_frame(false)
+        .build();
+    {
+        let id = rule.id;
+        let ctx = ctx.clone();
+        let pop = popover.clone();
+        duplicate_btn.connect_clicked(move |_| {
+            pop.popdown();
+            let mut rs = ctx.rule_set.borrow_mut();
+            let Some(pos) = rs.rules().iter().position(|r| r.id == id) else {
+                return;
+            };
+            let copy = RoutingRule {
+                id: Uuid::new_v4(),
+                enabled: true,
+                target: None,
+                ..rs.rules()[pos].clone()
+            };
+            if let Err(e) = rs.add_at(pos + 1, copy) {
+                log::error!("duplicate routing rule: {e}");
+                return;
+            }
+            if let Err(e) = persistence::save_routing_rules(&ctx.paths, &rs) {
+                log::error!("save routing rules: {e}");
+            }
+            drop(rs);
+            render_routing_rules(&ctx);
+        });
+    }
+    popover_box.append(&duplicate_btn);
+
     let delete_btn = gtk::Button::builder()
-        .label("Delete")
+        .label(tr("Delete"))
         .has_frame(false)
         .css_classes(["destructive-action"])
         .build();
@@ -528,11 +1266,15 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
     let is_edit = existing.is_some();
 
     let dialog = adw::AlertDialog::builder()
-        .heading(if is_edit { "Edit Rule" } else { "Add Rule" })
+        .heading(if is_edit {
+            tr("Edit Rule")
+        } else {
+            tr("Add Rule")
+        })
         .build();
 
-    dialog.add_response("cancel", "Cancel");
-    dialog.add_response("save", if is_edit { "Save" } else { "Add" });
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("save", &if is_edit { tr("Save") } else { tr("Add") });
     dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
     dialog.set_default_response(Some("save"));
     dialog.set_close_response("cancel");
@@ -565,8 +1307,8 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
         .build();
 
     let type_combo = adw::ComboRow::builder()
-        .title("Rule Type")
-        .model(&gtk::StringList::new(&[
+        .title(tr("Rule Type"))
+        .model(&translated_list(&[
             "GeoIP Country Code",
             "GeoSite Category",
             "Domain Pattern",
@@ -575,21 +1317,75 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
         .selected(init_type_idx)
         .build();
 
+    let (init_negated, init_value) = match init_type_idx {
+        1 => {
+            let (negated, base) = split_geosite_negation(&init_value);
+            (negated, base.to_owned())
+        }
+        _ => (false, init_value),
+    };
+
     let value_entry = adw::EntryRow::builder()
-        .title("Match Value")
+        .title(tr("Match Value"))
         .text(&init_value)
         .build();
 
+    let negate_row = adw::SwitchRow::builder()
+        .title(tr("Negate"))
+        .subtitle(tr("Match domains outside this category"))
+        .active(init_negated)
+        .visible(init_type_idx == 1)
+        .build();
+    {
+        let negate_row = negate_row.clone();
+        type_combo.connect_selected_notify(move |combo| {
+            negate_row.set_visible(combo.selected() == 1);
+        });
+    }
+
     let action_combo = adw::ComboRow::builder()
-        .title("Action")
-        .model(&gtk::StringList::new(&["Proxy", "Direct", "Block"]))
+        .title(tr("Action"))
+        .model(&translated_list(&["Proxy", "Direct", "Block"]))
         .selected(init_action_idx)
         .build();
 
+    // The primary node, then every routing target. A target that is no
+    // longer enabled stays selectable so editing the rule does not drop it.
+    let mut targets: Vec<(Option<Uuid>, String)> = vec![(None, tr("Primary Node"))];
+    targets.extend(
+        load_route_targets(ctx)
+            .into_iter()
+            .map(|(id, label)| (Some(id), label)),
+    );
+    let init_target = existing.as_ref().and_then(|r| r.target);
+    if init_target.is_some() && !targets.iter().any(|(id, _)| *id == init_target) {
+        targets.push((init_target, tr("Disabled node (uses the primary node)")));
+    }
+    let target_labels: Vec<&str> = targets.iter().map(|(_, label)| label.as_str()).collect();
+    let target_combo = adw::ComboRow::builder()
+        .title(tr("Outbound"))
+        .model(&gtk::StringList::new(&target_labels))
+        .selected(
+            targets
+                .iter()
+                .position(|(id, _)| *id == init_target)
+                .unwrap_or(0) as u32,
+        )
+        .visible(targets.len() > 1 && init_action_idx == 0)
+        .build();
+    if targets.len() > 1 {
+        let target_combo = target_combo.clone();
+        action_combo.connect_selected_notify(move |combo| {
+            target_combo.set_visible(combo.selected() == 0);
+        });
+    }
+
     let group = adw::PreferencesGroup::new();
     group.add(&type_combo);
     group.add(&value_entry);
+    group.add(&negate_row);
     group.add(&action_combo);
+    group.add(&target_combo);
     content.append(&group);
 
     dialog.set_extra_child(Some(&content));
@@ -610,14 +1406,21 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
             0 => RuleMatch::GeoIp {
                 country_code: value,
             },
-            1 => RuleMatch::GeoSite { category: value },
+            1 => RuleMatch::GeoSite {
+                category: if negate_row.is_active() {
+                    format!("!{}", value.trim_start_matches('!'))
+                } else {
+                    value
+                },
+            },
             2 => RuleMatch::Domain { pattern: value },
             3 => match IpNet::from_str(&value) {
                 Ok(cidr) => RuleMatch::IpCidr { cidr },
                 Err(_) => return,
             },
             _ => return,
-        };
+        }
+        .normalized();
 
         let action = match action_combo.selected() {
             0 => RuleAction::Proxy,
@@ -625,11 +1428,18 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
             _ => RuleAction::Block,
         };
 
+        let target = match action {
+            RuleAction::Proxy => targets
+                .get(target_combo.selected() as usize)
+                .and_then(|(id, _)| *id),
+            _ => None,
+        };
         let rule = RoutingRule {
             id: editing_id.unwrap_or_else(Uuid::new_v4),
             match_condition,
             action,
             enabled: true,
+            target,
         };
 
         {
@@ -650,11 +1460,101 @@ fn show_routing_rule_dialog(existing: Option<RoutingRule>, ctx: &RenderCtx) {
     dialog.present(gtk::Window::NONE);
 }
 
+fn show_import_rules_dialog(ctx: &RenderCtx) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Import Rules"))
+        .body(tr("Select a text file with one domain or IP/CIDR per line. Lines starting with # are ignored."))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("choose", &tr("Choose File…"));
+    dialog.set_response_appearance("choose", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("choose"));
+    dialog.set_close_response("cancel");
+
+    let action_combo = adw::ComboRow::builder()
+        .title(tr("Action"))
+        .model(&translated_list(&["Proxy", "Direct", "Block"]))
+        .build();
+
+    let group = adw::PreferencesGroup::new();
+    group.add(&action_combo);
+    dialog.set_extra_child(Some(&group));
+
+    let ctx = ctx.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "choose" {
+            return;
+        }
+
+        let action = match action_combo.selected() {
+            0 => RuleAction::Proxy,
+            1 => RuleAction::Direct,
+            _ => RuleAction::Block,
+        };
+
+        let file_dialog = gtk::FileDialog::builder().title(tr("Import Rules")).build();
+        let ctx = ctx.clone();
+        file_dialog.open(
+            gtk::Window::NONE,
+            gtk::gio::Cancellable::NONE,
+            move |result| {
+                let Some(path) = result.ok().and_then(|f| f.path()) else {
+                    return;
+                };
+                let content = match std::fs::read_to_string(&path) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        show_message_dialog(&tr("Import Failed"), &e.to_string());
+                        return;
+                    }
+                };
+
+                let (rules, errors) = parse_rule_lines(&content, action);
+                let imported = rules.len();
+                {
+                    let mut rs = ctx.rule_set.borrow_mut();
+                    for rule in rules {
+                        rs.add(rule);
+                    }
+                    if let Err(e) = persistence::save_routing_rules(&ctx.paths, &rs) {
+                        log::error!("save routing rules: {e}");
+                    }
+                }
+                render_routing_rules(&ctx);
+
+                for (line, e) in &errors {
+                    log::warn!("skipped rule {line:?}: {e}");
+                }
+                show_message_dialog(
+                    &tr("Rules Imported"),
+                    &trf(
+                        "Imported {imported} rules, skipped {skipped}",
+                        &[("imported", &imported), ("skipped", &errors.len())],
+                    ),
+                );
+            },
+        );
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
+pub(crate) fn show_message_dialog(heading: &str, body: &str) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(heading)
+        .body(body)
+        .build();
+    dialog.add_response("close", &tr("Close"));
+    dialog.set_close_response("close");
+    dialog.present(gtk::Window::NONE);
+}
+
 fn show_routing_presets_dialog(paths: &Rc<AppPaths>, ctx: &RenderCtx) {
     let dialog = adw::AlertDialog::builder()
-        .heading("Routing Presets")
+        .heading(tr("Routing Presets"))
         .build();
-    dialog.add_response("close", "Close");
+    dialog.add_response("close", &tr("Close"));
     dialog.set_default_response(Some("close"));
     dialog.set_close_response("close");
 
@@ -663,14 +1563,16 @@ fn show_routing_presets_dialog(paths: &Rc<AppPaths>, ctx: &RenderCtx) {
         .spacing(12)
         .build();
 
-    let builtin_group = adw::PreferencesGroup::builder().title("Built-in").build();
+    let builtin_group = adw::PreferencesGroup::builder()
+        .title(tr("Built-in"))
+        .build();
     for preset in builtin_presets() {
         let row = adw::ActionRow::builder()
             .title(&preset.name)
             .subtitle(&preset.description)
             .build();
         let apply_btn = gtk::Button::builder()
-            .label("Apply")
+            .label(tr("Apply"))
             .valign(gtk::Align::Center)
             .css_classes(["suggested-action"])
             .build();
@@ -690,29 +1592,62 @@ fn show_routing_presets_dialog(paths: &Rc<AppPaths>, ctx: &RenderCtx) {
 
     let custom = persistence::load_custom_presets(paths).unwrap_or_default();
     if !custom.is_empty() {
-        let custom_group = adw::PreferencesGroup::builder().title("Custom").build();
+        let custom_group = adw::PreferencesGroup::builder().title(tr("Custom")).build();
         for preset in &custom {
             let row = adw::ActionRow::builder()
                 .title(&preset.name)
                 .subtitle(&preset.description)
                 .build();
             let apply_btn = gtk::Button::builder()
-                .label("Apply")
+                .label(tr("Apply"))
                 .valign(gtk::Align::Center)
                 .css_classes(["suggested-action"])
                 .build();
-            let ctx = ctx.clone();
-            let p = preset.clone();
-            apply_btn.connect_clicked(move |_| {
-                ctx.rule_set.borrow_mut().apply_preset(&p);
-                if let Err(e) = persistence::save_routing_rules(&ctx.paths, &ctx.rule_set.borrow())
-                {
-                    log::error!("save routing rules: {e}");
-                }
-                render_routing_rules(&ctx);
-            });
+            {
+                let ctx = ctx.clone();
+                let p = preset.clone();
+                apply_btn.connect_clicked(move |_| {
+                    ctx.rule_set.borrow_mut().apply_preset(&p);
+                    if let Err(e) =
+                        persistence::save_routing_rules(&ctx.paths, &ctx.rule_set.borrow())
+                    {
+                        log::error!("save routing rules: {e}");
+                    }
+                    render_routing_rules(&ctx);
+                });
+            }
             row.add_suffix(&apply_btn);
 
+            let edit_btn = gtk::Button::builder()
+                .icon_name("document-edit-symbolic")
+                .tooltip_text(tr("Edit Preset"))
+                .valign(gtk::Align::Center)
+                .has_frame(false)
+                .build();
+            {
+                let p = preset.clone();
+                let rs = ctx.rule_set.clone();
+                let pp = paths.clone();
+                let d = dialog.clone();
+                edit_btn.connect_clicked(move |_| {
+                    d.close();
+                    show_edit_preset_dialog(&p, &rs.borrow(), &pp);
+                });
+            }
+            row.add_suffix(&edit_btn);
+
+            let export_btn = gtk::Button::builder()
+                .icon_name("document-send-symbolic")
+                .tooltip_text(tr("Export Preset…"))
+                .valign(gtk::Align::Center)
+                .has_frame(false)
+                .build();
+            {
+                let p = preset.clone();
+                export_btn.connect_clicked(move |_| show_export_preset_dialog(&p));
+            }
+            row.add_suffix(&export_btn);
+
             let delete_btn = gtk::Button::builder()
                 .icon_name("user-trash-symbolic")
                 .valign(gtk::Align::Center)
@@ -734,7 +1669,7 @@ fn show_routing_presets_dialog(paths: &Rc<AppPaths>, ctx: &RenderCtx) {
 
     let save_group = adw::PreferencesGroup::new();
     let save_row = adw::ActionRow::builder()
-        .title("Save Current Rules as Preset")
+        .title(tr("Save Current Rules as Preset"))
         .activatable(true)
         .build();
     save_row.add_prefix(
@@ -750,6 +1685,25 @@ fn show_routing_presets_dialog(paths: &Rc<AppPaths>, ctx: &RenderCtx) {
         });
     }
     save_group.add(&save_row);
+
+    let import_row = adw::ActionRow::builder()
+        .title(tr("Import Preset…"))
+        .activatable(true)
+        .build();
+    import_row.add_prefix(
+        &gtk::Image::builder()
+            .icon_name("document-open-symbolic")
+            .build(),
+    );
+    {
+        let pp = paths.clone();
+        let d = dialog.clone();
+        import_row.connect_activated(move |_| {
+            d.close();
+            show_import_preset_dialog(&pp);
+        });
+    }
+    save_group.add(&import_row);
     content.append(&save_group);
 
     let scrolled = gtk::ScrolledWindow::builder()
@@ -767,11 +1721,11 @@ fn show_save_preset_dialog(rule_set: &RoutingRuleSet, paths: &AppPaths) {
     let paths = paths.clone();
 
     let dialog = adw::AlertDialog::builder()
-        .heading("Save as Preset")
+        .heading(tr("Save as Preset"))
         .build();
 
-    dialog.add_response("cancel", "Cancel");
-    dialog.add_response("save", "Save");
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("save", &tr("Save"));
     dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
     dialog.set_default_response(Some("save"));
     dialog.set_close_response("cancel");
@@ -786,8 +1740,8 @@ fn show_save_preset_dialog(rule_set: &RoutingRuleSet, paths: &AppPaths) {
         .build();
 
     let group = adw::PreferencesGroup::new();
-    let name_entry = adw::EntryRow::builder().title("Name").build();
-    let desc_entry = adw::EntryRow::builder().title("Description").build();
+    let name_entry = adw::EntryRow::builder().title(tr("Name")).build();
+    let desc_entry = adw::EntryRow::builder().title(tr("Description")).build();
     group.add(&name_entry);
     group.add(&desc_entry);
     content.append(&group);
@@ -812,14 +1766,163 @@ fn show_save_preset_dialog(rule_set: &RoutingRuleSet, paths: &AppPaths) {
     dialog.present(gtk::Window::NONE);
 }
 
-fn format_action(action: &RuleAction) -> &'static str {
+fn show_export_preset_dialog(preset: &Preset) {
+    let preset = preset.clone();
+    let file_dialog = gtk::FileDialog::builder()
+        .title(tr("Export Preset"))
+        .initial_name(format!("{}.json", preset.name))
+        .build();
+    file_dialog.save(
+        gtk::Window::NONE,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let Some(path) = result.ok().and_then(|f| f.path()) else {
+                return;
+            };
+            if let Err(e) = persistence::export_preset(&preset, &path) {
+                show_message_dialog(&tr("Export Failed"), &e.to_string());
+            }
+        },
+    );
+}
+
+fn show_import_preset_dialog(paths: &AppPaths) {
+    let paths = paths.clone();
+    let file_dialog = gtk::FileDialog::builder()
+        .title(tr("Import Preset"))
+        .build();
+    file_dialog.open(
+        gtk::Window::NONE,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let Some(path) = result.ok().and_then(|f| f.path()) else {
+                return;
+            };
+            match persistence::import_preset(&paths, &path) {
+                Ok(preset) => show_message_dialog(
+                    &tr("Preset Imported"),
+                    &trf(
+                        "\"{name}\" is now available under Custom presets",
+                        &[("name", &preset.name)],
+                    ),
+                ),
+                Err(e) => show_message_dialog(&tr("Import Failed"), &e.to_string()),
+            }
+        },
+    );
+}
+
+fn show_edit_preset_dialog(preset: &Preset, rule_set: &RoutingRuleSet, paths: &AppPaths) {
+    let original = preset.clone();
+    let rules: Vec<RoutingRule> = rule_set.rules().to_vec();
+    let paths = paths.clone();
+
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Edit Preset"))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("save", &tr("Save"));
+    dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("save"));
+    dialog.set_close_response("cancel");
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(12)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+
+    let group = adw::PreferencesGroup::new();
+    let name_entry = adw::EntryRow::builder()
+        .title(tr("Name"))
+        .text(&preset.name)
+        .build();
+    let desc_entry = adw::EntryRow::builder()
+        .title(tr("Description"))
+        .text(&preset.description)
+        .build();
+    let replace_rules = adw::SwitchRow::builder()
+        .title(tr("Replace Rules"))
+        .subtitle(tr("Save the current routing rules into this preset"))
+        .active(true)
+        .build();
+    group.add(&name_entry);
+    group.add(&desc_entry);
+    group.add(&replace_rules);
+    content.append(&group);
+
+    dialog.set_extra_child(Some(&content));
+
+    dialog.connect_response(None, move |_, response| {
+        if response != "save" {
+            return;
+        }
+        let name = name_entry.text().to_string();
+        if name.trim().is_empty() {
+            return;
+        }
+        let description = desc_entry.text().to_string();
+        let updated = if replace_rules.is_active() {
+            Preset::from_rules(name.trim(), description.trim(), &rules)
+        } else {
+            let mut p = original.clone();
+            p.name = name.trim().to_owned();
+            p.description = description.trim().to_owned();
+            p
+        };
+        if let Err(e) = persistence::update_preset(&paths, &original.name, &updated) {
+            log::error!("update preset: {e}");
+            show_message_dialog(&tr("Could Not Save Preset"), &e.to_string());
+        }
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
+/// Nodes rules can route to, then the subscription groups when sing-box
+/// offers them.
+fn load_route_targets(ctx: &RenderCtx) -> Vec<(Uuid, String)> {
+    let subscriptions = persistence::load_subscriptions(&ctx.paths).unwrap_or_default();
+    let mut targets = route_targets(&subscriptions);
+    if ctx.settings.borrow().serves_clash_api() {
+        targets.extend(
+            group_targets(&subscriptions)
+                .into_iter()
+                .map(|(id, name)| (id, trf("{name} (group)", &[("name", &name)]))),
+        );
+    }
+    targets
+}
+
+/// The action, naming the node a proxy rule is routed to when it has one.
+fn format_rule_action(rule: &RoutingRule, targets: &[(Uuid, String)]) -> String {
+    let Some(id) = rule.target.filter(|_| rule.action == RuleAction::Proxy) else {
+        return format_action(&rule.action);
+    };
+    match targets.iter().find(|(target, _)| *target == id) {
+        Some((_, node)) => trf("Proxy via {node}", &[("node", node)]),
+        None => tr("Proxy via a disabled node — uses the primary node"),
+    }
+}
+
+fn format_action(action: &RuleAction) -> String {
     match action {
-        RuleAction::Proxy => "Proxy",
-        RuleAction::Direct => "Direct",
-        RuleAction::Block => "Block",
+        RuleAction::Proxy => tr("Proxy"),
+        RuleAction::Direct => tr("Direct"),
+        RuleAction::Block => tr("Block"),
     }
 }
 
+fn translated_list(items: &[&str]) -> gtk::StringList {
+    let items: Vec<String> = items.iter().map(|item| tr(item)).collect();
+    let items: Vec<&str> = items.iter().map(String::as_str).collect();
+    gtk::StringList::new(&items)
+}
+
 fn format_match(m: &RuleMatch) -> String {
     match m {
         RuleMatch::GeoIp { country_code } => format!("GeoIP: {country_code}"),
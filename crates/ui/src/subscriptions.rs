@@ -2,24 +2,70 @@ use adw::prelude::*;
 use gtk::gdk;
 use relm4::adw;
 use relm4::prelude::*;
-use std::collections::HashSet;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::rc::Rc;
 use uuid::Uuid;
 
-use v2ray_rs_core::models::{AppSettings, Subscription, SubscriptionSource};
+use v2ray_rs_core::models::{
+    AppSettings, FetchProxy, LatencyTrend, NodeKey, NodeSort, ProxyNode, Subscription,
+    SubscriptionNode, SubscriptionSort, SubscriptionSource, SubscriptionUsage, TlsOverride,
+    compare_nodes, enable_by_latency, find_subscription_by_url, group_name, group_subscriptions,
+    move_subscription, set_primary_node, should_defer_auto_update, should_notify_expiry,
+    sort_nodes, sort_subscriptions,
+};
 use v2ray_rs_core::persistence::{self, AppPaths};
-use v2ray_rs_subscription::manager::SubscriptionService;
+
+use crate::i18n::{tr, trf};
+use crate::preferences::show_message_dialog;
+use v2ray_rs_subscription::manager::{
+    FailureKind, ImportOutcome, SubscriptionError, SubscriptionService, import_outcome,
+    overdue_subscriptions,
+};
+use v2ray_rs_subscription::parser::{ParseOptions, parse_uri_with};
+use v2ray_rs_subscription::ping::{PingLimiter, ping_nodes_with};
+use v2ray_rs_subscription::qr::decode_qr_to_uri;
 use v2ray_rs_subscription::update::UpdateResult;
 
 pub struct SubscriptionsPage {
     paths: AppPaths,
     service: SubscriptionService,
+    parse_options: ParseOptions,
     subscriptions: Vec<Subscription>,
     list_container: gtk::ListBox,
+    search_entry: gtk::SearchEntry,
+    filter: String,
     auto_update_interval_secs: u64,
+    pause_on_metered: bool,
+    expiry_warning_days: u32,
     testing_latency: HashSet<Uuid>,
+    /// Refreshes in flight, with the handle that cancels each.
+    updating: HashMap<Uuid, tokio::task::AbortHandle>,
+    /// Subscriptions the running automatic update has yet to refresh, one at
+    /// a time; the front one is in flight.
+    auto_updating: VecDeque<Uuid>,
+    /// Refreshes the running automatic update has finished: (updated, failed).
+    auto_update_tally: (usize, usize),
+    /// Added subscriptions whose first refresh has not finished; its outcome
+    /// is reported so an empty or broken import does not go unnoticed.
+    importing: HashSet<Uuid>,
+    ping_limiter: PingLimiter,
+    latency_progress: (usize, usize),
     locked: bool,
+    node_sort: Option<NodeSort>,
+    subscription_sort: Option<SubscriptionSort>,
+    /// Mirrors the two sort fields for the sort menu, which is built on open.
+    sort_state: Rc<Cell<SortState>>,
+    /// Groups folded away under their header; `None` is the ungrouped bucket.
+    collapsed_groups: HashSet<Option<String>>,
 }
 
+/// Starting value of the "Enable by Latency" threshold.
+const DEFAULT_LATENCY_THRESHOLD_MS: u64 = 300;
+
+type SortState = (Option<NodeSort>, Option<SubscriptionSort>);
+
 #[derive(Debug, Clone, Copy)]
 pub enum Direction {
     Up,
@@ -29,34 +75,87 @@ pub enum Direction {
 #[derive(Debug)]
 pub enum SubscriptionsOutput {
     ActiveNodesChanged(bool),
+    TestNode(Uuid, Box<ProxyNode>),
+    AutoUpdated {
+        updated: usize,
+        failed: usize,
+    },
+    ExpiryWarning {
+        name: String,
+        detail: String,
+    },
+    SortChanged(Option<NodeSort>, Option<SubscriptionSort>),
+    /// What a refresh changed, or why an import came up empty, worded for a
+    /// toast.
+    Updated(String),
+    /// A refresh of an existing subscription failed; `kind` picks the remedy
+    /// the toast offers.
+    RefreshFailed {
+        id: Uuid,
+        message: String,
+        kind: FailureKind,
+    },
 }
 
 #[derive(Debug)]
 pub enum SubscriptionsMsg {
     ToggleSubscription(Uuid),
     ToggleNode(Uuid, usize),
+    ToggleFavorite(Uuid, usize),
+    TogglePrimary(Uuid, usize),
+    ToggleRouteTarget(Uuid, usize),
+    EditNode(Uuid, usize),
+    TestAndEnableNode(Uuid, usize),
+    /// A "Test & Enable" result for the node with this key, wherever it
+    /// has moved to meanwhile: its latency, or `None` if it failed.
+    NodeTested(Uuid, NodeKey, Option<u64>),
+    SetNodeTls(Uuid, usize, TlsOverride),
     DeleteSubscription(Uuid),
-    RenameSubscription(Uuid, String),
+    EditSubscription(Uuid, String, Vec<String>),
     MoveSubscription(Uuid, Direction),
+    ChooseGroup(Uuid),
+    SetGroup(Uuid, Option<String>),
+    ToggleGroupCollapsed(Option<String>),
     MoveNode(Uuid, usize, Direction),
     AddSubscription(String, String),
+    AddFileSubscription(String, String),
     UpdateSubscription(Uuid),
+    /// Abandons a refresh in flight; nothing it fetched is stored.
+    CancelUpdate(Uuid),
+    /// Opens the edit dialog, e.g. to fix a URL that failed to refresh.
+    ShowEditDialog(Uuid),
+    UpdateAll,
+    SetFilter(String),
+    FocusSearch,
     TestLatency(Uuid),
+    TestAllLatency,
     SortByLatency(Uuid),
+    SetNodeSort(Option<NodeSort>),
+    SetSubscriptionSort(Option<SubscriptionSort>),
     EnableAllNodes(Uuid),
     DisableAllNodes(Uuid),
-    DragDropSubscription(usize, usize),
+    EnableByLatency(Uuid, u64),
+    DragDropSubscription(Uuid, Uuid),
     DragDropNode(Uuid, usize, usize),
     CheckAutoUpdate,
+    CheckExpiry,
     SetLocked(bool),
+    Reload(AppPaths, Box<AppSettings>),
+    SetParseOptions(ParseOptions),
+    SetFetchProxy(FetchProxy),
+    /// Re-renders with the current language after a switch.
+    Relabel,
+    ImportLink(String),
 }
 
 #[derive(Debug)]
 pub enum SubscriptionsCmdOutput {
-    RefreshDone(Uuid, Subscription, UpdateResult),
+    RefreshDone(Uuid, Box<Subscription>, UpdateResult),
+    LatencyProgress(Uuid, usize, Option<u64>),
     LatencyResult(Uuid, Vec<Option<u64>>),
-    RefreshFailed(Uuid, String),
-    AutoUpdateDone(Vec<(Uuid, Result<UpdateResult, String>)>),
+    RefreshFailed(Uuid, SubscriptionError),
+    RefreshCancelled(Uuid),
+    AutoUpdateDone(Uuid, Result<UpdateResult, SubscriptionError>),
 }
 
 #[relm4::component(pub)]
@@ -73,13 +172,72 @@ impl Component for SubscriptionsPage {
 
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
-                set_halign: gtk::Align::End,
+                set_spacing: 6,
                 set_margin_top: 6,
+                set_margin_start: 12,
                 set_margin_end: 6,
 
+                #[local_ref]
+                search_entry -> gtk::SearchEntry {
+                    set_hexpand: true,
+                    #[watch]
+                    set_placeholder_text: Some(&tr("Search nodes")),
+                    connect_search_changed[sender] => move |entry| {
+                        sender.input(SubscriptionsMsg::SetFilter(entry.text().to_string()));
+                    },
+                },
+
+                gtk::Label {
+                    add_css_class: "dim-label",
+                    add_css_class: "caption",
+                    #[watch]
+                    set_visible: !model.testing_latency.is_empty(),
+                    #[watch]
+                    set_label: &trf(
+                        "Testing {done}/{total}",
+                        &[
+                            ("done", &model.latency_progress.0),
+                            ("total", &model.latency_progress.1),
+                        ],
+                    ),
+                },
+
+                gtk::Button {
+                    set_icon_name: "network-wireless-signal-good-symbolic",
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Test Latency of All Subscriptions")),
+                    add_css_class: "flat",
+                    #[watch]
+                    set_sensitive: !model.subscriptions.is_empty(),
+                    connect_clicked => SubscriptionsMsg::TestAllLatency,
+                },
+
+                #[name = "sort_button"]
+                gtk::MenuButton {
+                    set_icon_name: "view-sort-ascending-symbolic",
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Sort")),
+                    add_css_class: "flat",
+                    #[watch]
+                    set_sensitive: !model.subscriptions.is_empty(),
+                },
+
+                gtk::Button {
+                    set_icon_name: "image-x-generic-symbolic",
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Import from QR Image")),
+                    add_css_class: "flat",
+                    #[watch]
+                    set_sensitive: !model.locked,
+                    connect_clicked[sender] => move |_| {
+                        show_import_qr_dialog(sender.clone());
+                    },
+                },
+
                 gtk::Button {
                     set_icon_name: "list-add-symbolic",
-                    set_tooltip_text: Some("Add Subscription"),
+                    #[watch]
+                    set_tooltip_text: Some(&tr("Add Subscription")),
                     add_css_class: "flat",
                     #[watch]
                     set_sensitive: !model.locked,
@@ -105,7 +263,11 @@ impl Component for SubscriptionsPage {
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let (paths, settings) = init;
-        let service = SubscriptionService::new(paths.clone());
+        let parse_options = parse_options(&settings);
+        let mut service = SubscriptionService::new(paths.clone()).with_parse_options(parse_options);
+        if let Err(e) = service.set_fetch_proxy(&settings.subscription_proxy) {
+            log::error!("subscription proxy unusable: {e}");
+        }
         let subscriptions = persistence::load_subscriptions(&paths).unwrap_or_default();
 
         let list_container = gtk::ListBox::builder()
@@ -117,30 +279,58 @@ impl Component for SubscriptionsPage {
             .selection_mode(gtk::SelectionMode::None)
             .build();
 
-        let model = SubscriptionsPage {
+        let mut model = SubscriptionsPage {
             paths,
             service,
+            parse_options,
             subscriptions,
             list_container: list_container.clone(),
+            search_entry: gtk::SearchEntry::new(),
+            filter: String::new(),
             auto_update_interval_secs: settings.subscription_update_interval_secs,
+            pause_on_metered: settings.pause_updates_on_metered,
+            expiry_warning_days: settings.expiry_warning_days,
             testing_latency: HashSet::new(),
+            updating: HashMap::new(),
+            auto_updating: VecDeque::new(),
+            auto_update_tally: (0, 0),
+            importing: HashSet::new(),
+            ping_limiter: PingLimiter::default(),
+            latency_progress: (0, 0),
             locked: false,
+            node_sort: settings.node_sort,
+            subscription_sort: settings.subscription_sort,
+            sort_state: Rc::new(Cell::new((settings.node_sort, settings.subscription_sort))),
+            collapsed_groups: HashSet::new(),
         };
+        model.apply_sort();
 
-        render_list(
-            &model.subscriptions,
-            &list_container,
-            &sender,
-            &HashSet::new(),
-            &HashSet::new(),
-            false,
-        );
+        render_list(&model, &sender, &HashSet::new());
 
+        sender.input(SubscriptionsMsg::CheckExpiry);
         if settings.auto_update_subscriptions {
             sender.input(SubscriptionsMsg::CheckAutoUpdate);
+            if settings.pause_updates_on_metered {
+                let input = sender.input_sender().clone();
+                gtk::gio::NetworkMonitor::default().connect_network_metered_notify(
+                    move |monitor| {
+                        if !monitor.is_network_metered() {
+                            input.emit(SubscriptionsMsg::CheckAutoUpdate);
+                        }
+                    },
+                );
+            }
         }
 
+        let search_entry = &model.search_entry;
         let widgets = view_output!();
+        {
+            let state = model.sort_state.clone();
+            let input = sender.input_sender().clone();
+            widgets.sort_button.set_create_popup_func(move |button| {
+                button.set_popover(Some(&build_sort_popover(state.get(), &input)));
+            });
+        }
         ComponentParts { model, widgets }
     }
 
@@ -152,62 +342,151 @@ impl Component for SubscriptionsPage {
 
         match msg {
             SubscriptionsMsg::ToggleSubscription(id) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    sub.enabled = !sub.enabled;
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
+                if let Some(enabled) = self.subscription(id).map(|s| !s.enabled) {
+                    self.edit_subscription(id, |s| s.enabled = enabled);
                 }
             }
             SubscriptionsMsg::ToggleNode(sub_id, idx) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == sub_id)
-                    && let Some(node) = sub.nodes.get_mut(idx)
-                {
-                    node.enabled = !node.enabled;
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
+                if let Some(enabled) = self.node(sub_id, idx).map(|n| !n.enabled) {
+                    self.edit_node(sub_id, idx, |n| n.enabled = enabled);
+                }
+            }
+            SubscriptionsMsg::ToggleFavorite(sub_id, idx) => {
+                if let Some(favorite) = self.node(sub_id, idx).map(|n| !n.favorite) {
+                    self.edit_node(sub_id, idx, |n| n.favorite = favorite);
                 }
+                self.apply_sort();
             }
-            SubscriptionsMsg::RenameSubscription(id, new_name) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    sub.name = new_name;
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
+            SubscriptionsMsg::TogglePrimary(sub_id, idx) => {
+                let target = match self.node(sub_id, idx) {
+                    Some(node) if node.primary => None,
+                    Some(_) => Some((sub_id, idx)),
+                    None => return,
+                };
+                set_primary_node(&mut self.subscriptions, target);
+                let result = persistence::with_subscriptions(&self.paths, |subs| {
+                    set_primary_node(subs, target).then_some(())
+                });
+                if let Err(e) = result {
+                    log::error!("save primary node: {e}");
+                }
+            }
+            SubscriptionsMsg::ToggleRouteTarget(sub_id, idx) => {
+                let target = match self.node(sub_id, idx) {
+                    Some(node) if node.route_target.is_some() => None,
+                    Some(_) => Some(Uuid::new_v4()),
+                    None => return,
+                };
+                self.edit_node(sub_id, idx, |n| n.route_target = target);
+            }
+            SubscriptionsMsg::EditNode(sub_id, idx) => {
+                if let Some(node) = self.node(sub_id, idx) {
+                    show_node_edit_dialog(sub_id, idx, &node.node, sender.clone());
+                }
+                return;
+            }
+            SubscriptionsMsg::TestAndEnableNode(sub_id, idx) => {
+                if let Some(node) = self.node(sub_id, idx) {
+                    let _ = sender.output(SubscriptionsOutput::TestNode(
+                        sub_id,
+                        Box::new(node.node.clone()),
+                    ));
+                }
+                return;
+            }
+            SubscriptionsMsg::NodeTested(sub_id, key, latency) => {
+                self.edit_subscription(sub_id, |s| {
+                    if let Some(node) = s.nodes.iter_mut().find(|n| n.node.key() == key) {
+                        node.last_latency_ms = latency;
+                        // A failure leaves the node as the user set it.
+                        if latency.is_some() {
+                            node.enabled = true;
+                        }
                     }
+                });
+            }
+            SubscriptionsMsg::SetNodeTls(sub_id, idx, tls) => {
+                let Some(node) = self.node(sub_id, idx) else {
+                    return;
+                };
+                let mut updated = node.node.clone();
+                if let Err(e) = updated.apply_tls_override(&tls) {
+                    show_message_dialog(&tr("Invalid TLS Settings"), &e.to_string());
+                    return;
                 }
+                self.edit_node(sub_id, idx, |n| {
+                    n.node = updated.clone();
+                    n.tls_override = Some(tls.clone());
+                });
+            }
+            SubscriptionsMsg::EditSubscription(id, new_name, mirror_urls) => {
+                self.edit_subscription(id, |s| {
+                    s.name = new_name.clone();
+                    s.mirror_urls = mirror_urls.clone();
+                });
             }
             SubscriptionsMsg::MoveSubscription(id, direction) => {
+                // Steps past members of other groups, which are listed
+                // elsewhere.
                 if let Some(pos) = self.subscriptions.iter().position(|s| s.id == id) {
+                    let group = self.subscriptions[pos].group.clone();
+                    let same_group = |s: &Subscription| s.group == group;
                     let new_pos = match direction {
-                        Direction::Up if pos > 0 => pos - 1,
-                        Direction::Down if pos + 1 < self.subscriptions.len() => pos + 1,
-                        _ => pos,
+                        Direction::Up => self.subscriptions[..pos].iter().rposition(same_group),
+                        Direction::Down => self.subscriptions[pos + 1..]
+                            .iter()
+                            .position(same_group)
+                            .map(|i| pos + 1 + i),
                     };
-                    if new_pos != pos {
+                    if let Some(new_pos) = new_pos {
+                        self.set_sort(self.node_sort, None, &sender);
                         self.subscriptions.swap(pos, new_pos);
-                        if let Err(e) =
-                            persistence::save_subscriptions(&self.paths, &self.subscriptions)
-                        {
-                            log::error!("save subscriptions: {e}");
-                        }
+                        self.save_order();
+                        self.show_reordered(id, &sender);
                     }
                 }
+                return;
+            }
+            SubscriptionsMsg::ChooseGroup(id) => {
+                if let Some(sub) = self.subscription(id) {
+                    let groups = group_subscriptions(&self.subscriptions)
+                        .into_iter()
+                        .filter_map(|(group, _)| group.map(str::to_owned))
+                        .collect();
+                    show_group_dialog(id, sub.group.as_deref(), groups, sender.clone());
+                }
+                return;
+            }
+            SubscriptionsMsg::SetGroup(id, group) => {
+                let group = group.as_deref().and_then(group_name);
+                if self.subscription(id).is_none_or(|s| s.group == group) {
+                    return;
+                }
+                self.edit_subscription(id, |s| s.group = group.clone());
+            }
+            SubscriptionsMsg::ToggleGroupCollapsed(group) => {
+                if !self.collapsed_groups.remove(&group) {
+                    self.collapsed_groups.insert(group);
+                }
             }
             SubscriptionsMsg::MoveNode(sub_id, idx, direction) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == sub_id) {
+                if let Some(sub) = self.subscription(sub_id) {
                     let new_idx = match direction {
                         Direction::Up if idx > 0 => idx - 1,
                         Direction::Down if idx + 1 < sub.nodes.len() => idx + 1,
                         _ => idx,
                     };
                     if new_idx != idx {
-                        sub.nodes.swap(idx, new_idx);
-                        if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                            log::error!("update subscription: {e}");
-                        }
+                        self.edit_subscription(sub_id, |s| {
+                            if new_idx < s.nodes.len() {
+                                s.nodes.swap(idx, new_idx);
+                            }
+                        });
+                        move_node_row(&self.list_container, sub_id, idx, new_idx);
+                        self.set_sort(None, self.subscription_sort, &sender);
                     }
                 }
+                return;
             }
             SubscriptionsMsg::DeleteSubscription(id) => {
                 if let Err(e) = persistence::remove_subscription(&self.paths, &id) {
@@ -216,123 +495,252 @@ impl Component for SubscriptionsPage {
                 self.subscriptions.retain(|s| s.id != id);
             }
             SubscriptionsMsg::AddSubscription(name, url) => {
-                let sub = Subscription::new_from_url(name, url);
-                let id = sub.id;
-                if let Err(e) = persistence::add_subscription(&self.paths, sub.clone()) {
-                    log::error!("add subscription: {e}");
+                if let Some(existing) = find_subscription_by_url(&self.subscriptions, &url) {
+                    show_duplicate_dialog(existing, sender.clone());
+                    return;
                 }
-                self.subscriptions.push(sub);
-                sender.input(SubscriptionsMsg::UpdateSubscription(id));
+                self.add_subscription(Subscription::new_from_url(name, url), &sender);
+            }
+            SubscriptionsMsg::AddFileSubscription(name, path) => {
+                self.add_subscription(Subscription::new_from_file(name, path), &sender);
             }
             SubscriptionsMsg::UpdateSubscription(id) => {
+                if self.updating.contains_key(&id) || self.auto_updating.contains(&id) {
+                    return;
+                }
                 let svc = self.service.clone();
+                let task = relm4::spawn(async move { svc.refresh(id).await });
+                self.updating.insert(id, task.abort_handle());
                 sender.oneshot_command(async move {
-                    match svc.refresh(id).await {
-                        Ok((sub, result)) => SubscriptionsCmdOutput::RefreshDone(id, sub, result),
-                        Err(e) => SubscriptionsCmdOutput::RefreshFailed(id, e.to_string()),
+                    match task.await {
+                        Ok(Ok((sub, result))) => {
+                            SubscriptionsCmdOutput::RefreshDone(id, Box::new(sub), result)
+                        }
+                        Ok(Err(e)) => SubscriptionsCmdOutput::RefreshFailed(id, e),
+                        Err(e) => {
+                            if !e.is_cancelled() {
+                                log::error!("refresh of subscription {id} panicked: {e}");
+                            }
+                            SubscriptionsCmdOutput::RefreshCancelled(id)
+                        }
                     }
                 });
+            }
+            SubscriptionsMsg::CancelUpdate(id) => {
+                // The refresh only stores anything once its fetch is done, so
+                // aborting it leaves the subscription as it was.
+                if let Some(task) = self.updating.remove(&id) {
+                    task.abort();
+                }
+                self.importing.remove(&id);
+            }
+            SubscriptionsMsg::ShowEditDialog(id) => {
+                if let Some(sub) = self.subscription(id) {
+                    show_edit_dialog(sub, sender.clone());
+                }
                 return;
             }
-            SubscriptionsMsg::TestLatency(id) => {
-                if self.testing_latency.contains(&id) {
+            SubscriptionsMsg::UpdateAll => {
+                if self.locked {
                     return;
                 }
-                let sub = match self.subscriptions.iter().find(|s| s.id == id) {
-                    Some(s) => s.clone(),
-                    None => return,
-                };
-                self.testing_latency.insert(id);
-                let nodes = sub.nodes.clone();
-                sender.oneshot_command(async move {
-                    let results = v2ray_rs_subscription::ping::ping_nodes(&nodes).await;
-                    SubscriptionsCmdOutput::LatencyResult(id, results)
-                });
+                for sub in &self.subscriptions {
+                    sender.input(SubscriptionsMsg::UpdateSubscription(sub.id));
+                }
                 return;
             }
+            SubscriptionsMsg::SetFilter(filter) => {
+                let filter = filter.trim().to_lowercase();
+                if filter == self.filter {
+                    return;
+                }
+                self.filter = filter;
+            }
+            SubscriptionsMsg::FocusSearch => {
+                self.search_entry.grab_focus();
+                return;
+            }
+            SubscriptionsMsg::TestLatency(id) => {
+                if !self.start_latency_test(id, &sender) {
+                    return;
+                }
+            }
+            SubscriptionsMsg::TestAllLatency => {
+                let ids: Vec<Uuid> = self.subscriptions.iter().map(|s| s.id).collect();
+                let mut started = false;
+                for id in ids {
+                    started |= self.start_latency_test(id, &sender);
+                }
+                if !started {
+                    return;
+                }
+            }
             SubscriptionsMsg::SortByLatency(id) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    sub.nodes.sort_by(|a, b| {
-                        let la = a.last_latency_ms.unwrap_or(u64::MAX);
-                        let lb = b.last_latency_ms.unwrap_or(u64::MAX);
-                        la.cmp(&lb)
-                    });
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
+                self.set_sort(None, self.subscription_sort, &sender);
+                self.sort_subscription_nodes(id, NodeSort::Latency);
+            }
+            SubscriptionsMsg::SetNodeSort(key) => {
+                if !self.set_sort(key, self.subscription_sort, &sender) {
+                    return;
+                }
+                self.apply_sort();
+            }
+            SubscriptionsMsg::SetSubscriptionSort(key) => {
+                if !self.set_sort(self.node_sort, key, &sender) {
+                    return;
                 }
+                self.apply_sort();
             }
             SubscriptionsMsg::EnableAllNodes(id) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    for node in &mut sub.nodes {
+                self.edit_subscription(id, |s| {
+                    for node in &mut s.nodes {
                         node.enabled = true;
                     }
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
-                }
+                });
             }
             SubscriptionsMsg::DisableAllNodes(id) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    for node in &mut sub.nodes {
+                self.edit_subscription(id, |s| {
+                    for node in &mut s.nodes {
                         node.enabled = false;
                     }
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
+                });
+            }
+            SubscriptionsMsg::EnableByLatency(id, max_ms) => {
+                self.edit_subscription(id, |s| {
+                    enable_by_latency(&mut s.nodes, max_ms);
+                });
+                if let Some(sub) = self.subscription(id) {
+                    let enabled = sub.nodes.iter().filter(|n| n.enabled).count();
+                    let _ = sender.output(SubscriptionsOutput::Updated(trf(
+                        "{enabled} of {total} nodes enabled in {name}",
+                        &[
+                            ("enabled", &enabled),
+                            ("total", &sub.nodes.len()),
+                            ("name", &sub.name),
+                        ],
+                    )));
                 }
             }
-            SubscriptionsMsg::DragDropSubscription(from, to) => {
-                if from != to && from < self.subscriptions.len() && to < self.subscriptions.len() {
-                    let sub = self.subscriptions.remove(from);
-                    self.subscriptions.insert(to, sub);
-                    if let Err(e) =
-                        persistence::save_subscriptions(&self.paths, &self.subscriptions)
-                    {
-                        log::error!("save subscriptions: {e}");
+            SubscriptionsMsg::DragDropSubscription(from_id, to_id) => {
+                let old_group = self.subscription(from_id).map(|s| s.group.clone());
+                if move_subscription(&mut self.subscriptions, from_id, to_id) {
+                    self.set_sort(self.node_sort, None, &sender);
+                    let group = self.subscription(from_id).and_then(|s| s.group.clone());
+                    if old_group != Some(group.clone()) {
+                        let result = persistence::edit_subscription(&self.paths, &from_id, |s| {
+                            s.group = group.clone();
+                        });
+                        if let Err(e) = result {
+                            log::error!("update subscription: {e}");
+                        }
                     }
+                    self.save_order();
+                    self.show_reordered(from_id, &sender);
                 }
+                return;
             }
             SubscriptionsMsg::DragDropNode(sub_id, from, to) => {
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == sub_id)
+                if let Some(sub) = self.subscription(sub_id)
                     && from != to
                     && from < sub.nodes.len()
                     && to < sub.nodes.len()
                 {
-                    let node = sub.nodes.remove(from);
-                    sub.nodes.insert(to, node);
-                    if let Err(e) = persistence::update_subscription(&self.paths, sub.clone()) {
-                        log::error!("update subscription: {e}");
-                    }
+                    self.edit_subscription(sub_id, |s| {
+                        if from < s.nodes.len() && to < s.nodes.len() {
+                            let node = s.nodes.remove(from);
+                            s.nodes.insert(to, node);
+                        }
+                    });
+                    move_node_row(&self.list_container, sub_id, from, to);
+                    self.set_sort(None, self.subscription_sort, &sender);
                 }
+                return;
+            }
+            SubscriptionsMsg::SetParseOptions(options) => {
+                self.parse_options = options;
+                self.service = self.service.clone().with_parse_options(options);
+                return;
+            }
+            SubscriptionsMsg::SetFetchProxy(proxy) => {
+                if let Err(e) = self.service.set_fetch_proxy(&proxy) {
+                    show_message_dialog(&tr("Subscription Proxy Unusable"), &e.to_string());
+                }
+                return;
             }
             SubscriptionsMsg::SetLocked(locked) => {
                 self.locked = locked;
             }
-            SubscriptionsMsg::CheckAutoUpdate => {
-                let svc = self.service.clone();
-                let interval = self.auto_update_interval_secs;
-                sender.oneshot_command(async move {
-                    let results = svc.refresh_all_overdue(interval).await;
-                    let mapped: Vec<_> = results
-                        .into_iter()
-                        .map(|(id, r)| (id, r.map_err(|e| e.to_string())))
-                        .collect();
-                    SubscriptionsCmdOutput::AutoUpdateDone(mapped)
-                });
+            SubscriptionsMsg::ImportLink(link) => {
+                if link.starts_with("http://") || link.starts_with("https://") {
+                    let name = link
+                        .split_once("://")
+                        .and_then(|(_, rest)| rest.split(['/', '?', '#']).next())
+                        .filter(|host| !host.is_empty())
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| tr("Imported"));
+                    self.add_subscription(Subscription::new_from_url(name, link), &sender);
+                } else {
+                    match parse_uri_with(&link, self.parse_options) {
+                        Ok(node) => self.import_node(node),
+                        Err(e) => show_message_dialog(&tr("Import Failed"), &e.to_string()),
+                    }
+                }
+            }
+            SubscriptionsMsg::Reload(paths, settings) => {
+                self.subscriptions = persistence::load_subscriptions(&paths).unwrap_or_default();
+                self.parse_options = parse_options(&settings);
+                self.service =
+                    SubscriptionService::new(paths.clone()).with_parse_options(self.parse_options);
+                if let Err(e) = self.service.set_fetch_proxy(&settings.subscription_proxy) {
+                    log::error!("subscription proxy unusable: {e}");
+                }
+                self.paths = paths;
+                self.auto_update_interval_secs = settings.subscription_update_interval_secs;
+                self.pause_on_metered = settings.pause_updates_on_metered;
+                self.expiry_warning_days = settings.expiry_warning_days;
+                sender.input(SubscriptionsMsg::CheckExpiry);
+                self.testing_latency.clear();
+                for (_, task) in self.updating.drain() {
+                    task.abort();
+                }
+                self.auto_updating.clear();
+                self.auto_update_tally = (0, 0);
+                self.importing.clear();
+                self.collapsed_groups.clear();
+                self.latency_progress = (0, 0);
+                self.node_sort = settings.node_sort;
+                self.subscription_sort = settings.subscription_sort;
+                self.sort_state
+                    .set((self.node_sort, self.subscription_sort));
+                self.apply_sort();
+                if settings.auto_update_subscriptions {
+                    sender.input(SubscriptionsMsg::CheckAutoUpdate);
+                }
+            }
+            SubscriptionsMsg::Relabel => {}
+            SubscriptionsMsg::CheckExpiry => {
+                self.check_expiry(&sender);
                 return;
             }
+            SubscriptionsMsg::CheckAutoUpdate => {
+                let metered = gtk::gio::NetworkMonitor::default().is_network_metered();
+                if should_defer_auto_update(self.pause_on_metered, metered) {
+                    log::info!("network is metered, deferring subscription auto-update");
+                    return;
+                }
+                if !self.auto_updating.is_empty() {
+                    return;
+                }
+                self.auto_updating.extend(overdue_subscriptions(
+                    &self.subscriptions,
+                    self.auto_update_interval_secs,
+                    chrono::Utc::now(),
+                ));
+                self.next_auto_update(&sender);
+            }
         }
         emit_active_nodes(&self.subscriptions, &sender);
-        let expanded = capture_expanded(&self.list_container);
-        render_list(
-            &self.subscriptions,
-            &self.list_container,
-            &sender,
-            &expanded,
-            &self.testing_latency,
-            self.locked,
-        );
+        self.render(&sender);
     }
 
     fn update_cmd(
@@ -343,56 +751,545 @@ impl Component for SubscriptionsPage {
     ) {
         match msg {
             SubscriptionsCmdOutput::RefreshDone(id, sub, result) => {
+                self.updating.remove(&id);
+                let summary = match self.imported(id, Ok(&sub)) {
+                    Some(problem) => problem,
+                    None => update_summary(&sub.name, &result),
+                };
+                let _ = sender.output(SubscriptionsOutput::Updated(summary));
                 if let Some(existing) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    *existing = sub;
+                    *existing = *sub;
                 }
+                self.apply_sort();
                 log::info!(
-                    "updated subscription {id}: +{} -{} ={}",
+                    "updated subscription {id}: +{} -{} ={} from {}",
                     result.added,
                     result.removed,
-                    result.unchanged
+                    result.unchanged,
+                    result.source_url.as_deref().unwrap_or("file")
                 );
+                self.check_expiry(&sender);
+            }
+            SubscriptionsCmdOutput::LatencyProgress(id, idx, latency) => {
+                self.latency_progress.0 += 1;
+                if let Some(node) = self
+                    .subscriptions
+                    .iter_mut()
+                    .find(|s| s.id == id)
+                    .and_then(|s| s.nodes.get_mut(idx))
+                {
+                    node.last_latency_ms = latency;
+                }
+                return;
             }
             SubscriptionsCmdOutput::LatencyResult(id, results) => {
                 self.testing_latency.remove(&id);
-                if let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-                    for (node, latency) in sub.nodes.iter_mut().zip(results.iter()) {
-                        node.last_latency_ms = *latency;
-                    }
+                if self.testing_latency.is_empty() {
+                    self.latency_progress = (0, 0);
                 }
+                self.edit_subscription(id, |s| {
+                    for (node, latency) in s.nodes.iter_mut().zip(results.iter()) {
+                        node.record_latency(*latency);
+                    }
+                });
+                self.apply_sort();
             }
             SubscriptionsCmdOutput::RefreshFailed(id, error) => {
+                self.updating.remove(&id);
                 log::error!("failed to update subscription {id}: {error}");
+                if let Some(problem) = self.imported(id, Err(&error)) {
+                    let _ = sender.output(SubscriptionsOutput::Updated(problem));
+                } else if let Some(sub) = self.subscription(id) {
+                    let _ = sender.output(SubscriptionsOutput::RefreshFailed {
+                        id,
+                        message: trf(
+                            "Could not update {name}: {error}",
+                            &[("name", &sub.name), ("error", &error)],
+                        ),
+                        kind: error.kind(),
+                    });
+                }
+                if let Ok(Some(stored)) = persistence::get_subscription(&self.paths, &id)
+                    && let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id)
+                {
+                    sub.last_error = stored.last_error;
+                    sub.last_error_at = stored.last_error_at;
+                }
             }
-            SubscriptionsCmdOutput::AutoUpdateDone(results) => {
-                if !results.is_empty() {
-                    self.subscriptions =
-                        persistence::load_subscriptions(&self.paths).unwrap_or_default();
-                    for (id, result) in &results {
-                        match result {
-                            Ok(r) => log::info!(
-                                "auto-updated {id}: +{} -{} ={}",
-                                r.added,
-                                r.removed,
-                                r.unchanged
-                            ),
-                            Err(e) => log::warn!("auto-update {id} failed: {e}"),
-                        }
+            SubscriptionsCmdOutput::RefreshCancelled(id) => {
+                self.updating.remove(&id);
+                self.importing.remove(&id);
+                log::info!("update of subscription {id} cancelled");
+                if let Some(sub) = self.subscription(id) {
+                    let _ = sender.output(SubscriptionsOutput::Updated(trf(
+                        "Update of {name} cancelled",
+                        &[("name", &sub.name)],
+                    )));
+                }
+            }
+            SubscriptionsCmdOutput::AutoUpdateDone(id, result) => {
+                // A reload since it started dropped the update it belonged to.
+                if self.auto_updating.front() != Some(&id) {
+                    return;
+                }
+                self.auto_updating.pop_front();
+                match &result {
+                    Ok(r) => {
+                        self.auto_update_tally.0 += 1;
+                        log::info!(
+                            "auto-updated {id}: +{} -{} ={}",
+                            r.added,
+                            r.removed,
+                            r.unchanged
+                        );
+                    }
+                    Err(e) => {
+                        self.auto_update_tally.1 += 1;
+                        log::warn!("auto-update {id} failed ({:?}): {e}", e.kind());
                     }
                 }
+                self.merge_stored(&[id]);
+                self.apply_sort();
+                self.next_auto_update(&sender);
             }
         }
         let has_active = self.subscriptions.iter().any(|s| s.has_enabled_nodes());
         let _ = sender.output(SubscriptionsOutput::ActiveNodesChanged(has_active));
+        self.render(&sender);
+    }
+}
+
+/// What is running for one subscription, shown on its row.
+#[derive(Debug, Clone, Copy)]
+struct RowActivity {
+    testing_latency: bool,
+    /// A refresh the user started, which the row's menu can cancel.
+    updating: bool,
+    auto_updating: bool,
+}
+
+impl SubscriptionsPage {
+    fn activity(&self, id: Uuid) -> RowActivity {
+        RowActivity {
+            testing_latency: self.testing_latency.contains(&id),
+            updating: self.updating.contains_key(&id),
+            auto_updating: self.auto_updating.contains(&id),
+        }
+    }
+
+    /// The subscriptions as currently shown, including unsaved toggles.
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    /// Rebuilds the list, keeping expanded subscriptions open.
+    fn render(&self, sender: &ComponentSender<Self>) {
         let expanded = capture_expanded(&self.list_container);
-        render_list(
-            &self.subscriptions,
-            &self.list_container,
-            &sender,
-            &expanded,
-            &self.testing_latency,
-            self.locked,
-        );
+        render_list(self, sender, &expanded);
+    }
+
+    /// Shows subscription `id` at its new position. Rows can only be moved
+    /// in place while there are no group headers between them.
+    fn show_reordered(&self, id: Uuid, sender: &ComponentSender<Self>) {
+        if self.subscriptions.iter().any(|s| s.group.is_some()) {
+            self.render(sender);
+        } else {
+            reorder_subscription_row(&self.list_container, &self.subscriptions, id);
+        }
+    }
+
+    /// Records the kept orders and reports them for saving; returns false if
+    /// nothing changed.
+    fn set_sort(
+        &mut self,
+        node_sort: Option<NodeSort>,
+        subscription_sort: Option<SubscriptionSort>,
+        sender: &ComponentSender<Self>,
+    ) -> bool {
+        if self.sort_state.get() == (node_sort, subscription_sort) {
+            return false;
+        }
+        self.node_sort = node_sort;
+        self.subscription_sort = subscription_sort;
+        self.sort_state.set((node_sort, subscription_sort));
+        let _ = sender.output(SubscriptionsOutput::SortChanged(
+            node_sort,
+            subscription_sort,
+        ));
+        true
+    }
+
+    /// Re-applies the kept orders. Subscriptions under a latency test keep
+    /// their order because results arrive by node index.
+    fn apply_sort(&mut self) {
+        if let Some(key) = self.node_sort {
+            let ids: Vec<Uuid> = self
+                .subscriptions
+                .iter()
+                .filter(|s| !self.testing_latency.contains(&s.id))
+                .map(|s| s.id)
+                .collect();
+            for id in ids {
+                self.sort_subscription_nodes(id, key);
+            }
+        }
+        if let Some(key) = self.subscription_sort
+            && sort_subscriptions(&mut self.subscriptions, key)
+        {
+            self.save_order();
+        }
+    }
+
+    fn sort_subscription_nodes(&mut self, id: Uuid, key: NodeSort) {
+        let sorted = self.subscription(id).is_none_or(|s| {
+            s.nodes
+                .is_sorted_by(|a, b| compare_nodes(a, b, key).is_le())
+        });
+        if !sorted {
+            self.edit_subscription(id, |s| {
+                sort_nodes(&mut s.nodes, key);
+            });
+        }
+    }
+
+    /// Refreshes the next subscription the automatic update has queued,
+    /// skipping ones the user is already refreshing, or reports how it went
+    /// once the queue is empty.
+    fn next_auto_update(&mut self, sender: &ComponentSender<Self>) {
+        while let Some(&id) = self.auto_updating.front() {
+            if self.updating.contains_key(&id) {
+                self.auto_updating.pop_front();
+                continue;
+            }
+            let svc = self.service.clone();
+            sender.oneshot_command(async move {
+                SubscriptionsCmdOutput::AutoUpdateDone(id, svc.refresh(id).await.map(|(_, r)| r))
+            });
+            return;
+        }
+        let (updated, failed) = std::mem::take(&mut self.auto_update_tally);
+        if updated + failed > 0 {
+            let _ = sender.output(SubscriptionsOutput::AutoUpdated { updated, failed });
+            self.check_expiry(sender);
+        }
+    }
+
+    /// Takes the stored copies of subscriptions refreshed in the background,
+    /// leaving every other in-memory subscription as it is.
+    fn merge_stored(&mut self, ids: &[Uuid]) {
+        let stored = match persistence::load_subscriptions(&self.paths) {
+            Ok(stored) => stored,
+            Err(e) => {
+                log::error!("load subscriptions: {e}");
+                return;
+            }
+        };
+        for sub in stored.into_iter().filter(|s| ids.contains(&s.id)) {
+            if let Some(existing) = self.subscriptions.iter_mut().find(|s| s.id == sub.id) {
+                *existing = sub;
+            }
+        }
+    }
+
+    fn subscription(&self, id: Uuid) -> Option<&Subscription> {
+        self.subscriptions.iter().find(|s| s.id == id)
+    }
+
+    fn node(&self, sub_id: Uuid, idx: usize) -> Option<&SubscriptionNode> {
+        self.subscription(sub_id).and_then(|s| s.nodes.get(idx))
+    }
+
+    /// Applies `edit` to the subscription here and to its stored copy in one
+    /// transaction, so fields other writers saved in the meantime are kept.
+    fn edit_subscription(&mut self, id: Uuid, edit: impl Fn(&mut Subscription)) {
+        let Some(sub) = self.subscriptions.iter_mut().find(|s| s.id == id) else {
+            return;
+        };
+        edit(sub);
+        if let Err(e) = persistence::edit_subscription(&self.paths, &id, &edit) {
+            log::error!("update subscription: {e}");
+        }
+    }
+
+    fn edit_node(&mut self, sub_id: Uuid, idx: usize, edit: impl Fn(&mut SubscriptionNode)) {
+        self.edit_subscription(sub_id, |s| {
+            if let Some(node) = s.nodes.get_mut(idx) {
+                edit(node);
+            }
+        });
+    }
+
+    /// Stores the current subscription order without rewriting their contents.
+    fn save_order(&self) {
+        let order: Vec<Uuid> = self.subscriptions.iter().map(|s| s.id).collect();
+        let result = persistence::with_subscriptions(&self.paths, |subs| {
+            subs.sort_by_key(|s| order.iter().position(|id| *id == s.id));
+            Some(())
+        });
+        if let Err(e) = result {
+            log::error!("save subscriptions: {e}");
+        }
+    }
+
+    /// Starts pinging a subscription's nodes under the shared limiter.
+    /// Returns false if it is already being tested or has no nodes.
+    fn start_latency_test(&mut self, id: Uuid, sender: &ComponentSender<Self>) -> bool {
+        if self.testing_latency.contains(&id) {
+            return false;
+        }
+        let Some(sub) = self.subscriptions.iter().find(|s| s.id == id) else {
+            return false;
+        };
+        if sub.nodes.is_empty() {
+            return false;
+        }
+        self.testing_latency.insert(id);
+        self.latency_progress.1 += sub.nodes.len();
+
+        let nodes = sub.nodes.clone();
+        let limiter = self.ping_limiter.clone();
+        sender.command(move |out, shutdown| {
+            shutdown
+                .register(async move {
+                    let progress = out.clone();
+                    let results = ping_nodes_with(&nodes, &limiter, move |idx, latency| {
+                        let _ = progress
+                            .send(SubscriptionsCmdOutput::LatencyProgress(id, idx, latency));
+                    })
+                    .await;
+                    let _ = out.send(SubscriptionsCmdOutput::LatencyResult(id, results));
+                })
+                .drop_on_shutdown()
+        });
+        true
+    }
+
+    /// Warns about subscriptions close to expiry or out of data, at most once a day each.
+    fn check_expiry(&mut self, sender: &ComponentSender<Self>) {
+        let now = chrono::Utc::now();
+        for sub in &mut self.subscriptions {
+            let Some(usage) = &sub.usage else {
+                continue;
+            };
+            if !should_notify_expiry(usage, sub.expiry_notified_at, self.expiry_warning_days, now) {
+                continue;
+            }
+            let _ = sender.output(SubscriptionsOutput::ExpiryWarning {
+                name: sub.name.clone(),
+                detail: expiry_detail(usage, now),
+            });
+            sub.expiry_notified_at = Some(now);
+            if let Err(e) = persistence::edit_subscription(&self.paths, &sub.id, |s| {
+                s.expiry_notified_at = Some(now);
+            }) {
+                log::error!("update subscription: {e}");
+            }
+        }
+    }
+
+    /// Appends a single node to the manual "Imported" subscription, creating it on first use.
+    fn import_node(&mut self, node: ProxyNode) {
+        let index = match self
+            .subscriptions
+            .iter()
+            .position(|s| s.source == SubscriptionSource::Manual)
+        {
+            Some(index) => index,
+            None => {
+                let sub = Subscription::new_manual(tr("Imported"));
+                if let Err(e) = persistence::add_subscription(&self.paths, sub.clone()) {
+                    log::error!("add subscription: {e}");
+                }
+                self.subscriptions.push(sub);
+                self.subscriptions.len() - 1
+            }
+        };
+        let node = SubscriptionNode::new(node);
+        let id = self.subscriptions[index].id;
+        self.edit_subscription(id, |s| s.nodes.push(node.clone()));
+        self.apply_sort();
+    }
+
+    fn add_subscription(&mut self, sub: Subscription, sender: &ComponentSender<Self>) {
+        let id = sub.id;
+        if let Err(e) = persistence::add_subscription(&self.paths, sub.clone()) {
+            log::error!("add subscription: {e}");
+        }
+        self.subscriptions.push(sub);
+        self.importing.insert(id);
+        sender.input(SubscriptionsMsg::UpdateSubscription(id));
+    }
+
+    /// For the first refresh of an added subscription, what went wrong with
+    /// it, worded for a toast. Later refreshes and good imports yield `None`.
+    fn imported(
+        &mut self,
+        id: Uuid,
+        result: Result<&Subscription, &SubscriptionError>,
+    ) -> Option<String> {
+        if !self.importing.remove(&id) {
+            return None;
+        }
+        let name = self.subscription(id)?.name.clone();
+        match import_outcome(result) {
+            ImportOutcome::Imported(_) => None,
+            ImportOutcome::NoNodes => Some(trf(
+                "{name} has no usable nodes — check the subscription URL",
+                &[("name", &name)],
+            )),
+            ImportOutcome::Failed(error) => Some(trf(
+                "Could not import {name}: {error}",
+                &[("name", &name), ("error", &error)],
+            )),
+        }
+    }
+}
+
+pub fn parse_options(settings: &AppSettings) -> ParseOptions {
+    ParseOptions {
+        strict_uuid: settings.strict_uuid,
+    }
+}
+
+/// Radio lists for the kept node and subscription orders, built each time the
+/// menu opens so it shows the current choice in the current language.
+fn build_sort_popover(state: SortState, input: &relm4::Sender<SubscriptionsMsg>) -> gtk::Popover {
+    let popover = gtk::Popover::new();
+    let popover_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(4)
+        .build();
+
+    let heading = |text: &str| {
+        gtk::Label::builder()
+            .label(text)
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .build()
+    };
+    let radio_group = |options: &[&str], selected: usize, on_select: Rc<dyn Fn(usize)>| {
+        let group = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        let mut first: Option<gtk::CheckButton> = None;
+        for (index, label) in options.iter().enumerate() {
+            let check = gtk::CheckButton::builder()
+                .label(tr(label))
+                .active(index == selected)
+                .build();
+            if let Some(first) = &first {
+                check.set_group(Some(first));
+            } else {
+                first = Some(check.clone());
+            }
+            let on_select = on_select.clone();
+            check.connect_toggled(move |check| {
+                if check.is_active() {
+                    on_select(index);
+                }
+            });
+            group.append(&check);
+        }
+        group
+    };
+
+    const NODE_SORTS: [Option<NodeSort>; 5] = [
+        None,
+        Some(NodeSort::Name),
+        Some(NodeSort::Address),
+        Some(NodeSort::Latency),
+        Some(NodeSort::Protocol),
+    ];
+    const SUBSCRIPTION_SORTS: [Option<SubscriptionSort>; 3] = [
+        None,
+        Some(SubscriptionSort::Name),
+        Some(SubscriptionSort::LastUpdated),
+    ];
+    let (node_sort, subscription_sort) = state;
+
+    popover_box.append(&heading(&tr("Sort Nodes")));
+    {
+        let input = input.clone();
+        popover_box.append(&radio_group(
+            &["Manual Order", "Name", "Address", "Latency", "Protocol"],
+            NODE_SORTS.iter().position(|k| *k == node_sort).unwrap_or(0),
+            Rc::new(move |i| input.emit(SubscriptionsMsg::SetNodeSort(NODE_SORTS[i]))),
+        ));
+    }
+    popover_box.append(
+        &gtk::Label::builder()
+            .label(tr(
+                "Nodes are used in list order, so sorting can change the primary node",
+            ))
+            .wrap(true)
+            .max_width_chars(30)
+            .xalign(0.0)
+            .css_classes(["dim-label", "caption"])
+            .build(),
+    );
+    popover_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+    popover_box.append(&heading(&tr("Sort Subscriptions")));
+    {
+        let input = input.clone();
+        popover_box.append(&radio_group(
+            &["Manual Order", "Name", "Last Updated"],
+            SUBSCRIPTION_SORTS
+                .iter()
+                .position(|k| *k == subscription_sort)
+                .unwrap_or(0),
+            Rc::new(move |i| {
+                input.emit(SubscriptionsMsg::SetSubscriptionSort(SUBSCRIPTION_SORTS[i]))
+            }),
+        ));
+    }
+
+    popover.set_child(Some(&popover_box));
+    popover
+}
+
+/// Removed nodes named in an update summary before it is cut short.
+const SUMMARY_REMOVED_NAMES: usize = 3;
+
+fn update_summary(name: &str, result: &UpdateResult) -> String {
+    if result.added == 0 && result.removed == 0 {
+        return trf("{name} is up to date", &[("name", &name)]);
+    }
+    let summary = trf(
+        "{name}: {added} added, {removed} removed",
+        &[
+            ("name", &name),
+            ("added", &result.added),
+            ("removed", &result.removed),
+        ],
+    );
+    let mut removed: Vec<&str> = result
+        .removed_nodes
+        .iter()
+        .filter_map(|n| n.node.remark())
+        .take(SUMMARY_REMOVED_NAMES + 1)
+        .collect();
+    if removed.is_empty() {
+        return summary;
+    }
+    if removed.len() > SUMMARY_REMOVED_NAMES {
+        removed.truncate(SUMMARY_REMOVED_NAMES);
+        removed.push("…");
+    }
+    format!("{summary} ({})", removed.join(", "))
+}
+
+fn expiry_detail(usage: &SubscriptionUsage, now: chrono::DateTime<chrono::Utc>) -> String {
+    if usage.over_cap() {
+        return tr("data limit reached");
+    }
+    match usage.expire {
+        Some(expire) if expire <= now => tr("expired"),
+        Some(expire) => match (expire - now).num_days() {
+            0 => tr("expires today"),
+            1 => tr("expires tomorrow"),
+            days => trf("expires in {days} days", &[("days", &days)]),
+        },
+        None => String::new(),
     }
 }
 
@@ -414,13 +1311,13 @@ fn capture_expanded(container: &gtk::ListBox) -> HashSet<Uuid> {
 }
 
 fn render_list(
-    subs: &[Subscription],
-    container: &gtk::ListBox,
+    page: &SubscriptionsPage,
     sender: &ComponentSender<SubscriptionsPage>,
     expanded_subs: &HashSet<Uuid>,
-    testing_latency: &HashSet<Uuid>,
-    locked: bool,
 ) {
+    let subs = &page.subscriptions;
+    let container = &page.list_container;
+    let filter = page.filter.as_str();
     while let Some(child) = container.first_child() {
         container.remove(&child);
     }
@@ -428,8 +1325,8 @@ fn render_list(
     if subs.is_empty() {
         let empty = adw::StatusPage::builder()
             .icon_name("folder-download-symbolic")
-            .title("No Subscriptions")
-            .description("Add a subscription to get started")
+            .title(tr("No Subscriptions"))
+            .description(tr("Add a subscription to get started"))
             .build();
         let row = gtk::ListBoxRow::builder()
             .selectable(false)
@@ -440,42 +1337,181 @@ fn render_list(
         return;
     }
 
-    for (idx, sub) in subs.iter().enumerate() {
-        let expander =
-            build_subscription_group(sub, idx, sender, expanded_subs, testing_latency, locked);
-        container.append(&expander);
-    }
+    // Headers only appear once something is grouped; until then the list is
+    // a single unnamed bucket.
+    let grouped = subs.iter().any(|s| s.group.is_some());
+    let mut shown = 0;
+    for (group, members) in group_subscriptions(subs) {
+        let members: Vec<&Subscription> = members
+            .into_iter()
+            .filter(|sub| {
+                filter.is_empty()
+                    || sub.name.to_lowercase().contains(filter)
+                    || sub.nodes.iter().any(|n| node_matches(n, filter))
+            })
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        shown += members.len();
+
+        let group = group.map(str::to_owned);
+        // A search shows every match, collapsed or not.
+        let collapsed = filter.is_empty() && page.collapsed_groups.contains(&group);
+        if grouped {
+            container.append(&build_group_header(
+                group,
+                members.len(),
+                collapsed,
+                page.locked,
+                sender,
+            ));
+        }
+        if collapsed {
+            continue;
+        }
+        for sub in members {
+            let expander = build_subscription_group(
+                sub,
+                sender,
+                expanded_subs,
+                page.activity(sub.id),
+                page.locked,
+                filter,
+            );
+            container.append(&expander);
+        }
+    }
+
+    if shown == 0 {
+        let empty = adw::StatusPage::builder()
+            .icon_name("edit-find-symbolic")
+            .title(tr("No Matches"))
+            .description(tr("No subscriptions or nodes match the search"))
+            .build();
+        let row = gtk::ListBoxRow::builder()
+            .selectable(false)
+            .activatable(false)
+            .child(&empty)
+            .build();
+        container.append(&row);
+    }
+}
+
+/// A clickable header that folds its group away; subscriptions dropped on it
+/// join the group.
+fn build_group_header(
+    group: Option<String>,
+    count: usize,
+    collapsed: bool,
+    locked: bool,
+    sender: &ComponentSender<SubscriptionsPage>,
+) -> gtk::ListBoxRow {
+    let arrow = gtk::Image::from_icon_name(if collapsed {
+        "pan-end-symbolic"
+    } else {
+        "pan-down-symbolic"
+    });
+    let title = gtk::Label::builder()
+        .label(group.clone().unwrap_or_else(|| tr("Ungrouped")))
+        .xalign(0.0)
+        .hexpand(true)
+        .ellipsize(gtk::pango::EllipsizeMode::End)
+        .css_classes(["heading"])
+        .build();
+    let count_label = gtk::Label::builder()
+        .label(count.to_string())
+        .css_classes(["dim-label", "caption"])
+        .build();
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(8)
+        .build();
+    content.append(&arrow);
+    content.append(&title);
+    content.append(&count_label);
+
+    let button = gtk::Button::builder()
+        .child(&content)
+        .has_frame(false)
+        .tooltip_text(if collapsed {
+            tr("Show Group")
+        } else {
+            tr("Hide Group")
+        })
+        .build();
+    {
+        let group = group.clone();
+        let s = sender.clone();
+        button.connect_clicked(move |_| {
+            s.input(SubscriptionsMsg::ToggleGroupCollapsed(group.clone()));
+        });
+    }
+
+    let row = gtk::ListBoxRow::builder()
+        .selectable(false)
+        .activatable(false)
+        .child(&button)
+        .build();
+
+    if !locked {
+        let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
+        let s = sender.clone();
+        drop_target.connect_drop(move |_target, value, _x, _y| {
+            if let Ok(val) = value.get::<String>()
+                && let Some(from_str) = val.strip_prefix("sub_")
+                && let Ok(from_id) = Uuid::parse_str(from_str)
+            {
+                s.input(SubscriptionsMsg::SetGroup(from_id, group.clone()));
+                return true;
+            }
+            false
+        });
+        row.add_controller(drop_target);
+    }
+    row
+}
+
+fn node_matches(node: &v2ray_rs_core::models::SubscriptionNode, filter: &str) -> bool {
+    node.node
+        .remark()
+        .is_some_and(|r| r.to_lowercase().contains(filter))
+        || node.node.address().to_lowercase().contains(filter)
 }
 
 fn build_subscription_group(
     sub: &Subscription,
-    sub_idx: usize,
     sender: &ComponentSender<SubscriptionsPage>,
     expanded_subs: &HashSet<Uuid>,
-    testing_latency: &HashSet<Uuid>,
+    activity: RowActivity,
     locked: bool,
+    filter: &str,
 ) -> adw::ExpanderRow {
+    let updating = activity.updating;
     let source_text = match &sub.source {
         SubscriptionSource::Url { url } => truncate(url, 50),
         SubscriptionSource::File { path } => path.clone(),
+        SubscriptionSource::Manual => tr("Added manually"),
     };
 
     let updated_text = match &sub.last_updated {
-        Some(dt) => format!("Updated: {}", dt.format("%Y-%m-%d %H:%M")),
-        None => "Never updated".into(),
+        Some(dt) => trf("Updated: {date}", &[("date", &dt.format("%Y-%m-%d %H:%M"))]),
+        None => tr("Never updated"),
     };
 
     let expander = adw::ExpanderRow::builder()
         .title(&sub.name)
-        .subtitle(format!(
-            "{} | {} nodes | {}",
-            source_text,
-            sub.nodes.len(),
-            updated_text
+        .subtitle(trf(
+            "{source} | {count} nodes | {updated}",
+            &[
+                ("source", &source_text),
+                ("count", &sub.nodes.len()),
+                ("updated", &updated_text),
+            ],
         ))
         .show_enable_switch(false)
         .enable_expansion(true)
-        .expanded(expanded_subs.contains(&sub.id))
+        .expanded(!filter.is_empty() || expanded_subs.contains(&sub.id))
         .build();
 
     expander.set_widget_name(&sub.id.to_string());
@@ -492,10 +1528,10 @@ fn build_subscription_group(
         let drag_source = gtk::DragSource::new();
         drag_source.set_actions(gdk::DragAction::MOVE);
         {
-            let idx = sub_idx;
+            let id = sub.id;
             drag_source.connect_prepare(move |_src, _x, _y| {
                 Some(gdk::ContentProvider::for_value(
-                    &format!("sub_{idx}").to_value(),
+                    &format!("sub_{id}").to_value(),
                 ))
             });
         }
@@ -503,14 +1539,14 @@ fn build_subscription_group(
 
         let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
         {
-            let target_idx = sub_idx;
+            let target_id = sub.id;
             let s = sender.clone();
             drop_target.connect_drop(move |_target, value, _x, _y| {
                 if let Ok(val) = value.get::<String>()
                     && let Some(from_str) = val.strip_prefix("sub_")
-                    && let Ok(from_idx) = from_str.parse::<usize>()
+                    && let Ok(from_id) = Uuid::parse_str(from_str)
                 {
-                    s.input(SubscriptionsMsg::DragDropSubscription(from_idx, target_idx));
+                    s.input(SubscriptionsMsg::DragDropSubscription(from_id, target_id));
                     return true;
                 }
                 false
@@ -520,6 +1556,28 @@ fn build_subscription_group(
     }
     expander.add_prefix(&handle);
 
+    if let Some(error) = &sub.last_error {
+        let tooltip = match &sub.last_error_at {
+            Some(at) => format!(
+                "{error}\n{}",
+                at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => error.clone(),
+        };
+        let warning = gtk::Image::builder()
+            .icon_name("dialog-warning-symbolic")
+            .tooltip_text(tooltip)
+            .css_classes(["error"])
+            .build();
+        expander.add_prefix(&warning);
+    }
+
+    if activity.updating || activity.auto_updating {
+        let spinner = adw::Spinner::new();
+        spinner.set_tooltip_text(Some(&tr("Updating…")));
+        expander.add_suffix(&spinner);
+    }
+
     let toggle = gtk::Switch::builder()
         .active(sub.enabled)
         .valign(gtk::Align::Center)
@@ -549,8 +1607,14 @@ fn build_subscription_group(
         .build();
 
     let update_btn = gtk::Button::builder()
-        .label("Update")
+        .label(if updating {
+            tr("Cancel Update")
+        } else {
+            tr("Update")
+        })
         .has_frame(false)
+        // An automatic refresh of it is queued or running.
+        .sensitive(!activity.auto_updating)
         .build();
     {
         let id = sub.id;
@@ -558,27 +1622,30 @@ fn build_subscription_group(
         let p = popover.clone();
         update_btn.connect_clicked(move |_| {
             p.popdown();
-            s.input(SubscriptionsMsg::UpdateSubscription(id));
+            s.input(if updating {
+                SubscriptionsMsg::CancelUpdate(id)
+            } else {
+                SubscriptionsMsg::UpdateSubscription(id)
+            });
         });
     }
 
     let rename_btn = gtk::Button::builder()
-        .label("Rename")
+        .label(tr("Edit"))
         .has_frame(false)
         .build();
     {
-        let id = sub.id;
-        let current_name = sub.name.clone();
+        let sub = sub.clone();
         let s = sender.clone();
         let p = popover.clone();
         rename_btn.connect_clicked(move |_| {
             p.popdown();
-            show_rename_dialog(id, &current_name, s.clone());
+            show_edit_dialog(&sub, s.clone());
         });
     }
 
     let delete_btn = gtk::Button::builder()
-        .label("Delete")
+        .label(tr("Delete"))
         .has_frame(false)
         .build();
     delete_btn.add_css_class("destructive-action");
@@ -593,7 +1660,7 @@ fn build_subscription_group(
     }
 
     let move_up_btn = gtk::Button::builder()
-        .label("Move Up")
+        .label(tr("Move Up"))
         .has_frame(false)
         .build();
     {
@@ -607,7 +1674,7 @@ fn build_subscription_group(
     }
 
     let move_down_btn = gtk::Button::builder()
-        .label("Move Down")
+        .label(tr("Move Down"))
         .has_frame(false)
         .build();
     {
@@ -620,14 +1687,28 @@ fn build_subscription_group(
         });
     }
 
-    let is_testing = testing_latency.contains(&sub.id);
+    let group_btn = gtk::Button::builder()
+        .label(tr("Move to Group…"))
+        .has_frame(false)
+        .build();
+    {
+        let id = sub.id;
+        let s = sender.clone();
+        let p = popover.clone();
+        group_btn.connect_clicked(move |_| {
+            p.popdown();
+            s.input(SubscriptionsMsg::ChooseGroup(id));
+        });
+    }
+
+    let is_testing = activity.testing_latency;
     let has_latency = sub.nodes.iter().any(|n| n.last_latency_ms.is_some());
 
     let test_latency_btn = gtk::Button::builder()
         .label(if is_testing {
-            "Testing..."
+            tr("Testing...")
         } else {
-            "Test Latency"
+            tr("Test Latency")
         })
         .has_frame(false)
         .sensitive(!is_testing)
@@ -643,7 +1724,7 @@ fn build_subscription_group(
     }
 
     let sort_latency_btn = gtk::Button::builder()
-        .label("Sort by Latency")
+        .label(tr("Sort by Latency"))
         .has_frame(false)
         .sensitive(has_latency)
         .build();
@@ -658,7 +1739,7 @@ fn build_subscription_group(
     }
 
     let enable_all_btn = gtk::Button::builder()
-        .label("Enable All Nodes")
+        .label(tr("Enable All Nodes"))
         .has_frame(false)
         .build();
     {
@@ -672,7 +1753,7 @@ fn build_subscription_group(
     }
 
     let disable_all_btn = gtk::Button::builder()
-        .label("Disable All Nodes")
+        .label(tr("Disable All Nodes"))
         .has_frame(false)
         .build();
     {
@@ -685,6 +1766,21 @@ fn build_subscription_group(
         });
     }
 
+    let enable_by_latency_btn = gtk::Button::builder()
+        .label(tr("Enable by Latency…"))
+        .has_frame(false)
+        .sensitive(has_latency && !is_testing)
+        .build();
+    {
+        let id = sub.id;
+        let s = sender.clone();
+        let p = popover.clone();
+        enable_by_latency_btn.connect_clicked(move |_| {
+            p.popdown();
+            show_latency_threshold_dialog(id, s.clone());
+        });
+    }
+
     popover_box.append(&update_btn);
     popover_box.append(&rename_btn);
     popover_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
@@ -693,9 +1789,11 @@ fn build_subscription_group(
     popover_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
     popover_box.append(&enable_all_btn);
     popover_box.append(&disable_all_btn);
+    popover_box.append(&enable_by_latency_btn);
     popover_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
     popover_box.append(&move_up_btn);
     popover_box.append(&move_down_btn);
+    popover_box.append(&group_btn);
     popover_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
     popover_box.append(&delete_btn);
     popover.set_child(Some(&popover_box));
@@ -703,7 +1801,11 @@ fn build_subscription_group(
 
     expander.add_suffix(&menu_btn);
 
+    let name_matches = filter.is_empty() || sub.name.to_lowercase().contains(filter);
     for (idx, node) in sub.nodes.iter().enumerate() {
+        if !name_matches && !node_matches(node, filter) {
+            continue;
+        }
         let node_row = build_node_row(sub.id, idx, node, sender, locked);
         expander.add_row(&node_row);
     }
@@ -726,13 +1828,18 @@ fn build_node_row(
     };
 
     let address = format!("{}:{}", node.node.address(), node.node.port());
-    let name = node.node.remark().unwrap_or("Unnamed Node");
+    let name = node
+        .node
+        .remark()
+        .map(str::to_owned)
+        .unwrap_or_else(|| tr("Unnamed Node"));
 
     let row = adw::ActionRow::builder()
-        .title(name)
+        .title(&name)
         .subtitle(&address)
         .build();
 
+    row.set_widget_name(&format!("node-{idx}"));
     if !node.enabled {
         row.set_opacity(0.5);
     }
@@ -747,8 +1854,8 @@ fn build_node_row(
         drag_source.set_actions(gdk::DragAction::MOVE);
         {
             let id = sub_id;
-            let source_idx = idx;
-            drag_source.connect_prepare(move |_src, _x, _y| {
+            drag_source.connect_prepare(move |src, _x, _y| {
+                let source_idx = node_row_index(&src.widget()?)?;
                 Some(gdk::ContentProvider::for_value(
                     &format!("node_{id}_{source_idx}").to_value(),
                 ))
@@ -759,13 +1866,13 @@ fn build_node_row(
         let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
         {
             let target_id = sub_id;
-            let target_idx = idx;
             let s = sender.clone();
-            drop_target.connect_drop(move |_target, value, _x, _y| {
+            drop_target.connect_drop(move |target, value, _x, _y| {
                 let prefix = format!("node_{target_id}_");
                 if let Ok(val) = value.get::<String>()
                     && let Some(from_str) = val.strip_prefix(&prefix)
                     && let Ok(from_idx) = from_str.parse::<usize>()
+                    && let Some(target_idx) = target.widget().as_ref().and_then(node_row_index)
                 {
                     s.input(SubscriptionsMsg::DragDropNode(
                         target_id, from_idx, target_idx,
@@ -800,6 +1907,21 @@ fn build_node_row(
             latency_label.add_css_class("error");
         }
         row.add_suffix(&latency_label);
+
+        if let Some(trend) = node.latency_trend() {
+            let (arrow, tooltip) = match trend {
+                LatencyTrend::Worse => ("↑", "Latency increased since last test"),
+                LatencyTrend::Better => ("↓", "Latency decreased since last test"),
+                LatencyTrend::Flat => ("→", "Latency unchanged since last test"),
+            };
+            let trend_label = gtk::Label::builder()
+                .label(arrow)
+                .tooltip_text(tr(tooltip))
+                .css_classes(["caption", "dim-label"])
+                .valign(gtk::Align::Center)
+                .build();
+            row.add_suffix(&trend_label);
+        }
     }
 
     let move_box = gtk::Box::builder()
@@ -811,35 +1933,153 @@ fn build_node_row(
     let up_btn = gtk::Button::builder()
         .icon_name("go-up-symbolic")
         .has_frame(false)
-        .tooltip_text("Move Up")
+        .tooltip_text(tr("Move Up"))
         .sensitive(!locked)
         .build();
     up_btn.add_css_class("flat");
     {
         let s = sender.clone();
-        up_btn.connect_clicked(move |_| {
-            s.input(SubscriptionsMsg::MoveNode(sub_id, idx, Direction::Up));
+        up_btn.connect_clicked(move |btn| {
+            if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                s.input(SubscriptionsMsg::MoveNode(sub_id, idx, Direction::Up));
+            }
         });
     }
 
     let down_btn = gtk::Button::builder()
         .icon_name("go-down-symbolic")
         .has_frame(false)
-        .tooltip_text("Move Down")
+        .tooltip_text(tr("Move Down"))
         .sensitive(!locked)
         .build();
     down_btn.add_css_class("flat");
     {
         let s = sender.clone();
-        down_btn.connect_clicked(move |_| {
-            s.input(SubscriptionsMsg::MoveNode(sub_id, idx, Direction::Down));
+        down_btn.connect_clicked(move |btn| {
+            if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                s.input(SubscriptionsMsg::MoveNode(sub_id, idx, Direction::Down));
+            }
         });
     }
 
     move_box.append(&up_btn);
     move_box.append(&down_btn);
+
+    let favorite_btn = gtk::ToggleButton::builder()
+        .icon_name(if node.favorite {
+            "starred-symbolic"
+        } else {
+            "non-starred-symbolic"
+        })
+        .active(node.favorite)
+        .has_frame(false)
+        .tooltip_text(if node.favorite {
+            tr("Unpin")
+        } else {
+            tr("Pin to Top")
+        })
+        .valign(gtk::Align::Center)
+        .sensitive(!locked)
+        .build();
+    favorite_btn.add_css_class("flat");
+    {
+        let s = sender.clone();
+        favorite_btn.connect_toggled(move |btn| {
+            if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                s.input(SubscriptionsMsg::ToggleFavorite(sub_id, idx));
+            }
+        });
+    }
+    row.add_suffix(&favorite_btn);
+
+    let primary_btn = gtk::ToggleButton::builder()
+        .icon_name("emblem-ok-symbolic")
+        .active(node.primary)
+        .has_frame(false)
+        .tooltip_text(if node.primary {
+            tr("Primary Node — proxied traffic goes here")
+        } else {
+            tr("Use as Primary")
+        })
+        .valign(gtk::Align::Center)
+        .sensitive(!locked)
+        .build();
+    primary_btn.add_css_class("flat");
+    {
+        let s = sender.clone();
+        primary_btn.connect_toggled(move |btn| {
+            if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                s.input(SubscriptionsMsg::TogglePrimary(sub_id, idx));
+            }
+        });
+    }
+    row.add_suffix(&primary_btn);
+
+    let target_btn = gtk::ToggleButton::builder()
+        .icon_name("document-send-symbolic")
+        .active(node.route_target.is_some())
+        .has_frame(false)
+        .tooltip_text(if node.route_target.is_some() {
+            tr("Routing Target — rules can send traffic here")
+        } else {
+            tr("Use as Routing Target")
+        })
+        .valign(gtk::Align::Center)
+        .sensitive(!locked)
+        .build();
+    target_btn.add_css_class("flat");
+    {
+        let s = sender.clone();
+        target_btn.connect_toggled(move |btn| {
+            if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                s.input(SubscriptionsMsg::ToggleRouteTarget(sub_id, idx));
+            }
+        });
+    }
+    row.add_suffix(&target_btn);
     row.add_suffix(&move_box);
 
+    if !node.enabled {
+        let test_btn = gtk::Button::builder()
+            .icon_name("network-transmit-receive-symbolic")
+            .has_frame(false)
+            .tooltip_text(tr("Test & Enable"))
+            .valign(gtk::Align::Center)
+            .sensitive(!locked)
+            .build();
+        test_btn.add_css_class("flat");
+        {
+            let s = sender.clone();
+            test_btn.connect_clicked(move |btn| {
+                if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                    btn.set_sensitive(false);
+                    s.input(SubscriptionsMsg::TestAndEnableNode(sub_id, idx));
+                }
+            });
+        }
+        row.add_suffix(&test_btn);
+    }
+
+    if node.node.tls().is_some() {
+        let edit_btn = gtk::Button::builder()
+            .icon_name("document-edit-symbolic")
+            .has_frame(false)
+            .tooltip_text(tr("Edit TLS Settings"))
+            .valign(gtk::Align::Center)
+            .sensitive(!locked)
+            .build();
+        edit_btn.add_css_class("flat");
+        {
+            let s = sender.clone();
+            edit_btn.connect_clicked(move |btn| {
+                if let Some(idx) = node_row_index(btn.upcast_ref()) {
+                    s.input(SubscriptionsMsg::EditNode(sub_id, idx));
+                }
+            });
+        }
+        row.add_suffix(&edit_btn);
+    }
+
     let node_toggle = gtk::Switch::builder()
         .active(node.enabled)
         .valign(gtk::Align::Center)
@@ -847,8 +2087,10 @@ fn build_node_row(
         .build();
     {
         let s = sender.clone();
-        node_toggle.connect_active_notify(move |_| {
-            s.input(SubscriptionsMsg::ToggleNode(sub_id, idx));
+        node_toggle.connect_active_notify(move |switch| {
+            if let Some(idx) = node_row_index(switch.upcast_ref()) {
+                s.input(SubscriptionsMsg::ToggleNode(sub_id, idx));
+            }
         });
     }
     row.add_suffix(&node_toggle);
@@ -856,6 +2098,98 @@ fn build_node_row(
     row
 }
 
+// Reordering moves the existing row widgets instead of re-rendering, so expander
+// state and scroll position survive. Node rows carry their index in the widget
+// name, and every handler resolves it at event time rather than capturing it.
+fn find_subscription_row(container: &gtk::ListBox, id: Uuid) -> Option<adw::ExpanderRow> {
+    let name = id.to_string();
+    let mut child = container.first_child();
+    while let Some(widget) = child {
+        if let Some(expander) = widget.downcast_ref::<adw::ExpanderRow>()
+            && expander.widget_name() == name
+        {
+            return Some(expander.clone());
+        }
+        child = widget.next_sibling();
+    }
+    None
+}
+
+fn reorder_subscription_row(container: &gtk::ListBox, subs: &[Subscription], id: Uuid) {
+    let Some(row) = find_subscription_row(container, id) else {
+        return;
+    };
+    let position = subs
+        .iter()
+        .take_while(|s| s.id != id)
+        .filter(|s| find_subscription_row(container, s.id).is_some())
+        .count();
+    container.remove(&row);
+    container.insert(&row, position as i32);
+}
+
+fn node_row_index(widget: &gtk::Widget) -> Option<usize> {
+    let row = widget.ancestor(adw::ActionRow::static_type())?;
+    row.widget_name().strip_prefix("node-")?.parse().ok()
+}
+
+fn collect_node_rows(widget: &gtk::Widget, rows: &mut Vec<(usize, adw::ActionRow)>) {
+    let mut child = widget.first_child();
+    while let Some(w) = child {
+        match w.downcast_ref::<adw::ActionRow>() {
+            Some(row) if row.widget_name().starts_with("node-") => {
+                if let Some(idx) = node_row_index(&w) {
+                    rows.push((idx, row.clone()));
+                }
+            }
+            _ => collect_node_rows(&w, rows),
+        }
+        child = w.next_sibling();
+    }
+}
+
+fn moved_index(idx: usize, from: usize, to: usize) -> usize {
+    if idx == from {
+        to
+    } else if from < to && idx > from && idx <= to {
+        idx - 1
+    } else if to < from && idx >= to && idx < from {
+        idx + 1
+    } else {
+        idx
+    }
+}
+
+fn move_node_row(container: &gtk::ListBox, sub_id: Uuid, from: usize, to: usize) {
+    let Some(expander) = find_subscription_row(container, sub_id) else {
+        return;
+    };
+    let mut rows = Vec::new();
+    collect_node_rows(expander.upcast_ref(), &mut rows);
+
+    let mut moved = None;
+    for (idx, row) in &rows {
+        let new_idx = moved_index(*idx, from, to);
+        row.set_widget_name(&format!("node-{new_idx}"));
+        if *idx == from {
+            moved = Some(row.clone());
+        }
+    }
+
+    let Some(row) = moved else {
+        return;
+    };
+    let Some(list) = row.parent().and_downcast::<gtk::ListBox>() else {
+        return;
+    };
+    let position = rows
+        .iter()
+        .filter(|(idx, _)| *idx != from && moved_index(*idx, from, to) < to)
+        .count();
+    list.remove(&row);
+    list.insert(&row, position as i32);
+}
+
 fn truncate(s: &str, max: usize) -> String {
     if s.len() <= max {
         s.to_string()
@@ -872,11 +2206,11 @@ fn truncate(s: &str, max: usize) -> String {
 
 fn show_add_dialog(sender: ComponentSender<SubscriptionsPage>) {
     let dialog = adw::AlertDialog::builder()
-        .heading("Add Subscription")
+        .heading(tr("Add Subscription"))
         .build();
 
-    dialog.add_response("cancel", "Cancel");
-    dialog.add_response("add", "Add");
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("add", &tr("Add"));
     dialog.set_response_appearance("add", adw::ResponseAppearance::Suggested);
     dialog.set_default_response(Some("add"));
     dialog.set_close_response("cancel");
@@ -890,41 +2224,163 @@ fn show_add_dialog(sender: ComponentSender<SubscriptionsPage>) {
         .margin_end(12)
         .build();
 
-    let name_entry = adw::EntryRow::builder().title("Name").build();
-    let url_entry = adw::EntryRow::builder().title("URL").build();
+    let url_toggle = gtk::ToggleButton::builder()
+        .label(tr("URL"))
+        .active(true)
+        .hexpand(true)
+        .build();
+    let file_toggle = gtk::ToggleButton::builder()
+        .label(tr("File"))
+        .group(&url_toggle)
+        .hexpand(true)
+        .build();
+    let mode_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["linked"])
+        .build();
+    mode_box.append(&url_toggle);
+    mode_box.append(&file_toggle);
+    content.append(&mode_box);
+
+    let name_entry = adw::EntryRow::builder().title(tr("Name")).build();
+    let url_entry = adw::EntryRow::builder().title(tr("URL")).build();
+
+    let selected_file: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
+    let choose_btn = gtk::Button::builder()
+        .label(tr("Choose…"))
+        .valign(gtk::Align::Center)
+        .build();
+    let file_row = adw::ActionRow::builder()
+        .title(tr("File"))
+        .subtitle(tr("No file selected"))
+        .visible(false)
+        .build();
+    file_row.add_suffix(&choose_btn);
 
     let group = adw::PreferencesGroup::new();
     group.add(&name_entry);
     group.add(&url_entry);
+    group.add(&file_row);
     content.append(&group);
 
+    {
+        let url_entry = url_entry.clone();
+        let file_row = file_row.clone();
+        file_toggle.connect_toggled(move |btn| {
+            let file_mode = btn.is_active();
+            url_entry.set_visible(!file_mode);
+            file_row.set_visible(file_mode);
+        });
+    }
+
+    {
+        let dialog = dialog.clone();
+        let file_row = file_row.clone();
+        let name_entry = name_entry.clone();
+        let selected_file = selected_file.clone();
+        choose_btn.connect_clicked(move |_| {
+            let file_dialog = gtk::FileDialog::builder()
+                .title(tr("Choose Subscription File"))
+                .build();
+            let parent = dialog.root().and_downcast::<gtk::Window>();
+            let file_row = file_row.clone();
+            let name_entry = name_entry.clone();
+            let selected_file = selected_file.clone();
+            file_dialog.open(
+                parent.as_ref(),
+                gtk::gio::Cancellable::NONE,
+                move |result| {
+                    let Some(path) = result.ok().and_then(|f| f.path()) else {
+                        return;
+                    };
+                    file_row.set_subtitle(&path.to_string_lossy());
+                    if name_entry.text().trim().is_empty()
+                        && let Some(stem) = path.file_stem()
+                    {
+                        name_entry.set_text(&stem.to_string_lossy());
+                    }
+                    *selected_file.borrow_mut() = Some(path);
+                },
+            );
+        });
+    }
+
     dialog.set_extra_child(Some(&content));
 
     dialog.connect_response(None, move |_, response| {
-        if response == "add" {
-            let name = name_entry.text().to_string();
-            let url = url_entry.text().to_string();
-            if !name.trim().is_empty() && !url.trim().is_empty() {
-                sender.input(SubscriptionsMsg::AddSubscription(
-                    name.trim().into(),
-                    url.trim().into(),
+        if response != "add" {
+            return;
+        }
+        let name = name_entry.text().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        if file_toggle.is_active() {
+            if let Some(path) = selected_file.borrow().as_ref() {
+                sender.input(SubscriptionsMsg::AddFileSubscription(
+                    name,
+                    path.to_string_lossy().into_owned(),
                 ));
             }
+        } else {
+            let url = url_entry.text().trim().to_string();
+            if !url.is_empty() {
+                sender.input(SubscriptionsMsg::AddSubscription(name, url));
+            }
         }
     });
 
     dialog.present(gtk::Window::NONE);
 }
 
-fn show_rename_dialog(id: Uuid, current_name: &str, sender: ComponentSender<SubscriptionsPage>) {
+fn show_import_qr_dialog(sender: ComponentSender<SubscriptionsPage>) {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some(&tr("PNG images")));
+    filter.add_mime_type("image/png");
+    let filters = gtk::gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(&filter);
+
+    let file_dialog = gtk::FileDialog::builder()
+        .title(tr("Import from QR Image"))
+        .filters(&filters)
+        .build();
+    file_dialog.open(
+        gtk::Window::NONE,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let Some(path) = result.ok().and_then(|f| f.path()) else {
+                return;
+            };
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    show_message_dialog(&tr("Import Failed"), &e.to_string());
+                    return;
+                }
+            };
+            match decode_qr_to_uri(&bytes) {
+                Some(link) => sender.input(SubscriptionsMsg::ImportLink(link)),
+                None => show_message_dialog(
+                    &tr("No QR Code Found"),
+                    &tr(
+                        "The image does not contain a readable QR code with a proxy or subscription link.",
+                    ),
+                ),
+            }
+        },
+    );
+}
+
+fn show_edit_dialog(sub: &Subscription, sender: ComponentSender<SubscriptionsPage>) {
+    let id = sub.id;
     let dialog = adw::AlertDialog::builder()
-        .heading("Rename Subscription")
+        .heading(tr("Edit Subscription"))
         .build();
 
-    dialog.add_response("cancel", "Cancel");
-    dialog.add_response("rename", "Rename");
-    dialog.set_response_appearance("rename", adw::ResponseAppearance::Suggested);
-    dialog.set_default_response(Some("rename"));
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("save", &tr("Save"));
+    dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("save"));
     dialog.set_close_response("cancel");
 
     let content = gtk::Box::builder()
@@ -937,23 +2393,56 @@ fn show_rename_dialog(id: Uuid, current_name: &str, sender: ComponentSender<Subs
         .build();
 
     let name_entry = adw::EntryRow::builder()
-        .title("Name")
-        .text(current_name)
+        .title(tr("Name"))
+        .text(&sub.name)
         .build();
 
     let group = adw::PreferencesGroup::new();
     group.add(&name_entry);
     content.append(&group);
 
+    let mirrors_buffer = gtk::TextBuffer::new(None);
+    mirrors_buffer.set_text(&sub.mirror_urls.join("\n"));
+    if matches!(sub.source, SubscriptionSource::Url { .. }) {
+        let mirrors_view = gtk::TextView::builder()
+            .buffer(&mirrors_buffer)
+            .wrap_mode(gtk::WrapMode::WordChar)
+            .top_margin(8)
+            .bottom_margin(8)
+            .left_margin(8)
+            .right_margin(8)
+            .build();
+        let scrolled = gtk::ScrolledWindow::builder()
+            .min_content_height(96)
+            .child(&mirrors_view)
+            .css_classes(["card"])
+            .build();
+        let mirrors_group = adw::PreferencesGroup::builder()
+            .title(tr("Mirror URLs"))
+            .description(tr("Tried in order when the main URL fails, one per line"))
+            .build();
+        mirrors_group.add(&scrolled);
+        content.append(&mirrors_group);
+    }
+
     dialog.set_extra_child(Some(&content));
 
     dialog.connect_response(None, move |_, response| {
-        if response == "rename" {
+        if response == "save" {
             let new_name = name_entry.text().to_string();
+            let (start, end) = mirrors_buffer.bounds();
+            let mirror_urls = mirrors_buffer
+                .text(&start, &end, false)
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect();
             if !new_name.trim().is_empty() {
-                sender.input(SubscriptionsMsg::RenameSubscription(
+                sender.input(SubscriptionsMsg::EditSubscription(
                     id,
                     new_name.trim().into(),
+                    mirror_urls,
                 ));
             }
         }
@@ -962,14 +2451,209 @@ fn show_rename_dialog(id: Uuid, current_name: &str, sender: ComponentSender<Subs
     dialog.present(gtk::Window::NONE);
 }
 
+/// Asks which group to file a subscription under, offering the existing ones.
+/// An empty name ungroups it.
+fn show_group_dialog(
+    id: Uuid,
+    current: Option<&str>,
+    groups: Vec<String>,
+    sender: ComponentSender<SubscriptionsPage>,
+) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Move to Group"))
+        .body(tr("Leave the name empty to ungroup the subscription."))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("move", &tr("Move"));
+    dialog.set_response_appearance("move", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("move"));
+    dialog.set_close_response("cancel");
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(12)
+        .build();
+
+    let name_entry = adw::EntryRow::builder()
+        .title(tr("Group"))
+        .text(current.unwrap_or_default())
+        .activates_default(true)
+        .build();
+    let list = adw::PreferencesGroup::new();
+    list.add(&name_entry);
+    content.append(&list);
+
+    if !groups.is_empty() {
+        let choices = gtk::FlowBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .column_spacing(6)
+            .row_spacing(6)
+            .build();
+        for group in groups {
+            let button = gtk::Button::builder().label(&group).build();
+            button.add_css_class("pill");
+            let entry = name_entry.clone();
+            button.connect_clicked(move |_| entry.set_text(&group));
+            choices.append(&button);
+        }
+        content.append(&choices);
+    }
+
+    dialog.set_extra_child(Some(&content));
+
+    dialog.connect_response(None, move |_, response| {
+        if response == "move" {
+            let group = name_entry.text().to_string();
+            sender.input(SubscriptionsMsg::SetGroup(id, Some(group)));
+        }
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
+fn show_node_edit_dialog(
+    sub_id: Uuid,
+    idx: usize,
+    node: &ProxyNode,
+    sender: ComponentSender<SubscriptionsPage>,
+) {
+    let Some(tls) = node.tls() else {
+        return;
+    };
+    let current = TlsOverride::from_settings(tls);
+
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Edit TLS Settings"))
+        .body(node.remark().unwrap_or(node.address()))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("save", &tr("Save"));
+    dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("save"));
+    dialog.set_close_response("cancel");
+
+    let sni_entry = adw::EntryRow::builder()
+        .title(tr("Server Name (SNI)"))
+        .text(&current.server_name)
+        .build();
+    let fingerprint_entry = adw::EntryRow::builder()
+        .title(tr("Fingerprint"))
+        .text(current.fingerprint.as_deref().unwrap_or_default())
+        .build();
+    let alpn_entry = adw::EntryRow::builder()
+        .title(tr("ALPN (comma separated)"))
+        .text(current.alpn.join(","))
+        .build();
+    let verify_row = adw::SwitchRow::builder()
+        .title(tr("Verify Certificate"))
+        .subtitle(tr("Turning this off allows insecure connections"))
+        .active(current.verify)
+        .build();
+
+    let group = adw::PreferencesGroup::new();
+    group.add(&sni_entry);
+    group.add(&fingerprint_entry);
+    group.add(&alpn_entry);
+    group.add(&verify_row);
+    dialog.set_extra_child(Some(&group));
+
+    dialog.connect_response(None, move |_, response| {
+        if response != "save" {
+            return;
+        }
+        let fingerprint = fingerprint_entry.text().trim().to_owned();
+        let tls = TlsOverride {
+            server_name: sni_entry.text().trim().to_owned(),
+            fingerprint: (!fingerprint.is_empty()).then_some(fingerprint),
+            alpn: alpn_entry
+                .text()
+                .split(',')
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(String::from)
+                .collect(),
+            verify: verify_row.is_active(),
+        };
+        sender.input(SubscriptionsMsg::SetNodeTls(sub_id, idx, tls));
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
+fn show_latency_threshold_dialog(id: Uuid, sender: ComponentSender<SubscriptionsPage>) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Enable by Latency"))
+        .body(tr(
+            "Nodes faster than the threshold are enabled; slower, unreachable and untested nodes are disabled.",
+        ))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("apply", &tr("Apply"));
+    dialog.set_response_appearance("apply", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("apply"));
+    dialog.set_close_response("cancel");
+
+    let threshold_row = adw::SpinRow::builder()
+        .title(tr("Threshold (ms)"))
+        .adjustment(&gtk::Adjustment::new(
+            DEFAULT_LATENCY_THRESHOLD_MS as f64,
+            1.0,
+            10_000.0,
+            50.0,
+            0.0,
+            0.0,
+        ))
+        .build();
+    let group = adw::PreferencesGroup::new();
+    group.add(&threshold_row);
+    dialog.set_extra_child(Some(&group));
+
+    dialog.connect_response(None, move |_, response| {
+        if response == "apply" {
+            let max_ms = threshold_row.value() as u64;
+            sender.input(SubscriptionsMsg::EnableByLatency(id, max_ms));
+        }
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
+fn show_duplicate_dialog(existing: &Subscription, sender: ComponentSender<SubscriptionsPage>) {
+    let dialog = adw::AlertDialog::builder()
+        .heading(tr("Subscription Already Added"))
+        .body(trf(
+            "\"{name}\" already uses this URL. Update it instead of adding a duplicate?",
+            &[("name", &existing.name)],
+        ))
+        .build();
+
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("update", &tr("Update"));
+    dialog.set_response_appearance("update", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("update"));
+    dialog.set_close_response("cancel");
+
+    let id = existing.id;
+    dialog.connect_response(None, move |_, response| {
+        if response == "update" {
+            sender.input(SubscriptionsMsg::UpdateSubscription(id));
+        }
+    });
+
+    dialog.present(gtk::Window::NONE);
+}
+
 fn show_delete_dialog(id: Uuid, sender: ComponentSender<SubscriptionsPage>) {
     let dialog = adw::AlertDialog::builder()
-        .heading("Delete Subscription")
-        .body("Are you sure you want to delete this subscription?")
+        .heading(tr("Delete Subscription"))
+        .body(tr("Are you sure you want to delete this subscription?"))
         .build();
 
-    dialog.add_response("cancel", "Cancel");
-    dialog.add_response("delete", "Delete");
+    dialog.add_response("cancel", &tr("Cancel"));
+    dialog.add_response("delete", &tr("Delete"));
     dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
     dialog.set_default_response(Some("cancel"));
     dialog.set_close_response("cancel");
@@ -6,6 +6,8 @@ use v2ray_rs_core::backend::{DetectedBackend, all_install_guidance, backend_name
 use v2ray_rs_core::models::{AppSettings, BackendConfig, BackendType};
 use v2ray_rs_core::persistence::AppPaths;
 
+use crate::i18n::tr;
+
 pub struct OnboardingWizard {
     _paths: AppPaths,
     settings: AppSettings,
@@ -65,8 +67,8 @@ impl SimpleComponent for OnboardingWizard {
 
                     adw::StatusPage {
                         set_icon_name: Some("network-vpn-symbolic"),
-                        set_title: "Welcome to V2Ray Manager",
-                        set_description: Some("A desktop GUI for managing v2ray, xray, and sing-box proxy configurations.\n\nLet's get started with the initial setup."),
+                        set_title: &tr("Welcome to V2Ray Manager"),
+                        set_description: Some(&tr("A desktop GUI for managing v2ray, xray, and sing-box proxy configurations.\n\nLet's get started with the initial setup.")),
                         set_vexpand: true,
                     },
 
@@ -77,7 +79,7 @@ impl SimpleComponent for OnboardingWizard {
                         set_margin_all: 24,
 
                         gtk::Button {
-                            set_label: "Next",
+                            set_label: &tr("Next"),
                             add_css_class: "pill",
                             add_css_class: "suggested-action",
                             connect_clicked => WizardMsg::NextPage,
@@ -107,8 +109,8 @@ impl SimpleComponent for OnboardingWizard {
 
                                 adw::StatusPage {
                                     set_icon_name: Some("application-x-executable-symbolic"),
-                                    set_title: "Select Backend",
-                                    set_description: Some("Choose which proxy backend to use"),
+                                    set_title: &tr("Select Backend"),
+                                    set_description: Some(&tr("Choose which proxy backend to use")),
                                 },
 
                                 #[name = "backend_list_container"]
@@ -123,7 +125,7 @@ impl SimpleComponent for OnboardingWizard {
 
                                     #[name = "backend_next_button"]
                                     gtk::Button {
-                                        set_label: "Next",
+                                        set_label: &tr("Next"),
                                         add_css_class: "pill",
                                         add_css_class: "suggested-action",
                                         #[watch]
@@ -158,13 +160,13 @@ impl SimpleComponent for OnboardingWizard {
 
                                 adw::StatusPage {
                                     set_icon_name: Some("folder-download-symbolic"),
-                                    set_title: "Import Subscription",
-                                    set_description: Some("Add your proxy subscription URL (optional)"),
+                                    set_title: &tr("Import Subscription"),
+                                    set_description: Some(&tr("Add your proxy subscription URL (optional)")),
                                 },
 
                                 adw::PreferencesGroup {
                                     adw::EntryRow {
-                                        set_title: "Subscription Name",
+                                        set_title: &tr("Subscription Name"),
                                         connect_changed[sender] => move |entry| {
                                             sender.input(WizardMsg::SubscriptionNameChanged(entry.text().to_string()));
                                         },
@@ -172,7 +174,7 @@ impl SimpleComponent for OnboardingWizard {
 
                                     #[name = "subscription_entry"]
                                     adw::EntryRow {
-                                        set_title: "Subscription URL",
+                                        set_title: &tr("Subscription URL"),
                                         connect_changed[sender] => move |entry| {
                                             sender.input(WizardMsg::SubscriptionUrlChanged(entry.text().to_string()));
                                         },
@@ -185,13 +187,13 @@ impl SimpleComponent for OnboardingWizard {
                                     set_spacing: 12,
 
                                     gtk::Button {
-                                        set_label: "Skip",
+                                        set_label: &tr("Skip"),
                                         add_css_class: "pill",
                                         connect_clicked => WizardMsg::SkipSubscription,
                                     },
 
                                     gtk::Button {
-                                        set_label: "Import",
+                                        set_label: &tr("Import"),
                                         add_css_class: "pill",
                                         add_css_class: "suggested-action",
                                         #[watch]
@@ -211,8 +213,8 @@ impl SimpleComponent for OnboardingWizard {
 
                     adw::StatusPage {
                         set_icon_name: Some("emblem-ok-symbolic"),
-                        set_title: "Setup Complete",
-                        set_description: Some("You're all set! Click Finish to start using V2Ray Manager."),
+                        set_title: &tr("Setup Complete"),
+                        set_description: Some(&tr("You're all set! Click Finish to start using V2Ray Manager.")),
                         set_vexpand: true,
                     },
 
@@ -223,7 +225,7 @@ impl SimpleComponent for OnboardingWizard {
                         set_margin_all: 24,
 
                         gtk::Button {
-                            set_label: "Finish",
+                            set_label: &tr("Finish"),
                             add_css_class: "pill",
                             add_css_class: "suggested-action",
                             connect_clicked => WizardMsg::Complete,
@@ -256,13 +258,13 @@ impl SimpleComponent for OnboardingWizard {
         if detected_backends.is_empty() {
             let status = adw::StatusPage::builder()
                 .icon_name("dialog-error-symbolic")
-                .title("No Backend Found")
+                .title(tr("No Backend Found"))
                 .description(all_install_guidance())
                 .build();
             widgets.backend_list_container.append(&status);
         } else {
             let group = adw::PreferencesGroup::builder()
-                .title("Detected Backends")
+                .title(tr("Detected Backends"))
                 .build();
 
             let mut first_check: Option<gtk::CheckButton> = None;
@@ -314,8 +316,7 @@ impl SimpleComponent for OnboardingWizard {
 
                 let subscription = if !self.subscription_url.is_empty() {
                     let name = if self.subscription_name.trim().is_empty() {
-                        extract_host(&self.subscription_url)
-                            .unwrap_or_else(|| "Subscription".into())
+                        extract_host(&self.subscription_url).unwrap_or_else(|| tr("Subscription"))
                     } else {
                         self.subscription_name.clone()
                     };
@@ -0,0 +1,34 @@
+use v2ray_rs_ui::autoscroll::{AutoScroll, at_bottom};
+
+#[test]
+fn scrolling_up_pauses_until_back_at_bottom() {
+    let mut scroll = AutoScroll::new(true);
+    assert!(scroll.follows());
+
+    scroll.scrolled(at_bottom(200.0, 300.0, 1000.0));
+    assert!(!scroll.follows(), "new lines must not yank the view back");
+    scroll.scrolled(at_bottom(500.0, 300.0, 1000.0));
+    assert!(!scroll.follows());
+
+    scroll.scrolled(at_bottom(700.0, 300.0, 1000.0));
+    assert!(scroll.follows());
+    scroll.scrolled(at_bottom(698.5, 300.0, 1000.0));
+    assert!(scroll.follows(), "a pixel or two short still counts");
+}
+
+#[test]
+fn disabled_never_follows() {
+    let mut scroll = AutoScroll::new(false);
+    scroll.scrolled(true);
+    assert!(!scroll.follows());
+
+    scroll.scrolled(false);
+    scroll.set_enabled(true);
+    assert!(scroll.follows(), "enabling resumes following");
+    assert!(scroll.enabled());
+}
+
+#[test]
+fn short_content_is_always_at_bottom() {
+    assert!(at_bottom(0.0, 300.0, 120.0));
+}
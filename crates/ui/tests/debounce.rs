@@ -0,0 +1,36 @@
+use std::time::{Duration, Instant};
+
+use v2ray_rs_ui::debounce::Debouncer;
+
+const DELAY: Duration = Duration::from_millis(300);
+
+#[test]
+fn burst_coalesces_into_last_value() {
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+    let mut debouncer = Debouncer::new(DELAY);
+
+    for (i, ms) in [0, 50, 100, 150].into_iter().enumerate() {
+        debouncer.push(i, at(ms));
+        assert_eq!(debouncer.poll(at(ms)), None);
+    }
+    assert_eq!(
+        debouncer.poll(at(300)),
+        None,
+        "quiet period restarts on push"
+    );
+    assert_eq!(debouncer.poll(at(450)), Some(3));
+    assert_eq!(debouncer.poll(at(900)), None, "released only once");
+}
+
+#[test]
+fn flush_releases_pending_value_early() {
+    let now = Instant::now();
+    let mut debouncer = Debouncer::new(DELAY);
+    assert_eq!(debouncer.flush(), None);
+
+    debouncer.push("a", now);
+    debouncer.push("b", now);
+    assert_eq!(debouncer.flush(), Some("b"));
+    assert_eq!(debouncer.poll(now + DELAY), None);
+}
@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use v2ray_rs_core::models::Language;
+use v2ray_rs_ui::i18n::locale_name;
+
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+fn catalog_path(language: Language, ext: &str) -> PathBuf {
+    repo_root()
+        .join("locale")
+        .join(locale_name(language))
+        .join("LC_MESSAGES")
+        .join(format!("v2ray-rs.{ext}"))
+}
+
+/// Reads a Rust string literal starting right after its opening quote.
+fn read_literal(src: &str) -> Option<String> {
+    split_literal(src).map(|(literal, _)| literal)
+}
+
+/// Like [`read_literal`], also returning what follows the closing quote.
+fn split_literal(src: &str) -> Option<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some((out, chars.as_str())),
+            '\\' => {
+                out.push('\\');
+                out.push(chars.next()?);
+            }
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+/// String literals in the message argument of a call whose arguments start
+/// at `src`: all of them up to the closing parenthesis, so both branches of
+/// `tr(if … { "a" } else { "b" })` count, but only the first argument's
+/// when `first_only`, leaving out `trf`'s placeholder names.
+fn argument_literals(src: &str, first_only: bool) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut depth = 0;
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let Some((literal, after)) = split_literal(chars.as_str()) else {
+                    break;
+                };
+                literals.push(literal);
+                chars = after.chars();
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => break,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 && first_only => break,
+            _ => {}
+        }
+    }
+    literals
+}
+
+fn used_msgids() -> HashSet<String> {
+    let mut ids = HashSet::new();
+    let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    for entry in fs::read_dir(src_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let src = fs::read_to_string(&path).unwrap();
+        for call in ["tr(", "trf("] {
+            for (pos, _) in src.match_indices(call) {
+                let prev = src[..pos].chars().next_back();
+                if prev.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    continue;
+                }
+                ids.extend(argument_literals(&src[pos + call.len()..], call == "trf("));
+            }
+        }
+    }
+    ids
+}
+
+fn catalog(path: &Path) -> Vec<(String, String)> {
+    let text = fs::read_to_string(path).unwrap();
+    let mut entries = Vec::new();
+    let mut msgid = None;
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("msgid \"") {
+            msgid = read_literal(rest);
+        } else if let Some(rest) = line.strip_prefix("msgstr \"")
+            && let (Some(id), Some(s)) = (msgid.take(), read_literal(rest))
+        {
+            entries.push((id, s));
+        }
+    }
+    entries
+}
+
+#[test]
+fn every_ui_string_is_in_every_catalog() {
+    let used = used_msgids();
+    assert!(!used.is_empty());
+
+    for language in Language::ALL {
+        let path = catalog_path(language, "po");
+        let entries = catalog(&path);
+        let translated: HashSet<&str> = entries
+            .iter()
+            .filter(|(id, s)| !id.is_empty() && !s.is_empty())
+            .map(|(id, _)| id.as_str())
+            .collect();
+        let mut missing: Vec<&String> = used
+            .iter()
+            .filter(|id| !translated.contains(id.as_str()))
+            .collect();
+        missing.sort();
+        assert!(
+            missing.is_empty(),
+            "{} is missing {missing:#?}",
+            path.display()
+        );
+    }
+}
+
+/// Looks `msgid` up in a compiled catalog the same way gettext does.
+fn mo_lookup(data: &[u8], msgid: &str) -> Option<String> {
+    let word = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+    assert_eq!(word(0), 0x950412de, "not a little-endian .mo file");
+    let (count, originals, translations) = (word(8), word(12), word(16));
+    let string = |table: usize, i: usize| {
+        let (len, offset) = (word(table + i * 8), word(table + i * 8 + 4));
+        std::str::from_utf8(&data[offset..offset + len]).unwrap()
+    };
+    (0..count)
+        .find(|&i| string(originals, i) == msgid)
+        .map(|i| string(translations, i).to_owned())
+}
+
+#[test]
+fn compiled_catalogs_translate_per_language() {
+    for language in Language::ALL {
+        let expected = match language {
+            Language::English => "Subscriptions",
+            Language::Russian => "Подписки",
+            Language::Chinese => "订阅",
+        };
+        let data = fs::read(catalog_path(language, "mo")).unwrap();
+        assert_eq!(
+            mo_lookup(&data, "Subscriptions").as_deref(),
+            Some(expected),
+            "{language:?}"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+fn crate_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Quoted `*-symbolic` names in `src`, i.e. the icons the UI depends on.
+fn symbolic_names(src: &str) -> BTreeSet<String> {
+    src.split('"')
+        .skip(1)
+        .step_by(2)
+        .filter(|s| {
+            s.ends_with("-symbolic")
+                && s.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn every_used_icon_has_a_bundled_fallback() {
+    let src_dir = crate_dir().join("src");
+    let mut used = BTreeSet::new();
+    for entry in fs::read_dir(&src_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "rs") && !path.ends_with("icons.rs") {
+            used.extend(symbolic_names(&fs::read_to_string(&path).unwrap()));
+        }
+    }
+    assert!(used.contains("list-drag-handle-symbolic"));
+
+    let bundled = symbolic_names(&fs::read_to_string(src_dir.join("icons.rs")).unwrap());
+    let missing: Vec<_> = used.difference(&bundled).collect();
+    assert!(missing.is_empty(), "no fallback for {missing:#?}");
+
+    for name in &bundled {
+        let svg = fs::read_to_string(crate_dir().join(format!("icons/{name}.svg")))
+            .unwrap_or_else(|e| panic!("{name}: {e}"));
+        assert!(svg.starts_with("<svg "), "{name} is not an SVG");
+    }
+}